@@ -0,0 +1,139 @@
+use crate::types::Type;
+
+/// The class of an eightbyte of an argument or a return value, which decides how the System V
+/// ABI passes it (AMD64 psABI 3.2.3). There are no floating types, so there's no SSE class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    // Only padding, so nothing is passed for it.
+    Empty,
+    // In the next general purpose register.
+    Integer,
+    // On the stack, or through a hidden pointer in `rdi` for a return value.
+    Memory,
+}
+
+/// The classes of the eightbytes of a `ty`, in order. Either all or none of them are `Memory`.
+pub fn classify(ty: &Type) -> Vec<Class> {
+    let size = ty.size();
+    let eightbytes = size.div_ceil(8);
+    // Only vectors are passed in registers past two eightbytes.
+    if size > 16 {
+        return vec![Class::Memory; eightbytes];
+    }
+
+    let mut classes = vec![Class::Empty; eightbytes];
+    classify_at(ty, 0, &mut classes);
+
+    if classes.contains(&Class::Memory) {
+        vec![Class::Memory; eightbytes]
+    } else {
+        classes
+    }
+}
+
+/// Whether a `ty` is passed on the stack, or returned through a hidden pointer.
+pub fn is_memory(ty: &Type) -> bool {
+    classify(ty).contains(&Class::Memory)
+}
+
+// Merges the classes of the fields of a `ty` at `offset` into the eightbytes they are in.
+fn classify_at(ty: &Type, offset: usize, classes: &mut [Class]) {
+    match ty.unqualified() {
+        Type::Array { element, len } => {
+            for index in 0..len.unwrap_or(0) {
+                classify_at(element, offset + index * element.size(), classes);
+            }
+        }
+        Type::Struct(struct_ref) => {
+            for member in struct_ref.borrow().members.iter().flatten() {
+                classify_at(&member.ty, offset + member.offset, classes);
+            }
+        }
+        // An unaligned field makes the whole argument `Memory`.
+        ty if offset % ty.align() != 0 => classes[offset / 8] = Class::Memory,
+        _ => classes[offset / 8] = merge(classes[offset / 8], Class::Integer),
+    }
+}
+
+fn merge(lhs: Class, rhs: Class) -> Class {
+    match (lhs, rhs) {
+        (Class::Empty, class) | (class, Class::Empty) => class,
+        (Class::Memory, _) | (_, Class::Memory) => Class::Memory,
+        (Class::Integer, Class::Integer) => Class::Integer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Field, StructRef};
+
+    use super::*;
+
+    fn struct_of(members: Vec<Type>) -> Type {
+        let struct_ref = StructRef::new(None);
+        struct_ref
+            .define(
+                members
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, ty)| Field::new(&format!("m{index}"), ty))
+                    .collect(),
+            )
+            .unwrap();
+        Type::Struct(struct_ref)
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(classify(&Type::Char), vec![Class::Integer]);
+        assert_eq!(classify(&Type::ULong), vec![Class::Integer]);
+        assert_eq!(
+            classify(&Type::pointer_to(Type::Void)),
+            vec![Class::Integer]
+        );
+    }
+
+    #[test]
+    fn test_small_structs() {
+        // Fields sharing an eightbyte are passed in one register.
+        let ty = struct_of(vec![Type::Int, Type::Char, Type::Short]);
+        assert_eq!(classify(&ty), vec![Class::Integer]);
+
+        let ty = struct_of(vec![Type::Char, Type::Long]);
+        assert_eq!(classify(&ty), vec![Class::Integer, Class::Integer]);
+
+        let ty = struct_of(vec![Type::array_of(Type::Char, Some(3))]);
+        assert_eq!(classify(&ty), vec![Class::Integer]);
+
+        let ty = struct_of(vec![Type::Int, Type::array_of(Type::Short, Some(5))]);
+        assert_eq!((ty.size(), classify(&ty).len()), (16, 2));
+        assert!(!is_memory(&ty));
+    }
+
+    #[test]
+    fn test_nested_structs() {
+        let inner = struct_of(vec![Type::Int, Type::Int]);
+        let ty = struct_of(vec![inner.clone(), Type::pointer_to(Type::Char)]);
+        assert_eq!(classify(&ty), vec![Class::Integer, Class::Integer]);
+
+        let ty = struct_of(vec![Type::array_of(inner, Some(2))]);
+        assert_eq!(classify(&ty), vec![Class::Integer, Class::Integer]);
+    }
+
+    #[test]
+    fn test_memory() {
+        let ty = struct_of(vec![Type::Long, Type::Long, Type::Char]);
+        assert_eq!(classify(&ty), vec![Class::Memory; 3]);
+        assert!(is_memory(&ty));
+
+        let ty = struct_of(vec![Type::array_of(Type::Char, Some(17))]);
+        assert_eq!(classify(&ty), vec![Class::Memory; 3]);
+    }
+
+    #[test]
+    fn test_empty_struct() {
+        // Like gcc, nothing is passed for it.
+        assert_eq!(classify(&struct_of(vec![])), vec![]);
+        assert!(!is_memory(&struct_of(vec![])));
+    }
+}
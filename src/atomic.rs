@@ -0,0 +1,288 @@
+use core::fmt;
+
+/// The memory order of an atomic operation, numbered like the `__ATOMIC_*` macros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryOrder {
+    Relaxed,
+    Consume,
+    Acquire,
+    Release,
+    AcqRel,
+    SeqCst,
+}
+
+impl MemoryOrder {
+    pub const ALL: [MemoryOrder; 6] = [
+        MemoryOrder::Relaxed,
+        MemoryOrder::Consume,
+        MemoryOrder::Acquire,
+        MemoryOrder::Release,
+        MemoryOrder::AcqRel,
+        MemoryOrder::SeqCst,
+    ];
+
+    /// The order of the value of an `__ATOMIC_*` macro. Like gcc, any other value is `SeqCst`.
+    pub fn from_value(value: i64) -> MemoryOrder {
+        usize::try_from(value)
+            .ok()
+            .and_then(|value| MemoryOrder::ALL.get(value).copied())
+            .unwrap_or(MemoryOrder::SeqCst)
+    }
+}
+
+/// The operation of `__atomic_fetch_add` and the like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    Nand,
+}
+
+impl fmt::Display for FetchOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FetchOp::Add => "add",
+                FetchOp::Sub => "sub",
+                FetchOp::And => "and",
+                FetchOp::Or => "or",
+                FetchOp::Xor => "xor",
+                FetchOp::Nand => "nand",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicOp {
+    Load,
+    // The value is the stored one.
+    Store,
+    // Stores a value and returns the previous one.
+    Exchange,
+    // Stores the desired value if the object holds the expected one. The expected value is read
+    // through a pointer, which gets the actual one on failure, unless it's given as is like for
+    // `__sync`. The value is whether it succeeded, or the previous one.
+    CompareExchange {
+        through_pointer: bool,
+        returns_previous: bool,
+    },
+    // The value is the previous one, or the result for `__atomic_add_fetch` and the like.
+    Fetch {
+        op: FetchOp,
+        returns_new: bool,
+    },
+    // Only orders memory accesses, for `__atomic_thread_fence`.
+    Fence,
+}
+
+/// The kind of arguments an atomic builtin takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    // `__atomic_*`, which take memory orders, and a flag for a weak compare-exchange.
+    Gnu,
+    // `__sync_*`, which are sequentially consistent except for the lock ones.
+    Sync,
+    // `__c11_atomic_*`, which operate on `_Atomic` objects, as used by `<stdatomic.h>`.
+    C11,
+}
+
+/// Where the memory order of an atomic builtin comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orders {
+    // The last arguments, like the success and failure orders of a compare-exchange, of which the
+    // first is used.
+    Taken,
+    // It doesn't take any.
+    Fixed(MemoryOrder),
+    // They're taken but don't change anything, like for `__atomic_signal_fence`.
+    Ignored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Builtin {
+    pub op: AtomicOp,
+    pub family: Family,
+    pub orders: Orders,
+}
+
+impl Builtin {
+    /// How many arguments it takes before its memory orders, which are the pointer to the object
+    /// unless it's a fence, then the operands. And how many memory orders it takes.
+    pub fn arity(&self) -> (usize, usize) {
+        let (operands, orders) = match self.op {
+            AtomicOp::Fence => (0, 1),
+            AtomicOp::Load => (1, 1),
+            // `__sync_lock_release` stores 0.
+            AtomicOp::Store if self.family == Family::Sync => (1, 0),
+            AtomicOp::Store | AtomicOp::Exchange | AtomicOp::Fetch { .. } => (2, 1),
+            AtomicOp::CompareExchange { .. } => match self.family {
+                // The weak flag comes after the operands.
+                Family::Gnu => (4, 2),
+                _ => (3, 2),
+            },
+        };
+        match self.orders {
+            Orders::Fixed(_) => (operands, 0),
+            Orders::Taken | Orders::Ignored => (operands, orders),
+        }
+    }
+}
+
+/// The atomic builtin named `name`, like `__atomic_fetch_add`.
+pub fn builtin(name: &str) -> Option<Builtin> {
+    if let Some(name) = name.strip_prefix("__sync_") {
+        return sync(name);
+    }
+    let (name, family) = match name.strip_prefix("__c11_atomic_") {
+        Some(name) => (name, Family::C11),
+        None => (name.strip_prefix("__atomic_")?, Family::Gnu),
+    };
+
+    let fetch = |op: &str, returns_new| fetch_op(op).map(|op| AtomicOp::Fetch { op, returns_new });
+    let op = match (name, family) {
+        ("load_n", Family::Gnu) | ("load", Family::C11) => AtomicOp::Load,
+        ("store_n", Family::Gnu) | ("store", Family::C11) => AtomicOp::Store,
+        ("exchange_n", Family::Gnu) | ("exchange", Family::C11) => AtomicOp::Exchange,
+        ("compare_exchange_n", Family::Gnu)
+        | ("compare_exchange_strong" | "compare_exchange_weak", Family::C11) => {
+            AtomicOp::CompareExchange {
+                through_pointer: true,
+                returns_previous: false,
+            }
+        }
+        ("thread_fence", _) => AtomicOp::Fence,
+        // Nothing is reordered within a thread, so there's nothing to do.
+        ("signal_fence", _) => {
+            return Some(Builtin {
+                op: AtomicOp::Fence,
+                family,
+                orders: Orders::Ignored,
+            })
+        }
+        // Initializing isn't atomic.
+        ("init", Family::C11) => {
+            return Some(Builtin {
+                op: AtomicOp::Store,
+                family,
+                orders: Orders::Fixed(MemoryOrder::Relaxed),
+            })
+        }
+        (name, _) => match name.strip_prefix("fetch_") {
+            Some(op) => fetch(op, false)?,
+            None if family == Family::Gnu => fetch(name.strip_suffix("_fetch")?, true)?,
+            None => return None,
+        },
+    };
+
+    Some(Builtin {
+        op,
+        family,
+        orders: Orders::Taken,
+    })
+}
+
+// A `__sync` builtin, by its name without the prefix.
+fn sync(name: &str) -> Option<Builtin> {
+    let (op, order) = match name {
+        "bool_compare_and_swap" | "val_compare_and_swap" => (
+            AtomicOp::CompareExchange {
+                through_pointer: false,
+                returns_previous: name == "val_compare_and_swap",
+            },
+            MemoryOrder::SeqCst,
+        ),
+        "lock_test_and_set" => (AtomicOp::Exchange, MemoryOrder::Acquire),
+        "lock_release" => (AtomicOp::Store, MemoryOrder::Release),
+        "synchronize" => (AtomicOp::Fence, MemoryOrder::SeqCst),
+        _ => {
+            let op = match name.strip_prefix("fetch_and_") {
+                Some(op) => AtomicOp::Fetch {
+                    op: fetch_op(op)?,
+                    returns_new: false,
+                },
+                None => AtomicOp::Fetch {
+                    op: fetch_op(name.strip_suffix("_and_fetch")?)?,
+                    returns_new: true,
+                },
+            };
+            (op, MemoryOrder::SeqCst)
+        }
+    };
+
+    Some(Builtin {
+        op,
+        family: Family::Sync,
+        orders: Orders::Fixed(order),
+    })
+}
+
+fn fetch_op(name: &str) -> Option<FetchOp> {
+    match name {
+        "add" => Some(FetchOp::Add),
+        "sub" => Some(FetchOp::Sub),
+        "and" => Some(FetchOp::And),
+        "or" => Some(FetchOp::Or),
+        "xor" => Some(FetchOp::Xor),
+        "nand" => Some(FetchOp::Nand),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let fetch_add = builtin("__atomic_fetch_add").unwrap();
+        assert_eq!(
+            fetch_add.op,
+            AtomicOp::Fetch {
+                op: FetchOp::Add,
+                returns_new: false
+            }
+        );
+        assert_eq!(fetch_add.arity(), (2, 1));
+        assert_eq!(
+            builtin("__sync_nand_and_fetch").unwrap().op,
+            AtomicOp::Fetch {
+                op: FetchOp::Nand,
+                returns_new: true
+            }
+        );
+        assert_eq!(
+            builtin("__atomic_compare_exchange_n").unwrap().arity(),
+            (4, 2)
+        );
+        assert_eq!(
+            builtin("__c11_atomic_compare_exchange_weak")
+                .unwrap()
+                .arity(),
+            (3, 2)
+        );
+        assert_eq!(
+            builtin("__sync_val_compare_and_swap").unwrap().arity(),
+            (3, 0)
+        );
+        assert_eq!(builtin("__sync_synchronize").unwrap().arity(), (0, 0));
+        assert_eq!(builtin("__sync_lock_release").unwrap().arity(), (1, 0));
+        assert_eq!(builtin("__atomic_signal_fence").unwrap().arity(), (0, 1));
+        assert_eq!(builtin("__c11_atomic_init").unwrap().arity(), (2, 0));
+        assert_eq!(builtin("__atomic_load"), None);
+        assert_eq!(builtin("__c11_atomic_add_fetch"), None);
+        assert_eq!(builtin("__sync_fetch_and_mul"), None);
+    }
+
+    #[test]
+    fn test_memory_order() {
+        assert_eq!(MemoryOrder::from_value(2), MemoryOrder::Acquire);
+        assert_eq!(MemoryOrder::from_value(5), MemoryOrder::SeqCst);
+        assert_eq!(MemoryOrder::from_value(-1), MemoryOrder::SeqCst);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{parser::ArithOp, types::Type};
+
+/// A builtin which operates on the bits of an integer, like `__builtin_popcount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    // The number of leading 0 bits, which is undefined for 0.
+    Clz,
+    // The number of trailing 0 bits, which is undefined for 0.
+    Ctz,
+    Popcount,
+    // One plus the index of the least significant 1 bit, or 0 for 0.
+    Ffs,
+    // Whether the number of 1 bits is odd.
+    Parity,
+    // The bytes in reverse order.
+    Bswap,
+}
+
+/// A builtin called like a function, which is only recognized where its name isn't declared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Builtin {
+    Alloca,
+    // `__builtin_expect(value, expected)`, which is `value`.
+    Expect,
+    // `__builtin_unreachable` and `__builtin_trap`, which stop the program.
+    Trap,
+    // The type is that of the operand.
+    Bit { op: BitOp, operand: Type },
+    // `__builtin_add_overflow(lhs, rhs, result)` and the like, with `Add`, `Sub` or `Mul`.
+    Overflow(ArithOp),
+    ConstantP,
+    Memcpy,
+    Memset,
+}
+
+impl Builtin {
+    pub fn arity(&self) -> usize {
+        match self {
+            Builtin::Trap => 0,
+            Builtin::Alloca | Builtin::Bit { .. } | Builtin::ConstantP => 1,
+            Builtin::Expect => 2,
+            Builtin::Overflow(_) | Builtin::Memcpy | Builtin::Memset => 3,
+        }
+    }
+}
+
+/// The builtin named `name`, like `__builtin_clzll`. Atomic builtins are in `atomic`.
+pub fn builtin(name: &str) -> Option<Builtin> {
+    let name = name.strip_prefix("__builtin_")?;
+    let bswap = |operand| Builtin::Bit {
+        op: BitOp::Bswap,
+        operand,
+    };
+
+    Some(match name {
+        "alloca" => Builtin::Alloca,
+        "expect" => Builtin::Expect,
+        // Reaching it is undefined, so it traps too.
+        "unreachable" | "trap" => Builtin::Trap,
+        "add_overflow" => Builtin::Overflow(ArithOp::Add),
+        "sub_overflow" => Builtin::Overflow(ArithOp::Sub),
+        "mul_overflow" => Builtin::Overflow(ArithOp::Mul),
+        "constant_p" => Builtin::ConstantP,
+        "memcpy" => Builtin::Memcpy,
+        "memset" => Builtin::Memset,
+        "bswap16" => bswap(Type::UShort),
+        "bswap32" => bswap(Type::UInt),
+        "bswap64" => bswap(Type::ULong),
+        _ => return bit(name),
+    })
+}
+
+// `clz`, `ctz`, `popcount`, `ffs` or `parity`, which take an `unsigned int`, or an `int` for `ffs`,
+// and a `long` or a `long long` with the suffix `l` or `ll`.
+fn bit(name: &str) -> Option<Builtin> {
+    let (name, long) = match name.strip_suffix("ll") {
+        Some(name) => (name, 2),
+        None => match name.strip_suffix('l') {
+            Some(name) => (name, 1),
+            None => (name, 0),
+        },
+    };
+    let op = match name {
+        "clz" => BitOp::Clz,
+        "ctz" => BitOp::Ctz,
+        "popcount" => BitOp::Popcount,
+        "ffs" => BitOp::Ffs,
+        "parity" => BitOp::Parity,
+        _ => return None,
+    };
+    let operand = match (op, long) {
+        (BitOp::Ffs, 0) => Type::Int,
+        (BitOp::Ffs, 1) => Type::Long,
+        (BitOp::Ffs, _) => Type::LongLong,
+        (_, 0) => Type::UInt,
+        (_, 1) => Type::ULong,
+        _ => Type::ULongLong,
+    };
+
+    Some(Builtin::Bit { op, operand })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        assert_eq!(
+            builtin("__builtin_clzll"),
+            Some(Builtin::Bit {
+                op: BitOp::Clz,
+                operand: Type::ULongLong
+            })
+        );
+        assert_eq!(
+            builtin("__builtin_ffsl"),
+            Some(Builtin::Bit {
+                op: BitOp::Ffs,
+                operand: Type::Long
+            })
+        );
+        assert_eq!(
+            builtin("__builtin_bswap16"),
+            Some(Builtin::Bit {
+                op: BitOp::Bswap,
+                operand: Type::UShort
+            })
+        );
+        assert_eq!(builtin("__builtin_mul_overflow").unwrap().arity(), 3);
+        assert_eq!(builtin("__builtin_unreachable"), Some(Builtin::Trap));
+        assert_eq!(builtin("__builtin_popcountlll"), None);
+        assert_eq!(builtin("__builtin_bswap"), None);
+        assert_eq!(builtin("popcount"), None);
+    }
+}
@@ -1,178 +1,1348 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+};
+
 use crate::{
-    lexer,
-    parser::{self, ArithOp, Node, ParseResult},
+    abi::{self, Class},
+    atomic::{AtomicOp, FetchOp, MemoryOrder},
+    builtin::BitOp,
+    inline_asm::{Piece, Register},
+    ir::{
+        Address, Asm, AsmOperand, AsmPlace, Base, BinaryOp, Block, BlockId, Callee, Function, Inst,
+        Operand, Program, Terminator, VReg,
+    },
+    parser::{ArithOp, CmpOp},
+    regalloc::{self, Allocation, Location},
+    sema::{Global, SymbolAttributes, Visibility},
+    types::{Target, Type},
 };
-use anyhow::Result;
-
-pub fn generate(c_code: &str) -> Result<String> {
-    let ParseResult { nodes, stack_size } = parser::parse(lexer::tokenize(c_code)?);
-
-    Ok(format!(
-        "\
-.intel_syntax noprefix
-.globl main
-
-main:
-        push rbp
-        mov rbp, rsp
-        sub rsp, {stack_size}
-",
-    ) + &nodes_to_asem(&nodes)?)
-}
 
-fn nodes_to_asem(nodes: &[Node]) -> Result<String> {
-    let mut asem = String::new();
+// Argument registers of the System V ABI, by size: 1, 2, 4 and 8 bytes.
+const ARG_REGS: [[&str; 4]; 6] = [
+    ["dil", "di", "edi", "rdi"],
+    ["sil", "si", "esi", "rsi"],
+    ["dl", "dx", "edx", "rdx"],
+    ["cl", "cx", "ecx", "rcx"],
+    ["r8b", "r8w", "r8d", "r8"],
+    ["r9b", "r9w", "r9d", "r9"],
+];
+
+// Where up to two eightbytes of a value are returned.
+const RETURN_REGS: [[&str; 4]; 2] = [["al", "ax", "eax", "rax"], ["dl", "dx", "edx", "rdx"]];
+
+// A register, and the operand moved into it, or the eightbyte at an offset and of a size of the
+// struct the operand points to.
+type Move<'a> = (Register, &'a Operand, Option<(usize, usize)>);
+
+/// Selects x86-64 instructions for the IR, in Intel syntax.
+pub fn generate(program: &Program, target: Target) -> String {
+    let mut codegen = Codegen {
+        asm: ".intel_syntax noprefix\n".to_string(),
+        labels: 0,
+        function: String::new(),
+        return_type: Type::Void,
+        return_pointer: None,
+        frame_size: 0,
+        allocation: Allocation {
+            locations: vec![],
+            stack_slots: 0,
+            callee_saved: vec![],
+        },
+        uses: vec![],
+        symbols: &program.symbols,
+        thread_locals: program
+            .globals
+            .iter()
+            .filter(|global| global.is_thread_local)
+            .map(|global| global.name.as_str())
+            .collect(),
+        static_globals: program
+            .globals
+            .iter()
+            .filter(|global| global.is_static)
+            .map(|global| global.name.as_str())
+            .collect(),
+        aliases: BTreeSet::new(),
+        target,
+    };
+
+    // Also applies to symbols which are only declared.
+    for (name, attributes) in &program.symbols {
+        let symbol = codegen.symbol(name);
+        if attributes.is_weak {
+            codegen.emit(&format!(".weak {symbol}"));
+        }
+        if let Some(visibility) = attributes.visibility.filter(|&v| v != Visibility::Default) {
+            codegen.emit(&format!(".{visibility} {symbol}"));
+        }
+    }
+
+    for asm in &program.asm {
+        codegen.asm_lines(asm);
+    }
 
-    for node in nodes {
-        asem += &to_asem(node)?;
+    for global in &program.globals {
+        codegen.global(global);
     }
 
-    Ok(asem)
+    for function in &program.functions {
+        codegen.function(function);
+    }
+
+    // Called by the C runtime before and after `main`.
+    for (name, attributes) in &program.symbols {
+        for (section, priority) in [
+            ("init_array", attributes.constructor),
+            ("fini_array", attributes.destructor),
+        ] {
+            let Some(priority) = priority else {
+                continue;
+            };
+            if priority == u16::MAX {
+                codegen.emit(&format!(".section .{section},\"aw\""));
+            } else {
+                codegen.emit(&format!(".section .{section}.{priority:05},\"aw\""));
+            }
+            codegen.emit(".align 8");
+            let symbol = codegen.operand_symbol(name);
+            codegen.emit(&format!(".quad {symbol}"));
+        }
+    }
+
+    // Registers start with `%` in AT&T syntax, so the names are symbols there.
+    if !codegen.aliases.is_empty() {
+        codegen.emit(".att_syntax");
+        for symbol in std::mem::take(&mut codegen.aliases) {
+            codegen.emit(&format!(".set .L.symbol.{symbol}, {symbol}"));
+        }
+        codegen.emit(".intel_syntax noprefix");
+    }
+
+    codegen.asm
 }
 
-fn to_asem(ast: &Node) -> Result<String> {
-    match ast {
-        Node::Num(n) => Ok(format!("        push {}\n", n)),
-        Node::LocalVar { offset } => Ok(format!("        push [rbp-{offset}]\n")),
-        Node::ArithOp {
-            value: arith_op,
-            lhs,
-            rhs,
-        } => match arith_op {
-            ArithOp::Add | ArithOp::Sub | ArithOp::Mul => Ok(to_asem(lhs.as_ref())?
-                + &to_asem(rhs.as_ref())?
-                + &format!(
-                    "        pop rdi
-        pop rax
-        {arith_op} rax, rdi
-        push rax
-"
-                )),
-            ArithOp::Div => Ok(to_asem(lhs.as_ref())?
-                + &to_asem(rhs.as_ref())?
-                + &format!(
-                    "        pop rdi
-        pop rax
-        cqo
-        {arith_op} rdi
-        push rax
-"
-                )),
-            ArithOp::Assign => Ok(to_asem(rhs.as_ref())?
-                + &{
-                    if let Node::LocalVar { offset } = lhs.as_ref() {
-                        format!(
-                            "        pop rax
-        mov [rbp-{offset}], rax
-        push rax
-"
-                        )
-                    } else {
-                        panic!("lhs of assign must be LocalVar")
-                    }
-                }),
-        },
-        Node::CmpOp {
-            value: cmp_op,
-            lhs,
-            rhs,
-        } => Ok(to_asem(lhs.as_ref())?
-            + &to_asem(rhs.as_ref())?
-            + &format!(
-                "        pop rdi
-        pop rax
-        cmp rax, rdi
-        {cmp_op} al
-        movzb rax, al
-        push rax
-"
-            )),
-        Node::Ret { value } => Ok(to_asem(value.as_ref())?
-            + "        pop rax
-        mov rsp, rbp
-        pop rbp
-        ret
-"),
-        Node::If {
-            label,
-            cond,
-            then,
-            else_,
-        } => Ok(to_asem(cond.as_ref())?
-            + &format!(
-                "        pop rax
-        cmp rax, 0
-        je {label}
-"
-            )
-            + &to_asem(then.as_ref())?
-            + &format!(
-                "{label}:
-"
-            )
-            + &(match else_ {
-                Some(else_) => to_asem(else_.as_ref())?,
-                None => "".to_string(),
-            })),
-        Node::While {
-            start_label,
-            end_label,
-            cond,
-            then,
-        } => Ok(format!(
-            "{start_label}:
-"
-        ) + &to_asem(cond.as_ref())?
-            + &format!(
-                "        pop rax
-        cmp rax, 0
-        je {end_label}
-"
-            )
-            + &to_asem(then.as_ref())?
-            + &format!(
-                "        jmp {start_label}
-"
-            )
-            + &format!(
-                "{end_label}:
-"
-            )),
-        Node::For {
-            start_label,
-            end_label,
+// Each virtual register is in the register it was allocated, or in a slot in the frame below the
+// variables. An instruction works in rax and r11, which are left out of allocation.
+struct Codegen<'a> {
+    asm: String,
+    // For unique labels.
+    labels: usize,
+    // Where `return` jumps.
+    function: String,
+    return_type: Type,
+    // Where the hidden pointer to the return value is stored, if it's returned in memory.
+    return_pointer: Option<usize>,
+    // The size of the variables in the frame, which the slots of virtual registers follow.
+    frame_size: usize,
+    allocation: Allocation,
+    // How many times each virtual register is read.
+    uses: Vec<usize>,
+    symbols: &'a BTreeMap<String, SymbolAttributes>,
+    // The thread-local variables defined in this file.
+    thread_locals: HashSet<&'a str>,
+    // The static globals, which no other module can define instead.
+    static_globals: HashSet<&'a str>,
+    // The symbols named like registers which operands refer to, see `operand_symbol`.
+    aliases: BTreeSet<String>,
+    target: Target,
+}
+
+impl Codegen<'_> {
+    fn emit(&mut self, line: &str) {
+        writeln!(self.asm, "        {line}").unwrap();
+    }
+
+    fn label(&mut self, label: &str) {
+        writeln!(self.asm, "{label}:").unwrap();
+    }
+
+    // The name of a function or a global in the assembly, which an asm label can change.
+    fn symbol(&self, name: &str) -> String {
+        self.symbols
+            .get(name)
+            .and_then(|attributes| attributes.label.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    // The name of a function or a global in an operand. GAS reads names like `sp` or `offset` as
+    // registers or operators there, so those refer to an alias defined at the end.
+    fn operand_symbol(&mut self, name: &str) -> String {
+        let symbol = self.symbol(name);
+        if !is_reserved_in_operands(&symbol) {
+            return symbol;
+        }
+
+        let alias = format!(".L.symbol.{symbol}");
+        self.aliases.insert(symbol);
+        alias
+    }
+
+    // Starts the section of a symbol, and makes it visible to other files unless it's static or
+    // weak.
+    fn start_symbol(&mut self, name: &str, section: &str, flags: &str, is_static: bool) {
+        let attributes = self.symbols.get(name).cloned().unwrap_or_default();
+        match attributes.section {
+            Some(section) => self.emit(&format!(".section {section},\"{flags}\",@progbits")),
+            None => self.emit(section),
+        }
+        if !is_static && !attributes.is_weak {
+            let symbol = self.symbol(name);
+            self.emit(&format!(".globl {symbol}"));
+        }
+    }
+
+    // Whether a global with this name may be one in another module at runtime, unless it's static
+    // or only visible in its own.
+    fn is_preemptible(&self, name: &str) -> bool {
+        let visibility = self
+            .symbols
+            .get(name)
+            .and_then(|attributes| attributes.visibility);
+        !self.static_globals.contains(name)
+            && visibility.map_or(true, |visibility| visibility == Visibility::Default)
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn global(&mut self, global: &Global) {
+        let Global {
+            name,
+            ty,
             init,
-            cond,
-            update,
-            then,
-        } => {
-            Ok(match init {
-                Some(init) => to_asem(init.as_ref())?,
-                None => "".to_string(),
-            } + &format!(
-                "{start_label}:
-    "
-            ) + &(match cond {
-                Some(cond) => to_asem(cond.as_ref())?,
-                // if no condition, it's an infinite loop
-                None => "        push 1".to_string(),
-            }) + &format!(
-                "        pop rax
-            cmp rax, 0
-            je {end_label}
-    "
-            ) + &(match update {
-                Some(update) => to_asem(update.as_ref())?,
-                None => "".to_string(),
-            }) + &to_asem(then.as_ref())?
-                + &format!(
-                    "        jmp {start_label}
-"
-                )
-                + &format!(
-                    "{end_label}:
-"
-                ))
+            relocations,
+            is_static,
+            is_thread_local,
+            align,
+        } = global;
+
+        // Thread-local variables are the image each thread's copy starts as.
+        let (section, flags) = match (is_thread_local, init.is_some()) {
+            (true, true) => (".section .tdata,\"awT\",@progbits", "awT"),
+            (true, false) => (".section .tbss,\"awT\",@nobits", "awT"),
+            (false, true) => (".data", "aw"),
+            (false, false) => (".bss", "aw"),
+        };
+        self.start_symbol(name, section, flags, *is_static);
+        self.emit(&format!(".align {align}"));
+        let symbol = self.symbol(name);
+        self.label(&symbol);
+
+        let Some(init) = init else {
+            self.emit(&format!(".zero {}", ty.size()));
+            return;
+        };
+
+        let mut offset = 0;
+        for relocation in relocations {
+            self.bytes(&init[offset..relocation.offset]);
+            let symbol = self.operand_symbol(&relocation.label);
+            self.emit(&format!(".quad {symbol}{:+}", relocation.addend));
+            offset = relocation.offset + 8;
+        }
+        self.bytes(&init[offset..]);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.emit(&format!(".byte {byte}"));
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let name = &function.name;
+        self.function = name.clone();
+        self.return_type = function.return_type.clone();
+        self.return_pointer = function.return_pointer;
+        self.frame_size = function.frame_size;
+        self.allocation = regalloc::allocate(function, self.target);
+        self.uses = vec![0; function.vregs];
+        for block in &function.blocks {
+            let insts = block.insts.iter().flat_map(Inst::uses);
+            for vreg in insts.chain(block.terminator.uses()) {
+                self.uses[vreg.0] += 1;
+            }
+        }
+
+        self.start_symbol(name, ".text", "ax", function.is_static);
+        let symbol = self.symbol(name);
+        self.label(&symbol);
+        self.emit("push rbp");
+        self.emit("mov rbp, rsp");
+        // A multiple of 16, to keep calls aligned. The callee-saved registers are saved below the
+        // slots.
+        let slots = self.allocation.stack_slots + self.allocation.callee_saved.len();
+        let stack_size = (function.frame_size + 8 * slots).next_multiple_of(16);
+        if stack_size > 0 {
+            self.emit(&format!("sub rsp, {stack_size}"));
+        }
+        for (index, register) in self.allocation.callee_saved.clone().into_iter().enumerate() {
+            let slot = self.slot(self.allocation.stack_slots + index);
+            self.emit(&format!("mov {slot}, {register}"));
+        }
+
+        let mut regs = ARG_REGS.iter();
+        if let Some(offset) = function.return_pointer {
+            let reg = regs.next().unwrap()[3];
+            self.emit(&format!("mov [rbp-{offset}], {reg}"));
+        }
+
+        // Registers are stored first, since copying from the stack uses some of them.
+        let mut stack_params = vec![];
+        for param in &function.params {
+            let classes = abi::classify(&param.ty);
+            let eightbytes = eightbytes(&param.ty);
+            if classes.contains(&Class::Memory) || regs.len() < eightbytes.len() {
+                stack_params.push(param);
+                continue;
+            }
+
+            for (offset, size) in eightbytes {
+                let address = format!("rbp-{}+{offset}", param.offset);
+                self.store_bytes(*regs.next().unwrap(), &address, size);
+            }
+        }
+
+        // Past the return address and the saved rbp.
+        let mut stack_offset = 16;
+        for param in stack_params {
+            self.emit(&format!("lea rsi, [rbp+{stack_offset}]"));
+            self.emit(&format!("lea rdi, [rbp-{}]", param.offset));
+            self.emit(&format!("mov rcx, {}", param.ty.size()));
+            self.emit("rep movsb");
+            stack_offset += param.ty.size().next_multiple_of(8);
+        }
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            self.block(BlockId(index), block, index + 1 == function.blocks.len());
+        }
+
+        self.label(&format!(".L.return.{name}"));
+        for (index, register) in self.allocation.callee_saved.clone().into_iter().enumerate() {
+            let slot = self.slot(self.allocation.stack_slots + index);
+            self.emit(&format!("mov {register}, {slot}"));
+        }
+        self.emit("mov rsp, rbp");
+        self.emit("pop rbp");
+        self.emit("ret");
+    }
+
+    fn block_label(&self, block: BlockId) -> String {
+        format!(".L.{}.{}", self.function, block)
+    }
+
+    // A jump to the next block is left out, since control falls through to it.
+    fn block(&mut self, id: BlockId, block: &Block, is_last: bool) {
+        let label = self.block_label(id);
+        self.label(&label);
+
+        // A comparison only branched on sets the flags for the branch itself.
+        let mut insts = &block.insts[..];
+        let mut fused = None;
+        if let (
+            Some(Inst::Cmp {
+                op,
+                is_unsigned,
+                dest,
+                lhs,
+                rhs,
+            }),
+            Terminator::Branch {
+                cond: Operand::Reg(cond),
+                ..
+            },
+        ) = (insts.last(), &block.terminator)
+        {
+            if dest == cond && self.uses[dest.0] == 1 {
+                insts = &insts[..insts.len() - 1];
+                fused = Some((*op, *is_unsigned, lhs, rhs));
+            }
+        }
+        for inst in insts {
+            self.inst(inst);
+        }
+
+        let next = BlockId(id.0 + 1);
+        match &block.terminator {
+            Terminator::Jump(target) => {
+                if *target != next {
+                    let label = self.block_label(*target);
+                    self.emit(&format!("jmp {label}"));
+                }
+            }
+            Terminator::Branch { cond, then, else_ } => {
+                if let Operand::Imm(value) = cond {
+                    let target = if *value != 0 { then } else { else_ };
+                    if *target != next {
+                        let label = self.block_label(*target);
+                        self.emit(&format!("jmp {label}"));
+                    }
+                    return;
+                }
+                let condition = match fused {
+                    Some((op, is_unsigned, lhs, rhs)) => self.compare(op, is_unsigned, lhs, rhs),
+                    None => {
+                        let cond = self.rm(cond, "rax");
+                        self.emit(&format!("cmp {cond}, 0"));
+                        "ne"
+                    }
+                };
+                let (then_label, else_label) = (self.block_label(*then), self.block_label(*else_));
+                if *then == next {
+                    self.emit(&format!("j{} {else_label}", negate(condition)));
+                } else {
+                    self.emit(&format!("j{condition} {then_label}"));
+                    if *else_ != next {
+                        self.emit(&format!("jmp {else_label}"));
+                    }
+                }
+            }
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    self.load("rax", value);
+                    if let Type::Struct(_) = self.return_type {
+                        self.return_struct();
+                    }
+                }
+                if !is_last {
+                    self.emit(&format!("jmp .L.return.{}", self.function));
+                }
+            }
+        }
+    }
+
+    fn slot(&self, index: usize) -> String {
+        format!("QWORD PTR [rbp-{}]", self.frame_size + 8 * (index + 1))
+    }
+
+    // The register or the slot of a virtual register.
+    fn vreg(&self, vreg: VReg) -> String {
+        match self.allocation.locations[vreg.0] {
+            Location::Register(register) => register.to_string(),
+            Location::Stack(index) => self.slot(index),
+        }
+    }
+
+    fn load(&mut self, reg: &str, operand: &Operand) {
+        let src = match operand {
+            Operand::Reg(vreg) => self.vreg(*vreg),
+            Operand::Imm(value) => value.to_string(),
+        };
+        if src != reg {
+            self.emit(&format!("mov {reg}, {src}"));
+        }
+    }
+
+    fn set(&mut self, dest: VReg, reg: &str) {
+        let dest = self.vreg(dest);
+        if dest != reg {
+            self.emit(&format!("mov {dest}, {reg}"));
+        }
+    }
+
+    // The register an operand is allocated, if it's in one.
+    fn register(&self, operand: &Operand) -> Option<Register> {
+        match operand {
+            Operand::Reg(vreg) => match self.allocation.locations[vreg.0] {
+                Location::Register(register) => Some(register),
+                Location::Stack(_) => None,
+            },
+            Operand::Imm(_) => None,
+        }
+    }
+
+    // Where an instruction computes `dest`, which is its own register unless it's spilled or
+    // holds one of `reads`, which are read after the result is first written.
+    fn result(&self, dest: VReg, reads: &[&Operand]) -> Register {
+        match self.register(&Operand::Reg(dest)) {
+            Some(register)
+                if !reads
+                    .iter()
+                    .any(|read| self.register(read) == Some(register)) =>
+            {
+                register
+            }
+            _ => Register::RAX,
+        }
+    }
+
+    // An operand as the source of an arithmetic instruction, which only takes a 32-bit immediate.
+    // Larger ones are loaded into `scratch`.
+    fn source(&mut self, operand: &Operand, scratch: &str) -> String {
+        match operand {
+            Operand::Imm(value) if i32::try_from(*value).is_ok() => value.to_string(),
+            _ => self.rm(operand, scratch),
         }
     }
+
+    // An operand as a register or memory, which immediates are loaded into `scratch` for.
+    fn rm(&mut self, operand: &Operand, scratch: &str) -> String {
+        match operand {
+            Operand::Reg(vreg) => self.vreg(*vreg),
+            Operand::Imm(_) => {
+                self.load(scratch, operand);
+                scratch.to_string()
+            }
+        }
+    }
+
+    // An address in brackets, whose base is loaded into `scratch` if it's in a spilled virtual
+    // register.
+    fn address(&mut self, address: &Address, scratch: &str) -> String {
+        let base = match &address.base {
+            Base::Reg(vreg) => match self.allocation.locations[vreg.0] {
+                Location::Register(register) => register.to_string(),
+                Location::Stack(_) => {
+                    self.load(scratch, &Operand::Reg(*vreg));
+                    scratch.to_string()
+                }
+            },
+            Base::Local(offset) => {
+                return format!("[rbp{:+}]", address.offset - *offset as i64);
+            }
+            // Code in a shared library reads the address of a global another module may define
+            // from the GOT.
+            Base::Global(name) if self.target.pic && self.is_preemptible(name) => {
+                let symbol = self.operand_symbol(name);
+                self.emit(&format!("mov {scratch}, QWORD PTR [rip+{symbol}@GOTPCREL]"));
+                scratch.to_string()
+            }
+            Base::Global(name) => format!("rip+{}", self.operand_symbol(name)),
+        };
+        match address.offset {
+            0 => format!("[{base}]"),
+            offset => format!("[{base}{offset:+}]"),
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dest, src } => {
+                let reg = self.result(*dest, &[]).name(8);
+                self.load(reg, src);
+                self.set(*dest, reg);
+            }
+            Inst::Binary { op, dest, lhs, rhs } => {
+                // A division and a shift by a variable count work in rax.
+                let reg = match (op, rhs) {
+                    (BinaryOp::SDiv | BinaryOp::UDiv, _)
+                    | (BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr, Operand::Reg(_)) => {
+                        Register::RAX
+                    }
+                    _ => self.result(*dest, &[rhs]),
+                };
+                self.load(reg.name(8), lhs);
+                self.binary(reg, *op, rhs);
+                self.set(*dest, reg.name(8));
+            }
+            Inst::Cmp {
+                op,
+                is_unsigned,
+                dest,
+                lhs,
+                rhs,
+            } => {
+                let condition = self.compare(*op, *is_unsigned, lhs, rhs);
+                self.emit(&format!("set{condition} al"));
+                let reg = self.result(*dest, &[]);
+                self.emit(&format!("movzx {}, al", reg.name(4)));
+                self.set(*dest, reg.name(8));
+            }
+            Inst::Neg { dest, src } => {
+                let reg = self.result(*dest, &[]).name(8);
+                self.load(reg, src);
+                self.emit(&format!("neg {reg}"));
+                self.set(*dest, reg);
+            }
+            Inst::Extend {
+                dest,
+                src,
+                size,
+                is_unsigned,
+            } => {
+                let reg = self.result(*dest, &[]);
+                let src = match self.register(src) {
+                    Some(register) => register,
+                    None => {
+                        self.load(reg.name(8), src);
+                        reg
+                    }
+                };
+                self.extend(reg, src, *size, *is_unsigned);
+                self.set(*dest, reg.name(8));
+            }
+            Inst::Addr { dest, address } => {
+                let reg = self.result(*dest, &[]).name(8);
+                let address = self.address(address, "r11");
+                self.emit(&format!("lea {reg}, {address}"));
+                self.set(*dest, reg);
+            }
+            // Through the GOT, which also works for functions in shared libraries.
+            Inst::FunctionAddr { dest, name } => {
+                let reg = self.result(*dest, &[]).name(8);
+                let symbol = self.operand_symbol(name);
+                self.emit(&format!("mov {reg}, [rip+{symbol}@GOTPCREL]"));
+                self.set(*dest, reg);
+            }
+            Inst::ThreadLocalAddr { dest, name } => {
+                self.thread_local(name);
+                self.set(*dest, "rax");
+            }
+            Inst::Load {
+                dest,
+                address,
+                size,
+                is_unsigned,
+                ..
+            } => {
+                let reg = self.result(*dest, &[]);
+                let address = self.address(address, "r11");
+                let (reg32, reg64) = (reg.name(4), reg.name(8));
+                self.emit(&match (size, is_unsigned) {
+                    (1, true) => format!("movzx {reg64}, BYTE PTR {address}"),
+                    (1, false) => format!("movsx {reg64}, BYTE PTR {address}"),
+                    (2, true) => format!("movzx {reg64}, WORD PTR {address}"),
+                    (2, false) => format!("movsx {reg64}, WORD PTR {address}"),
+                    // Writing a 32-bit register clears the upper half.
+                    (4, true) => format!("mov {reg32}, DWORD PTR {address}"),
+                    (4, false) => format!("movsxd {reg64}, DWORD PTR {address}"),
+                    _ => format!("mov {reg64}, QWORD PTR {address}"),
+                });
+                self.set(*dest, reg64);
+            }
+            Inst::Store {
+                address, src, size, ..
+            } => {
+                let address = self.address(address, "r11");
+                let ptr = ["BYTE", "WORD", "DWORD", "QWORD"][size.trailing_zeros() as usize];
+                let src = match (src, self.register(src)) {
+                    (Operand::Imm(value), _) if i32::try_from(*value).is_ok() => value.to_string(),
+                    (_, Some(register)) => register.name(*size).to_string(),
+                    _ => {
+                        self.load("rax", src);
+                        Register::RAX.name(*size).to_string()
+                    }
+                };
+                self.emit(&format!("mov {ptr} PTR {address}, {src}"));
+            }
+            Inst::MemCopy { dest, src, size } => {
+                self.load("rdi", dest);
+                self.load("rsi", src);
+                self.load("rcx", size);
+                self.emit("rep movsb");
+            }
+            Inst::MemSet { dest, value, size } => {
+                self.load("rdi", dest);
+                self.load("rax", value);
+                self.load("rcx", size);
+                self.emit("rep stosb");
+            }
+            Inst::Call {
+                dest,
+                callee,
+                args,
+                ty,
+                temporary,
+            } => {
+                self.call(callee, args, *temporary, ty);
+                self.set(*dest, "rax");
+            }
+            // Grows the stack by a multiple of 16 to keep it aligned.
+            Inst::Alloca { dest, size } => {
+                self.load("rax", size);
+                self.emit("add rax, 15");
+                self.emit("and rax, -16");
+                self.emit("sub rsp, rax");
+                self.set(*dest, "rsp");
+            }
+            Inst::StackSave { dest } => self.set(*dest, "rsp"),
+            Inst::StackRestore { src } => self.load("rsp", src),
+            Inst::Atomic {
+                op,
+                order,
+                dest,
+                args,
+                size,
+            } => {
+                self.atomic(*op, *order, args, *size);
+                self.set(*dest, "rax");
+            }
+            // Every locked instruction is a full barrier, so only a sequentially consistent
+            // fence needs an `mfence`.
+            Inst::Fence { order } => {
+                if *order == MemoryOrder::SeqCst {
+                    self.emit("mfence");
+                }
+            }
+            Inst::BitOp {
+                op,
+                dest,
+                src,
+                size,
+            } => {
+                self.load("rax", src);
+                self.bit_op(*op, *size);
+                self.set(*dest, "rax");
+            }
+            Inst::Overflow {
+                op,
+                is_unsigned,
+                dest,
+                lhs,
+                rhs,
+            } => {
+                self.load("rax", lhs);
+                self.load("r11", rhs);
+                match op {
+                    ArithOp::Mul if *is_unsigned => self.emit("mul r11"),
+                    _ => self.emit(&format!("{op} rax, r11")),
+                }
+                self.emit(match (op, is_unsigned) {
+                    (ArithOp::Add | ArithOp::Sub, true) => "setc al",
+                    _ => "seto al",
+                });
+                self.emit("movzx eax, al");
+                self.set(*dest, "rax");
+            }
+            Inst::Trap => self.emit("ud2"),
+            Inst::Phi { .. } => unreachable!("Phis are removed before code generation"),
+            Inst::Asm(asm) => self.inline_asm(asm),
+        }
+    }
+
+    // Compares `lhs` with `rhs`, and returns the condition code of `op`.
+    fn compare(
+        &mut self,
+        op: CmpOp,
+        is_unsigned: bool,
+        lhs: &Operand,
+        rhs: &Operand,
+    ) -> &'static str {
+        let lhs = match self.register(lhs) {
+            Some(register) => register.name(8),
+            None => {
+                self.load("rax", lhs);
+                "rax"
+            }
+        };
+        let rhs = self.source(rhs, "r11");
+        self.emit(&format!("cmp {lhs}, {rhs}"));
+        match (op, is_unsigned) {
+            (CmpOp::Eq, _) => "e",
+            (CmpOp::Neq, _) => "ne",
+            (CmpOp::Lt, false) => "l",
+            (CmpOp::Lte, false) => "le",
+            (CmpOp::Gt, false) => "g",
+            (CmpOp::Gte, false) => "ge",
+            (CmpOp::Lt, true) => "b",
+            (CmpOp::Lte, true) => "be",
+            (CmpOp::Gt, true) => "a",
+            (CmpOp::Gte, true) => "ae",
+        }
+    }
+
+    // Applies `op` to `reg` and `rhs`, where `reg` is rax for a division or a shift by a variable
+    // count.
+    fn binary(&mut self, reg: Register, op: BinaryOp, rhs: &Operand) {
+        let reg = reg.name(8);
+        match op {
+            BinaryOp::SDiv => {
+                let rhs = self.rm(rhs, "r11");
+                self.emit("cqo");
+                self.emit(&format!("idiv {rhs}"));
+            }
+            BinaryOp::UDiv => {
+                let rhs = self.rm(rhs, "r11");
+                self.emit("mov rdx, 0");
+                self.emit(&format!("div {rhs}"));
+            }
+            BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr => {
+                let mnemonic = match op {
+                    BinaryOp::Shl => "shl",
+                    BinaryOp::LShr => "shr",
+                    _ => "sar",
+                };
+                match rhs {
+                    Operand::Imm(count) => self.emit(&format!("{mnemonic} {reg}, {}", count & 63)),
+                    Operand::Reg(_) => {
+                        self.load("rcx", rhs);
+                        self.emit(&format!("{mnemonic} {reg}, cl"));
+                    }
+                }
+            }
+            _ => {
+                let mnemonic = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Mul => "imul",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    _ => "xor",
+                };
+                let rhs = self.source(rhs, "r11");
+                self.emit(&format!("{mnemonic} {reg}, {rhs}"));
+            }
+        }
+    }
+
+    // Replaces the operand of `size` bytes in rax with the result. Only the bits of the operand's
+    // size are counted, since a signed one is sign-extended.
+    fn bit_op(&mut self, op: BitOp, size: usize) {
+        let rax = Register::RAX.name(size);
+        match op {
+            // The index of the most significant 1 bit, subtracted from the highest one.
+            BitOp::Clz => {
+                self.emit(&format!("bsr {rax}, {rax}"));
+                self.emit(&format!("xor eax, {}", size * 8 - 1));
+            }
+            BitOp::Ctz => self.emit(&format!("bsf {rax}, {rax}")),
+            // Sums the bits in pairs, nibbles and then bytes, whose sum the multiplication leaves
+            // in the top byte. `popcnt` isn't in baseline x86-64.
+            BitOp::Popcount => {
+                if size == 4 {
+                    self.emit("mov eax, eax");
+                }
+                self.emit("mov r11, rax");
+                self.emit("shr r11, 1");
+                self.emit("mov rcx, 0x5555555555555555");
+                self.emit("and r11, rcx");
+                self.emit("sub rax, r11");
+                self.emit("mov r11, rax");
+                self.emit("shr r11, 2");
+                self.emit("mov rcx, 0x3333333333333333");
+                self.emit("and rax, rcx");
+                self.emit("and r11, rcx");
+                self.emit("add rax, r11");
+                self.emit("mov r11, rax");
+                self.emit("shr r11, 4");
+                self.emit("add rax, r11");
+                self.emit("mov rcx, 0x0f0f0f0f0f0f0f0f");
+                self.emit("and rax, rcx");
+                self.emit("mov rcx, 0x0101010101010101");
+                self.emit("imul rax, rcx");
+                self.emit("shr rax, 56");
+            }
+            // `bsf` sets ZF for 0, which gives -1 + 1.
+            BitOp::Ffs => {
+                self.emit("mov r11, -1");
+                self.emit(&format!("bsf {rax}, {rax}"));
+                self.emit("cmovz rax, r11");
+                self.emit("add eax, 1");
+            }
+            // Folds the operand into al with xor, which keeps the parity. PF is set for an even
+            // number of 1 bits in the result's low byte.
+            BitOp::Parity => {
+                if size == 8 {
+                    self.emit("mov r11, rax");
+                    self.emit("shr r11, 32");
+                    self.emit("xor eax, r11d");
+                }
+                self.emit("mov r11d, eax");
+                self.emit("shr r11d, 16");
+                self.emit("xor eax, r11d");
+                self.emit("xor al, ah");
+                self.emit("setnp al");
+                self.emit("movzx eax, al");
+            }
+            BitOp::Bswap if size == 2 => {
+                self.emit("rol ax, 8");
+                self.emit("movzx eax, ax");
+            }
+            BitOp::Bswap => self.emit(&format!("bswap {rax}")),
+        }
+    }
+
+    // Every locked instruction is a full barrier, so only a sequentially consistent store needs an
+    // `mfence`. The object's address is in rdi, and the operands in rsi and rdx. The result is left
+    // in rax, with only the bits of the object's size set.
+    fn atomic(&mut self, op: AtomicOp, order: MemoryOrder, args: &[Operand], size: usize) {
+        for (reg, arg) in ["rdi", "rsi", "rdx"].into_iter().zip(args) {
+            self.load(reg, arg);
+        }
+
+        let (rax, rsi, rdx) = (
+            Register::RAX.name(size),
+            Register::RSI.name(size),
+            Register::RDX.name(size),
+        );
+        match op {
+            AtomicOp::Load => self.load_bytes(RETURN_REGS[0], "rdi", size),
+            AtomicOp::Store => {
+                self.emit(&format!("mov [rdi], {rsi}"));
+                if order == MemoryOrder::SeqCst {
+                    self.emit("mfence");
+                }
+                self.emit("mov rax, rsi");
+            }
+            AtomicOp::Exchange => {
+                self.emit(&format!("xchg [rdi], {rsi}"));
+                self.emit("mov rax, rsi");
+            }
+            AtomicOp::Fetch {
+                op: op @ (FetchOp::Add | FetchOp::Sub),
+                returns_new,
+            } => {
+                if op == FetchOp::Sub {
+                    self.emit("neg rsi");
+                }
+                self.emit("mov rax, rsi");
+                self.emit(&format!("lock xadd [rdi], {rax}"));
+                if returns_new {
+                    self.emit("add rax, rsi");
+                }
+            }
+            // Retries until the object still holds the value the result was computed from.
+            AtomicOp::Fetch { op, returns_new } => {
+                let label = self.new_label();
+                self.emit(&format!("mov {rax}, [rdi]"));
+                self.label(&format!(".L.atomic.{label}"));
+                self.emit("mov rdx, rax");
+                match op {
+                    FetchOp::Nand => {
+                        self.emit("and rdx, rsi");
+                        self.emit("not rdx");
+                    }
+                    _ => self.emit(&format!("{op} rdx, rsi")),
+                }
+                self.emit(&format!("lock cmpxchg [rdi], {rdx}"));
+                self.emit(&format!("jne .L.atomic.{label}"));
+                if returns_new {
+                    self.emit("mov rax, rdx");
+                }
+            }
+            AtomicOp::CompareExchange {
+                through_pointer,
+                returns_previous,
+            } => {
+                if through_pointer {
+                    self.emit(&format!("mov {rax}, [rsi]"));
+                } else {
+                    self.emit("mov rax, rsi");
+                }
+                self.emit(&format!("lock cmpxchg [rdi], {rdx}"));
+                // The expected value is updated only on failure.
+                if through_pointer {
+                    let label = self.new_label();
+                    self.emit(&format!("je .L.atomic.{label}"));
+                    self.emit(&format!("mov [rsi], {rax}"));
+                    self.label(&format!(".L.atomic.{label}"));
+                }
+                if !returns_previous {
+                    self.emit("sete al");
+                    self.emit("movzx eax, al");
+                }
+            }
+            AtomicOp::Fence => unreachable!("A fence is an instruction of its own"),
+        }
+    }
+
+    // Calls by the System V ABI, and leaves the result in rax. The arguments passed on the stack
+    // are copied below rsp, and the rest are loaded into registers.
+    fn call(
+        &mut self,
+        callee: &Callee,
+        args: &[(Operand, Type)],
+        temporary: Option<usize>,
+        ty: &Type,
+    ) {
+        // A struct returned in memory is written where the hidden first argument points to.
+        let mut regs = ARG_REGS.iter();
+        let return_pointer = temporary.filter(|_| abi::is_memory(ty));
+        if return_pointer.is_some() {
+            regs.next();
+        }
+
+        // Each argument goes in registers, one per eightbyte, or on the stack if they run out.
+        let mut reg_args = vec![];
+        let mut stack_args = vec![];
+        let mut stack_size = 0;
+        for (index, (_, ty)) in args.iter().enumerate() {
+            let eightbytes = eightbytes(ty);
+            if abi::is_memory(ty) || regs.len() < eightbytes.len() {
+                stack_args.push((index, stack_size));
+                stack_size += ty.size().next_multiple_of(8);
+                continue;
+            }
+            for (offset, size) in eightbytes {
+                reg_args.push((index, offset, size, *regs.next().unwrap()));
+            }
+        }
+
+        // rsp must be a multiple of 16 at the call.
+        let area = stack_size.next_multiple_of(16);
+        if area > 0 {
+            self.emit(&format!("sub rsp, {area}"));
+        }
+
+        // Only through rax and r11, since the arguments in registers may be in any other.
+        for (index, offset) in stack_args {
+            let (arg, ty) = &args[index];
+            if let Type::Struct(_) = ty {
+                self.load("r11", arg);
+                for eightbyte in (0..ty.size()).step_by(8) {
+                    let size = (ty.size() - eightbyte).min(8);
+                    self.load_bytes(RETURN_REGS[0], &format!("r11+{eightbyte}"), size);
+                    self.store_bytes(RETURN_REGS[0], &format!("rsp+{}", offset + eightbyte), size);
+                }
+            } else {
+                self.load("rax", arg);
+                self.emit(&format!("mov [rsp+{offset}], rax"));
+            }
+        }
+
+        // Each register gets an argument, an eightbyte of the struct an argument points to, or
+        // the address of the callee.
+        let mut moves: Vec<Move> = reg_args
+            .into_iter()
+            .map(|(index, offset, size, reg)| {
+                let (arg, ty) = &args[index];
+                let part = matches!(ty, Type::Struct(_)).then_some((offset, size));
+                (Register::from_name(reg[3]).unwrap(), arg, part)
+            })
+            .collect();
+        if let Callee::Indirect(address) = callee {
+            moves.push((Register::R10, address, None));
+        }
+        self.parallel_move(moves);
+        if let Some(offset) = return_pointer {
+            self.emit(&format!("lea rdi, [rbp-{offset}]"));
+        }
+
+        match callee {
+            Callee::Direct(name) => {
+                // The number of vector registers used by variadic functions.
+                self.emit("mov rax, 0");
+                let symbol = self.operand_symbol(name);
+                self.emit(&format!("call {symbol}"));
+            }
+            Callee::Indirect(_) => {
+                self.emit("mov rax, 0");
+                self.emit("call r10");
+            }
+        }
+
+        if area > 0 {
+            self.emit(&format!("add rsp, {area}"));
+        }
+
+        match temporary {
+            // The callee returns the hidden pointer.
+            Some(_) if return_pointer.is_some() => {}
+            // The value of a struct is its address.
+            Some(offset) => {
+                for ((eightbyte, size), reg) in eightbytes(ty).into_iter().zip(RETURN_REGS) {
+                    self.store_bytes(reg, &format!("rbp-{offset}+{eightbyte}"), size);
+                }
+                self.emit(&format!("lea rax, [rbp-{offset}]"));
+            }
+            // Only the bits of the return type are set.
+            None => self.truncate(ty),
+        }
+    }
+
+    // Makes the moves as if all at once, since a register may be read by one move and written by
+    // another. A move whose register no other move still reads goes first, and when there's none,
+    // the rest are cycles, which swapping two registers shortens. What a move reads is in a
+    // register, or the operand itself if it isn't in one.
+    fn parallel_move(&mut self, moves: Vec<Move>) {
+        let mut moves: Vec<_> = moves
+            .into_iter()
+            .map(|(dest, src, part)| (dest, self.register(src).ok_or(src), part))
+            .collect();
+        while !moves.is_empty() {
+            let ready = (0..moves.len()).find(|&index| {
+                let dest = moves[index].0;
+                moves
+                    .iter()
+                    .enumerate()
+                    .all(|(other, (_, src, _))| other == index || *src != Ok(dest))
+            });
+            let index = match ready {
+                Some(index) => index,
+                None => {
+                    let (dest, Ok(src), _) = moves[0] else {
+                        unreachable!("A move in a cycle reads a register");
+                    };
+                    self.emit(&format!("xchg {dest}, {src}"));
+                    for (_, read, _) in &mut moves {
+                        if *read == Ok(src) {
+                            *read = Ok(dest);
+                        } else if *read == Ok(dest) {
+                            *read = Ok(src);
+                        }
+                    }
+                    0
+                }
+            };
+
+            let (dest, src, part) = moves.remove(index);
+            let names = [1, 2, 4, 8].map(|size| dest.name(size));
+            match (src, part) {
+                (Ok(src), None) if src != dest => self.emit(&format!("mov {dest}, {src}")),
+                (Ok(_), None) => {}
+                (Err(src), None) => self.load(dest.name(8), src),
+                (Ok(src), Some((offset, size))) => {
+                    self.emit(&format!("mov r11, {src}"));
+                    self.load_bytes(names, &format!("r11+{offset}"), size);
+                }
+                (Err(src), Some((offset, size))) => {
+                    self.load("r11", src);
+                    self.load_bytes(names, &format!("r11+{offset}"), size);
+                }
+            }
+        }
+    }
+
+    // Returns the struct whose address is in rax, in registers or through the hidden pointer.
+    fn return_struct(&mut self) {
+        let size = self.return_type.size();
+        if let Some(offset) = self.return_pointer {
+            self.emit("mov rsi, rax");
+            self.emit(&format!("mov rdi, [rbp-{offset}]"));
+            self.emit(&format!("mov rcx, {size}"));
+            self.emit("rep movsb");
+            self.emit(&format!("mov rax, [rbp-{offset}]"));
+            return;
+        }
+
+        self.emit("mov r11, rax");
+        for ((offset, size), reg) in eightbytes(&self.return_type).into_iter().zip(RETURN_REGS) {
+            self.load_bytes(reg, &format!("r11+{offset}"), size);
+        }
+    }
+
+    // Loads `size` bytes at `address` into the register named `reg` by size, zero-extended.
+    fn load_bytes(&mut self, reg: [&str; 4], address: &str, size: usize) {
+        match size {
+            8 => self.emit(&format!("mov {}, [{address}]", reg[3])),
+            // Writing a 32-bit register clears the upper half.
+            4 => self.emit(&format!("mov {}, DWORD PTR [{address}]", reg[2])),
+            2 => self.emit(&format!("movzx {}, WORD PTR [{address}]", reg[2])),
+            1 => self.emit(&format!("movzx {}, BYTE PTR [{address}]", reg[2])),
+            // Byte by byte from the last, so that nothing past the value is read.
+            _ => {
+                self.emit(&format!("mov {}, 0", reg[3]));
+                for index in (0..size).rev() {
+                    self.emit(&format!("shl {}, 8", reg[3]));
+                    self.emit(&format!("mov {}, [{address}+{index}]", reg[0]));
+                }
+            }
+        }
+    }
+
+    // Stores the low `size` bytes of the register named `reg` by size to `address`. The register
+    // is clobbered for sizes other than 1, 2, 4 and 8.
+    fn store_bytes(&mut self, reg: [&str; 4], address: &str, size: usize) {
+        match size {
+            8 => self.emit(&format!("mov [{address}], {}", reg[3])),
+            4 => self.emit(&format!("mov [{address}], {}", reg[2])),
+            2 => self.emit(&format!("mov [{address}], {}", reg[1])),
+            1 => self.emit(&format!("mov [{address}], {}", reg[0])),
+            _ => {
+                for index in 0..size {
+                    self.emit(&format!("mov [{address}+{index}], {}", reg[0]));
+                    self.emit(&format!("shr {}, 8", reg[3]));
+                }
+            }
+        }
+    }
+
+    // The address of a thread-local variable in rax, relative to the thread pointer at fs:0.
+    fn thread_local(&mut self, name: &str) {
+        let symbol = self.operand_symbol(name);
+        if self.target.pic {
+            // General dynamic: `__tls_get_addr` finds it in whichever module defines it. The
+            // prefixes make the sequence as long as those the linker may replace it with.
+            self.emit(&format!("data16 lea rdi, {symbol}@tlsgd[rip]"));
+            self.emit(".value 0x6666");
+            self.emit("rex64");
+            self.emit("call __tls_get_addr@PLT");
+        } else if self.thread_locals.contains(name) {
+            // Local exec: the executable's own block is at a fixed offset.
+            self.emit("mov rax, QWORD PTR fs:0");
+            self.emit(&format!("lea rax, [rax+{symbol}@tpoff]"));
+        } else {
+            // Initial exec: the offset is only known at load time, and read from the GOT.
+            self.emit(&format!("mov rax, QWORD PTR {symbol}@gottpoff[rip]"));
+            self.emit("add rax, QWORD PTR fs:0");
+        }
+    }
+
+    // Every register an operand is in is loaded right before the asm, and the outputs in registers
+    // are stored to their slots right after it.
+    fn inline_asm(&mut self, asm: &Asm) {
+        let Asm {
+            template,
+            operands,
+            inputs,
+            outputs,
+            saved,
+        } = asm;
+
+        for register in saved {
+            self.emit(&format!("push {register}"));
+        }
+        for (register, operand) in inputs {
+            self.load(register.name(8), operand);
+        }
+
+        let unique = self.new_label();
+        let mut text = String::new();
+        for piece in template {
+            match piece {
+                Piece::Text(str) => text.push_str(str),
+                Piece::Operand { index, modifier } => {
+                    text.push_str(&self.asm_operand(&operands[*index], *modifier));
+                }
+                Piece::Unique => write!(text, "{unique}").unwrap(),
+            }
+        }
+        self.asm_lines(&text);
+
+        for (dest, register) in outputs {
+            self.set(*dest, register.name(8));
+        }
+        for register in saved.iter().rev() {
+            self.emit(&format!("pop {register}"));
+        }
+    }
+
+    // How an operand is written in the template, where `b`, `w`, `k` and `q` choose its size and
+    // `h` the high byte of a register.
+    fn asm_operand(&mut self, operand: &AsmOperand, modifier: Option<char>) -> String {
+        let size = match modifier {
+            Some('b') => 1,
+            Some('w') => 2,
+            Some('k') => 4,
+            Some('q') => 8,
+            _ => operand.size,
+        };
+        let address = match &operand.place {
+            AsmPlace::Register(register) => {
+                return match modifier {
+                    Some('h')
+                        if [Register::RAX, Register::RBX, Register::RCX, Register::RDX]
+                            .contains(register) =>
+                    {
+                        register.name(1).replace('l', "h")
+                    }
+                    _ => register.name(size).to_string(),
+                };
+            }
+            AsmPlace::Immediate(value) => return value.to_string(),
+            AsmPlace::Memory(base) => format!("[{base}]"),
+            // Its base is never a virtual register.
+            AsmPlace::Variable(address) => self.address(address, "rax"),
+        };
+        match size {
+            1 => format!("BYTE PTR {address}"),
+            2 => format!("WORD PTR {address}"),
+            4 => format!("DWORD PTR {address}"),
+            8 => format!("QWORD PTR {address}"),
+            _ => address,
+        }
+    }
+
+    // The lines of an asm, emitted as they are written between the markers the peephole pass
+    // leaves alone.
+    fn asm_lines(&mut self, text: &str) {
+        writeln!(self.asm, "#APP").unwrap();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            self.emit(line);
+        }
+        writeln!(self.asm, "#NO_APP").unwrap();
+    }
+
+    // Truncates rax to `ty`, then extends it back to 64 bits by the signedness of `ty`.
+    fn truncate(&mut self, ty: &Type) {
+        if ty.is_integer() {
+            let is_unsigned = self.target.is_unsigned(ty);
+            self.extend(Register::RAX, Register::RAX, ty.size(), is_unsigned);
+        }
+    }
+
+    // Truncates `src` to `size` bytes into `dest`, then extends it back to 64 bits.
+    fn extend(&mut self, dest: Register, src: Register, size: usize, is_unsigned: bool) {
+        let (dest32, dest64, src) = (dest.name(4), dest.name(8), src.name(size));
+        match size {
+            1 | 2 if is_unsigned => self.emit(&format!("movzx {dest64}, {src}")),
+            1 | 2 => self.emit(&format!("movsx {dest64}, {src}")),
+            // Writing a 32-bit register clears the upper half.
+            4 if is_unsigned => self.emit(&format!("mov {dest32}, {src}")),
+            4 => self.emit(&format!("movsxd {dest64}, {src}")),
+            _ if dest64 != src => self.emit(&format!("mov {dest64}, {src}")),
+            _ => {}
+        }
+    }
+}
+
+// The condition code which holds when `condition` doesn't.
+fn negate(condition: &str) -> &'static str {
+    match condition {
+        "e" => "ne",
+        "ne" => "e",
+        "l" => "ge",
+        "le" => "g",
+        "g" => "le",
+        "ge" => "l",
+        "b" => "ae",
+        "be" => "a",
+        "a" => "be",
+        "ae" => "b",
+        _ => unreachable!("Unknown condition code '{condition}'"),
+    }
+}
+
+// Whether GAS reads `symbol` in an Intel syntax operand as a register or an operator, ignoring
+// case.
+fn is_reserved_in_operands(symbol: &str) -> bool {
+    let symbol = symbol.to_ascii_lowercase();
+    let numbered =
+        |prefix: &str, count: u32| (0..count).any(|number| symbol == format!("{prefix}{number}"));
+
+    let general = ["ax", "bx", "cx", "dx", "si", "di", "sp", "bp"]
+        .into_iter()
+        .any(|name| {
+            ["", "e", "r"]
+                .iter()
+                .any(|prefix| symbol == format!("{prefix}{name}"))
+        });
+    let extended = (8..16).any(|number| {
+        ["", "d", "w", "b"]
+            .iter()
+            .any(|suffix| symbol == format!("r{number}{suffix}"))
+    });
+
+    general
+        || extended
+        || [
+            "al", "bl", "cl", "dl", "ah", "bh", "ch", "dh", "sil", "dil", "spl", "bpl", "rip",
+            "eip", "es", "cs", "ss", "ds", "fs", "gs", "st", "byte", "word", "dword", "qword",
+            "fword", "tbyte", "oword", "xmmword", "ymmword", "zmmword", "mmword", "offset",
+            "short", "near", "far", "flat", "and", "or", "xor", "not", "shl", "shr", "mod", "eq",
+            "ne", "lt", "le", "gt", "ge",
+        ]
+        .contains(&symbol.as_str())
+        || [("mm", 8), ("k", 8), ("xmm", 32), ("ymm", 32), ("zmm", 32)]
+            .into_iter()
+            .chain([("cr", 16), ("dr", 16), ("bnd", 4)])
+            .any(|(prefix, count)| numbered(prefix, count))
+}
+
+// The offset and size of each eightbyte of a `ty` passed in registers. A scalar is a single
+// eightbyte of its own size.
+fn eightbytes(ty: &Type) -> Vec<(usize, usize)> {
+    let Type::Struct(_) = ty.unqualified() else {
+        return vec![(0, ty.size())];
+    };
+
+    abi::classify(ty)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, class)| *class == Class::Integer)
+        .map(|(index, _)| (index * 8, (ty.size() - index * 8).min(8)))
+        .collect()
 }
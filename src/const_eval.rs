@@ -0,0 +1,381 @@
+use crate::{
+    builtin::BitOp,
+    parser::{ArithOp, CmpOp},
+    sema::{Expr, ExprKind},
+    source_map::Span,
+    types::{Target, Type},
+};
+
+/// The value of an integer constant expression.
+#[derive(Debug, PartialEq)]
+pub struct Constant {
+    pub value: i64,
+    // The first operation whose signed result didn't fit in its type, and so wrapped around.
+    pub overflow: Option<Span>,
+}
+
+/// The value of an address constant: `offset` bytes from `label`, or just `offset` without one.
+#[derive(Debug, Default, PartialEq)]
+pub struct Address {
+    pub label: Option<String>,
+    pub offset: i64,
+}
+
+/// Why an expression isn't an integer constant expression.
+#[derive(Debug, PartialEq)]
+pub enum NotConstant {
+    // Only known at runtime, like the value of a variable.
+    Runtime(Span),
+    DivisionByZero(Span),
+}
+
+/// Evaluates `expr` with the wraparound of its types. Signed overflow is undefined at runtime,
+/// but here it's reported in `Constant::overflow` and wraps around like at runtime. Values of
+/// unsigned types are kept as the bits of an `i64`.
+pub fn eval(expr: &Expr, target: Target) -> Result<Constant, NotConstant> {
+    let mut overflow = None;
+    let value = Evaluator {
+        overflow: &mut overflow,
+        target,
+    }
+    .eval(expr)?;
+
+    Ok(Constant { value, overflow })
+}
+
+/// Whether `expr` is a constant equal to 0, or None if it isn't constant. Unlike its sign, whether
+/// a value is 0 doesn't depend on the target.
+pub fn is_zero(expr: &Expr) -> Option<bool> {
+    eval(expr, Target::default())
+        .ok()
+        .map(|constant| constant.value == 0)
+}
+
+/// Evaluates a constant which can also be the address of a global plus an offset, like
+/// `&a[1]`, as allowed in the initializers of globals. Overflow isn't reported.
+pub fn eval_address(expr: &Expr, target: Target) -> Result<Address, NotConstant> {
+    Evaluator {
+        overflow: &mut None,
+        target,
+    }
+    .address(expr)
+}
+
+struct Evaluator<'a> {
+    overflow: &'a mut Option<Span>,
+    target: Target,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<i64, NotConstant> {
+        match &expr.kind {
+            ExprKind::Num(num) => Ok(*num),
+            // Conversions wrap around without overflowing.
+            ExprKind::Cast { operand } => {
+                let value = self.value(operand)?;
+                Ok(self.wrap(value, &expr.ty))
+            }
+            ExprKind::Neg { operand } => {
+                let (value, overflowed) = self.value(operand)?.overflowing_neg();
+                Ok(self.arithmetic(value, overflowed, expr))
+            }
+            ExprKind::ArithOp { value, lhs, rhs } => {
+                let (lhs, rhs) = (self.value(lhs)?, self.value(rhs)?);
+
+                let (value, overflowed) = match value {
+                    ArithOp::Add => lhs.overflowing_add(rhs),
+                    ArithOp::Sub => lhs.overflowing_sub(rhs),
+                    ArithOp::Mul => lhs.overflowing_mul(rhs),
+                    ArithOp::Div if rhs == 0 => return Err(NotConstant::DivisionByZero(expr.span)),
+                    // Truncates toward zero like C.
+                    ArithOp::Div => lhs.overflowing_div(rhs),
+                    ArithOp::Assign => unreachable!("Assignments are ExprKind::Assign"),
+                };
+                Ok(self.arithmetic(value, overflowed, expr))
+            }
+            ExprKind::CmpOp { value, lhs, rhs } => {
+                let (lhs, rhs) = (self.value(lhs)?, self.value(rhs)?);
+
+                Ok(i64::from(match value {
+                    CmpOp::Lt => lhs < rhs,
+                    CmpOp::Lte => lhs <= rhs,
+                    CmpOp::Gt => lhs > rhs,
+                    CmpOp::Gte => lhs >= rhs,
+                    CmpOp::Eq => lhs == rhs,
+                    CmpOp::Neq => lhs != rhs,
+                }))
+            }
+            ExprKind::BitOp { op, operand } => {
+                let bits = operand.ty.size() as u32 * 8;
+                let value = self.eval(operand)? as u64 & (u64::MAX >> (64 - bits));
+
+                Ok(match op {
+                    // Undefined, so left to runtime.
+                    BitOp::Clz | BitOp::Ctz if value == 0 => {
+                        return Err(NotConstant::Runtime(expr.span))
+                    }
+                    BitOp::Clz => i64::from(value.leading_zeros() - (64 - bits)),
+                    BitOp::Ctz => i64::from(value.trailing_zeros()),
+                    BitOp::Popcount => i64::from(value.count_ones()),
+                    BitOp::Ffs if value == 0 => 0,
+                    BitOp::Ffs => i64::from(value.trailing_zeros() + 1),
+                    BitOp::Parity => i64::from(value.count_ones() % 2),
+                    BitOp::Bswap => (value.swap_bytes() >> (64 - bits)) as i64,
+                })
+            }
+            ExprKind::Local { .. }
+            | ExprKind::Global { .. }
+            | ExprKind::ThreadLocal { .. }
+            | ExprKind::Function { .. }
+            | ExprKind::Assign { .. }
+            | ExprKind::Addr { .. }
+            | ExprKind::Deref { .. }
+            | ExprKind::Member { .. }
+            | ExprKind::MemZero { .. }
+            | ExprKind::Comma { .. }
+            | ExprKind::Call { .. }
+            | ExprKind::Alloca { .. }
+            | ExprKind::StmtExpr { .. }
+            | ExprKind::Atomic { .. }
+            | ExprKind::Trap
+            | ExprKind::Overflow { .. }
+            | ExprKind::MemCopy { .. }
+            | ExprKind::MemSet { .. } => Err(NotConstant::Runtime(expr.span)),
+        }
+    }
+
+    fn address(&mut self, expr: &Expr) -> Result<Address, NotConstant> {
+        match &expr.kind {
+            ExprKind::Addr { operand } => self.lvalue(operand),
+            // Only conversions between pointers and `long` keep the address.
+            ExprKind::Cast { operand }
+                if expr.ty.size() == 8 && matches!(operand.ty, Type::Pointer(_)) =>
+            {
+                self.address(operand)
+            }
+            ExprKind::ArithOp { value, lhs, rhs } if matches!(expr.ty, Type::Pointer(_)) => {
+                let Address { label, offset } = self.address(lhs)?;
+                let rhs = self.eval(rhs)?;
+
+                let offset = match value {
+                    ArithOp::Sub => offset.wrapping_sub(rhs),
+                    _ => offset.wrapping_add(rhs),
+                };
+                Ok(Address { label, offset })
+            }
+            _ => Ok(Address {
+                label: None,
+                offset: self.eval(expr)?,
+            }),
+        }
+    }
+
+    // The address of an object with static storage, or of a function.
+    fn lvalue(&mut self, expr: &Expr) -> Result<Address, NotConstant> {
+        match &expr.kind {
+            ExprKind::Global { name } | ExprKind::Function { name } => Ok(Address {
+                label: Some(name.clone()),
+                offset: 0,
+            }),
+            ExprKind::Member {
+                operand, offset, ..
+            } => {
+                let address = self.lvalue(operand)?;
+                Ok(Address {
+                    offset: address.offset + *offset as i64,
+                    ..address
+                })
+            }
+            ExprKind::Deref { operand } => self.address(operand),
+            _ => Err(NotConstant::Runtime(expr.span)),
+        }
+    }
+
+    // The value of `expr` as a number, not as the bits of an `i64`.
+    fn value(&mut self, expr: &Expr) -> Result<i128, NotConstant> {
+        let value = self.eval(expr)?;
+
+        Ok(if self.target.is_unsigned(&expr.ty) {
+            i128::from(value as u64)
+        } else {
+            i128::from(value)
+        })
+    }
+
+    // The result of arithmetic in `expr`, wrapped around to its type. It's exact unless it
+    // `overflowed` an `i128`, which keeps the low bits. Unsigned arithmetic wraps around without
+    // overflowing.
+    fn arithmetic(&mut self, value: i128, overflowed: bool, expr: &Expr) -> i64 {
+        let wrapped = self.wrap(value, &expr.ty);
+        if !self.target.is_unsigned(&expr.ty) && (overflowed || i128::from(wrapped) != value) {
+            self.overflow.get_or_insert(expr.span);
+        }
+
+        wrapped
+    }
+
+    // The value of `ty` with the same low bits as `value`, or 0 or 1 for `_Bool`.
+    fn wrap(&self, value: i128, ty: &Type) -> i64 {
+        let is_unsigned = self.target.is_unsigned(ty);
+
+        match (ty.unqualified(), ty.size()) {
+            (Type::Bool, _) => i64::from(value != 0),
+            (_, 1) if is_unsigned => value as u8 as i64,
+            (_, 1) => value as i8 as i64,
+            (_, 2) if is_unsigned => value as u16 as i64,
+            (_, 2) => value as i16 as i64,
+            (_, 4) if is_unsigned => value as u32 as i64,
+            (_, 4) => value as i32 as i64,
+            _ => value as i64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lower::parse_str, sema, sema::Stmt};
+
+    use super::*;
+
+    // The value of the return statement ending the last function.
+    fn return_expr(c_code: &str) -> Expr {
+        let (nodes, mut diags) = parse_str(c_code, &[]);
+        let mut program = sema::analyze(&nodes, Target::default(), &mut diags);
+
+        let Some(Stmt::Return(Some(expr))) = program.functions.pop().unwrap().body.pop() else {
+            panic!("Must be a return statement");
+        };
+        expr
+    }
+
+    // Evaluates `return <c_expr>;` in a function returning `long`.
+    fn eval_str(c_expr: &str) -> Result<Constant, NotConstant> {
+        eval(
+            &return_expr(&format!("long f(void) {{ return {c_expr}; }}")),
+            Target::default(),
+        )
+    }
+
+    fn value(c_expr: &str) -> i64 {
+        eval_str(c_expr).unwrap().value
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(value("1 + 2 * 3 - 4"), 3);
+        assert_eq!(value("-7 / 2"), -3);
+        assert_eq!(value("(3 > 2) + (3 == 2) + (2 <= 2)"), 2);
+        assert_eq!(value("-(-5)"), 5);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(value("(char)255"), -1);
+        assert_eq!(value("(short)65537"), 1);
+        assert_eq!(value("(long)2147483647 + 1"), 2147483648);
+        assert_eq!(
+            value("sizeof(long) + sizeof 1 + sizeof(char) + _Alignof(short)"),
+            15
+        );
+    }
+
+    #[test]
+    fn test_unsigned() {
+        assert_eq!(value("(unsigned char)-1"), 255);
+        assert_eq!(value("-1 < 1u"), 0);
+        assert_eq!(value("(unsigned)-1 / 2"), 2147483647);
+        assert_eq!(
+            value("(long)(18446744073709551615ul / 3)"),
+            6148914691236517205
+        );
+        assert_eq!(value("(_Bool)256 + (_Bool)0"), 1);
+        assert_eq!(eval_str("4294967295u + 1").unwrap().overflow, None);
+        // The exact product doesn't fit in an i128.
+        let product = eval_str("18446744073709551615ul * 18446744073709551615ul").unwrap();
+        assert_eq!(product.value, 1);
+        assert_eq!(product.overflow, None);
+        assert_eq!(value("(0ul - 1) * (0ul - 1) > 0"), 1);
+
+        let unsigned_char = Target {
+            unsigned_char: true,
+            ..Target::default()
+        };
+        let expr = return_expr("long f(void) { return (char)200; }");
+        assert_eq!(eval(&expr, Target::default()).unwrap().value, -56);
+        assert_eq!(eval(&expr, unsigned_char).unwrap().value, 200);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let span = |start, end| Span::new(crate::source_map::FileId::default(), start, end);
+
+        assert_eq!(
+            eval_str("2147483647 + 1"),
+            Ok(Constant {
+                value: -2147483648,
+                overflow: Some(span(22, 36)),
+            })
+        );
+        assert_eq!(
+            eval_str("-(-2147483647 - 1)"),
+            Ok(Constant {
+                value: -2147483648,
+                overflow: Some(span(22, 40)),
+            })
+        );
+        assert_eq!(value("9223372036854775807 * 2"), -2);
+        // `char` is promoted to `int` before the addition.
+        assert_eq!(eval_str("(char)127 + 1").unwrap().overflow, None);
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(value("__builtin_clzl(1) + __builtin_ctz(8u) * 100"), 363);
+        assert_eq!(value("__builtin_popcountll(-1) + __builtin_parity(7)"), 65);
+        assert_eq!(value("__builtin_ffs(0) + __builtin_ffsl(-4)"), 3);
+        assert_eq!(value("__builtin_bswap16(-256)"), 255);
+        assert_eq!(value("__builtin_bswap32(255)"), 4278190080);
+        // Counting the zeros of 0 is undefined.
+        assert!(matches!(
+            eval_str("__builtin_clz(0)"),
+            Err(NotConstant::Runtime(_))
+        ));
+    }
+
+    #[test]
+    fn test_address() {
+        let address = |c_code| {
+            eval_address(
+                &return_expr(&format!(
+                    "struct P {{ int x; long y[2]; }} p; long *f(void) {{ return {c_code}; }}"
+                )),
+                Target::default(),
+            )
+        };
+
+        assert_eq!(
+            address("&p.y[1] - 1"),
+            Ok(Address {
+                label: Some("p".to_string()),
+                offset: 8,
+            })
+        );
+        assert_eq!(
+            address("(long *)16"),
+            Ok(Address {
+                label: None,
+                offset: 16,
+            })
+        );
+        assert!(matches!(address("&p.y[p.x]"), Err(NotConstant::Runtime(_))));
+    }
+
+    #[test]
+    fn test_not_constant() {
+        assert!(matches!(
+            eval_str("1 / (2 - 2)"),
+            Err(NotConstant::DivisionByZero(_))
+        ));
+        assert!(matches!(eval_str("1 + f()"), Err(NotConstant::Runtime(_))));
+    }
+}
@@ -0,0 +1,433 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::{
+    source_map::{FileId, Span},
+    warning::{DiagnosticPragma, Warning, WarningLevel, WarningOptions},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            }
+        )
+    }
+}
+
+/// A secondary span shown alongside the primary one, e.g. "previous declaration is here".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    Note,
+    Help,
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                NoteKind::Note => "note",
+                NoteKind::Help => "help",
+            }
+        )
+    }
+}
+
+/// Additional information printed after a [`Diagnostic`], optionally with its own snippet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub kind: NoteKind,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+/// A suggested edit: replace the text of `span` (empty for an insertion) with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixIt {
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    // The group of a warning, even when -Werror has turned it into an error.
+    pub warning: Option<Warning>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
+    pub fix_its: Vec<FixIt>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            warning: None,
+            labels: vec![],
+            notes: vec![],
+            fix_its: vec![],
+        }
+    }
+
+    pub fn warning(warning: Warning, span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            warning: Some(warning),
+            ..Self::error(span, message)
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, span: Option<Span>, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Note,
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_help(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Help,
+            span: None,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_fix_it(mut self, span: Span, replacement: impl Into<String>) -> Self {
+        self.fix_its.push(FixIt {
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+}
+
+/// Every diagnostic of a compilation that failed, or the warnings of one that succeeded.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{}: {}", diagnostic.severity, diagnostic.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Collects diagnostics so that a single run reports as many problems as possible.
+#[derive(Debug, Default)]
+pub struct DiagnosticEngine {
+    diagnostics: Vec<Diagnostic>,
+    warning_options: WarningOptions,
+    // `#pragma GCC diagnostic` directives in source order, with the span of their `#`.
+    pragmas: Vec<(Span, DiagnosticPragma)>,
+    // The file of each macro expansion, with the name of the macro and the identifier it replaced.
+    expansions: HashMap<FileId, (String, Span)>,
+}
+
+impl DiagnosticEngine {
+    pub fn new(warning_options: WarningOptions) -> Self {
+        Self {
+            warning_options,
+            ..Self::default()
+        }
+    }
+
+    /// Emits `diagnostic`. A warning is dropped, kept, or turned into an error depending on the
+    /// -W flags and the pragmas before it. Inside a macro expansion, it gets a note for every
+    /// macro it was expanded from.
+    pub fn emit(&mut self, mut diagnostic: Diagnostic) {
+        if let Some(warning) = diagnostic.warning {
+            diagnostic.severity = match self.warning_level(warning, diagnostic.span) {
+                WarningLevel::Ignored => return,
+                WarningLevel::Warning => Severity::Warning,
+                WarningLevel::Error => Severity::Error,
+            };
+        }
+
+        let mut span = diagnostic.span;
+        while let Some((name, site)) = self.expansions.get(&span.file) {
+            diagnostic = diagnostic.with_note(Some(*site), format!("Expanded from macro '{name}'"));
+            span = *site;
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn pragma(&mut self, span: Span, pragma: DiagnosticPragma) {
+        self.pragmas.push((span, pragma));
+    }
+
+    /// Records that the tokens of `file` are the replacement list of the macro `name`, which
+    /// replaced the identifier at `site`.
+    pub fn expansion(&mut self, file: FileId, name: &str, site: Span) {
+        self.expansions.insert(file, (name.to_string(), site));
+    }
+
+    // Where the code `span` comes from is, outside of every macro expansion.
+    fn site(&self, mut span: Span) -> Span {
+        while let Some(&(_, site)) = self.expansions.get(&span.file) {
+            span = site;
+        }
+        span
+    }
+
+    fn warning_level(&self, warning: Warning, span: Span) -> WarningLevel {
+        let span = self.site(span);
+        let mut level = self.warning_options.level(warning);
+        let mut stack = vec![];
+
+        for &(_, pragma) in self.pragmas.iter().filter(|(pragma_span, _)| {
+            pragma_span.file == span.file && pragma_span.start < span.start
+        }) {
+            match pragma {
+                DiagnosticPragma::Push => stack.push(level),
+                // Like gcc, a pop without a matching push restores the command-line level.
+                DiagnosticPragma::Pop => {
+                    level = stack
+                        .pop()
+                        .unwrap_or_else(|| self.warning_options.level(warning))
+                }
+                DiagnosticPragma::Set(target, target_level) if target == warning => {
+                    level = target_level
+                }
+                DiagnosticPragma::Set(..) => {}
+            }
+        }
+
+        level
+    }
+
+    pub fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.emit(Diagnostic::error(span, message));
+    }
+
+    /// Drops the warnings of `warning` inside `span`, for an error about the same problem.
+    pub fn retract(&mut self, warning: Warning, span: Span) {
+        self.diagnostics.retain(|diagnostic| {
+            diagnostic.warning != Some(warning)
+                || diagnostic.span.file != span.file
+                || diagnostic.span.start < span.start
+                || diagnostic.span.end > span.end
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// `value` with the warnings if no error has been emitted, otherwise every diagnostic. They are
+    /// in source order, although each phase emits its own after the previous one's.
+    pub fn finish<T>(mut self, value: T) -> Result<(T, Diagnostics), Diagnostics> {
+        self.sort();
+
+        if self.has_errors() {
+            Err(Diagnostics(self.diagnostics))
+        } else {
+            Ok((value, Diagnostics(self.diagnostics)))
+        }
+    }
+
+    // Sorts by position within each file, keeping the files in the order of their first
+    // diagnostic. A diagnostic in a macro expansion is where the macro is used.
+    fn sort(&mut self) {
+        let sites: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| self.site(diagnostic.span))
+            .collect();
+
+        let mut files = vec![];
+        for site in &sites {
+            if !files.contains(&site.file) {
+                files.push(site.file);
+            }
+        }
+
+        let mut diagnostics: Vec<_> = sites
+            .into_iter()
+            .zip(std::mem::take(&mut self.diagnostics))
+            .collect();
+        diagnostics.sort_by_key(|(site, _)| {
+            let file = files.iter().position(|&file| file == site.file);
+            (file, site.start)
+        });
+        self.diagnostics = diagnostics
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_map::SourceMap;
+
+    use super::*;
+
+    #[test]
+    fn test_finish_without_errors() {
+        let diags = DiagnosticEngine::default();
+
+        assert_eq!(diags.finish(42), Ok((42, Diagnostics(vec![]))));
+    }
+
+    #[test]
+    fn test_finish_with_errors() {
+        let mut diags = DiagnosticEngine::default();
+        diags.error(Span::default(), "first");
+        diags.error(Span::default(), "second");
+
+        assert!(diags.has_errors());
+        assert_eq!(
+            diags.finish(42),
+            Err(Diagnostics(vec![
+                Diagnostic::error(Span::default(), "first"),
+                Diagnostic::error(Span::default(), "second"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_warning_levels() {
+        let mut options = WarningOptions::default();
+        options.apply_flag("-Wall").unwrap();
+        options.apply_flag("-Werror=unused-value").unwrap();
+
+        let mut diags = DiagnosticEngine::new(options);
+        diags.emit(Diagnostic::warning(
+            Warning::UnusedVariable,
+            Span::default(),
+            "unused",
+        ));
+        diags.emit(Diagnostic::warning(
+            Warning::UnusedValue,
+            Span::default(),
+            "no effect",
+        ));
+        diags.emit(Diagnostic::warning(
+            Warning::Shadow,
+            Span::default(),
+            "shadows",
+        ));
+
+        let severities: Vec<_> = diags
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.warning))
+            .collect();
+        assert_eq!(
+            severities,
+            vec![
+                (Severity::Warning, Some(Warning::UnusedVariable)),
+                (Severity::Error, Some(Warning::UnusedValue)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macro_expansions() {
+        let span = |file, start| Span::new(file, start, start + 1);
+        let mut source_map = SourceMap::new();
+        let main = source_map.add_file("main.c", "X; 1 @ 2;");
+        let x = source_map.add_file("<command-line>", "Y");
+        let y = source_map.add_file("<command-line>", "a");
+
+        let mut diags = DiagnosticEngine::default();
+        diags.expansion(x, "X", span(main, 0));
+        diags.expansion(y, "Y", span(x, 0));
+        diags.error(span(main, 5), "parser");
+        diags.error(span(y, 0), "sema");
+
+        // In the order of the code, where the macro is used.
+        assert_eq!(
+            diags.finish(()).unwrap_err().0,
+            vec![
+                Diagnostic::error(span(y, 0), "sema")
+                    .with_note(Some(span(x, 0)), "Expanded from macro 'Y'")
+                    .with_note(Some(span(main, 0)), "Expanded from macro 'X'"),
+                Diagnostic::error(span(main, 5), "parser"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warning_pragmas() {
+        let span = |start| Span::new(Default::default(), start, start + 1);
+
+        let mut diags = DiagnosticEngine::default();
+        diags.pragma(span(10), DiagnosticPragma::Push);
+        diags.pragma(
+            span(20),
+            DiagnosticPragma::Set(Warning::DivByZero, WarningLevel::Ignored),
+        );
+        diags.pragma(
+            span(30),
+            DiagnosticPragma::Set(Warning::Shadow, WarningLevel::Error),
+        );
+        diags.pragma(span(40), DiagnosticPragma::Pop);
+
+        for start in [0, 25, 35, 45] {
+            diags.emit(Diagnostic::warning(Warning::DivByZero, span(start), "zero"));
+            diags.emit(Diagnostic::warning(Warning::Shadow, span(start), "shadow"));
+        }
+
+        let emitted: Vec<_> = diags
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start,
+                    diagnostic.severity,
+                    diagnostic.warning,
+                )
+            })
+            .collect();
+        assert_eq!(
+            emitted,
+            vec![
+                (0, Severity::Warning, Some(Warning::DivByZero)),
+                (35, Severity::Error, Some(Warning::Shadow)),
+                (45, Severity::Warning, Some(Warning::DivByZero)),
+            ]
+        );
+    }
+}
@@ -1,75 +1,672 @@
-use std::process::exit;
+use crate::{
+    diagnostic::{Diagnostic, Label, NoteKind, Severity},
+    source_map::{Location, SourceFile, SourceMap, Span},
+};
 
-pub fn report(user_input: &str, error_location: usize, message: &str) -> ! {
-    if error_location > user_input.as_bytes().len() {
-        panic!("Invalid error_location")
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const MAGENTA: &str = "\x1b[1;35m";
+const CYAN: &str = "\x1b[1;36m";
+
+const TAB_WIDTH: usize = 8;
+
+/// Renders `diagnostic` like clang does: a `file:line:col: error: message` header, the source
+/// line with the span underlined, secondary labels, fix-it hints, and then every note. Warnings
+/// end with the flag that controls them, e.g. `[-Wunused-variable]`.
+///
+/// With `color`, the output contains ANSI escape sequences.
+pub fn render(source_map: &SourceMap, diagnostic: &Diagnostic, color: bool) -> String {
+    let severity_color = match diagnostic.severity {
+        Severity::Warning => MAGENTA,
+        Severity::Error => RED,
+    };
+
+    let message = match (diagnostic.severity, diagnostic.warning) {
+        (Severity::Error, Some(warning)) => format!("{} [-Werror,{warning}]", diagnostic.message),
+        (_, Some(warning)) => format!("{} [{warning}]", diagnostic.message),
+        (_, None) => diagnostic.message.clone(),
+    };
+
+    let mut rendered = header(
+        source_map,
+        diagnostic.span,
+        &paint(&diagnostic.severity.to_string(), severity_color, color),
+        &message,
+        color,
+    );
+
+    let primary_line = source_map.location(diagnostic.span).line;
+    let same_line = |span: Span| {
+        span.file == diagnostic.span.file && source_map.location(span).line == primary_line
+    };
+
+    rendered += &snippet(
+        source_map,
+        diagnostic.span,
+        Some(diagnostic.span),
+        diagnostic
+            .labels
+            .iter()
+            .filter(|label| same_line(label.span)),
+        color,
+    );
+
+    // Every fix-it goes on one line, below the column it applies to. Removals have nothing to
+    // show, so they only appear in JSON.
+    let mut fix_its: Vec<_> = diagnostic
+        .fix_its
+        .iter()
+        .filter(|fix_it| same_line(fix_it.span) && !fix_it.replacement.is_empty())
+        .map(|fix_it| {
+            let file = source_map.file(fix_it.span.file);
+            let line = file.line(primary_line);
+            let column = file.location(fix_it.span.start).column;
+            (display_column(line, column), &fix_it.replacement)
+        })
+        .collect();
+    fix_its.sort_by_key(|&(column, _)| column);
+
+    if !fix_its.is_empty() {
+        let mut fix_it_line = String::new();
+        let mut fix_it_line_end = 1;
+        for (column, replacement) in fix_its {
+            fix_it_line += &" ".repeat(column.saturating_sub(fix_it_line_end));
+            fix_it_line += &paint(replacement, GREEN, color);
+            fix_it_line_end = column.max(fix_it_line_end) + replacement.len();
+        }
+        rendered += &format!("{fix_it_line}\n");
     }
 
-    eprintln!("{}", error_message(user_input, error_location, message));
-    exit(1);
-}
+    for label in diagnostic
+        .labels
+        .iter()
+        .filter(|label| !same_line(label.span))
+    {
+        if label.span.file != diagnostic.span.file {
+            let location = source_map.location(label.span);
+            rendered += &format!(
+                "{}\n",
+                paint(
+                    &format!(
+                        "{}:{}:{}:",
+                        source_map.file(label.span.file).name(),
+                        location.line,
+                        location.column
+                    ),
+                    BOLD,
+                    color
+                )
+            );
+        }
 
-fn error_message(user_input: &str, error_location: usize, message: &str) -> String {
-    let (error_line, cumulative_bytes_count) = {
-        let mut error_line = "";
-        let mut cumulative_bytes_count = 0;
-        for line in user_input.lines() {
-            // + 1 is for \n.
-            cumulative_bytes_count += line.as_bytes().len() + 1;
-            if error_location <= cumulative_bytes_count {
-                error_line = line;
-                cumulative_bytes_count -= line.as_bytes().len() + 1;
-                break;
+        rendered += &snippet(source_map, label.span, None, [label], color);
+    }
+
+    for note in &diagnostic.notes {
+        let kind = paint(
+            &note.kind.to_string(),
+            match note.kind {
+                NoteKind::Note => CYAN,
+                NoteKind::Help => GREEN,
+            },
+            color,
+        );
+
+        rendered += &match note.span {
+            Some(span) => {
+                header(source_map, span, &kind, &note.message, color)
+                    + &snippet(source_map, span, Some(span), [], color)
             }
-            error_line = line;
+            None => format!("{}: {}\n", kind, paint(&note.message, BOLD, color)),
+        };
+    }
+
+    rendered
+}
+
+fn header(
+    source_map: &SourceMap,
+    span: Span,
+    severity: &str,
+    message: &str,
+    color: bool,
+) -> String {
+    let location = source_map.location(span);
+
+    format!(
+        "{} {}: {}\n",
+        paint(
+            &format!(
+                "{}:{}:{}:",
+                source_map.file(span.file).name(),
+                location.line,
+                location.column
+            ),
+            BOLD,
+            color
+        ),
+        severity,
+        paint(message, BOLD, color),
+    )
+}
+
+// The source line containing `line_span`, followed by a line underlining `primary` with `^~~~`
+// and every label with `----`. Label messages follow their underline.
+fn snippet<'a>(
+    source_map: &SourceMap,
+    line_span: Span,
+    primary: Option<Span>,
+    labels: impl IntoIterator<Item = &'a Label>,
+    color: bool,
+) -> String {
+    let file = source_map.file(line_span.file);
+    let line = file.location(line_span.start).line;
+
+    struct Marker<'a> {
+        // 1-based, end exclusive.
+        columns: (usize, usize),
+        primary: bool,
+        message: Option<&'a str>,
+    }
+
+    let text = file.line(line);
+    let display_columns = |span: Span| {
+        let (start, end) = columns(file, line, span);
+        (display_column(text, start), display_column(text, end))
+    };
+    let mut markers: Vec<_> = primary
+        .map(|span| Marker {
+            columns: display_columns(span),
+            primary: true,
+            message: None,
+        })
+        .into_iter()
+        .chain(labels.into_iter().map(|label| Marker {
+            columns: display_columns(label.span),
+            primary: false,
+            message: Some(&label.message),
+        }))
+        .collect();
+    markers.sort_by_key(|marker| marker.columns.0);
+
+    let mut underline = String::new();
+    let mut underline_end = 1;
+    for marker in &markers {
+        let (start, end) = marker.columns;
+        // Overlapping markers keep whatever was drawn first.
+        let start = start.max(underline_end);
+        if start >= end {
+            continue;
+        }
+
+        let (text, style) = if marker.primary && start == marker.columns.0 {
+            (format!("^{}", "~".repeat(end - start - 1)), GREEN)
+        } else if marker.primary {
+            ("~".repeat(end - start), GREEN)
+        } else {
+            ("-".repeat(end - start), BLUE)
+        };
+
+        underline += &" ".repeat(start - underline_end);
+        underline += &paint(&text, style, color);
+        underline_end = end;
+    }
+
+    // The rightmost marker's message goes on the same line, and the others below it.
+    let mut messages = markers
+        .iter()
+        .filter_map(|marker| marker.message.map(|message| (marker.columns.0, message)))
+        .rev()
+        .peekable();
+    if let (Some(&(column, message)), Some(last)) = (messages.peek(), markers.last()) {
+        if column == last.columns.0 && last.message.is_some() {
+            underline += &format!(" {}", paint(message, BLUE, color));
+            messages.next();
         }
-        (error_line, cumulative_bytes_count)
+    }
+
+    let mut snippet = format!("{}\n{}\n", expand_tabs(text), underline);
+    for (column, message) in messages {
+        snippet += &format!(
+            "{}{}\n",
+            " ".repeat(column - 1),
+            paint(message, BLUE, color)
+        );
+    }
+
+    snippet
+}
+
+// Columns `span` covers on `line`, clipped to the line. Empty spans still cover one column.
+fn columns(file: &SourceFile, line: usize, span: Span) -> (usize, usize) {
+    let start = file.location(span.start).column;
+    let end = match file.location(span.end) {
+        Location {
+            line: end_line,
+            column,
+        } if end_line == line => column,
+        _ => file.line(line).len() + 1,
     };
 
-    let error_column_number = error_location - cumulative_bytes_count;
+    (start, end.max(start + 1))
+}
+
+// Where a byte column of `line` is on the screen, counting characters rather than bytes, with
+// tabs expanded to the next multiple of 8 like `expand_tabs` does. 1-based.
+fn display_column(line: &str, column: usize) -> usize {
+    let mut display = 1;
+    for (offset, char) in line.char_indices() {
+        if offset + 1 >= column {
+            break;
+        }
+        display = match char {
+            '\t' => (display - 1) / TAB_WIDTH * TAB_WIDTH + TAB_WIDTH + 1,
+            _ => display + 1,
+        };
+    }
+    // Past the end of the line.
+    display + column.saturating_sub(line.len() + 1)
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    let mut width = 0;
+    for char in line.chars() {
+        match char {
+            '\t' => {
+                let spaces = TAB_WIDTH - width % TAB_WIDTH;
+                expanded += &" ".repeat(spaces);
+                width += spaces;
+            }
+            _ => {
+                expanded.push(char);
+                width += 1;
+            }
+        }
+    }
+    expanded
+}
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{style}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Renders every diagnostic as a JSON array, for tools that annotate source code with them.
+pub fn render_json(source_map: &SourceMap, diagnostics: &[Diagnostic]) -> String {
+    let objects: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let labels: Vec<_> = diagnostic
+                .labels
+                .iter()
+                .map(|label| {
+                    format!(
+                        r#"{{"span":{},"message":{}}}"#,
+                        json_span(source_map, label.span),
+                        json_string(&label.message)
+                    )
+                })
+                .collect();
+            let notes: Vec<_> = diagnostic
+                .notes
+                .iter()
+                .map(|note| {
+                    format!(
+                        r#"{{"kind":"{}","span":{},"message":{}}}"#,
+                        note.kind,
+                        note.span.map_or("null".to_string(), |span| json_span(
+                            source_map, span
+                        )),
+                        json_string(&note.message)
+                    )
+                })
+                .collect();
+            let fix_its: Vec<_> = diagnostic
+                .fix_its
+                .iter()
+                .map(|fix_it| {
+                    format!(
+                        r#"{{"span":{},"replacement":{}}}"#,
+                        json_span(source_map, fix_it.span),
+                        json_string(&fix_it.replacement)
+                    )
+                })
+                .collect();
+
+            format!(
+                r#"{{"severity":"{}","span":{},"message":{},"option":{},"labels":[{}],"notes":[{}],"fix_its":[{}]}}"#,
+                diagnostic.severity,
+                json_span(source_map, diagnostic.span),
+                json_string(&diagnostic.message),
+                diagnostic
+                    .warning
+                    .map_or("null".to_string(), |warning| json_string(&warning.to_string())),
+                labels.join(","),
+                notes.join(","),
+                fix_its.join(","),
+            )
+        })
+        .collect();
+
+    format!("[{}]\n", objects.join(","))
+}
+
+fn json_span(source_map: &SourceMap, span: Span) -> String {
+    let file = source_map.file(span.file);
+    let start = file.location(span.start);
+    let end = file.location(span.end);
 
     format!(
-        "{}
-{}^ {}
-",
-        error_line,
-        " ".repeat(error_column_number),
-        message
+        r#"{{"file":{},"start":{{"line":{},"column":{}}},"end":{{"line":{},"column":{}}}}}"#,
+        json_string(file.name()),
+        start.line,
+        start.column,
+        end.line,
+        end.column
     )
 }
 
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            c if (c as u32) < 0x20 => escaped += &format!("\\u{:04x}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::warning::Warning;
+
     use super::*;
 
+    fn render_plain(source_map: &SourceMap, diagnostic: &Diagnostic) -> String {
+        render(source_map, diagnostic, false)
+    }
+
     #[test]
     fn test_error_message_single_line() {
-        let actual = error_message("1 + 2 + hoge", 8, "Invalid token");
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "1 + 2 + hoge");
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 8, 12), "Invalid token"),
+        );
 
         assert_eq!(
             actual,
             "\
+main.c:1:9: error: Invalid token
 1 + 2 + hoge
-        ^ Invalid token
+        ^~~~
 "
         );
     }
 
     #[test]
     fn test_error_message_multi_lines() {
-        let actual = error_message(
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(
+            "main.c",
             "1 + 2 + 2
 1 + 3 + hoge",
-            18,
-            "Invalid token",
+        );
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 18, 22), "Invalid token"),
         );
 
         assert_eq!(
             actual,
             "\
+main.c:2:9: error: Invalid token
 1 + 3 + hoge
-        ^ Invalid token
+        ^~~~
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_start_of_line() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(
+            "main.c",
+            "1 + 2;
+hoge;",
+        );
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 7, 11), "Invalid token"),
+        );
+
+        assert_eq!(
+            actual,
+            "\
+main.c:2:1: error: Invalid token
+hoge;
+^~~~
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_span_across_lines() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = 1 +\n  2;");
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 4, 11), "Too complex"),
+        );
+
+        assert_eq!(
+            actual,
+            "\
+main.c:1:5: error: Too complex
+a = 1 +
+    ^~~
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_with_labels() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = (1 + (2;\nb = 3;");
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 11, 12), "Expected ')'")
+                .with_label(Span::new(file, 4, 5), "to match this '('")
+                .with_label(Span::new(file, 9, 10), "and this '('")
+                .with_label(Span::new(file, 13, 14), "b is here"),
+        );
+
+        assert_eq!(
+            actual,
+            "\
+main.c:1:12: error: Expected ')'
+a = (1 + (2;
+    -    - ^
+         and this '('
+    to match this '('
+b = 3;
+- b is here
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_with_notes_and_fix_its() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = 1 b = 2;");
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 6, 7), "Expected ';'")
+                .with_fix_it(Span::new(file, 6, 6), ";")
+                .with_note(Some(Span::new(file, 0, 1)), "a is here")
+                .with_help("insert ';'"),
+        );
+
+        assert_eq!(
+            actual,
+            "\
+main.c:1:7: error: Expected ';'
+a = 1 b = 2;
+      ^
+      ;
+main.c:1:1: note: a is here
+a = 1 b = 2;
+^
+help: insert ';'
 "
         );
     }
+
+    #[test]
+    fn test_fix_its_on_one_line() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "if (a = b) 1;");
+
+        let actual = render_plain(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 4, 9), "Assignment")
+                .with_fix_it(Span::new(file, 9, 9), ")")
+                .with_fix_it(Span::new(file, 4, 4), "("),
+        );
+
+        assert_eq!(
+            actual,
+            "\
+main.c:1:5: error: Assignment
+if (a = b) 1;
+    ^~~~~
+    (    )
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_tabs_and_utf8() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "{\n\tx = 1;\n  s = \"ééé\"; 1 +;\n}");
+
+        assert_eq!(
+            render_plain(
+                &source_map,
+                &Diagnostic::error(Span::new(file, 3, 4), "Use of undeclared identifier 'x'")
+                    .with_label(Span::new(file, 7, 8), "here"),
+            ),
+            "\
+main.c:2:2: error: Use of undeclared identifier 'x'
+        x = 1;
+        ^   - here
+"
+        );
+        // The column in the header counts bytes, like clang's.
+        assert_eq!(
+            render_plain(
+                &source_map,
+                &Diagnostic::error(Span::new(file, 29, 30), "Invalid token"),
+            ),
+            "\
+main.c:3:20: error: Invalid token
+  s = \"ééé\"; 1 +;
+                ^
+"
+        );
+    }
+
+    #[test]
+    fn test_error_message_with_color() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "hoge");
+
+        let actual = render(
+            &source_map,
+            &Diagnostic::error(Span::new(file, 0, 4), "Invalid token"),
+            true,
+        );
+
+        assert_eq!(
+            actual,
+            "\x1b[1mmain.c:1:1:\x1b[0m \x1b[1;31merror\x1b[0m: \x1b[1mInvalid token\x1b[0m
+hoge
+\x1b[1;32m^~~~\x1b[0m
+"
+        );
+    }
+
+    #[test]
+    fn test_warning_message() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = 1;");
+
+        let mut diagnostic = Diagnostic::warning(
+            Warning::UnusedVariable,
+            Span::new(file, 0, 1),
+            "Unused variable 'a'",
+        );
+
+        assert_eq!(
+            render_plain(&source_map, &diagnostic),
+            "\
+main.c:1:1: warning: Unused variable 'a' [-Wunused-variable]
+a = 1;
+^
+"
+        );
+        assert!(render(&source_map, &diagnostic, true).contains("\x1b[1;35mwarning\x1b[0m"));
+
+        // Promoted by -Werror.
+        diagnostic.severity = Severity::Error;
+        assert!(render_plain(&source_map, &diagnostic)
+            .starts_with("main.c:1:1: error: Unused variable 'a' [-Werror,-Wunused-variable]\n"));
+        assert!(render_json(&source_map, &[diagnostic]).contains(r#""option":"-Wunused-variable""#));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("dir\\main.c", "a = 1 b = 2;");
+
+        let actual = render_json(
+            &source_map,
+            &[
+                Diagnostic::error(Span::new(file, 6, 7), "Expected \"';'\"")
+                    .with_fix_it(Span::new(file, 6, 6), ";")
+                    .with_help("insert ';'"),
+                Diagnostic::error(Span::new(file, 0, 1), "Invalid token")
+                    .with_label(Span::new(file, 4, 5), "here"),
+            ],
+        );
+
+        assert_eq!(
+            actual,
+            r#"[{"severity":"error","span":{"file":"dir\\main.c","start":{"line":1,"column":7},"end":{"line":1,"column":8}},"message":"Expected \"';'\"","option":null,"labels":[],"notes":[{"kind":"help","span":null,"message":"insert ';'"}],"fix_its":[{"span":{"file":"dir\\main.c","start":{"line":1,"column":7},"end":{"line":1,"column":7}},"replacement":";"}]},{"severity":"error","span":{"file":"dir\\main.c","start":{"line":1,"column":1},"end":{"line":1,"column":2}},"message":"Invalid token","option":null,"labels":[{"span":{"file":"dir\\main.c","start":{"line":1,"column":5},"end":{"line":1,"column":6}},"message":"here"}],"notes":[],"fix_its":[]}]
+"#
+        );
+    }
 }
@@ -0,0 +1,228 @@
+use core::fmt;
+
+use crate::types::Type;
+
+/// The kind of format string a function declared with `format(kind, ...)` takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Printf,
+    Scanf,
+}
+
+/// The argument a conversion specification like `%ld` takes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    // An integer is accepted with either signedness, and after the default argument promotions.
+    Type(Type),
+    // There are no floating types, so nothing is accepted.
+    Floating(&'static str),
+}
+
+impl Expected {
+    /// Whether an argument of type `ty`, after the default argument promotions, is accepted.
+    pub fn accepts(&self, ty: &Type) -> bool {
+        let Expected::Type(expected) = self else {
+            return false;
+        };
+        match (expected, ty.unqualified()) {
+            (Type::Pointer(expected), Type::Pointer(base)) => match expected.unqualified() {
+                Type::Void => true,
+                expected if expected.is_char() => base.is_char(),
+                expected => base.is_integer() && base.size() == expected.size(),
+            },
+            (expected, ty) => {
+                expected.is_integer() && ty.is_integer() && ty.size() == expected.size()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Type(ty) => write!(f, "{ty}"),
+            Expected::Floating(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The arguments that the conversion specifications of `format` take, in order, or why the
+/// format is invalid. `*` for a field width or a precision takes an `int`.
+pub fn arguments(format: &str, style: Style) -> Result<Vec<Expected>, String> {
+    let mut arguments = vec![];
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            continue;
+        }
+
+        let mut assigned = true;
+        match style {
+            Style::Printf => {
+                while chars.next_if(|c| "-+ #0'".contains(*c)).is_some() {}
+                // The field width, then the precision.
+                for prefix in [None, Some('.')] {
+                    if prefix.is_some() && chars.next_if_eq(&'.').is_none() {
+                        continue;
+                    }
+                    if chars.next_if_eq(&'*').is_some() {
+                        arguments.push(Expected::Type(Type::Int));
+                    }
+                    while chars.next_if(char::is_ascii_digit).is_some() {}
+                }
+            }
+            Style::Scanf => {
+                assigned = chars.next_if_eq(&'*').is_none();
+                while chars.next_if(char::is_ascii_digit).is_some() {}
+            }
+        }
+
+        let mut length = String::new();
+        while let Some(c) = chars.next_if(|c| "hljztLq".contains(*c)) {
+            length.push(c);
+        }
+        let Some(conversion) = chars.next() else {
+            return Err("Incomplete format specifier".to_string());
+        };
+        // A scanset, like `%[^,]`, where `]` right after `[` or `[^` is in the set.
+        if style == Style::Scanf && conversion == '[' {
+            chars.next_if_eq(&'^');
+            chars.next_if_eq(&']');
+            if !chars.by_ref().any(|c| c == ']') {
+                return Err("No closing ']' for '%[' in scanf format string".to_string());
+            }
+        }
+
+        let integer = |signed: bool| {
+            let (signed_type, unsigned_type) = match length.as_str() {
+                "hh" if style == Style::Scanf => (Type::SChar, Type::UChar),
+                "h" if style == Style::Scanf => (Type::Short, Type::UShort),
+                // Promoted to `int` in printf.
+                "" | "hh" | "h" => (Type::Int, Type::UInt),
+                "l" | "j" | "z" | "t" => (Type::Long, Type::ULong),
+                "ll" | "q" => (Type::LongLong, Type::ULongLong),
+                _ => return None,
+            };
+            Some(if signed { signed_type } else { unsigned_type })
+        };
+        let expected = match conversion {
+            'd' | 'i' => integer(true).map(Expected::Type),
+            'o' | 'u' | 'x' | 'X' => integer(false).map(Expected::Type),
+            'n' => integer(true).map(|ty| Expected::Type(Type::pointer_to(ty))),
+            'c' | 's' if style == Style::Printf && length == "l" => {
+                Some(Expected::Type(match conversion {
+                    'c' => Type::UInt,
+                    _ => Type::pointer_to(Type::Int),
+                }))
+            }
+            'c' if style == Style::Printf && length.is_empty() => Some(Expected::Type(Type::Int)),
+            'c' | 's' | '[' if length.is_empty() => {
+                Some(Expected::Type(Type::pointer_to(Type::Char)))
+            }
+            'p' if length.is_empty() => Some(Expected::Type(Type::pointer_to(Type::Void))),
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'a' | 'A' => {
+                Some(Expected::Floating(match (style, length.as_str()) {
+                    (Style::Printf, "L") => "long double",
+                    (Style::Printf, _) => "double",
+                    (Style::Scanf, "l") => "double *",
+                    (Style::Scanf, "L") => "long double *",
+                    (Style::Scanf, _) => "float *",
+                }))
+            }
+            // glibc's `%m` prints `strerror(errno)`.
+            'm' if style == Style::Printf => continue,
+            _ => None,
+        };
+        let Some(mut expected) = expected else {
+            return Err(format!("Invalid conversion specifier '{conversion}'"));
+        };
+
+        // Every conversion of scanf assigns through a pointer, unless it's suppressed by `*`.
+        if style == Style::Scanf && !assigned {
+            continue;
+        }
+        if let (Style::Scanf, Expected::Type(ty)) = (style, &expected) {
+            if conversion != 'n' && !matches!(conversion, 'c' | 's' | '[') {
+                expected = Expected::Type(Type::pointer_to(ty.clone()));
+            }
+        }
+        arguments.push(expected);
+    }
+
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(format: &str, style: Style) -> Result<Vec<String>, String> {
+        arguments(format, style)
+            .map(|arguments| arguments.iter().map(Expected::to_string).collect())
+    }
+
+    #[test]
+    fn test_printf() {
+        assert_eq!(
+            names("%d%% %-5ld %*.*s %c %zu %p %hhx %llu %f%m", Style::Printf).unwrap(),
+            [
+                "int",
+                "long",
+                "int",
+                "int",
+                "char *",
+                "int",
+                "unsigned long",
+                "void *",
+                "unsigned int",
+                "unsigned long long",
+                "double"
+            ]
+        );
+        assert_eq!(
+            names("%q", Style::Printf),
+            Err("Incomplete format specifier".to_string())
+        );
+        assert_eq!(
+            names("%y", Style::Printf),
+            Err("Invalid conversion specifier 'y'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_scanf() {
+        assert_eq!(
+            names("%d %*d %5s %hu %[^]] %ln %c", Style::Scanf).unwrap(),
+            [
+                "int *",
+                "char *",
+                "unsigned short *",
+                "char *",
+                "long *",
+                "char *"
+            ]
+        );
+        assert_eq!(
+            names("%[abc", Style::Scanf),
+            Err("No closing ']' for '%[' in scanf format string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_accepts() {
+        let expected = Expected::Type(Type::Int);
+        assert!(expected.accepts(&Type::UInt));
+        assert!(!expected.accepts(&Type::Long));
+        assert!(!expected.accepts(&Type::pointer_to(Type::Int)));
+
+        let string = Expected::Type(Type::pointer_to(Type::Char));
+        assert!(string.accepts(&Type::pointer_to(Type::UChar)));
+        assert!(!string.accepts(&Type::pointer_to(Type::Int)));
+        assert!(Expected::Type(Type::pointer_to(Type::Void)).accepts(&Type::pointer_to(Type::Long)));
+        assert!(!Expected::Floating("double").accepts(&Type::Int));
+    }
+}
@@ -0,0 +1,309 @@
+use core::fmt;
+
+// The names of each register for 1, 2, 4 and 8 bytes, by their number in the encoding.
+const NAMES: [[&str; 4]; 16] = [
+    ["al", "ax", "eax", "rax"],
+    ["cl", "cx", "ecx", "rcx"],
+    ["dl", "dx", "edx", "rdx"],
+    ["bl", "bx", "ebx", "rbx"],
+    ["spl", "sp", "esp", "rsp"],
+    ["bpl", "bp", "ebp", "rbp"],
+    ["sil", "si", "esi", "rsi"],
+    ["dil", "di", "edi", "rdi"],
+    ["r8b", "r8w", "r8d", "r8"],
+    ["r9b", "r9w", "r9d", "r9"],
+    ["r10b", "r10w", "r10d", "r10"],
+    ["r11b", "r11w", "r11d", "r11"],
+    ["r12b", "r12w", "r12d", "r12"],
+    ["r13b", "r13w", "r13d", "r13"],
+    ["r14b", "r14w", "r14d", "r14"],
+    ["r15b", "r15w", "r15d", "r15"],
+];
+
+/// A general-purpose register, by its number in the encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register(usize);
+
+impl Register {
+    pub const RAX: Register = Register(0);
+    pub const RCX: Register = Register(1);
+    pub const RDX: Register = Register(2);
+    pub const RBX: Register = Register(3);
+    pub const RSP: Register = Register(4);
+    pub const RBP: Register = Register(5);
+    pub const RSI: Register = Register(6);
+    pub const RDI: Register = Register(7);
+    pub const R8: Register = Register(8);
+    pub const R9: Register = Register(9);
+    pub const R10: Register = Register(10);
+    pub const R11: Register = Register(11);
+    pub const R12: Register = Register(12);
+    pub const R13: Register = Register(13);
+    pub const R14: Register = Register(14);
+    pub const R15: Register = Register(15);
+
+    /// The registers given to operands with the `r` constraint, in order. Those after `r11` are
+    /// callee-saved, so they're saved around the asm.
+    pub const ALLOCATABLE: [Register; 14] = [
+        Register::RAX,
+        Register::RCX,
+        Register::RDX,
+        Register::RSI,
+        Register::RDI,
+        Register::R8,
+        Register::R9,
+        Register::R10,
+        Register::R11,
+        Register::RBX,
+        Register::R12,
+        Register::R13,
+        Register::R14,
+        Register::R15,
+    ];
+
+    /// The register named `name` with any size, like `eax` or `%r8d`.
+    pub fn from_name(name: &str) -> Option<Register> {
+        let name = name.strip_prefix('%').unwrap_or(name);
+        NAMES
+            .iter()
+            .position(|names| names.contains(&name))
+            .map(Register)
+    }
+
+    /// The register of a constraint letter, like `a` for `rax`.
+    pub fn from_constraint(letter: char) -> Option<Register> {
+        match letter {
+            'a' => Some(Register::RAX),
+            'b' => Some(Register::RBX),
+            'c' => Some(Register::RCX),
+            'd' => Some(Register::RDX),
+            'S' => Some(Register::RSI),
+            'D' => Some(Register::RDI),
+            _ => None,
+        }
+    }
+
+    /// The name of its low `size` bytes, which must be 1, 2, 4 or 8.
+    pub fn name(self, size: usize) -> &'static str {
+        NAMES[self.0][size.trailing_zeros() as usize]
+    }
+
+    /// Whether a function must restore it before returning, by the System V ABI.
+    pub fn is_callee_saved(self) -> bool {
+        matches!(self.0, 3 | 5 | 12..=15)
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name(8))
+    }
+}
+
+/// What a constraint string like `=&r` allows, without choosing between its alternatives.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraint {
+    // `=`, written by the asm.
+    pub is_output: bool,
+    // `+`, both read and written.
+    pub is_read_write: bool,
+    // `&`, written before every input is read. Operands always get their own registers, so it
+    // doesn't change anything.
+    pub is_early_clobber: bool,
+    // `r`, `g` or a letter for a specific register.
+    pub register: Option<Option<Register>>,
+    // `m` or `g`.
+    pub memory: bool,
+    // `i`, `n` or `g`.
+    pub immediate: bool,
+    // A digit, for an input in the same place as that output.
+    pub tied: Option<usize>,
+}
+
+/// Parses a constraint, or returns the letter it doesn't know.
+pub fn constraint(constraint: &str) -> Result<Constraint, char> {
+    let mut result = Constraint::default();
+    let mut chars = constraint.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '=' => result.is_output = true,
+            '+' => result.is_read_write = true,
+            '&' => result.is_early_clobber = true,
+            'r' | 'q' => result.register = Some(None),
+            'm' => result.memory = true,
+            'i' | 'n' => result.immediate = true,
+            'g' => {
+                result.register = Some(None);
+                result.memory = true;
+                result.immediate = true;
+            }
+            '0'..='9' => {
+                let mut index = c.to_digit(10).unwrap() as usize;
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    index = index * 10 + digit.to_digit(10).unwrap() as usize;
+                }
+                result.tied = Some(index);
+            }
+            // Alternatives and hints for the register allocator.
+            ',' | '%' | ' ' => {}
+            c => match Register::from_constraint(c) {
+                Some(register) => result.register = Some(Some(register)),
+                None => return Err(c),
+            },
+        }
+    }
+
+    Ok(result)
+}
+
+/// A part of an extended asm template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    // `%0`, or `%k0` with a modifier.
+    Operand {
+        index: usize,
+        modifier: Option<char>,
+    },
+    // `%=`, a number unique to each asm statement in the file.
+    Unique,
+}
+
+/// Splits the template of an extended asm into its operands, which are named by `names` in order.
+pub fn template(template: &str, names: &[Option<&str>]) -> Result<Vec<Piece>, String> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        let modifier = chars.next_if(|c| "bwkqhc".contains(*c));
+        let index = match chars.next() {
+            Some(c @ ('%' | '{' | '|' | '}')) if modifier.is_none() => {
+                text.push(c);
+                continue;
+            }
+            Some('=') if modifier.is_none() => None,
+            Some('[') => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("Unterminated symbolic operand name".to_string()),
+                    }
+                }
+                match names.iter().position(|&other| other == Some(name.as_str())) {
+                    Some(index) => Some(index),
+                    None => {
+                        return Err(format!(
+                            "Unknown symbolic operand name '{name}' in inline asm string"
+                        ))
+                    }
+                }
+            }
+            Some(c @ '0'..='9') => {
+                let mut index = c.to_digit(10).unwrap() as usize;
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    index = index * 10 + digit.to_digit(10).unwrap() as usize;
+                }
+                if index >= names.len() {
+                    return Err(format!(
+                        "Invalid operand number {index} in inline asm string"
+                    ));
+                }
+                Some(index)
+            }
+            _ => return Err("Invalid % escape in inline asm string".to_string()),
+        };
+
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pieces.push(match index {
+            Some(index) => Piece::Operand { index, modifier },
+            None => Piece::Unique,
+        });
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(Register::from_name("eax"), Some(Register::RAX));
+        assert_eq!(Register::from_name("%r9w"), Register::from_name("r9"));
+        assert_eq!(Register::from_name("xmm0"), None);
+        assert_eq!(Register::RSI.name(1), "sil");
+        assert_eq!(Register::from_name("r12").unwrap().name(4), "r12d");
+        assert!(Register::RBX.is_callee_saved() && !Register::RDI.is_callee_saved());
+    }
+
+    #[test]
+    fn test_constraint() {
+        assert_eq!(
+            constraint("=&r"),
+            Ok(Constraint {
+                is_output: true,
+                is_early_clobber: true,
+                register: Some(None),
+                ..Constraint::default()
+            })
+        );
+        assert_eq!(
+            constraint("+a"),
+            Ok(Constraint {
+                is_read_write: true,
+                register: Some(Some(Register::RAX)),
+                ..Constraint::default()
+            })
+        );
+        assert_eq!(constraint("1").unwrap().tied, Some(1));
+        assert!(constraint("rmi").is_ok_and(|c| c.memory && c.immediate));
+        assert_eq!(constraint("x"), Err('x'));
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            template("add %k0, %[n] %% %=", &[None, Some("n")]),
+            Ok(vec![
+                Piece::Text("add ".to_string()),
+                Piece::Operand {
+                    index: 0,
+                    modifier: Some('k')
+                },
+                Piece::Text(", ".to_string()),
+                Piece::Operand {
+                    index: 1,
+                    modifier: None
+                },
+                Piece::Text(" % ".to_string()),
+                Piece::Unique,
+            ])
+        );
+        assert_eq!(
+            template("%2", &[None]),
+            Err("Invalid operand number 2 in inline asm string".to_string())
+        );
+        assert_eq!(
+            template("%[x]", &[Some("y")]),
+            Err("Unknown symbolic operand name 'x' in inline asm string".to_string())
+        );
+        assert_eq!(
+            template("%!", &[]),
+            Err("Invalid % escape in inline asm string".to_string())
+        );
+    }
+}
@@ -0,0 +1,694 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::{
+    atomic::{AtomicOp, MemoryOrder},
+    builtin::BitOp,
+    inline_asm::{Piece, Register},
+    parser::{ArithOp, CmpOp},
+    sema::{Global, Local, SymbolAttributes},
+    types::Type,
+};
+
+/// A program lowered to three-address code, with what code generation needs from the analysis.
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub globals: Vec<Global>,
+    pub symbols: BTreeMap<String, SymbolAttributes>,
+    pub asm: Vec<String>,
+}
+
+/// The control flow graph of a function, which starts at the first block.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    // Where the arguments are stored in the frame, in order.
+    pub params: Vec<Local>,
+    pub return_type: Type,
+    // Where the hidden pointer to the return value is stored, if it's returned in memory.
+    pub return_pointer: Option<usize>,
+    pub blocks: Vec<Block>,
+    // How many virtual registers it uses, numbered from 0.
+    pub vregs: usize,
+    // The size of the variables in the frame, which are at `rbp - offset`.
+    pub frame_size: usize,
+    pub is_static: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// A virtual register of 64 bits. An integer in one is extended by the signedness of its type,
+/// and an array, a struct or a function is its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VReg(pub usize);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Reg(VReg),
+    Imm(i64),
+}
+
+/// `base + offset`, which loads and stores take as is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Address {
+    pub base: Base,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Base {
+    Reg(VReg),
+    // A variable in the frame, at `rbp - offset`.
+    Local(usize),
+    Global(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    And,
+    Or,
+    Xor,
+    Shl,
+    // Shifts in zeros.
+    LShr,
+    // Shifts in the sign bit.
+    AShr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    Direct(String),
+    Indirect(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    // The operand from the predecessor control came from, at the start of a block. There's one
+    // for each predecessor, and they only exist between mem2reg and out-of-SSA.
+    Phi {
+        dest: VReg,
+        args: Vec<(BlockId, Operand)>,
+    },
+    Copy {
+        dest: VReg,
+        src: Operand,
+    },
+    Binary {
+        op: BinaryOp,
+        dest: VReg,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    // 1 if the comparison holds, and 0 otherwise.
+    Cmp {
+        op: CmpOp,
+        is_unsigned: bool,
+        dest: VReg,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    Neg {
+        dest: VReg,
+        src: Operand,
+    },
+    // Truncates to `size` bytes, then extends back to 64 bits.
+    Extend {
+        dest: VReg,
+        src: Operand,
+        size: usize,
+        is_unsigned: bool,
+    },
+    // The address itself.
+    Addr {
+        dest: VReg,
+        address: Address,
+    },
+    FunctionAddr {
+        dest: VReg,
+        name: String,
+    },
+    // The address of this thread's copy.
+    ThreadLocalAddr {
+        dest: VReg,
+        name: String,
+    },
+    // `size` is 1, 2, 4 or 8. A volatile access is never removed or moved to a register.
+    Load {
+        dest: VReg,
+        address: Address,
+        size: usize,
+        is_unsigned: bool,
+        is_volatile: bool,
+    },
+    Store {
+        address: Address,
+        src: Operand,
+        size: usize,
+        is_volatile: bool,
+    },
+    MemCopy {
+        dest: Operand,
+        src: Operand,
+        size: Operand,
+    },
+    MemSet {
+        dest: Operand,
+        value: Operand,
+        size: Operand,
+    },
+    // An argument or a returned value which is a struct is its address. A struct returned in
+    // memory is written to the frame's temporary at `temporary`.
+    Call {
+        dest: VReg,
+        callee: Callee,
+        args: Vec<(Operand, Type)>,
+        ty: Type,
+        temporary: Option<usize>,
+    },
+    // Grows the stack by `size` bytes, rounded up to keep it aligned.
+    Alloca {
+        dest: VReg,
+        size: Operand,
+    },
+    StackSave {
+        dest: VReg,
+    },
+    StackRestore {
+        src: Operand,
+    },
+    // The pointer to the object of `size` bytes, then the operands of `op`.
+    Atomic {
+        op: AtomicOp,
+        order: MemoryOrder,
+        dest: VReg,
+        args: Vec<Operand>,
+        size: usize,
+    },
+    Fence {
+        order: MemoryOrder,
+    },
+    // On the low `size` bytes of `src`.
+    BitOp {
+        op: BitOp,
+        dest: VReg,
+        src: Operand,
+        size: usize,
+    },
+    // 1 if `lhs op rhs` overflows 64 bits, where `op` is `Add`, `Sub` or `Mul`.
+    Overflow {
+        op: ArithOp,
+        is_unsigned: bool,
+        dest: VReg,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    Trap,
+    Asm(Asm),
+}
+
+/// An asm statement whose operands are in the registers they were given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asm {
+    pub template: Vec<Piece>,
+    // How each operand is written in the template.
+    pub operands: Vec<AsmOperand>,
+    // Loaded right before it.
+    pub inputs: Vec<(Register, Operand)>,
+    // Read right after it.
+    pub outputs: Vec<(VReg, Register)>,
+    // The callee-saved registers it uses or clobbers, which are restored after it.
+    pub saved: Vec<Register>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmOperand {
+    pub place: AsmPlace,
+    // The size of its type.
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmPlace {
+    Register(Register),
+    // The memory at the address in a register.
+    Memory(Register),
+    // A local or a global variable.
+    Variable(Address),
+    Immediate(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    // To `then` if `cond` isn't 0.
+    Branch {
+        cond: Operand,
+        then: BlockId,
+        else_: BlockId,
+    },
+    // A struct is returned from its address, and nothing for one which isn't returned.
+    Return(Option<Operand>),
+}
+
+impl Inst {
+    /// The virtual register it defines.
+    pub fn dest(&self) -> Option<VReg> {
+        match self {
+            Inst::Phi { dest, .. }
+            | Inst::Copy { dest, .. }
+            | Inst::Binary { dest, .. }
+            | Inst::Cmp { dest, .. }
+            | Inst::Neg { dest, .. }
+            | Inst::Extend { dest, .. }
+            | Inst::Addr { dest, .. }
+            | Inst::FunctionAddr { dest, .. }
+            | Inst::ThreadLocalAddr { dest, .. }
+            | Inst::Load { dest, .. }
+            | Inst::Call { dest, .. }
+            | Inst::Alloca { dest, .. }
+            | Inst::StackSave { dest }
+            | Inst::Atomic { dest, .. }
+            | Inst::BitOp { dest, .. }
+            | Inst::Overflow { dest, .. } => Some(*dest),
+            Inst::Store { .. }
+            | Inst::MemCopy { .. }
+            | Inst::MemSet { .. }
+            | Inst::StackRestore { .. }
+            | Inst::Fence { .. }
+            | Inst::Trap
+            | Inst::Asm(_) => None,
+        }
+    }
+}
+
+impl Inst {
+    /// The virtual registers it defines, which are the outputs of an asm.
+    pub fn defs(&self) -> Vec<VReg> {
+        match self {
+            Inst::Asm(asm) => asm.outputs.iter().map(|(dest, _)| *dest).collect(),
+            _ => self.dest().into_iter().collect(),
+        }
+    }
+
+    /// The virtual registers it reads.
+    pub fn uses(&self) -> Vec<VReg> {
+        let mut operands = vec![];
+        let mut addresses = vec![];
+        match self {
+            Inst::Phi { args, .. } => operands.extend(args.iter().map(|(_, arg)| arg)),
+            Inst::Copy { src, .. }
+            | Inst::Neg { src, .. }
+            | Inst::Extend { src, .. }
+            | Inst::StackRestore { src }
+            | Inst::BitOp { src, .. } => operands.push(src),
+            Inst::Binary { lhs, rhs, .. }
+            | Inst::Cmp { lhs, rhs, .. }
+            | Inst::Overflow { lhs, rhs, .. } => operands.extend([lhs, rhs]),
+            Inst::Addr { address, .. } | Inst::Load { address, .. } => addresses.push(address),
+            Inst::Store { address, src, .. } => {
+                addresses.push(address);
+                operands.push(src);
+            }
+            Inst::MemCopy { dest, src, size } => operands.extend([dest, src, size]),
+            Inst::MemSet { dest, value, size } => operands.extend([dest, value, size]),
+            Inst::Call { callee, args, .. } => {
+                if let Callee::Indirect(operand) = callee {
+                    operands.push(operand);
+                }
+                operands.extend(args.iter().map(|(arg, _)| arg));
+            }
+            Inst::Alloca { size, .. } => operands.push(size),
+            Inst::Atomic { args, .. } => operands.extend(args),
+            Inst::Asm(asm) => {
+                operands.extend(asm.inputs.iter().map(|(_, operand)| operand));
+                for operand in &asm.operands {
+                    if let AsmPlace::Variable(address) = &operand.place {
+                        addresses.push(address);
+                    }
+                }
+            }
+            Inst::FunctionAddr { .. }
+            | Inst::ThreadLocalAddr { .. }
+            | Inst::StackSave { .. }
+            | Inst::Fence { .. }
+            | Inst::Trap => {}
+        }
+
+        let operands = operands.into_iter().filter_map(|operand| match operand {
+            Operand::Reg(vreg) => Some(*vreg),
+            Operand::Imm(_) => None,
+        });
+        let bases = addresses
+            .into_iter()
+            .filter_map(|address| match address.base {
+                Base::Reg(vreg) => Some(vreg),
+                _ => None,
+            });
+        operands.chain(bases).collect()
+    }
+}
+
+impl Inst {
+    /// Every operand it reads, besides the bases of addresses.
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Inst::Phi { args, .. } => args.iter_mut().map(|(_, arg)| arg).collect(),
+            Inst::Copy { src, .. }
+            | Inst::Neg { src, .. }
+            | Inst::Extend { src, .. }
+            | Inst::Store { src, .. }
+            | Inst::StackRestore { src }
+            | Inst::BitOp { src, .. } => vec![src],
+            Inst::Binary { lhs, rhs, .. }
+            | Inst::Cmp { lhs, rhs, .. }
+            | Inst::Overflow { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::MemCopy { dest, src, size } => vec![dest, src, size],
+            Inst::MemSet { dest, value, size } => vec![dest, value, size],
+            Inst::Call { callee, args, .. } => {
+                let mut operands = vec![];
+                if let Callee::Indirect(operand) = callee {
+                    operands.push(operand);
+                }
+                operands.extend(args.iter_mut().map(|(arg, _)| arg));
+                operands
+            }
+            Inst::Alloca { size, .. } => vec![size],
+            Inst::Atomic { args, .. } => args.iter_mut().collect(),
+            Inst::Asm(asm) => asm.inputs.iter_mut().map(|(_, operand)| operand).collect(),
+            Inst::Addr { .. }
+            | Inst::Load { .. }
+            | Inst::FunctionAddr { .. }
+            | Inst::ThreadLocalAddr { .. }
+            | Inst::StackSave { .. }
+            | Inst::Fence { .. }
+            | Inst::Trap => vec![],
+        }
+    }
+
+    /// Every address it reads or writes through.
+    pub fn addresses_mut(&mut self) -> Vec<&mut Address> {
+        match self {
+            Inst::Addr { address, .. }
+            | Inst::Load { address, .. }
+            | Inst::Store { address, .. } => vec![address],
+            Inst::Asm(asm) => asm
+                .operands
+                .iter_mut()
+                .filter_map(|operand| match &mut operand.place {
+                    AsmPlace::Variable(address) => Some(address),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Terminator {
+    /// The blocks it may go to.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then, else_, .. } => vec![*then, *else_],
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { then, else_, .. } => vec![then, else_],
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    /// The operand it reads.
+    pub fn operand_mut(&mut self) -> Option<&mut Operand> {
+        match self {
+            Terminator::Branch { cond, .. } => Some(cond),
+            Terminator::Return(value) => value.as_mut(),
+            Terminator::Jump(_) => None,
+        }
+    }
+
+    /// The virtual register it reads.
+    pub fn uses(&self) -> Option<VReg> {
+        match self {
+            Terminator::Branch {
+                cond: Operand::Reg(vreg),
+                ..
+            }
+            | Terminator::Return(Some(Operand::Reg(vreg))) => Some(*vreg),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.name)?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(index))?;
+            for inst in &block.insts {
+                writeln!(f, "  {inst}")?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(vreg) => write!(f, "{vreg}"),
+            Operand::Imm(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.base {
+            Base::Reg(vreg) => write!(f, "[{vreg}")?,
+            Base::Local(offset) => write!(f, "[local {offset}")?,
+            Base::Global(name) => write!(f, "[@{name}")?,
+        }
+        if self.offset != 0 {
+            write!(f, "{:+}", self.offset)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BinaryOp::Add => "add",
+                BinaryOp::Sub => "sub",
+                BinaryOp::Mul => "mul",
+                BinaryOp::SDiv => "sdiv",
+                BinaryOp::UDiv => "udiv",
+                BinaryOp::And => "and",
+                BinaryOp::Or => "or",
+                BinaryOp::Xor => "xor",
+                BinaryOp::Shl => "shl",
+                BinaryOp::LShr => "lshr",
+                BinaryOp::AShr => "ashr",
+            }
+        )
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(dest) = self.dest() {
+            write!(f, "{dest} = ")?;
+        }
+        match self {
+            Inst::Phi { args, .. } => {
+                write!(f, "phi")?;
+                for (index, (block, arg)) in args.iter().enumerate() {
+                    let separator = if index > 0 { "," } else { "" };
+                    write!(f, "{separator} [{block}: {arg}]")?;
+                }
+                Ok(())
+            }
+            Inst::Copy { src, .. } => write!(f, "copy {src}"),
+            Inst::Binary { op, lhs, rhs, .. } => write!(f, "{op} {lhs}, {rhs}"),
+            Inst::Cmp {
+                op,
+                is_unsigned,
+                lhs,
+                rhs,
+                ..
+            } => {
+                let op = match op {
+                    CmpOp::Lt => "lt",
+                    CmpOp::Lte => "le",
+                    CmpOp::Gt => "gt",
+                    CmpOp::Gte => "ge",
+                    CmpOp::Eq => "eq",
+                    CmpOp::Neq => "ne",
+                };
+                let sign = if *is_unsigned { "u" } else { "" };
+                write!(f, "cmp {sign}{op} {lhs}, {rhs}")
+            }
+            Inst::Neg { src, .. } => write!(f, "neg {src}"),
+            Inst::Extend {
+                src,
+                size,
+                is_unsigned,
+                ..
+            } => write!(f, "ext.{} {src}", int_name(*size, *is_unsigned)),
+            Inst::Addr { address, .. } => write!(f, "addr {address}"),
+            Inst::FunctionAddr { name, .. } => write!(f, "function @{name}"),
+            Inst::ThreadLocalAddr { name, .. } => write!(f, "thread_local @{name}"),
+            Inst::Load {
+                address,
+                size,
+                is_unsigned,
+                is_volatile,
+                ..
+            } => {
+                let volatile = if *is_volatile { " volatile" } else { "" };
+                write!(
+                    f,
+                    "load.{}{volatile} {address}",
+                    int_name(*size, *is_unsigned)
+                )
+            }
+            Inst::Store {
+                address,
+                src,
+                size,
+                is_volatile,
+            } => {
+                let volatile = if *is_volatile { " volatile" } else { "" };
+                write!(
+                    f,
+                    "store.{}{volatile} {address}, {src}",
+                    int_name(*size, false)
+                )
+            }
+            Inst::MemCopy { dest, src, size } => write!(f, "memcpy {dest}, {src}, {size}"),
+            Inst::MemSet { dest, value, size } => write!(f, "memset {dest}, {value}, {size}"),
+            Inst::Call { callee, args, .. } => {
+                match callee {
+                    Callee::Direct(name) => write!(f, "call @{name}(")?,
+                    Callee::Indirect(operand) => write!(f, "call {operand}(")?,
+                }
+                for (index, (arg, _)) in args.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            Inst::Alloca { size, .. } => write!(f, "alloca {size}"),
+            Inst::StackSave { .. } => write!(f, "stacksave"),
+            Inst::StackRestore { src } => write!(f, "stackrestore {src}"),
+            Inst::Atomic {
+                op,
+                order,
+                args,
+                size,
+                ..
+            } => {
+                write!(f, "atomic.{} {op:?} {order:?}", int_name(*size, false))?;
+                for arg in args {
+                    write!(f, ", {arg}")?;
+                }
+                Ok(())
+            }
+            Inst::Fence { order } => write!(f, "fence {order:?}"),
+            Inst::BitOp { op, src, size, .. } => {
+                write!(
+                    f,
+                    "{}.{} {src}",
+                    format!("{op:?}").to_lowercase(),
+                    int_name(*size, false)
+                )
+            }
+            Inst::Overflow {
+                op,
+                is_unsigned,
+                lhs,
+                rhs,
+                ..
+            } => {
+                let sign = if *is_unsigned { "u" } else { "s" };
+                write!(f, "{sign}{}.overflow {lhs}, {rhs}", BinaryOp::from(*op))
+            }
+            Inst::Trap => write!(f, "trap"),
+            Inst::Asm(asm) => {
+                write!(f, "asm")?;
+                for (vreg, register) in &asm.outputs {
+                    write!(f, " {vreg}={register}")?;
+                }
+                for (register, operand) in &asm.inputs {
+                    write!(f, " {register}={operand}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jmp {target}"),
+            Terminator::Branch { cond, then, else_ } => write!(f, "br {cond}, {then}, {else_}"),
+            Terminator::Return(Some(value)) => write!(f, "ret {value}"),
+            Terminator::Return(None) => write!(f, "ret"),
+        }
+    }
+}
+
+impl From<ArithOp> for BinaryOp {
+    fn from(op: ArithOp) -> Self {
+        match op {
+            ArithOp::Add => BinaryOp::Add,
+            ArithOp::Sub => BinaryOp::Sub,
+            ArithOp::Mul => BinaryOp::Mul,
+            ArithOp::Div => BinaryOp::SDiv,
+            ArithOp::Assign => unreachable!("Assignment isn't an operation"),
+        }
+    }
+}
+
+// Like `i32` or `u8`.
+fn int_name(size: usize, is_unsigned: bool) -> String {
+    let sign = if is_unsigned { "u" } else { "i" };
+    format!("{sign}{}", size * 8)
+}
@@ -1,70 +1,144 @@
-use anyhow::Result;
+use core::fmt;
+use std::{iter::Peekable, str::CharIndices};
 
-use crate::error_reporter;
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticEngine},
+    source_map::{FileId, SourceMap, Span},
+};
 
 #[derive(PartialEq, Debug)]
-pub struct Tokens<'a>(Vec<Token<'a>>);
+pub struct Tokens(Vec<Token>);
 
-impl<'a> IntoIterator for Tokens<'a> {
-    type Item = Token<'a>;
-    type IntoIter = std::vec::IntoIter<Token<'a>>;
+impl IntoIterator for Tokens {
+    type Item = Token;
+    type IntoIter = std::vec::IntoIter<Token>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.into_iter()
     }
 }
 
-impl<'a> Tokens<'a> {
-    fn push(&mut self, token: Token<'a>) {
+impl FromIterator<Token> for Tokens {
+    fn from_iter<T: IntoIterator<Item = Token>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Tokens {
+    fn push(&mut self, token: Token) {
         self.0.push(token);
     }
 }
 
-#[derive(PartialEq, Debug)]
-pub struct Token<'a> {
+#[derive(PartialEq, Debug, Clone)]
+pub struct Token {
     pub value: TokenKind,
-    pub metadata: TokenMetadata<'a>,
+    pub span: Span,
 }
 
-impl<'a> Token<'a> {
-    fn new(value: TokenKind, code_location: usize, user_input: &'a str) -> Self {
-        Self {
-            value,
-            metadata: TokenMetadata {
-                code_location,
-                user_input,
-            },
-        }
+impl Token {
+    fn new(value: TokenKind, span: Span) -> Self {
+        Self { value, span }
     }
 
-    pub fn symbol(symbol_kind: Symbol, code_location: usize, user_input: &'a str) -> Self {
-        Self::new(TokenKind::Symbol(symbol_kind), code_location, user_input)
+    pub fn symbol(symbol_kind: Symbol, span: Span) -> Self {
+        Self::new(TokenKind::Symbol(symbol_kind), span)
     }
 
-    pub fn num(num: i64, code_location: usize, user_input: &'a str) -> Self {
-        Self::new(TokenKind::Num(num), code_location, user_input)
+    pub fn num(num: u64, suffix: IntSuffix, span: Span) -> Self {
+        Self::new(TokenKind::Num(num, suffix), span)
     }
 
-    pub fn ident(ident: String, code_location: usize, user_input: &'a str) -> Self {
-        Self::new(TokenKind::Ident(ident), code_location, user_input)
+    pub fn ident(ident: String, span: Span) -> Self {
+        Self::new(TokenKind::Ident(ident), span)
+    }
+
+    pub fn str(str: String, span: Span) -> Self {
+        Self::new(TokenKind::Str(str), span)
+    }
+
+    pub fn error(span: Span) -> Self {
+        Self::new(TokenKind::Error, span)
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum TokenKind {
     Symbol(Symbol),
-    Num(i64),
+    Num(u64, IntSuffix),
     Ident(String),
+    // A string literal, with its escape sequences already resolved.
+    Str(String),
+    // Stands in for a token which couldn't be lexed and has already been reported, so that the
+    // parser goes on as if it were valid.
+    Error,
 }
 
-#[derive(PartialEq, Debug)]
-pub struct TokenMetadata<'a> {
-    // Indicates how many bytes of the source code the token starts from.
-    pub code_location: usize,
-    pub user_input: &'a str,
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Symbol(symbol) => write!(f, "{symbol}"),
+            TokenKind::Num(num, suffix) => write!(f, "{num}{suffix}"),
+            TokenKind::Ident(ident) => write!(f, "{ident}"),
+            TokenKind::Str(str) => {
+                write!(f, "\"")?;
+                for c in str.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\0' => write!(f, "\\0")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            TokenKind::Error => write!(f, "<error>"),
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+/// The suffix of an integer literal, which chooses its type with its value.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct IntSuffix {
+    pub is_unsigned: bool,
+    // 1 for `l` and 2 for `ll`.
+    pub longs: usize,
+}
+
+impl fmt::Display for IntSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unsigned {
+            write!(f, "u")?;
+        }
+        write!(f, "{}", "l".repeat(self.longs))
+    }
+}
+
+impl TryFrom<&str> for IntSuffix {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (is_unsigned, longs) = match value {
+            "" => (false, ""),
+            _ if value.starts_with(['u', 'U']) => (true, &value[1..]),
+            _ if value.ends_with(['u', 'U']) => (true, &value[..value.len() - 1]),
+            _ => (false, value),
+        };
+
+        let longs = match longs {
+            "" => 0,
+            "l" | "L" => 1,
+            "ll" | "LL" => 2,
+            _ => return Err(()),
+        };
+        Ok(Self { is_unsigned, longs })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum Symbol {
     Add,
     Sub,
@@ -79,103 +153,243 @@ pub enum Symbol {
     Eq,
     Neq,
     Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    Inc,
+    Dec,
     SemiColon,
+    Colon,
     Ret,
     If,
     Else,
     While,
     For,
+    LBrace,
+    RBrace,
+    Hash,
+    Comma,
+    Void,
+    Char,
+    Short,
+    Int,
+    Long,
+    Bool,
+    Signed,
+    Unsigned,
+    Sizeof,
+    Alignof,
+    Struct,
+    Union,
+    StaticAssert,
+    Generic,
+    Default,
+    Noreturn,
+    Alignas,
+    Atomic,
+    Inline,
+    Attribute,
+    Asm,
+    Extension,
+    Typeof,
+    Ellipsis,
+    LBracket,
+    RBracket,
+    Dot,
+    Arrow,
+    Amp,
+    Const,
+    Volatile,
+    Restrict,
+    Static,
+    Register,
+    Auto,
+    Extern,
+    ThreadLocal,
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Symbol::Add => "+",
+                Symbol::Sub => "-",
+                Symbol::Mul => "*",
+                Symbol::Div => "/",
+                Symbol::LParen => "(",
+                Symbol::RParen => ")",
+                Symbol::Lt => "<",
+                Symbol::Lte => "<=",
+                Symbol::Gt => ">",
+                Symbol::Gte => ">=",
+                Symbol::Eq => "==",
+                Symbol::Neq => "!=",
+                Symbol::Assign => "=",
+                Symbol::AddAssign => "+=",
+                Symbol::SubAssign => "-=",
+                Symbol::MulAssign => "*=",
+                Symbol::DivAssign => "/=",
+                Symbol::Inc => "++",
+                Symbol::Dec => "--",
+                Symbol::SemiColon => ";",
+                Symbol::Colon => ":",
+                Symbol::Ret => "return",
+                Symbol::If => "if",
+                Symbol::Else => "else",
+                Symbol::While => "while",
+                Symbol::For => "for",
+                Symbol::LBrace => "{",
+                Symbol::RBrace => "}",
+                Symbol::Hash => "#",
+                Symbol::Comma => ",",
+                Symbol::Void => "void",
+                Symbol::Char => "char",
+                Symbol::Short => "short",
+                Symbol::Int => "int",
+                Symbol::Long => "long",
+                Symbol::Bool => "_Bool",
+                Symbol::Signed => "signed",
+                Symbol::Unsigned => "unsigned",
+                Symbol::Sizeof => "sizeof",
+                Symbol::Alignof => "_Alignof",
+                Symbol::Struct => "struct",
+                Symbol::Union => "union",
+                Symbol::StaticAssert => "_Static_assert",
+                Symbol::Generic => "_Generic",
+                Symbol::Default => "default",
+                Symbol::Noreturn => "_Noreturn",
+                Symbol::Alignas => "_Alignas",
+                Symbol::Atomic => "_Atomic",
+                Symbol::Inline => "inline",
+                Symbol::Attribute => "__attribute__",
+                Symbol::Asm => "__asm__",
+                Symbol::Extension => "__extension__",
+                Symbol::Typeof => "__typeof__",
+                Symbol::Ellipsis => "...",
+                Symbol::LBracket => "[",
+                Symbol::RBracket => "]",
+                Symbol::Dot => ".",
+                Symbol::Arrow => "->",
+                Symbol::Amp => "&",
+                Symbol::Const => "const",
+                Symbol::Volatile => "volatile",
+                Symbol::Restrict => "restrict",
+                Symbol::Static => "static",
+                Symbol::Register => "register",
+                Symbol::Auto => "auto",
+                Symbol::Extern => "extern",
+                Symbol::ThreadLocal => "_Thread_local",
+            }
+        )
+    }
 }
 
-impl From<&str> for Symbol {
-    fn from(value: &str) -> Self {
+impl TryFrom<&str> for Symbol {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "<=" => Self::Lte,
-            ">=" => Self::Gte,
-            "==" => Self::Eq,
-            "!=" => Self::Neq,
-            "return" => Self::Ret,
-            "if" => Self::If,
-            "else" => Self::Else,
-            "while" => Self::While,
-            "for" => Self::For,
-            _ => panic!("Invalid symbol"),
+            "<=" => Ok(Self::Lte),
+            ">=" => Ok(Self::Gte),
+            "==" => Ok(Self::Eq),
+            "!=" => Ok(Self::Neq),
+            "+=" => Ok(Self::AddAssign),
+            "-=" => Ok(Self::SubAssign),
+            "*=" => Ok(Self::MulAssign),
+            "/=" => Ok(Self::DivAssign),
+            "++" => Ok(Self::Inc),
+            "--" => Ok(Self::Dec),
+            "return" => Ok(Self::Ret),
+            "if" => Ok(Self::If),
+            "else" => Ok(Self::Else),
+            "while" => Ok(Self::While),
+            "for" => Ok(Self::For),
+            "void" => Ok(Self::Void),
+            "char" => Ok(Self::Char),
+            "short" => Ok(Self::Short),
+            "int" => Ok(Self::Int),
+            "long" => Ok(Self::Long),
+            "_Bool" => Ok(Self::Bool),
+            "signed" => Ok(Self::Signed),
+            "unsigned" => Ok(Self::Unsigned),
+            "sizeof" => Ok(Self::Sizeof),
+            "_Alignof" => Ok(Self::Alignof),
+            "struct" => Ok(Self::Struct),
+            "union" => Ok(Self::Union),
+            "_Static_assert" => Ok(Self::StaticAssert),
+            "_Generic" => Ok(Self::Generic),
+            "default" => Ok(Self::Default),
+            "_Noreturn" => Ok(Self::Noreturn),
+            "_Alignas" => Ok(Self::Alignas),
+            "_Atomic" => Ok(Self::Atomic),
+            // GNU spellings, some of which are reserved identifiers to be usable in strict modes.
+            "inline" | "__inline" | "__inline__" => Ok(Self::Inline),
+            "__attribute__" | "__attribute" => Ok(Self::Attribute),
+            "asm" | "__asm" | "__asm__" => Ok(Self::Asm),
+            "__extension__" => Ok(Self::Extension),
+            "typeof" | "__typeof" | "__typeof__" => Ok(Self::Typeof),
+            "__alignof__" => Ok(Self::Alignof),
+            "__signed__" => Ok(Self::Signed),
+            "const" | "__const" => Ok(Self::Const),
+            "volatile" | "__volatile" | "__volatile__" => Ok(Self::Volatile),
+            "restrict" | "__restrict" | "__restrict__" => Ok(Self::Restrict),
+            "static" => Ok(Self::Static),
+            "register" => Ok(Self::Register),
+            "auto" => Ok(Self::Auto),
+            "extern" => Ok(Self::Extern),
+            "_Thread_local" | "__thread" => Ok(Self::ThreadLocal),
+            "->" => Ok(Self::Arrow),
+            _ => Err(()),
         }
     }
 }
 
-impl From<char> for Symbol {
-    fn from(value: char) -> Self {
+impl TryFrom<char> for Symbol {
+    type Error = ();
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
         match value {
-            '+' => Self::Add,
-            '-' => Self::Sub,
-            '*' => Self::Mul,
-            '/' => Self::Div,
-            '(' => Self::LParen,
-            ')' => Self::RParen,
-            '<' => Self::Lt,
-            '>' => Self::Gt,
-            '=' => Self::Assign,
-            ';' => Self::SemiColon,
-            _ => panic!("Invalid symbol"),
+            '+' => Ok(Self::Add),
+            '-' => Ok(Self::Sub),
+            '*' => Ok(Self::Mul),
+            '/' => Ok(Self::Div),
+            '(' => Ok(Self::LParen),
+            ')' => Ok(Self::RParen),
+            '{' => Ok(Self::LBrace),
+            '}' => Ok(Self::RBrace),
+            '<' => Ok(Self::Lt),
+            '>' => Ok(Self::Gt),
+            '=' => Ok(Self::Assign),
+            ';' => Ok(Self::SemiColon),
+            ':' => Ok(Self::Colon),
+            '#' => Ok(Self::Hash),
+            ',' => Ok(Self::Comma),
+            '[' => Ok(Self::LBracket),
+            ']' => Ok(Self::RBracket),
+            '.' => Ok(Self::Dot),
+            '&' => Ok(Self::Amp),
+            _ => Err(()),
         }
     }
 }
 
-pub fn tokenize(s: &str) -> Result<Tokens> {
+/// Splits the file into tokens. Invalid ones are reported to `diags` and replaced with
+/// `TokenKind::Error`.
+pub fn tokenize(source_map: &SourceMap, file: FileId, diags: &mut DiagnosticEngine) -> Tokens {
+    let s = source_map.file(file).text();
+
     let mut tokens = Tokens(vec![]);
 
     let mut chars = s.char_indices().peekable();
 
     while let Some((code_location, char)) = chars.next() {
         match char {
-            ' ' | '\n' | '\r' => {
+            ' ' | '\t' | '\n' | '\r' => {
                 continue;
             }
-            '+' | '-' | '*' | '/' | '(' | ')' | ';' => {
-                tokens.push(Token::symbol(Symbol::from(char), code_location, s))
-            }
-            '<' | '>' => {
-                let next_char = chars.peek().map(|&(_, c)| c);
-
-                match (char, next_char) {
-                    ('<', Some('=')) => {
-                        chars.next();
-                        tokens.push(Token::symbol(Symbol::Lte, code_location, s))
-                    }
-                    ('>', Some('=')) => {
-                        chars.next();
-                        tokens.push(Token::symbol(Symbol::Gte, code_location, s))
-                    }
-                    _ => tokens.push(Token::symbol(Symbol::from(char), code_location, s)),
-                }
-            }
-            '=' => {
-                let next_char = chars.peek().map(|&(_, c)| c);
-
-                match (char, next_char) {
-                    ('=', Some('=')) => {
-                        chars.next();
-                        tokens.push(Token::symbol(Symbol::Eq, code_location, s))
-                    }
-                    _ => {
-                        tokens.push(Token::symbol(Symbol::Assign, code_location, s));
-                    }
-                }
-            }
-            '!' => {
-                let next_char = chars.peek().map(|&(_, c)| c);
-
-                match (char, next_char) {
-                    ('!', Some('=')) => {
-                        chars.next();
-                        tokens.push(Token::symbol(Symbol::Neq, code_location, s))
-                    }
-                    _ => {
-                        error_reporter::report(s, code_location, "Invalid token");
-                    }
-                }
-            }
             '0'..='9' => {
                 let mut numbers = String::new();
                 numbers.push(char);
@@ -189,7 +403,35 @@ pub fn tokenize(s: &str) -> Result<Tokens> {
                     }
                 }
 
-                tokens.push(Token::num(numbers.parse().unwrap(), code_location, s))
+                let mut suffix = String::new();
+                while let Some(&(_, next_char)) = chars.peek() {
+                    if next_char.is_ascii_alphanumeric() || next_char == '_' {
+                        suffix.push(next_char);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                let span = Span::new(file, code_location, end_of(&mut chars, s));
+                let Ok(suffix) = IntSuffix::try_from(suffix.as_str()) else {
+                    diags.error(
+                        span,
+                        format!("Invalid suffix '{suffix}' on integer constant"),
+                    );
+                    tokens.push(Token::error(span));
+                    continue;
+                };
+                match numbers.parse() {
+                    Ok(num) => tokens.push(Token::num(num, suffix, span)),
+                    Err(_) => {
+                        diags.emit(
+                            Diagnostic::error(span, "Integer literal is too large")
+                                .with_note(None, format!("The largest one is {}", u64::MAX)),
+                        );
+                        tokens.push(Token::error(span));
+                    }
+                }
             }
             'a'..='z' | 'A'..='Z' | '_' => {
                 let mut str = String::new();
@@ -204,52 +446,159 @@ pub fn tokenize(s: &str) -> Result<Tokens> {
                     }
                 }
 
-                match str.as_str() {
-                    "return" => tokens.push(Token::symbol(Symbol::Ret, code_location, s)),
-                    "if" => tokens.push(Token::symbol(Symbol::If, code_location, s)),
-                    "else" => tokens.push(Token::symbol(Symbol::Else, code_location, s)),
-                    "while" => tokens.push(Token::symbol(Symbol::While, code_location, s)),
-                    "for" => tokens.push(Token::symbol(Symbol::For, code_location, s)),
-                    _ => tokens.push(Token::ident(str, code_location, s)),
+                let span = Span::new(file, code_location, code_location + str.len());
+                match Symbol::try_from(str.as_str()) {
+                    Ok(keyword) => tokens.push(Token::symbol(keyword, span)),
+                    Err(_) => tokens.push(Token::ident(str, span)),
+                }
+            }
+            '"' => {
+                let mut str = String::new();
+                let mut terminated = false;
+
+                while let Some((escape_location, next_char)) = chars.next() {
+                    match next_char {
+                        '"' => {
+                            terminated = true;
+                            break;
+                        }
+                        '\n' => break,
+                        '\\' => match chars.next() {
+                            Some((_, 'n')) => str.push('\n'),
+                            Some((_, 't')) => str.push('\t'),
+                            Some((_, 'r')) => str.push('\r'),
+                            Some((_, '0')) => str.push('\0'),
+                            Some((_, c @ ('"' | '\'' | '\\' | '?'))) => str.push(c),
+                            Some((_, c)) => diags.error(
+                                Span::new(file, escape_location, end_of(&mut chars, s)),
+                                format!("Unknown escape sequence '\\{c}'"),
+                            ),
+                            None => break,
+                        },
+                        c => str.push(c),
+                    }
+                }
+
+                let span = Span::new(file, code_location, end_of(&mut chars, s));
+                if terminated {
+                    tokens.push(Token::str(str, span));
+                } else {
+                    diags.error(span, "Unterminated string literal");
+                    tokens.push(Token::error(span));
                 }
             }
+            '.' if s[code_location..].starts_with("...") => {
+                chars.next();
+                chars.next();
+                tokens.push(Token::symbol(
+                    Symbol::Ellipsis,
+                    Span::new(file, code_location, code_location + 3),
+                ));
+            }
             _ => {
-                error_reporter::report(s, code_location, "Invalid token");
+                let next_char = chars.peek().map(|&(_, c)| c);
+
+                // Two-character punctuators take precedence over one-character ones.
+                let two_chars = next_char.and_then(|next_char| {
+                    Symbol::try_from(format!("{char}{next_char}").as_str()).ok()
+                });
+
+                if let Some(symbol) = two_chars {
+                    chars.next();
+                    tokens.push(Token::symbol(
+                        symbol,
+                        Span::new(file, code_location, end_of(&mut chars, s)),
+                    ));
+                } else if let Ok(symbol) = Symbol::try_from(char) {
+                    tokens.push(Token::symbol(
+                        symbol,
+                        Span::new(file, code_location, code_location + 1),
+                    ));
+                } else {
+                    let span = Span::new(file, code_location, code_location + char.len_utf8());
+                    diags.error(span, "Invalid token");
+                    tokens.push(Token::error(span));
+                }
             }
         }
     }
 
-    Ok(tokens)
+    tokens
+}
+
+// Byte offset just past the token that has been consumed from `chars`.
+fn end_of(chars: &mut Peekable<CharIndices>, s: &str) -> usize {
+    chars.peek().map_or(s.len(), |&(i, _)| i)
 }
 
 #[cfg(test)]
 mod tests {
+    use anyhow::Result;
+
     use super::*;
 
+    fn tokenize_str(
+        c_code: &str,
+    ) -> Result<(std::vec::IntoIter<Token>, impl Fn(usize, usize) -> Span)> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", c_code);
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = tokenize(&source_map, file, &mut diags);
+
+        Ok((diags.finish(tokens)?.0.into_iter(), move |start, end| {
+            Span::new(file, start, end)
+        }))
+    }
+
     #[test]
     fn test_success() -> Result<()> {
         let c_code = "(+1 + -2) * 3 - 4 / 5";
-        let mut actual = tokenize(c_code)?.into_iter();
+        let (mut actual, span) = tokenize_str(c_code)?;
 
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::LParen, 0, c_code))
+            Some(Token::symbol(Symbol::LParen, span(0, 1)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, 1, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(1, 2, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, 4, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Sub, 6, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(2, 7, c_code)));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, span(1, 2))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::RParen, 8, c_code))
+            Some(Token::num(1, IntSuffix::default(), span(2, 3)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, span(4, 5))));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Sub, span(6, 7))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(2, IntSuffix::default(), span(7, 8)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::RParen, span(8, 9)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Mul, span(10, 11)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(3, IntSuffix::default(), span(12, 13)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Sub, span(14, 15)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(4, IntSuffix::default(), span(16, 17)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Div, span(18, 19)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(5, IntSuffix::default(), span(20, 21)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Mul, 10, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(3, 12, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Sub, 14, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(4, 16, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Div, 18, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(5, 20, c_code)));
         assert_eq!(actual.next(), None);
 
         Ok(())
@@ -258,21 +607,48 @@ mod tests {
     #[test]
     fn test_success_with_comparison_operator() -> Result<()> {
         let c_code = "1 < 2 <= 3 > 4 >= 5 == 6 != 7";
-        let mut actual = tokenize(c_code)?.into_iter();
-
-        assert_eq!(actual.next(), Some(Token::num(1, 0, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, 2, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(2, 4, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lte, 6, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(3, 9, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Gt, 11, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(4, 13, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Gte, 15, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(5, 18, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Eq, 20, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(6, 23, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Neq, 25, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(7, 28, c_code)));
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(1, IntSuffix::default(), span(0, 1)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, span(2, 3))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(2, IntSuffix::default(), span(4, 5)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lte, span(6, 8))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(3, IntSuffix::default(), span(9, 10)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Gt, span(11, 12))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(4, IntSuffix::default(), span(13, 14)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Gte, span(15, 17)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(5, IntSuffix::default(), span(18, 19)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Eq, span(20, 22))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(6, IntSuffix::default(), span(23, 24)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Neq, span(25, 27)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(7, IntSuffix::default(), span(28, 29)))
+        );
         assert_eq!(actual.next(), None);
 
         Ok(())
@@ -281,45 +657,66 @@ mod tests {
     #[test]
     fn test_ok_assign() -> Result<()> {
         let c_code = "a = 1; bar = 2; car = a + bar; return car;";
-        let mut actual = tokenize(c_code)?.into_iter();
+        let (mut actual, span) = tokenize_str(c_code)?;
 
-        assert_eq!(actual.next(), Some(Token::ident("a".into(), 0, c_code)));
+        assert_eq!(actual.next(), Some(Token::ident("a".into(), span(0, 1))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Assign, span(2, 3)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(1, IntSuffix::default(), span(4, 5)))
+        );
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::Assign, 2, c_code))
+            Some(Token::symbol(Symbol::SemiColon, span(5, 6)))
         );
-        assert_eq!(actual.next(), Some(Token::num(1, 4, c_code)));
+        assert_eq!(actual.next(), Some(Token::ident("bar".into(), span(7, 10))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 5, c_code))
+            Some(Token::symbol(Symbol::Assign, span(11, 12)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("bar".into(), 7, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::Assign, 11, c_code))
+            Some(Token::num(2, IntSuffix::default(), span(13, 14)))
         );
-        assert_eq!(actual.next(), Some(Token::num(2, 13, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 14, c_code))
+            Some(Token::symbol(Symbol::SemiColon, span(14, 15)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("car".into(), 16, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::Assign, 20, c_code))
+            Some(Token::ident("car".into(), span(16, 19)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("a".into(), 22, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, 24, c_code)));
-        assert_eq!(actual.next(), Some(Token::ident("bar".into(), 26, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 29, c_code))
+            Some(Token::symbol(Symbol::Assign, span(20, 21)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Ret, 31, c_code)));
-        assert_eq!(actual.next(), Some(Token::ident("car".into(), 38, c_code)));
+        assert_eq!(actual.next(), Some(Token::ident("a".into(), span(22, 23))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 41, c_code))
+            Some(Token::symbol(Symbol::Add, span(24, 25)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::ident("bar".into(), span(26, 29)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(29, 30)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Ret, span(31, 37)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::ident("car".into(), span(38, 41)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(41, 42)))
         );
         assert_eq!(actual.next(), None);
 
@@ -329,32 +726,53 @@ mod tests {
     #[test]
     fn test_ok_if() -> Result<()> {
         let c_code = "if (1 < 2) return 3; else return 4;";
-        let mut actual = tokenize(c_code)?.into_iter();
+        let (mut actual, span) = tokenize_str(c_code)?;
 
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::If, 0, c_code)));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::If, span(0, 2))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::LParen, 3, c_code))
+            Some(Token::symbol(Symbol::LParen, span(3, 4)))
         );
-        assert_eq!(actual.next(), Some(Token::num(1, 4, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, 6, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(2, 8, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::RParen, 9, c_code))
+            Some(Token::num(1, IntSuffix::default(), span(4, 5)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Ret, 11, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(3, 18, c_code)));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, span(6, 7))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 19, c_code))
+            Some(Token::num(2, IntSuffix::default(), span(8, 9)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Else, 21, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Ret, 26, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(4, 33, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 34, c_code))
+            Some(Token::symbol(Symbol::RParen, span(9, 10)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Ret, span(11, 17)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(3, IntSuffix::default(), span(18, 19)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(19, 20)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Else, span(21, 25)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Ret, span(26, 32)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(4, IntSuffix::default(), span(33, 34)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(34, 35)))
         );
         assert_eq!(actual.next(), None);
 
@@ -364,25 +782,40 @@ mod tests {
     #[test]
     fn test_ok_while() -> Result<()> {
         let c_code = "while (1 < 2) return 3;";
-        let mut actual = tokenize(c_code)?.into_iter();
+        let (mut actual, span) = tokenize_str(c_code)?;
 
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::While, 0, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::LParen, 6, c_code))
+            Some(Token::symbol(Symbol::While, span(0, 5)))
         );
-        assert_eq!(actual.next(), Some(Token::num(1, 7, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, 9, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(2, 11, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::RParen, 12, c_code))
+            Some(Token::symbol(Symbol::LParen, span(6, 7)))
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Ret, 14, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(3, 21, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 22, c_code))
+            Some(Token::num(1, IntSuffix::default(), span(7, 8)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, span(9, 10))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(2, IntSuffix::default(), span(11, 12)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::RParen, span(12, 13)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Ret, span(14, 20)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(3, IntSuffix::default(), span(21, 22)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(22, 23)))
         );
         assert_eq!(actual.next(), None);
 
@@ -392,50 +825,341 @@ mod tests {
     #[test]
     fn test_ok_for() -> Result<()> {
         let c_code = "for (i = 0; i < 10; i = i + 1) return i;";
-        let mut actual = tokenize(c_code)?.into_iter();
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::For, span(0, 3))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::LParen, span(4, 5)))
+        );
+        assert_eq!(actual.next(), Some(Token::ident("i".into(), span(5, 6))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Assign, span(7, 8)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(0, IntSuffix::default(), span(9, 10)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(10, 11)))
+        );
+        assert_eq!(actual.next(), Some(Token::ident("i".into(), span(12, 13))));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, span(14, 15))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(10, IntSuffix::default(), span(16, 18)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(18, 19)))
+        );
+        assert_eq!(actual.next(), Some(Token::ident("i".into(), span(20, 21))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Assign, span(22, 23)))
+        );
+        assert_eq!(actual.next(), Some(Token::ident("i".into(), span(24, 25))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Add, span(26, 27)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::num(1, IntSuffix::default(), span(28, 29)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::RParen, span(29, 30)))
+        );
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::Ret, span(31, 37)))
+        );
+        assert_eq!(actual.next(), Some(Token::ident("i".into(), span(38, 39))));
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::SemiColon, span(39, 40)))
+        );
+        assert_eq!(actual.next(), None);
+
+        Ok(())
+    }
 
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::For, 0, c_code)));
+    #[test]
+    fn test_ok_block() -> Result<()> {
+        let c_code = "{ 1; }";
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual.next(),
+            Some(Token::symbol(Symbol::LBrace, span(0, 1)))
+        );
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::LParen, 4, c_code))
+            Some(Token::num(1, IntSuffix::default(), span(2, 3)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("i".into(), 5, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::Assign, 7, c_code))
+            Some(Token::symbol(Symbol::SemiColon, span(3, 4)))
         );
-        assert_eq!(actual.next(), Some(Token::num(0, 9, c_code)));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 10, c_code))
+            Some(Token::symbol(Symbol::RBrace, span(5, 6)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("i".into(), 12, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Lt, 14, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(10, 16, c_code)));
+        assert_eq!(actual.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_declaration() -> Result<()> {
+        let c_code = "long int a, b;";
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Long, span(0, 4))));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Int, span(5, 8))));
+        assert_eq!(actual.next(), Some(Token::ident("a".into(), span(9, 10))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 18, c_code))
+            Some(Token::symbol(Symbol::Comma, span(10, 11)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("i".into(), 20, c_code)));
+        assert_eq!(actual.next(), Some(Token::ident("b".into(), span(12, 13))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::Assign, 22, c_code))
+            Some(Token::symbol(Symbol::SemiColon, span(13, 14)))
         );
-        assert_eq!(actual.next(), Some(Token::ident("i".into(), 24, c_code)));
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Add, 26, c_code)));
-        assert_eq!(actual.next(), Some(Token::num(1, 28, c_code)));
+        assert_eq!(actual.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_compound_assignment() -> Result<()> {
+        let c_code = "a++ -= --b";
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(actual.next(), Some(Token::ident("a".into(), span(0, 1))));
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Inc, span(1, 3))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::RParen, 29, c_code))
+            Some(Token::symbol(Symbol::SubAssign, span(4, 6)))
+        );
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Dec, span(7, 9))));
+        assert_eq!(actual.next(), Some(Token::ident("b".into(), span(9, 10))));
+        assert_eq!(actual.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_member_access() -> Result<()> {
+        let c_code = "&p->a[0].b";
+        let (actual, _) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual
+                .map(|token| token.value.to_string())
+                .collect::<Vec<_>>(),
+            vec!["&", "p", "->", "a", "[", "0", "]", ".", "b"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_qualifiers_and_storage_classes() -> Result<()> {
+        let c_code = "static const volatile int *restrict p; register auto x;";
+        let (actual, _) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual
+                .filter(|token| matches!(token.value, TokenKind::Symbol(_)))
+                .map(|token| token.value.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "static", "const", "volatile", "int", "*", "restrict", ";", "register", "auto", ";"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_c11_keywords() -> Result<()> {
+        let c_code = "_Static_assert _Generic default _Noreturn _Alignas _Atomic _Thread_local union _Alignasx";
+        let (actual, _) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual.map(|token| token.value).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Symbol(Symbol::StaticAssert),
+                TokenKind::Symbol(Symbol::Generic),
+                TokenKind::Symbol(Symbol::Default),
+                TokenKind::Symbol(Symbol::Noreturn),
+                TokenKind::Symbol(Symbol::Alignas),
+                TokenKind::Symbol(Symbol::Atomic),
+                TokenKind::Symbol(Symbol::ThreadLocal),
+                TokenKind::Symbol(Symbol::Union),
+                TokenKind::Ident("_Alignasx".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_gnu_keywords() -> Result<()> {
+        let c_code = "__attribute__ __asm__ asm __extension__ typeof __typeof__ __inline \
+                      __restrict__ __volatile__ __thread ... . .. __attributes";
+        let (actual, _) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual.map(|token| token.value).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Symbol(Symbol::Attribute),
+                TokenKind::Symbol(Symbol::Asm),
+                TokenKind::Symbol(Symbol::Asm),
+                TokenKind::Symbol(Symbol::Extension),
+                TokenKind::Symbol(Symbol::Typeof),
+                TokenKind::Symbol(Symbol::Typeof),
+                TokenKind::Symbol(Symbol::Inline),
+                TokenKind::Symbol(Symbol::Restrict),
+                TokenKind::Symbol(Symbol::Volatile),
+                TokenKind::Symbol(Symbol::ThreadLocal),
+                TokenKind::Symbol(Symbol::Ellipsis),
+                TokenKind::Symbol(Symbol::Dot),
+                TokenKind::Symbol(Symbol::Dot),
+                TokenKind::Symbol(Symbol::Dot),
+                TokenKind::Ident("__attributes".to_string()),
+            ]
         );
-        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Ret, 31, c_code)));
-        assert_eq!(actual.next(), Some(Token::ident("i".into(), 38, c_code)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_integer_types() -> Result<()> {
+        let c_code =
+            "unsigned long long a = 18446744073709551615ULL; _Bool b = 1lu; signed c = 2L;";
+        let (actual, _) = tokenize_str(c_code)?;
+
+        assert_eq!(
+            actual
+                .filter(|token| !matches!(token.value, TokenKind::Ident(_)))
+                .map(|token| token.value.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "unsigned",
+                "long",
+                "long",
+                "=",
+                "18446744073709551615ull",
+                ";",
+                "_Bool",
+                "=",
+                "1ul",
+                ";",
+                "signed",
+                "=",
+                "2l",
+                ";"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_str_and_hash() -> Result<()> {
+        let c_code = r#"# "a\"b\n""#;
+        let (mut actual, span) = tokenize_str(c_code)?;
+
+        assert_eq!(actual.next(), Some(Token::symbol(Symbol::Hash, span(0, 1))));
         assert_eq!(
             actual.next(),
-            Some(Token::symbol(Symbol::SemiColon, 39, c_code))
+            Some(Token::str("a\"b\n".to_string(), span(2, 10)))
         );
         assert_eq!(actual.next(), None);
 
+        assert_eq!(
+            TokenKind::Str("a\"b\n".to_string()).to_string(),
+            r#""a\"b\n""#
+        );
+
         Ok(())
     }
+
+    #[test]
+    fn test_ng_unterminated_str() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", "\"abc");
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = tokenize(&source_map, file, &mut diags);
+
+        assert_eq!(
+            diags.finish(tokens).unwrap_err().0,
+            vec![Diagnostic::error(
+                Span::new(file, 0, 4),
+                "Unterminated string literal"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_ng_reports_every_invalid_token() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", "1 @ 2 $ 3;");
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = tokenize(&source_map, file, &mut diags);
+
+        // They are replaced, rather than dropped.
+        let kinds: Vec<_> = tokens.0.iter().map(|token| token.value.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Num(1, IntSuffix::default()),
+                TokenKind::Error,
+                TokenKind::Num(2, IntSuffix::default()),
+                TokenKind::Error,
+                TokenKind::Num(3, IntSuffix::default()),
+                TokenKind::Symbol(Symbol::SemiColon),
+            ]
+        );
+        assert_eq!(
+            diags.finish(tokens).unwrap_err().0,
+            vec![
+                Diagnostic::error(Span::new(file, 2, 3), "Invalid token"),
+                Diagnostic::error(Span::new(file, 6, 7), "Invalid token"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_integer_literals() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", "1lul + 2x + 18446744073709551616;");
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = tokenize(&source_map, file, &mut diags);
+
+        assert_eq!(
+            diags.finish(tokens).unwrap_err().0,
+            vec![
+                Diagnostic::error(
+                    Span::new(file, 0, 4),
+                    "Invalid suffix 'lul' on integer constant"
+                ),
+                Diagnostic::error(
+                    Span::new(file, 7, 9),
+                    "Invalid suffix 'x' on integer constant"
+                ),
+                Diagnostic::error(Span::new(file, 12, 32), "Integer literal is too large")
+                    .with_note(None, "The largest one is 18446744073709551615"),
+            ]
+        );
+    }
 }
@@ -0,0 +1,233 @@
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticEngine},
+    parser::{ArithOp, Node, NodeKind, TypeName, UnaryOp},
+    source_map::Span,
+    types::Type,
+    warning::Warning,
+};
+
+/// Reports the warnings that only need the syntax, e.g. discarded results.
+pub fn check(nodes: &[Node], diags: &mut DiagnosticEngine) {
+    let mut linter = Linter { diags };
+
+    for node in nodes {
+        linter.stmt(node);
+    }
+}
+
+struct Linter<'a> {
+    diags: &'a mut DiagnosticEngine,
+}
+
+impl Linter<'_> {
+    fn stmt(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::Block { stmts } => {
+                for stmt in stmts {
+                    self.stmt(stmt);
+                }
+            }
+            NodeKind::Function {
+                body: Some(body), ..
+            } => self.stmt(body),
+            NodeKind::Function { body: None, .. }
+            | NodeKind::Ret { .. }
+            | NodeKind::Decl { .. }
+            | NodeKind::StaticAssert { .. }
+            | NodeKind::Asm { .. }
+            | NodeKind::Error => {}
+            NodeKind::If {
+                cond, then, else_, ..
+            } => {
+                self.cond(cond);
+                self.stmt(then);
+                if let Some(else_) = else_ {
+                    self.stmt(else_);
+                }
+            }
+            NodeKind::While { cond, then, .. } => {
+                self.cond(cond);
+                self.stmt(then);
+            }
+            NodeKind::For {
+                init,
+                cond,
+                update,
+                then,
+                ..
+            } => {
+                if let Some(init) = init {
+                    self.expr_stmt(init);
+                }
+                if let Some(cond) = cond {
+                    self.cond(cond);
+                }
+                if let Some(update) = update {
+                    self.expr_stmt(update);
+                }
+                self.stmt(then);
+            }
+            _ => self.expr_stmt(node),
+        }
+    }
+
+    // An expression whose value is discarded.
+    fn expr_stmt(&mut self, node: &Node) {
+        // Casting to `void` discards the value on purpose.
+        let discarded = matches!(
+            node.kind,
+            NodeKind::Cast {
+                ty: TypeName::Base(Type::Void),
+                ..
+            }
+        );
+
+        if !discarded && !has_side_effects(node) {
+            self.diags.emit(Diagnostic::warning(
+                Warning::UnusedValue,
+                node.span,
+                "Expression result unused",
+            ));
+        }
+    }
+
+    fn cond(&mut self, node: &Node) {
+        if let NodeKind::ArithOp {
+            value: ArithOp::Assign,
+            lhs,
+            ..
+        } = &node.kind
+        {
+            // Parentheses around the assignment widen its span past the start of its lhs.
+            if node.span.start == lhs.span.start {
+                let start = Span::new(node.span.file, node.span.start, node.span.start);
+                let end = Span::new(node.span.file, node.span.end, node.span.end);
+
+                self.diags.emit(
+                    Diagnostic::warning(
+                        Warning::Parentheses,
+                        node.span,
+                        "Using the result of an assignment as a condition without parentheses",
+                    )
+                    .with_fix_it(start, "(")
+                    .with_fix_it(end, ")")
+                    .with_help("Place parentheses around the assignment to silence this warning")
+                    .with_help("Use '==' to turn this assignment into an equality comparison"),
+                );
+            }
+        }
+    }
+}
+
+fn has_side_effects(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::ArithOp {
+            value: ArithOp::Assign,
+            ..
+        }
+        | NodeKind::CompoundAssign { .. }
+        | NodeKind::Call { .. }
+        | NodeKind::StmtExpr { .. }
+        | NodeKind::Unary {
+            value: UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec,
+            ..
+        } => true,
+        NodeKind::ArithOp { lhs, rhs, .. }
+        | NodeKind::CmpOp { lhs, rhs, .. }
+        | NodeKind::Index {
+            base: lhs,
+            index: rhs,
+        } => has_side_effects(lhs) || has_side_effects(rhs),
+        NodeKind::Unary { operand, .. }
+        | NodeKind::Cast { operand, .. }
+        | NodeKind::Member { operand, .. } => has_side_effects(operand),
+        // The selected association isn't known yet.
+        NodeKind::Generic { associations, .. } => associations
+            .iter()
+            .any(|association| has_side_effects(&association.value)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::parse_str;
+
+    use super::*;
+
+    fn warnings(c_code: &str) -> Vec<(usize, Option<Warning>, String)> {
+        let (nodes, mut diags) = parse_str(c_code, &["-Wall"]);
+        check(&nodes, &mut diags);
+
+        diags
+            .finish(())
+            .unwrap()
+            .1
+             .0
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start,
+                    diagnostic.warning,
+                    diagnostic.message,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_no_warnings() {
+        assert_eq!(
+            warnings("a = 0; for (b = 0; b < 10; b = b + 1) a = a + 1; return a;"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_unused_value() {
+        assert_eq!(
+            warnings("a = 1; a + 1; for (a; a < 2; a = a + 1) a == 1; return a;"),
+            vec![
+                (
+                    7,
+                    Some(Warning::UnusedValue),
+                    "Expression result unused".to_string()
+                ),
+                (
+                    19,
+                    Some(Warning::UnusedValue),
+                    "Expression result unused".to_string()
+                ),
+                (
+                    40,
+                    Some(Warning::UnusedValue),
+                    "Expression result unused".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parentheses() {
+        assert_eq!(
+            warnings("a = 1; b = 2; if (a = b) return a; while ((a = b)) return 1; return 0;"),
+            vec![(
+                18,
+                Some(Warning::Parentheses),
+                "Using the result of an assignment as a condition without parentheses".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unused_value_in_functions() {
+        assert_eq!(
+            warnings("int f(void); int g(int a) { f(); -f(); -a; (void)a; return a; }"),
+            vec![(
+                39,
+                Some(Warning::UnusedValue),
+                "Expression result unused".to_string()
+            )]
+        );
+    }
+}
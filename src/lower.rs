@@ -0,0 +1,888 @@
+use crate::{
+    ir::{
+        self, Address, Asm, AsmOperand, AsmPlace, Base, BinaryOp, Block, BlockId, Callee, Inst,
+        Operand, Terminator, VReg,
+    },
+    parser::{ArithOp, CmpOp},
+    sema::{self, Expr, ExprKind, InlineAsm, Stmt},
+    types::{BitField, Target, Type},
+};
+
+/// Lowers the body of each function to a control flow graph of three-address code.
+pub fn lower(program: sema::Program, target: Target) -> ir::Program {
+    let functions = program
+        .functions
+        .iter()
+        .map(|function| self::function(function, target))
+        .collect();
+
+    ir::Program {
+        functions,
+        globals: program.globals,
+        symbols: program.symbols,
+        asm: program.asm,
+    }
+}
+
+fn function(function: &sema::Function, target: Target) -> ir::Function {
+    let mut lower = Lower {
+        blocks: vec![],
+        layout: vec![],
+        current: None,
+        vregs: 0,
+        target,
+    };
+    let entry = lower.new_block();
+    lower.switch_to(entry);
+
+    for stmt in &function.body {
+        lower.stmt(stmt);
+    }
+    // Falling off the end returns 0, which only matters for `main`.
+    if lower.current.is_some() {
+        let value = match function.return_type {
+            Type::Struct(_) => None,
+            _ => Some(Operand::Imm(0)),
+        };
+        lower.terminate(Terminator::Return(value));
+    }
+
+    ir::Function {
+        name: function.name.clone(),
+        params: function.params.clone(),
+        return_type: function.return_type.clone(),
+        return_pointer: function.return_pointer,
+        vregs: lower.vregs,
+        blocks: lower.finish(),
+        frame_size: function.stack_size,
+        is_static: function.is_static,
+    }
+}
+
+// Evaluates the rhs of a binary operator before its lhs, and the callee of a call before its
+// arguments.
+struct Lower {
+    // Numbered in the order they're created, and laid out in the order they're started.
+    blocks: Vec<Block>,
+    layout: Vec<BlockId>,
+    // The block instructions are added to, unless it's terminated. Code after a `return` starts a
+    // block of its own, which is never reached.
+    current: Option<BlockId>,
+    vregs: usize,
+    target: Target,
+}
+
+impl Lower {
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block {
+            insts: vec![],
+            terminator: Terminator::Return(None),
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = Some(block);
+        self.layout.push(block);
+    }
+
+    fn current_block(&mut self) -> BlockId {
+        match self.current {
+            Some(block) => block,
+            None => {
+                let block = self.new_block();
+                self.switch_to(block);
+                block
+            }
+        }
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        let block = self.current_block();
+        self.blocks[block.0].insts.push(inst);
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = self.current_block();
+        self.blocks[block.0].terminator = terminator;
+        self.current = None;
+    }
+
+    // Unless control can't reach here.
+    fn jump(&mut self, target: BlockId) {
+        if self.current.is_some() {
+            self.terminate(Terminator::Jump(target));
+        }
+    }
+
+    // The blocks renumbered in the order they were started.
+    fn finish(self) -> Vec<Block> {
+        let mut numbers = vec![BlockId(0); self.blocks.len()];
+        for (index, block) in self.layout.iter().enumerate() {
+            numbers[block.0] = BlockId(index);
+        }
+        let renumber = |block: &BlockId| numbers[block.0];
+
+        self.layout
+            .iter()
+            .map(|block| {
+                let Block { insts, terminator } = self.blocks[block.0].clone();
+                let terminator = match terminator {
+                    Terminator::Jump(target) => Terminator::Jump(renumber(&target)),
+                    Terminator::Branch { cond, then, else_ } => Terminator::Branch {
+                        cond,
+                        then: renumber(&then),
+                        else_: renumber(&else_),
+                    },
+                    Terminator::Return(value) => Terminator::Return(value),
+                };
+                Block { insts, terminator }
+            })
+            .collect()
+    }
+
+    fn new_vreg(&mut self) -> VReg {
+        self.vregs += 1;
+        VReg(self.vregs - 1)
+    }
+
+    // Lowers a statement, and returns the value of the last expression statement in it for a
+    // statement expression.
+    fn stmt(&mut self, stmt: &Stmt) -> Option<Operand> {
+        match stmt {
+            Stmt::Expr(expr) => return Some(self.expr(expr)),
+            Stmt::Return(value) => {
+                let value = value.as_ref().map(|value| self.expr(value));
+                self.terminate(Terminator::Return(value));
+            }
+            Stmt::Block(stmts) => return self.stmts(stmts),
+            // Its variable length arrays are freed at the end by restoring the stack pointer.
+            Stmt::StackBlock { stmts } => {
+                let stack_pointer = self.new_vreg();
+                self.emit(Inst::StackSave {
+                    dest: stack_pointer,
+                });
+                let value = self.stmts(stmts);
+                self.emit(Inst::StackRestore {
+                    src: Operand::Reg(stack_pointer),
+                });
+                return value;
+            }
+            Stmt::If { cond, then, else_ } => {
+                let cond = self.expr(cond);
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let end = match else_ {
+                    Some(_) => self.new_block(),
+                    None => else_block,
+                };
+                self.terminate(Terminator::Branch {
+                    cond,
+                    then: then_block,
+                    else_: else_block,
+                });
+
+                self.switch_to(then_block);
+                self.stmt(then);
+                self.jump(end);
+                if let Some(else_) = else_ {
+                    self.switch_to(else_block);
+                    self.stmt(else_);
+                    self.jump(end);
+                }
+                self.switch_to(end);
+            }
+            Stmt::While { cond, then } => self.loop_(Some(cond), None, then),
+            Stmt::For {
+                init,
+                cond,
+                update,
+                then,
+            } => {
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+                self.loop_(cond.as_ref(), update.as_ref(), then);
+            }
+            Stmt::Asm(asm) => self.asm(asm),
+        }
+        None
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Option<Operand> {
+        let (last, stmts) = stmts.split_last()?;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.stmt(last)
+    }
+
+    // Without a condition, it's an infinite loop.
+    fn loop_(&mut self, cond: Option<&Expr>, update: Option<&Expr>, then: &Stmt) {
+        let begin = self.new_block();
+        let body = self.new_block();
+        let end = self.new_block();
+
+        self.jump(begin);
+        self.switch_to(begin);
+        match cond {
+            Some(cond) => {
+                let cond = self.expr(cond);
+                self.terminate(Terminator::Branch {
+                    cond,
+                    then: body,
+                    else_: end,
+                });
+            }
+            None => self.terminate(Terminator::Jump(body)),
+        }
+
+        self.switch_to(body);
+        self.stmt(then);
+        if let Some(update) = update {
+            self.expr(update);
+        }
+        self.jump(begin);
+        self.switch_to(end);
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Operand {
+        match &expr.kind {
+            ExprKind::Num(value) => Operand::Imm(*value),
+            ExprKind::Local { .. }
+            | ExprKind::Global { .. }
+            | ExprKind::ThreadLocal { .. }
+            | ExprKind::Function { .. }
+            | ExprKind::Deref { .. }
+            | ExprKind::Member { .. } => {
+                let address = self.addr(expr);
+                let value = self.load(address, &expr.ty);
+                match &expr.kind {
+                    ExprKind::Member {
+                        bit_field: Some(bit_field),
+                        ..
+                    } => self.extract(value, &expr.ty, *bit_field),
+                    _ => value,
+                }
+            }
+            ExprKind::Addr { operand } => {
+                let address = self.addr(operand);
+                self.address_value(address)
+            }
+            ExprKind::MemZero { operand } => {
+                let address = self.addr(operand);
+                let dest = self.address_value(address);
+                self.emit(Inst::MemSet {
+                    dest,
+                    value: Operand::Imm(0),
+                    size: Operand::Imm(operand.ty.size() as i64),
+                });
+                Operand::Imm(0)
+            }
+            ExprKind::Comma { lhs, rhs } => {
+                self.expr(lhs);
+                self.expr(rhs)
+            }
+            ExprKind::StmtExpr { body } => self.stmt(body).unwrap_or(Operand::Imm(0)),
+            ExprKind::Alloca { size } => {
+                let size = self.expr(size);
+                let dest = self.new_vreg();
+                self.emit(Inst::Alloca { dest, size });
+                Operand::Reg(dest)
+            }
+            ExprKind::Assign { lhs, rhs } => {
+                let address = self.addr(lhs);
+                let value = self.expr(rhs);
+                self.store(address, value, lhs)
+            }
+            ExprKind::ArithOp { value, lhs, rhs } => {
+                let (lhs, rhs) = self.operands(lhs, rhs);
+                // The operands are extended to 64 bits, so dividing them as 64 bits is exact.
+                let op = match value {
+                    ArithOp::Div if self.target.is_unsigned(&expr.ty) => BinaryOp::UDiv,
+                    op => BinaryOp::from(*op),
+                };
+                let value = self.binary(op, lhs, rhs);
+                self.truncate(value, &expr.ty)
+            }
+            ExprKind::CmpOp {
+                value,
+                lhs: lhs_expr,
+                rhs,
+            } => {
+                let (lhs, rhs) = self.operands(lhs_expr, rhs);
+                let is_unsigned = self.target.is_unsigned(&lhs_expr.ty);
+                self.cmp(*value, is_unsigned, lhs, rhs)
+            }
+            ExprKind::Neg { operand } => {
+                let src = self.expr(operand);
+                let dest = self.new_vreg();
+                self.emit(Inst::Neg { dest, src });
+                self.truncate(Operand::Reg(dest), &expr.ty)
+            }
+            // Any nonzero value converts to a `_Bool` of 1.
+            ExprKind::Cast { operand } if *expr.ty.unqualified() == Type::Bool => {
+                let value = self.expr(operand);
+                self.cmp(CmpOp::Neq, false, value, Operand::Imm(0))
+            }
+            ExprKind::Cast { operand } => {
+                let value = self.expr(operand);
+                self.truncate(value, &expr.ty)
+            }
+            ExprKind::Call {
+                callee,
+                args,
+                temporary,
+            } => {
+                let callee = match &callee.kind {
+                    ExprKind::Function { name } => Callee::Direct(name.clone()),
+                    _ => Callee::Indirect(self.expr(callee)),
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| (self.expr(arg), arg.ty.clone()))
+                    .collect();
+                let dest = self.new_vreg();
+                self.emit(Inst::Call {
+                    dest,
+                    callee,
+                    args,
+                    ty: expr.ty.clone(),
+                    temporary: *temporary,
+                });
+                Operand::Reg(dest)
+            }
+            ExprKind::Atomic { op, order, args } => {
+                let Some(pointer) = args.first() else {
+                    self.emit(Inst::Fence { order: *order });
+                    return Operand::Imm(0);
+                };
+                let size = pointer.ty.base().unwrap().size();
+                let args = args.iter().map(|arg| self.expr(arg)).collect();
+                let dest = self.new_vreg();
+                self.emit(Inst::Atomic {
+                    op: *op,
+                    order: *order,
+                    dest,
+                    args,
+                    size,
+                });
+                self.truncate(Operand::Reg(dest), &expr.ty)
+            }
+            ExprKind::Trap => {
+                self.emit(Inst::Trap);
+                Operand::Imm(0)
+            }
+            ExprKind::BitOp { op, operand } => {
+                let src = self.expr(operand);
+                let dest = self.new_vreg();
+                self.emit(Inst::BitOp {
+                    op: *op,
+                    dest,
+                    src,
+                    size: operand.ty.size(),
+                });
+                Operand::Reg(dest)
+            }
+            ExprKind::Overflow {
+                op,
+                lhs,
+                rhs,
+                result,
+            } => self.overflow(*op, lhs, rhs, result),
+            ExprKind::MemCopy { dest, src, size } => {
+                let dest = self.expr(dest);
+                let src = self.expr(src);
+                let size = self.expr(size);
+                self.emit(Inst::MemCopy {
+                    dest: dest.clone(),
+                    src,
+                    size,
+                });
+                dest
+            }
+            ExprKind::MemSet { dest, value, size } => {
+                let dest = self.expr(dest);
+                let value = self.expr(value);
+                let size = self.expr(size);
+                self.emit(Inst::MemSet {
+                    dest: dest.clone(),
+                    value,
+                    size,
+                });
+                dest
+            }
+        }
+    }
+
+    // Evaluates rhs, then lhs.
+    fn operands(&mut self, lhs: &Expr, rhs: &Expr) -> (Operand, Operand) {
+        let rhs = self.expr(rhs);
+        let lhs = self.expr(lhs);
+        (lhs, rhs)
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: Operand, rhs: Operand) -> Operand {
+        let dest = self.new_vreg();
+        self.emit(Inst::Binary { op, dest, lhs, rhs });
+        Operand::Reg(dest)
+    }
+
+    fn cmp(&mut self, op: CmpOp, is_unsigned: bool, lhs: Operand, rhs: Operand) -> Operand {
+        let dest = self.new_vreg();
+        self.emit(Inst::Cmp {
+            op,
+            is_unsigned,
+            dest,
+            lhs,
+            rhs,
+        });
+        Operand::Reg(dest)
+    }
+
+    // Truncates a value to `ty`, then extends it back to 64 bits by the signedness of `ty`.
+    fn truncate(&mut self, value: Operand, ty: &Type) -> Operand {
+        if !ty.is_integer() || ty.size() == 8 {
+            return value;
+        }
+
+        let dest = self.new_vreg();
+        self.emit(Inst::Extend {
+            dest,
+            src: value,
+            size: ty.size(),
+            is_unsigned: self.target.is_unsigned(ty),
+        });
+        Operand::Reg(dest)
+    }
+
+    // The address of an lvalue.
+    fn addr(&mut self, expr: &Expr) -> Address {
+        match &expr.kind {
+            ExprKind::Local { offset } => Address {
+                base: Base::Local(*offset),
+                offset: 0,
+            },
+            ExprKind::Global { name } => Address {
+                base: Base::Global(name.clone()),
+                offset: 0,
+            },
+            ExprKind::ThreadLocal { name } => {
+                let dest = self.new_vreg();
+                self.emit(Inst::ThreadLocalAddr {
+                    dest,
+                    name: name.clone(),
+                });
+                address_in(dest)
+            }
+            ExprKind::Function { name } => {
+                let dest = self.new_vreg();
+                self.emit(Inst::FunctionAddr {
+                    dest,
+                    name: name.clone(),
+                });
+                address_in(dest)
+            }
+            ExprKind::Deref { operand } => {
+                let value = self.expr(operand);
+                self.address(value)
+            }
+            // The address of a bit-field is the one of its storage unit.
+            ExprKind::Member {
+                operand, offset, ..
+            } => {
+                let mut address = self.place(operand);
+                address.offset += *offset as i64;
+                address
+            }
+            ExprKind::Comma { lhs, rhs } => {
+                self.expr(lhs);
+                self.addr(rhs)
+            }
+            _ => unreachable!("Only lvalues have an address"),
+        }
+    }
+
+    // The address of an array or a struct, which is also its value.
+    fn place(&mut self, expr: &Expr) -> Address {
+        match &expr.kind {
+            ExprKind::Local { .. }
+            | ExprKind::Global { .. }
+            | ExprKind::ThreadLocal { .. }
+            | ExprKind::Deref { .. }
+            | ExprKind::Member { .. } => self.addr(expr),
+            ExprKind::Comma { lhs, rhs } => {
+                self.expr(lhs);
+                self.place(rhs)
+            }
+            _ => {
+                let value = self.expr(expr);
+                self.address(value)
+            }
+        }
+    }
+
+    // The address a value points to.
+    fn address(&mut self, value: Operand) -> Address {
+        match value {
+            Operand::Reg(vreg) => address_in(vreg),
+            Operand::Imm(_) => {
+                let dest = self.new_vreg();
+                self.emit(Inst::Copy { dest, src: value });
+                address_in(dest)
+            }
+        }
+    }
+
+    fn address_value(&mut self, address: Address) -> Operand {
+        match address {
+            Address {
+                base: Base::Reg(vreg),
+                offset: 0,
+            } => Operand::Reg(vreg),
+            address => {
+                let dest = self.new_vreg();
+                self.emit(Inst::Addr { dest, address });
+                Operand::Reg(dest)
+            }
+        }
+    }
+
+    // Arrays, structs and functions are left as their address.
+    fn load(&mut self, address: Address, ty: &Type) -> Operand {
+        if matches!(
+            ty.unqualified(),
+            Type::Void
+                | Type::Array { .. }
+                | Type::VariableArray { .. }
+                | Type::Struct(_)
+                | Type::Function { .. }
+        ) {
+            return self.address_value(address);
+        }
+
+        let dest = self.new_vreg();
+        self.emit(Inst::Load {
+            dest,
+            address,
+            size: ty.size(),
+            is_unsigned: self.target.is_unsigned(ty),
+            is_volatile: ty.qualifiers().is_volatile,
+        });
+        Operand::Reg(dest)
+    }
+
+    // Stores a value to the lvalue `lhs` at `address`, and returns the value of the assignment. A
+    // struct is copied from the address it's the value of.
+    fn store(&mut self, address: Address, value: Operand, lhs: &Expr) -> Operand {
+        if let ExprKind::Member {
+            bit_field: Some(bit_field),
+            ..
+        } = &lhs.kind
+        {
+            return self.store_bit_field(address, value, &lhs.ty, *bit_field);
+        }
+
+        if let Type::Struct(_) = lhs.ty.unqualified() {
+            let dest = self.address_value(address);
+            self.emit(Inst::MemCopy {
+                dest,
+                src: value.clone(),
+                size: Operand::Imm(lhs.ty.size() as i64),
+            });
+        } else {
+            self.emit(Inst::Store {
+                address,
+                src: value.clone(),
+                size: lhs.ty.size(),
+                is_volatile: lhs.ty.qualifiers().is_volatile,
+            });
+        }
+        value
+    }
+
+    // The bit-field of `ty` in its storage unit, extended to 64 bits by the signedness of `ty`.
+    fn extract(&mut self, unit: Operand, ty: &Type, bit_field: BitField) -> Operand {
+        let shift = if self.target.is_unsigned(ty) {
+            BinaryOp::LShr
+        } else {
+            BinaryOp::AShr
+        };
+        let left = 64 - bit_field.offset - bit_field.width;
+        let value = self.binary(BinaryOp::Shl, unit, Operand::Imm(left as i64));
+        self.binary(shift, value, Operand::Imm((64 - bit_field.width) as i64))
+    }
+
+    // Stores a `ty` to a bit-field in the storage unit at `address`, keeping the other bits of the
+    // unit. The value is the stored one, like it would be loaded.
+    fn store_bit_field(
+        &mut self,
+        address: Address,
+        value: Operand,
+        ty: &Type,
+        bit_field: BitField,
+    ) -> Operand {
+        let mask = (u64::MAX >> (64 - bit_field.width)) << bit_field.offset;
+        let size = ty.size();
+
+        let bits = self.binary(
+            BinaryOp::Shl,
+            value.clone(),
+            Operand::Imm(bit_field.offset as i64),
+        );
+        let bits = self.binary(BinaryOp::And, bits, Operand::Imm(mask as i64));
+        let unit = self.new_vreg();
+        let is_volatile = ty.qualifiers().is_volatile;
+        self.emit(Inst::Load {
+            dest: unit,
+            address: address.clone(),
+            size,
+            is_unsigned: true,
+            is_volatile,
+        });
+        let rest = self.binary(
+            BinaryOp::And,
+            Operand::Reg(unit),
+            Operand::Imm(!mask as i64),
+        );
+        let unit = self.binary(BinaryOp::Or, rest, bits);
+        self.emit(Inst::Store {
+            address,
+            src: unit,
+            size,
+            is_volatile,
+        });
+
+        self.extract(
+            value,
+            ty,
+            BitField {
+                offset: 0,
+                ..bit_field
+            },
+        )
+    }
+
+    // Whether `lhs op rhs` overflowed as 64 bits, or doesn't fit in the type `result` points to,
+    // where the result truncated to that type is stored. It fits if it doesn't change sign, and is
+    // unchanged by the conversion. An unsigned subtraction which borrows is negative, so it may
+    // fit a signed type.
+    fn overflow(&mut self, op: ArithOp, lhs: &Expr, rhs: &Expr, result: &Expr) -> Operand {
+        let pointer = self.expr(result);
+        let is_unsigned = self.target.is_unsigned(&lhs.ty);
+        let (lhs, rhs) = self.operands(lhs, rhs);
+
+        let overflow = self.new_vreg();
+        self.emit(Inst::Overflow {
+            op,
+            is_unsigned,
+            dest: overflow,
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        });
+        let value = self.binary(BinaryOp::from(op), lhs, rhs);
+
+        let ty = result.ty.base().unwrap().clone();
+        let mut overflow = Operand::Reg(overflow);
+        if self.target.is_unsigned(&ty) != is_unsigned {
+            let negative = self.cmp(CmpOp::Lt, false, value.clone(), Operand::Imm(0));
+            let merge = match (op, is_unsigned) {
+                (ArithOp::Sub, true) => BinaryOp::Xor,
+                _ => BinaryOp::Or,
+            };
+            overflow = self.binary(merge, overflow, negative);
+        }
+        let truncated = self.truncate(value.clone(), &ty);
+        let changed = self.cmp(CmpOp::Neq, false, truncated.clone(), value);
+        let overflow = self.binary(BinaryOp::Or, overflow, changed);
+
+        let address = self.address(pointer);
+        self.emit(Inst::Store {
+            address,
+            src: truncated,
+            size: ty.size(),
+            is_volatile: ty.qualifiers().is_volatile,
+        });
+        overflow
+    }
+
+    // The addresses of the outputs in registers are evaluated first, then the inputs. The outputs
+    // are stored after the asm.
+    fn asm(&mut self, asm: &InlineAsm) {
+        let InlineAsm {
+            template,
+            operands,
+            saved,
+        } = asm;
+
+        let mut outputs = vec![];
+        for operand in operands {
+            if let sema::AsmPlace::Register(register) = operand.place {
+                if operand.is_output {
+                    outputs.push((self.addr(&operand.expr), register, &operand.expr));
+                }
+            }
+        }
+
+        let mut inputs = vec![];
+        for operand in operands {
+            match operand.place {
+                sema::AsmPlace::Register(register) if operand.is_read => {
+                    inputs.push((register, self.expr(&operand.expr)));
+                }
+                sema::AsmPlace::Memory {
+                    base: Some(register),
+                } => {
+                    let address = self.addr(&operand.expr);
+                    inputs.push((register, self.address_value(address)));
+                }
+                _ => {}
+            }
+        }
+
+        let places = operands
+            .iter()
+            .map(|operand| AsmOperand {
+                place: match operand.place {
+                    sema::AsmPlace::Register(register) => AsmPlace::Register(register),
+                    sema::AsmPlace::Immediate(value) => AsmPlace::Immediate(value),
+                    sema::AsmPlace::Memory {
+                        base: Some(register),
+                    } => AsmPlace::Memory(register),
+                    sema::AsmPlace::Memory { base: None } => match &operand.expr.kind {
+                        ExprKind::Local { .. } | ExprKind::Global { .. } => {
+                            AsmPlace::Variable(self.addr(&operand.expr))
+                        }
+                        _ => unreachable!("Other addresses are in a register"),
+                    },
+                },
+                size: operand.expr.ty.size(),
+            })
+            .collect();
+
+        let dests: Vec<_> = outputs.iter().map(|_| self.new_vreg()).collect();
+        self.emit(Inst::Asm(Asm {
+            template: template.clone(),
+            operands: places,
+            inputs,
+            outputs: dests
+                .iter()
+                .zip(&outputs)
+                .map(|(dest, (_, register, _))| (*dest, *register))
+                .collect(),
+            saved: saved.clone(),
+        }));
+
+        for (dest, (address, _, lhs)) in dests.into_iter().zip(outputs) {
+            self.store(address, Operand::Reg(dest), lhs);
+        }
+    }
+}
+
+fn address_in(vreg: VReg) -> Address {
+    Address {
+        base: Base::Reg(vreg),
+        offset: 0,
+    }
+}
+
+// Preprocesses and parses a program like `kanic` with the warning `flags`, leaving its checks to
+// the caller.
+#[cfg(test)]
+pub fn parse_str(
+    c_code: &str,
+    flags: &[&str],
+) -> (
+    Vec<crate::parser::Node>,
+    crate::diagnostic::DiagnosticEngine,
+) {
+    use crate::{
+        diagnostic::DiagnosticEngine, lexer, options::Options, parser, preprocessor,
+        preprocessor::MacroTable, source_map::SourceMap,
+    };
+
+    let mut options = Options::default();
+    for flag in flags {
+        options.warnings.apply_flag(flag).unwrap();
+    }
+
+    let mut source_map = SourceMap::new();
+    let mut diags = DiagnosticEngine::new(options.warnings.clone());
+    let file = source_map.add_file("<input>", c_code);
+    let macros = MacroTable::new(&mut source_map, &mut diags, &options);
+    let tokens = lexer::tokenize(&source_map, file, &mut diags);
+    let tokens = preprocessor::preprocess(tokens, &macros, &mut source_map, &mut diags);
+
+    (parser::parse(tokens, &mut diags), diags)
+}
+
+// The IR of a program, which must have no errors.
+#[cfg(test)]
+pub fn lower_str(c_code: &str) -> ir::Program {
+    let (nodes, mut diags) = parse_str(c_code, &[]);
+    let program = sema::analyze(&nodes, Target::default(), &mut diags);
+    assert!(diags.finish(()).is_ok());
+
+    lower(program, Target::default())
+}
+
+#[cfg(test)]
+mod tests {
+    // Without passes, it's the IR as lowered.
+    use crate::opt::passes_str;
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(
+            passes_str("int f(int a, long b) { return a + b * 2; }", &[]),
+            "f:
+bb0:
+  %0 = load.i64 [local 16]
+  %1 = mul %0, 2
+  %2 = load.i32 [local 4]
+  %3 = add %2, %1
+  %4 = ext.i32 %3
+  ret %4
+"
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(
+            passes_str(
+                "int f(int n) {
+                   int s = 0;
+                   while (n > 0) { if (n == 3) s = 1; n = n - 1; }
+                   return s;
+                 }",
+                &[]
+            ),
+            "f:
+bb0:
+  store.i32 [local 8], 0
+  jmp bb1
+bb1:
+  %0 = load.i32 [local 4]
+  %1 = cmp gt %0, 0
+  br %1, bb2, bb5
+bb2:
+  %2 = load.i32 [local 4]
+  %3 = cmp eq %2, 3
+  br %3, bb3, bb4
+bb3:
+  store.i32 [local 8], 1
+  jmp bb4
+bb4:
+  %4 = load.i32 [local 4]
+  %5 = sub %4, 1
+  %6 = ext.i32 %5
+  store.i32 [local 4], %6
+  jmp bb1
+bb5:
+  %7 = load.i32 [local 8]
+  ret %7
+"
+        );
+    }
+}
@@ -1,16 +1,124 @@
-use std::env::args;
+use std::{
+    env::args,
+    fs,
+    io::{stderr, IsTerminal},
+    path::Path,
+    process::exit,
+};
 
 use anyhow::Result;
 
+mod abi;
+mod atomic;
+mod builtin;
 mod codegen;
+mod const_eval;
+mod diagnostic;
 mod error_reporter;
+mod format;
+mod inline_asm;
+mod ir;
 mod lexer;
+mod lint;
+mod lower;
+mod opt;
+mod options;
 mod parser;
+mod preprocessor;
+mod regalloc;
+mod sema;
+mod source_map;
+mod types;
+mod warning;
+
+use diagnostic::{DiagnosticEngine, Diagnostics};
+use options::{ColorChoice, DiagnosticsFormat, MacroDefinition, Options};
+use preprocessor::MacroTable;
+use source_map::SourceMap;
 
 fn main() -> Result<()> {
-    let c_code = args().nth(1).expect("Please provide a expr");
+    let options = Options::parse(args().skip(1))?;
+
+    let mut source_map = SourceMap::new();
+
+    let (output, Diagnostics(diagnostics)) = match compile(&options, &mut source_map)? {
+        Ok((output, warnings)) => (Some(output), warnings),
+        Err(diagnostics) => (None, diagnostics),
+    };
+
+    match options.diagnostics_format {
+        DiagnosticsFormat::Text => {
+            let color = match options.color {
+                ColorChoice::Auto => stderr().is_terminal(),
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+            };
 
-    println!("{}", codegen::generate(&c_code)?);
+            for diagnostic in &diagnostics {
+                eprintln!("{}", error_reporter::render(&source_map, diagnostic, color));
+            }
+        }
+        DiagnosticsFormat::Json if !diagnostics.is_empty() => {
+            eprint!("{}", error_reporter::render_json(&source_map, &diagnostics))
+        }
+        DiagnosticsFormat::Json => {}
+    }
+
+    match output {
+        Some(output) => print!("{output}"),
+        None => exit(1),
+    }
 
     Ok(())
 }
+
+// What kanic prints to stdout with the warnings, or every diagnostic if the program has errors.
+fn compile(
+    options: &Options,
+    source_map: &mut SourceMap,
+) -> Result<Result<(String, Diagnostics), Diagnostics>> {
+    let mut diags = DiagnosticEngine::new(options.warnings.clone());
+
+    let mut macros = MacroTable::new(source_map, &mut diags, options);
+    for definition in &options.macro_definitions {
+        match definition {
+            MacroDefinition::Define { name, value } => {
+                macros.define(source_map, &mut diags, name, value)
+            }
+            MacroDefinition::Undefine { name } => macros.undefine(name),
+        }
+    }
+
+    if options.preprocess_only && options.dump_macros {
+        return Ok(diags.finish(macros.dump()));
+    }
+
+    // The program is either a path to a .c file, or the C code itself.
+    let file = if options.c_code.ends_with(".c") && Path::new(&options.c_code).is_file() {
+        source_map.add_file(&options.c_code, &fs::read_to_string(&options.c_code)?)
+    } else {
+        source_map.add_file("<input>", &options.c_code)
+    };
+
+    let tokens = lexer::tokenize(source_map, file, &mut diags);
+    let tokens = preprocessor::preprocess(tokens, &macros, source_map, &mut diags);
+
+    if options.preprocess_only {
+        let output = tokens
+            .into_iter()
+            .map(|token| token.value.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Ok(diags.finish(output + "\n"));
+    }
+
+    let nodes = parser::parse(tokens, &mut diags);
+    lint::check(&nodes, &mut diags);
+    let program = sema::analyze(&nodes, options.target, &mut diags);
+
+    Ok(diags.finish(program).map(|(program, warnings)| {
+        let program = lower::lower(program, options.target);
+        let asm = opt::PassManager::new(options).run(program, options.target);
+        (asm + "\n", warnings)
+    }))
+}
@@ -0,0 +1,220 @@
+//! Passes which rewrite the IR of each function, and the pass manager which runs those of an
+//! optimization level.
+
+mod cfg;
+mod dce;
+mod fold;
+mod gvn;
+mod licm;
+mod mem2reg;
+mod out_of_ssa;
+mod peephole;
+mod sccp;
+mod simplify_cfg;
+
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    codegen,
+    ir::{Function, Program},
+    options::{OptLevel, Options},
+    types::Target,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Mem2Reg,
+    Fold,
+    Sccp,
+    Gvn,
+    Licm,
+    Dce,
+    SimplifyCfg,
+    OutOfSsa,
+    // On the assembly, after code generation.
+    Peephole,
+}
+
+impl Pass {
+    const ALL: [Pass; 9] = [
+        Pass::Mem2Reg,
+        Pass::Fold,
+        Pass::Sccp,
+        Pass::Gvn,
+        Pass::Licm,
+        Pass::Dce,
+        Pass::SimplifyCfg,
+        Pass::OutOfSsa,
+        Pass::Peephole,
+    ];
+
+    /// The name `-fdump-ir-after` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Mem2Reg => "mem2reg",
+            Pass::Fold => "fold",
+            Pass::Sccp => "sccp",
+            Pass::Gvn => "gvn",
+            Pass::Licm => "licm",
+            Pass::Dce => "dce",
+            Pass::SimplifyCfg => "simplify-cfg",
+            Pass::OutOfSsa => "out-of-ssa",
+            Pass::Peephole => "peephole",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Pass> {
+        Pass::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+
+    fn run(self, function: &mut Function, level: OptLevel) {
+        match self {
+            Pass::Mem2Reg => mem2reg::run(function),
+            // Turning a signed division into shifts takes more instructions.
+            Pass::Fold => fold::run(function, level != OptLevel::Os),
+            Pass::Sccp => sccp::run(function),
+            Pass::Gvn => gvn::run(function),
+            Pass::Licm => licm::run(function),
+            Pass::Dce => dce::run(function),
+            Pass::SimplifyCfg => simplify_cfg::run(function),
+            Pass::OutOfSsa => out_of_ssa::run(function),
+            Pass::Peephole => unreachable!("The peephole pass runs on the assembly"),
+        }
+    }
+}
+
+/// The passes of each level, in order. Constants are folded even without optimization, like
+/// other compilers do, and the IR is only in SSA form from mem2reg to out-of-ssa.
+fn pipeline(level: OptLevel) -> &'static [Pass] {
+    use Pass::*;
+    match level {
+        OptLevel::O0 => &[Fold],
+        OptLevel::O1 => &[Mem2Reg, Fold, SimplifyCfg, Dce, OutOfSsa, Peephole],
+        OptLevel::O2 => &[
+            Mem2Reg,
+            Sccp,
+            Fold,
+            Gvn,
+            Fold,
+            Licm,
+            Dce,
+            SimplifyCfg,
+            OutOfSsa,
+            Peephole,
+        ],
+        // Moving code out of loops makes it no smaller.
+        OptLevel::Os => &[
+            Mem2Reg,
+            Sccp,
+            Fold,
+            Gvn,
+            Fold,
+            Dce,
+            SimplifyCfg,
+            OutOfSsa,
+            Peephole,
+        ],
+    }
+}
+
+/// Runs the passes of the optimization level on the IR, then code generation and the passes on
+/// the assembly. What `-fdump-ir-after` and `-ftime-report` ask for is printed to stderr.
+pub struct PassManager<'a> {
+    level: OptLevel,
+    dump_after: &'a [Pass],
+    time_report: bool,
+    // The time each pass, or code generation, took in total, in the order they first ran.
+    times: Vec<(&'static str, Duration)>,
+}
+
+impl<'a> PassManager<'a> {
+    pub fn new(options: &'a Options) -> Self {
+        PassManager {
+            level: options.opt_level,
+            dump_after: &options.dump_ir_after,
+            time_report: options.time_report,
+            times: vec![],
+        }
+    }
+
+    /// The assembly of the program.
+    pub fn run(&mut self, mut program: Program, target: Target) -> String {
+        let (asm_passes, ir_passes): (Vec<Pass>, Vec<Pass>) = pipeline(self.level)
+            .iter()
+            .partition(|&&pass| pass == Pass::Peephole);
+
+        let level = self.level;
+        for pass in ir_passes {
+            self.time(pass.name(), || {
+                for function in &mut program.functions {
+                    pass.run(function, level);
+                }
+            });
+            if self.dump_after.contains(&pass) {
+                eprint!("{}", dump(pass, &program));
+            }
+        }
+
+        let mut asm = self.time("codegen", || codegen::generate(&program, target));
+        for pass in asm_passes {
+            asm = self.time(pass.name(), || peephole::run(&asm));
+            if self.dump_after.contains(&pass) {
+                eprint!("*** Assembly after {} ***\n{asm}", pass.name());
+            }
+        }
+
+        if self.time_report {
+            eprint!("{}", self.report());
+        }
+        asm
+    }
+
+    fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        match self.times.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, total)) => *total += elapsed,
+            None => self.times.push((name, elapsed)),
+        }
+        result
+    }
+
+    fn report(&self) -> String {
+        let mut report = "Execution times (seconds)\n".to_string();
+        let mut total = Duration::ZERO;
+        for (name, time) in &self.times {
+            writeln!(report, "  {name:<14}: {:>10.6}", time.as_secs_f64()).unwrap();
+            total += *time;
+        }
+        writeln!(report, "  {:<14}: {:>10.6}", "TOTAL", total.as_secs_f64()).unwrap();
+        report
+    }
+}
+
+fn dump(pass: Pass, program: &Program) -> String {
+    let mut dump = format!("*** IR after {} ***\n", pass.name());
+    for function in &program.functions {
+        write!(dump, "{function}").unwrap();
+    }
+    dump
+}
+
+// The IR of every function, after the passes.
+#[cfg(test)]
+pub fn passes_str(c_code: &str, passes: &[Pass]) -> String {
+    let mut program = crate::lower::lower_str(c_code);
+    for pass in passes {
+        for function in &mut program.functions {
+            pass.run(function, OptLevel::O2);
+        }
+    }
+    program
+        .functions
+        .iter()
+        .map(|function| function.to_string())
+        .collect()
+}
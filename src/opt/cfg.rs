@@ -0,0 +1,263 @@
+use crate::ir::{Block, BlockId, Function, Inst, Operand, Terminator};
+
+/// The blocks each block may be entered from, each once.
+pub fn predecessors(function: &Function) -> Vec<Vec<BlockId>> {
+    let mut predecessors = vec![vec![]; function.blocks.len()];
+    for (index, block) in function.blocks.iter().enumerate() {
+        for successor in block.terminator.successors() {
+            let predecessors: &mut Vec<_> = &mut predecessors[successor.0];
+            if !predecessors.contains(&BlockId(index)) {
+                predecessors.push(BlockId(index));
+            }
+        }
+    }
+    predecessors
+}
+
+/// The blocks reachable from the first, each before its successors except along back edges.
+pub fn reverse_postorder(function: &Function) -> Vec<BlockId> {
+    let mut visited = vec![false; function.blocks.len()];
+    let mut postorder = vec![];
+    // Each block with the index of the next successor to visit.
+    let mut stack = vec![(BlockId(0), 0)];
+    visited[0] = true;
+    while let Some((block, index)) = stack.pop() {
+        let successors = function.blocks[block.0].terminator.successors();
+        match successors.get(index) {
+            Some(&successor) => {
+                stack.push((block, index + 1));
+                if !visited[successor.0] {
+                    visited[successor.0] = true;
+                    stack.push((successor, 0));
+                }
+            }
+            None => postorder.push(block),
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// The dominator tree of the reachable blocks, by the algorithm of Cooper, Harvey and Kennedy.
+pub struct Dominators {
+    // The immediate dominator of each block, which is itself for the first one, and None for one
+    // which isn't reachable.
+    idoms: Vec<Option<BlockId>>,
+    // The index of each block in reverse postorder.
+    order: Vec<usize>,
+}
+
+impl Dominators {
+    pub fn new(function: &Function) -> Self {
+        let rpo = reverse_postorder(function);
+        let predecessors = predecessors(function);
+        let mut order = vec![usize::MAX; function.blocks.len()];
+        for (index, block) in rpo.iter().enumerate() {
+            order[block.0] = index;
+        }
+
+        let mut idoms = vec![None; function.blocks.len()];
+        idoms[0] = Some(BlockId(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &rpo[1..] {
+                let mut idom = None;
+                for &predecessor in &predecessors[block.0] {
+                    if idoms[predecessor.0].is_none() {
+                        continue;
+                    }
+                    idom = Some(match idom {
+                        None => predecessor,
+                        Some(idom) => intersect(&idoms, &order, predecessor, idom),
+                    });
+                }
+                if idom != idoms[block.0] {
+                    idoms[block.0] = idom;
+                    changed = true;
+                }
+            }
+        }
+        Dominators { idoms, order }
+    }
+
+    /// The immediate dominator, which is None for the first block and those which aren't
+    /// reachable.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idoms[block.0].filter(|&idom| idom != block)
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.idoms[block.0].is_some()
+    }
+
+    /// Whether every path to `b` goes through `a`.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        while self.order[a.0] < self.order[b.0] {
+            b = self.idoms[b.0].unwrap();
+        }
+        a == b
+    }
+
+    /// The blocks each block immediately dominates.
+    pub fn children(&self) -> Vec<Vec<BlockId>> {
+        let mut children = vec![vec![]; self.idoms.len()];
+        for (index, _) in self.idoms.iter().enumerate() {
+            if let Some(idom) = self.idom(BlockId(index)) {
+                children[idom.0].push(BlockId(index));
+            }
+        }
+        children
+    }
+
+    /// The blocks where the dominance of each block ends, where a value defined in it may meet
+    /// another.
+    pub fn frontiers(&self, function: &Function) -> Vec<Vec<BlockId>> {
+        let mut frontiers = vec![vec![]; function.blocks.len()];
+        for (index, predecessors) in predecessors(function).into_iter().enumerate() {
+            let block = BlockId(index);
+            if predecessors.len() < 2 || !self.is_reachable(block) {
+                continue;
+            }
+            for predecessor in predecessors {
+                let mut runner = predecessor;
+                while self.is_reachable(runner) && Some(runner) != self.idom(block) {
+                    let frontier: &mut Vec<_> = &mut frontiers[runner.0];
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    match self.idom(runner) {
+                        Some(idom) => runner = idom,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+// The nearest common dominator of `a` and `b`.
+fn intersect(
+    idoms: &[Option<BlockId>],
+    order: &[usize],
+    mut a: BlockId,
+    mut b: BlockId,
+) -> BlockId {
+    while a != b {
+        while order[a.0] > order[b.0] {
+            a = idoms[a.0].unwrap();
+        }
+        while order[b.0] > order[a.0] {
+            b = idoms[b.0].unwrap();
+        }
+    }
+    a
+}
+
+/// Removes the blocks which can't be reached from the first, and the operands of phis from them,
+/// and renumbers the rest in order. Whether anything was removed.
+pub fn remove_unreachable(function: &mut Function) -> bool {
+    let mut reachable = vec![false; function.blocks.len()];
+    for block in reverse_postorder(function) {
+        reachable[block.0] = true;
+    }
+    if reachable.iter().all(|&is_reachable| is_reachable) {
+        return false;
+    }
+
+    let mut ids = vec![];
+    let mut next = 0;
+    for &is_reachable in &reachable {
+        ids.push(BlockId(next));
+        next += is_reachable as usize;
+    }
+    let blocks = std::mem::take(&mut function.blocks);
+    for (mut block, is_reachable) in blocks.into_iter().zip(reachable.iter()) {
+        if !is_reachable {
+            continue;
+        }
+        for inst in &mut block.insts {
+            if let Inst::Phi { args, .. } = inst {
+                args.retain(|(predecessor, _)| reachable[predecessor.0]);
+                for (predecessor, _) in args {
+                    *predecessor = ids[predecessor.0];
+                }
+            }
+        }
+        for successor in block.terminator.successors_mut() {
+            *successor = ids[successor.0];
+        }
+        function.blocks.push(block);
+    }
+    true
+}
+
+/// Removes the operands of the phis in `successor` which come from `predecessor`, when control
+/// no longer goes from one to the other.
+pub fn remove_edge(function: &mut Function, predecessor: BlockId, successor: BlockId) {
+    for inst in &mut function.blocks[successor.0].insts {
+        if let Inst::Phi { args, .. } = inst {
+            args.retain(|(block, _)| *block != predecessor);
+        }
+    }
+}
+
+/// Makes the phis in `successor` take their operands from `new` where they took them from `old`.
+pub fn rename_predecessor(function: &mut Function, successor: BlockId, old: BlockId, new: BlockId) {
+    for inst in &mut function.blocks[successor.0].insts {
+        if let Inst::Phi { args, .. } = inst {
+            for (block, _) in args {
+                if *block == old {
+                    *block = new;
+                }
+            }
+        }
+    }
+}
+
+/// Replaces a branch on a constant, or to the same block either way, with a jump. Whether it did.
+pub fn simplify_branch(function: &mut Function, block: BlockId) -> bool {
+    let (target, removed) = match function.blocks[block.0].terminator {
+        Terminator::Branch {
+            cond: Operand::Imm(value),
+            then,
+            else_,
+        } => match value != 0 {
+            true => (then, else_),
+            false => (else_, then),
+        },
+        Terminator::Branch { then, else_, .. } if then == else_ => (then, then),
+        _ => return false,
+    };
+    function.blocks[block.0].terminator = Terminator::Jump(target);
+    if removed != target {
+        remove_edge(function, block, removed);
+    }
+    true
+}
+
+/// Inserts `block` before the one numbered `index`, renumbering those after it.
+pub fn insert_block(function: &mut Function, index: usize, block: Block) {
+    let renumber = |id: &mut BlockId| {
+        if id.0 >= index {
+            id.0 += 1;
+        }
+    };
+    function.blocks.insert(index, block);
+    for block in &mut function.blocks {
+        for inst in &mut block.insts {
+            if let Inst::Phi { args, .. } = inst {
+                args.iter_mut().for_each(|(id, _)| renumber(id));
+            }
+        }
+        block
+            .terminator
+            .successors_mut()
+            .into_iter()
+            .for_each(renumber);
+    }
+}
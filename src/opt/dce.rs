@@ -0,0 +1,77 @@
+use crate::ir::{Function, Inst};
+
+/// Dead code elimination, which removes the pure instructions whose results are never needed by
+/// one with an effect or by a terminator, even through a cycle of phis.
+pub fn run(function: &mut Function) {
+    let mut defs = vec![vec![]; function.vregs];
+    for block in &function.blocks {
+        for inst in &block.insts {
+            for vreg in inst.defs() {
+                defs[vreg.0].push(inst);
+            }
+        }
+    }
+
+    let mut live = vec![false; function.vregs];
+    let mut worklist = vec![];
+    for block in &function.blocks {
+        let roots = block.insts.iter().filter(|inst| !is_pure(inst));
+        let uses = roots.flat_map(Inst::uses).chain(block.terminator.uses());
+        worklist.extend(uses);
+    }
+    while let Some(vreg) = worklist.pop() {
+        if !live[vreg.0] {
+            live[vreg.0] = true;
+            worklist.extend(defs[vreg.0].iter().flat_map(|inst| inst.uses()));
+        }
+    }
+
+    for block in &mut function.blocks {
+        block
+            .insts
+            .retain(|inst| !is_pure(inst) || inst.defs().iter().any(|vreg| live[vreg.0]));
+    }
+}
+
+// Whether `inst` has no effect but its result.
+fn is_pure(inst: &Inst) -> bool {
+    match inst {
+        Inst::Load { is_volatile, .. } => !is_volatile,
+        Inst::Phi { .. }
+        | Inst::Copy { .. }
+        | Inst::Binary { .. }
+        | Inst::Cmp { .. }
+        | Inst::Neg { .. }
+        | Inst::Extend { .. }
+        | Inst::Addr { .. }
+        | Inst::FunctionAddr { .. }
+        | Inst::ThreadLocalAddr { .. }
+        | Inst::StackSave { .. }
+        | Inst::BitOp { .. }
+        | Inst::Overflow { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_unused() {
+        // The call is kept for what it does.
+        assert_eq!(
+            passes_str(
+                "int g(); long f(long a) { long x = a * 2; g(); return a; }",
+                &[Pass::Mem2Reg, Pass::Dce]
+            ),
+            "f:
+bb0:
+  %4 = load.i64 [local 8]
+  %2 = call @g()
+  %3 = copy %4
+  ret %3
+"
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::HashMap;
+
+use crate::{
+    ir::{Base, BinaryOp, BlockId, Function, Inst, Operand, VReg},
+    parser::CmpOp,
+};
+
+use super::cfg;
+
+/// Folds instructions whose operands are constants, simplifies those with an identity operand,
+/// and removes the blocks a constant branch never goes to. Constants and copies are propagated to
+/// the uses of a virtual register defined once, until nothing changes. A signed division by a
+/// power of 2 is only turned into shifts if `expand_division`, since they're longer.
+pub fn run(function: &mut Function, expand_division: bool) {
+    loop {
+        let mut changed = false;
+        let extensions = extensions(function);
+        for index in 0..function.blocks.len() {
+            let insts = std::mem::take(&mut function.blocks[index].insts);
+            for inst in insts {
+                let simplified = simplify(
+                    inst.clone(),
+                    &extensions,
+                    expand_division,
+                    &mut function.vregs,
+                );
+                changed |= simplified.len() != 1 || simplified[0] != inst;
+                function.blocks[index].insts.extend(simplified);
+            }
+            changed |= cfg::simplify_branch(function, BlockId(index));
+        }
+        changed |= propagate(function);
+        if !changed {
+            break;
+        }
+    }
+    cfg::remove_unreachable(function);
+}
+
+// The instructions `inst` is replaced with. `vregs` is the count of virtual registers, which new
+// ones are numbered from.
+fn simplify(
+    inst: Inst,
+    extensions: &HashMap<VReg, (usize, bool)>,
+    expand_division: bool,
+    vregs: &mut usize,
+) -> Vec<Inst> {
+    let inst = match inst {
+        // A phi whose operands are the same, besides itself, is that one.
+        Inst::Phi { dest, args } => {
+            let mut values = args
+                .iter()
+                .map(|(_, arg)| arg)
+                .filter(|&arg| *arg != Operand::Reg(dest));
+            match values.next() {
+                Some(value) if values.all(|other| other == value) => copy(dest, value.clone()),
+                _ => Inst::Phi { dest, args },
+            }
+        }
+        Inst::Binary {
+            op,
+            dest,
+            lhs: Operand::Imm(lhs),
+            rhs: Operand::Imm(rhs),
+        } => match binary(op, lhs, rhs) {
+            Some(value) => copy(dest, Operand::Imm(value)),
+            None => inst,
+        },
+        // A constant operand of a commutative operator goes on the right.
+        Inst::Binary {
+            op: op @ (BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor),
+            dest,
+            lhs: lhs @ Operand::Imm(_),
+            rhs: rhs @ Operand::Reg(_),
+        } => Inst::Binary {
+            op,
+            dest,
+            lhs: rhs,
+            rhs: lhs,
+        },
+        Inst::Binary {
+            op: BinaryOp::SDiv,
+            rhs: Operand::Imm(2..),
+            ..
+        } if !expand_division => inst,
+        Inst::Binary {
+            op,
+            dest,
+            lhs,
+            rhs: Operand::Imm(rhs),
+        } => return identity(op, dest, lhs, rhs, vregs),
+        Inst::Binary {
+            op: BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr,
+            dest,
+            lhs: Operand::Imm(0),
+            ..
+        } => copy(dest, Operand::Imm(0)),
+        Inst::Binary {
+            op: BinaryOp::Sub | BinaryOp::Xor,
+            dest,
+            lhs: Operand::Reg(lhs),
+            rhs: Operand::Reg(rhs),
+        } if lhs == rhs => copy(dest, Operand::Imm(0)),
+        Inst::Cmp {
+            op,
+            is_unsigned,
+            dest,
+            lhs: Operand::Imm(lhs),
+            rhs: Operand::Imm(rhs),
+        } => copy(
+            dest,
+            Operand::Imm(compare(op, is_unsigned, lhs, rhs) as i64),
+        ),
+        Inst::Neg {
+            dest,
+            src: Operand::Imm(value),
+        } => copy(dest, Operand::Imm(value.wrapping_neg())),
+        Inst::Extend {
+            dest,
+            src: Operand::Imm(value),
+            size,
+            is_unsigned,
+        } => copy(dest, Operand::Imm(extend(value, size, is_unsigned))),
+        Inst::Extend {
+            dest,
+            src: Operand::Reg(src),
+            size,
+            is_unsigned,
+        } if extensions
+            .get(&src)
+            .is_some_and(|&from| is_extended(from, size, is_unsigned)) =>
+        {
+            copy(dest, Operand::Reg(src))
+        }
+        inst => inst,
+    };
+    vec![inst]
+}
+
+fn copy(dest: VReg, src: Operand) -> Inst {
+    Inst::Copy { dest, src }
+}
+
+// The size and the signedness each virtual register defined once is extended from, if it's the
+// result of an extension, a load or a comparison, or a phi of those.
+fn extensions(function: &Function) -> HashMap<VReg, (usize, bool)> {
+    let mut defs = vec![0; function.vregs];
+    let mut extensions = HashMap::new();
+    for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+        for vreg in inst.defs() {
+            defs[vreg.0] += 1;
+        }
+        match *inst {
+            Inst::Extend {
+                dest,
+                size,
+                is_unsigned,
+                ..
+            }
+            | Inst::Load {
+                dest,
+                size,
+                is_unsigned,
+                ..
+            } if size < 8 => {
+                extensions.insert(dest, (size, is_unsigned));
+            }
+            Inst::Cmp { dest, .. } => {
+                extensions.insert(dest, (1, true));
+            }
+            _ => {}
+        }
+    }
+    extensions.retain(|vreg, _| defs[vreg.0] == 1);
+
+    // A phi is assumed to be extended like its first operand which is known to be, until one of
+    // them is found not to be, which is the case for a loop whose phis only take each other.
+    let phis: Vec<_> = function
+        .blocks
+        .iter()
+        .flat_map(|block| &block.insts)
+        .filter_map(|inst| match inst {
+            Inst::Phi { dest, args } => Some((*dest, args)),
+            _ => None,
+        })
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (dest, args) in &phis {
+            let first = args.iter().find_map(|(_, arg)| match arg {
+                Operand::Reg(vreg) => extensions.get(vreg).copied(),
+                Operand::Imm(_) => None,
+            });
+            if let (false, Some(first)) = (extensions.contains_key(dest), first) {
+                extensions.insert(*dest, first);
+                changed = true;
+            }
+        }
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (dest, args) in &phis {
+            let Some(&(size, is_unsigned)) = extensions.get(dest) else {
+                continue;
+            };
+            let are_extended = args.iter().all(|(_, arg)| match arg {
+                Operand::Imm(value) => extend(*value, size, is_unsigned) == *value,
+                Operand::Reg(vreg) => extensions
+                    .get(vreg)
+                    .is_some_and(|&from| is_extended(from, size, is_unsigned)),
+            });
+            if !are_extended {
+                extensions.remove(dest);
+                changed = true;
+            }
+        }
+    }
+    extensions
+}
+
+// Whether a value extended from `from` is the same extended from `size` bytes by `is_unsigned`,
+// which it is if it was extended from as few the same way, or zero-extended from fewer.
+fn is_extended((from_size, from_unsigned): (usize, bool), size: usize, is_unsigned: bool) -> bool {
+    from_size < size && from_unsigned || from_size <= size && from_unsigned == is_unsigned
+}
+
+// The value of `lhs op rhs` on 64 bits, as the instructions compute it. A division by 0, or one
+// which overflows, is left to trap at run time.
+pub(super) fn binary(op: BinaryOp, lhs: i64, rhs: i64) -> Option<i64> {
+    Some(match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::SDiv => lhs.checked_div(rhs)?,
+        BinaryOp::UDiv => (lhs as u64).checked_div(rhs as u64)? as i64,
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Xor => lhs ^ rhs,
+        // Only the low 6 bits of the count are used.
+        BinaryOp::Shl => lhs << (rhs & 63),
+        BinaryOp::LShr => ((lhs as u64) >> (rhs & 63)) as i64,
+        BinaryOp::AShr => lhs >> (rhs & 63),
+    })
+}
+
+pub(super) fn compare(op: CmpOp, is_unsigned: bool, lhs: i64, rhs: i64) -> bool {
+    let ordering = if is_unsigned {
+        (lhs as u64).cmp(&(rhs as u64))
+    } else {
+        lhs.cmp(&rhs)
+    };
+    match op {
+        CmpOp::Lt => ordering.is_lt(),
+        CmpOp::Lte => ordering.is_le(),
+        CmpOp::Gt => ordering.is_gt(),
+        CmpOp::Gte => ordering.is_ge(),
+        CmpOp::Eq => ordering.is_eq(),
+        CmpOp::Neq => ordering.is_ne(),
+    }
+}
+
+// Truncates `value` to `size` bytes, then extends it back to 64 bits.
+pub(super) fn extend(value: i64, size: usize, is_unsigned: bool) -> i64 {
+    match (size, is_unsigned) {
+        (1, true) => value as u8 as i64,
+        (1, false) => value as i8 as i64,
+        (2, true) => value as u16 as i64,
+        (2, false) => value as i16 as i64,
+        (4, true) => value as u32 as i64,
+        (4, false) => value as i32 as i64,
+        _ => value,
+    }
+}
+
+// `lhs op rhs` with a constant `rhs`, where an identity gives an operand itself, and a power of 2
+// turns a multiplication or a division into shifts.
+fn identity(op: BinaryOp, dest: VReg, lhs: Operand, rhs: i64, vregs: &mut usize) -> Vec<Inst> {
+    let shift = (rhs as u64)
+        .is_power_of_two()
+        .then(|| rhs.trailing_zeros() as i64);
+    let inst = match (op, rhs) {
+        (BinaryOp::Add | BinaryOp::Sub | BinaryOp::Or | BinaryOp::Xor, 0)
+        | (BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr, 0)
+        | (BinaryOp::Mul | BinaryOp::SDiv | BinaryOp::UDiv, 1)
+        | (BinaryOp::And, -1) => copy(dest, lhs),
+        (BinaryOp::Mul | BinaryOp::And, 0) => copy(dest, Operand::Imm(0)),
+        (BinaryOp::Or, -1) => copy(dest, Operand::Imm(-1)),
+        (BinaryOp::Mul, _) if shift.is_some() => Inst::Binary {
+            op: BinaryOp::Shl,
+            dest,
+            lhs,
+            rhs: Operand::Imm(shift.unwrap()),
+        },
+        (BinaryOp::UDiv, _) if shift.is_some() => Inst::Binary {
+            op: BinaryOp::LShr,
+            dest,
+            lhs,
+            rhs: Operand::Imm(shift.unwrap()),
+        },
+        // Rounds toward 0 by adding `2^k - 1` to a negative dividend before shifting, which is
+        // the sign bits shifted right.
+        (BinaryOp::SDiv, 2..) if shift.is_some() => {
+            let shift = shift.unwrap();
+            let [sign, bias, biased] = [(); 3].map(|_| {
+                *vregs += 1;
+                VReg(*vregs - 1)
+            });
+            return vec![
+                Inst::Binary {
+                    op: BinaryOp::AShr,
+                    dest: sign,
+                    lhs: lhs.clone(),
+                    rhs: Operand::Imm(63),
+                },
+                Inst::Binary {
+                    op: BinaryOp::LShr,
+                    dest: bias,
+                    lhs: Operand::Reg(sign),
+                    rhs: Operand::Imm(64 - shift),
+                },
+                Inst::Binary {
+                    op: BinaryOp::Add,
+                    dest: biased,
+                    lhs,
+                    rhs: Operand::Reg(bias),
+                },
+                Inst::Binary {
+                    op: BinaryOp::AShr,
+                    dest,
+                    lhs: Operand::Reg(biased),
+                    rhs: Operand::Imm(shift),
+                },
+            ];
+        }
+        _ => Inst::Binary {
+            op,
+            dest,
+            lhs,
+            rhs: Operand::Imm(rhs),
+        },
+    };
+    vec![inst]
+}
+
+// Replaces the uses of a virtual register copied from a constant, or from another virtual register
+// which is also defined once, then removes the copies nothing uses. Whether anything changed.
+fn propagate(function: &mut Function) -> bool {
+    let mut defs = vec![0; function.vregs];
+    for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+        for vreg in inst.defs() {
+            defs[vreg.0] += 1;
+        }
+    }
+
+    let mut values = HashMap::new();
+    for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+        if let Inst::Copy { dest, src } = inst {
+            let is_single = |operand: &Operand| match operand {
+                Operand::Reg(vreg) => defs[vreg.0] == 1,
+                Operand::Imm(_) => true,
+            };
+            if defs[dest.0] == 1 && is_single(src) && *src != Operand::Reg(*dest) {
+                values.insert(*dest, src.clone());
+            }
+        }
+    }
+    // A copy of a copy is resolved to the original.
+    let resolve = |mut operand: Operand| {
+        while let Some(value) = match &operand {
+            Operand::Reg(vreg) => values.get(vreg),
+            Operand::Imm(_) => None,
+        } {
+            operand = value.clone();
+        }
+        operand
+    };
+
+    let mut changed = false;
+    for block in &mut function.blocks {
+        for inst in &mut block.insts {
+            for operand in inst.operands_mut() {
+                let value = resolve(operand.clone());
+                changed |= value != *operand;
+                *operand = value;
+            }
+            // An address can only be based on a register.
+            for address in inst.addresses_mut() {
+                if let Base::Reg(vreg) = address.base {
+                    if let Operand::Reg(value) = resolve(Operand::Reg(vreg)) {
+                        changed |= value != vreg;
+                        address.base = Base::Reg(value);
+                    }
+                }
+            }
+        }
+        if let Some(operand) = block.terminator.operand_mut() {
+            let value = resolve(operand.clone());
+            changed |= value != *operand;
+            *operand = value;
+        }
+    }
+
+    let mut uses = vec![0; function.vregs];
+    for block in &function.blocks {
+        let insts = block.insts.iter().flat_map(Inst::uses);
+        for vreg in insts.chain(block.terminator.uses()) {
+            uses[vreg.0] += 1;
+        }
+    }
+    for block in &mut function.blocks {
+        let len = block.insts.len();
+        block
+            .insts
+            .retain(|inst| !matches!(inst, Inst::Copy { dest, .. } if uses[dest.0] == 0));
+        changed |= block.insts.len() != len;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    // The IR of every function, folded.
+    fn fold_str(c_code: &str) -> String {
+        passes_str(c_code, &[Pass::Fold])
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            fold_str("int f() { return 5+20-4; }"),
+            "f:\nbb0:\n  ret 21\n"
+        );
+        // A division by 0 is left to trap.
+        assert_eq!(
+            fold_str("int f() { return (3 < 4) + (-1 < 0u) + -(2 * 3) + (char)300 + 7 / 0; }"),
+            "f:
+bb0:
+  %0 = sdiv 7, 0
+  %1 = ext.i32 %0
+  %19 = add %1, 39
+  %20 = ext.i32 %19
+  ret %20
+"
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        assert_eq!(
+            fold_str("int f(int a) { if (1) a = 2; else a = 3; while (0) a = 4; return a; }"),
+            "f:
+bb0:
+  jmp bb1
+bb1:
+  store.i32 [local 4], 2
+  jmp bb2
+bb2:
+  jmp bb3
+bb3:
+  jmp bb4
+bb4:
+  %0 = load.i32 [local 4]
+  ret %0
+"
+        );
+    }
+
+    #[test]
+    fn test_identities() {
+        assert_eq!(
+            fold_str("long f(long a) { return a * 1 + 0; }"),
+            "f:\nbb0:\n  %0 = load.i64 [local 8]\n  ret %0\n"
+        );
+        // The call is kept.
+        assert_eq!(
+            fold_str("long g(); long f() { return 0 * g(); }"),
+            "f:\nbb0:\n  %0 = call @g()\n  ret 0\n"
+        );
+        assert_eq!(
+            fold_str("long f(long a) { return 8 * a; }"),
+            "f:\nbb0:\n  %0 = load.i64 [local 8]\n  %1 = shl %0, 3\n  ret %1\n"
+        );
+        assert_eq!(
+            fold_str("unsigned long f(unsigned long a) { return a / 16; }"),
+            "f:\nbb0:\n  %0 = load.u64 [local 8]\n  %1 = lshr %0, 4\n  ret %1\n"
+        );
+        // Rounded toward 0.
+        assert_eq!(
+            fold_str("long f(long a) { return a / 4; }"),
+            "f:
+bb0:
+  %0 = load.i64 [local 8]
+  %2 = ashr %0, 63
+  %3 = lshr %2, 62
+  %4 = add %0, %3
+  %1 = ashr %4, 2
+  ret %1
+"
+        );
+    }
+
+    #[test]
+    fn test_ssa() {
+        // x is already sign extended from a char, on either path.
+        assert_eq!(
+            passes_str(
+                "int f(char c, int a) { int x = c; if (a) x = 3; return (char)x; }",
+                &[Pass::Mem2Reg, Pass::Fold]
+            ),
+            "f:
+bb0:
+  %7 = load.i8 [local 1]
+  %8 = load.i32 [local 8]
+  br %8, bb1, bb2
+bb1:
+  jmp bb2
+bb2:
+  %6 = phi [bb0: %7], [bb1: 3]
+  ret %6
+"
+        );
+        // A phi of one value is that value.
+        assert_eq!(
+            passes_str(
+                "long f(long a, long n) { long x = a; while (n) { n = n - 1; x = x; } return x; }",
+                &[Pass::Mem2Reg, Pass::Fold]
+            ),
+            "f:
+bb0:
+  %8 = load.i64 [local 8]
+  %9 = load.i64 [local 16]
+  jmp bb1
+bb1:
+  %6 = phi [bb0: %9], [bb2: %3]
+  br %6, bb2, bb3
+bb2:
+  %3 = sub %6, 1
+  jmp bb1
+bb3:
+  ret %8
+"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use crate::{
+    ir::{Address, Base, BinaryOp, BlockId, Function, Inst, Operand, VReg},
+    parser::CmpOp,
+};
+
+use super::cfg::Dominators;
+
+// What a pure instruction computes, which is the same wherever it's computed from the same
+// operands.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expression {
+    Binary(BinaryOp, Operand, Operand),
+    Cmp(CmpOp, bool, Operand, Operand),
+    Neg(Operand),
+    Extend(Operand, usize, bool),
+    Addr(Address),
+    FunctionAddr(String),
+    ThreadLocalAddr(String),
+}
+
+impl Expression {
+    fn new(inst: &Inst) -> Option<Self> {
+        Some(match inst.clone() {
+            Inst::Binary { op, lhs, rhs, .. } => {
+                let is_commutative = matches!(
+                    op,
+                    BinaryOp::Add | BinaryOp::Mul | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor
+                );
+                // `b + a` is `a + b`.
+                match (&lhs, &rhs) {
+                    (Operand::Reg(a), Operand::Reg(b)) if is_commutative && b < a => {
+                        Expression::Binary(op, rhs, lhs)
+                    }
+                    _ => Expression::Binary(op, lhs, rhs),
+                }
+            }
+            Inst::Cmp {
+                op,
+                is_unsigned,
+                lhs,
+                rhs,
+                ..
+            } => Expression::Cmp(op, is_unsigned, lhs, rhs),
+            Inst::Neg { src, .. } => Expression::Neg(src),
+            Inst::Extend {
+                src,
+                size,
+                is_unsigned,
+                ..
+            } => Expression::Extend(src, size, is_unsigned),
+            Inst::Addr { address, .. } => Expression::Addr(address),
+            Inst::FunctionAddr { name, .. } => Expression::FunctionAddr(name),
+            Inst::ThreadLocalAddr { name, .. } => Expression::ThreadLocalAddr(name),
+            _ => return None,
+        })
+    }
+}
+
+/// Global value numbering, which replaces an instruction computing what one in a block dominating
+/// it already has with a copy of its result. Loads aren't numbered, since a store may come
+/// between them.
+pub fn run(function: &mut Function) {
+    let dominators = Dominators::new(function);
+    let children = dominators.children();
+
+    // The expressions available in the blocks dominating the current one, and those each block
+    // added, which are forgotten when its subtree is left.
+    let mut available: HashMap<Expression, VReg> = HashMap::new();
+    let mut added: Vec<Vec<Expression>> = vec![vec![]; function.blocks.len()];
+    // What each copy found so far copies, so the instructions after it are numbered by the
+    // original.
+    let mut copies: HashMap<VReg, Operand> = HashMap::new();
+    let mut worklist = vec![(BlockId(0), true)];
+    while let Some((block, is_entry)) = worklist.pop() {
+        if !is_entry {
+            for expression in std::mem::take(&mut added[block.0]) {
+                available.remove(&expression);
+            }
+            continue;
+        }
+
+        for inst in &mut function.blocks[block.0].insts {
+            // The copies dominate it, unless it's a phi.
+            if !matches!(inst, Inst::Phi { .. }) {
+                for operand in inst.operands_mut() {
+                    if let Some(value) = operand_vreg(operand).and_then(|vreg| copies.get(&vreg)) {
+                        *operand = value.clone();
+                    }
+                }
+                for address in inst.addresses_mut() {
+                    if let Base::Reg(vreg) = address.base {
+                        if let Some(&Operand::Reg(value)) = copies.get(&vreg) {
+                            address.base = Base::Reg(value);
+                        }
+                    }
+                }
+            }
+            if let Inst::Copy { dest, src } = inst {
+                copies.insert(*dest, src.clone());
+                continue;
+            }
+
+            let (Some(expression), Some(dest)) = (Expression::new(inst), inst.dest()) else {
+                continue;
+            };
+            match available.get(&expression) {
+                Some(&value) => {
+                    *inst = Inst::Copy {
+                        dest,
+                        src: Operand::Reg(value),
+                    };
+                    copies.insert(dest, Operand::Reg(value));
+                }
+                None => {
+                    added[block.0].push(expression.clone());
+                    available.insert(expression, dest);
+                }
+            }
+        }
+
+        worklist.push((block, false));
+        worklist.extend(children[block.0].iter().rev().map(|&child| (child, true)));
+    }
+}
+
+fn operand_vreg(operand: &Operand) -> Option<VReg> {
+    match operand {
+        Operand::Reg(vreg) => Some(*vreg),
+        Operand::Imm(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_commutative() {
+        assert_eq!(
+            passes_str(
+                "long f(long a, long b) { return a * b + b * a; }",
+                &[Pass::Mem2Reg, Pass::Gvn]
+            ),
+            "f:
+bb0:
+  %7 = load.i64 [local 8]
+  %8 = load.i64 [local 16]
+  %0 = copy %7
+  %1 = copy %8
+  %2 = mul %8, %7
+  %3 = copy %8
+  %4 = copy %7
+  %5 = copy %2
+  %6 = add %2, %2
+  ret %6
+"
+        );
+    }
+
+    #[test]
+    fn test_dominance() {
+        // Only what was computed in a dominating block is reused.
+        assert_eq!(
+            passes_str(
+                "long f(long a) { long x; if (a) x = a * 2; else x = a * 2 + 1; return x + a * 2; }",
+                &[Pass::Mem2Reg, Pass::Gvn]
+            ),
+            "f:
+bb0:
+  %11 = load.i64 [local 8]
+  %0 = copy %11
+  br %0, bb1, bb2
+bb1:
+  %1 = copy %11
+  %2 = mul %11, 2
+  jmp bb3
+bb2:
+  %3 = copy %11
+  %4 = mul %11, 2
+  %5 = add %4, 1
+  jmp bb3
+bb3:
+  %10 = phi [bb1: %2], [bb2: %5]
+  %6 = copy %11
+  %7 = mul %11, 2
+  %8 = copy %10
+  %9 = add %10, %7
+  ret %9
+"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::ir::{BinaryOp, Block, BlockId, Function, Inst, Terminator};
+
+use super::cfg::{self, Dominators};
+
+/// A natural loop, which is entered through its header.
+struct Loop {
+    header: BlockId,
+    // Whether each block is in it.
+    body: Vec<bool>,
+}
+
+/// Loop-invariant code motion, which moves the pure instructions of a loop whose operands are
+/// defined outside it to its preheader, the block control enters it from. Inner loops go first,
+/// so an instruction can be moved out of several.
+pub fn run(function: &mut Function) {
+    cfg::remove_unreachable(function);
+
+    // A loop entered from one block which may also go elsewhere gets a block of its own to enter
+    // from, before its header.
+    while let Some((predecessor, header)) =
+        loops(function)
+            .into_iter()
+            .find_map(|natural| match preheader(function, &natural) {
+                Err(Some(predecessor)) => Some((predecessor, natural.header)),
+                _ => None,
+            })
+    {
+        cfg::insert_block(
+            function,
+            header.0,
+            Block {
+                insts: vec![],
+                terminator: Terminator::Jump(header),
+            },
+        );
+        // The blocks from the header on moved up by one.
+        let preheader = header;
+        let header = BlockId(header.0 + 1);
+        let predecessor = match predecessor < preheader {
+            true => predecessor,
+            false => BlockId(predecessor.0 + 1),
+        };
+        for successor in function.blocks[predecessor.0].terminator.successors_mut() {
+            if *successor == header {
+                *successor = preheader;
+            }
+        }
+        cfg::rename_predecessor(function, header, predecessor, preheader);
+    }
+
+    let mut loops = loops(function);
+    loops.sort_by_key(|natural| natural.body.iter().filter(|&&is_in| is_in).count());
+
+    let mut def_blocks = vec![None; function.vregs];
+    for (index, block) in function.blocks.iter().enumerate() {
+        for vreg in block.insts.iter().flat_map(Inst::defs) {
+            def_blocks[vreg.0] = Some(index);
+        }
+    }
+    for natural in loops {
+        let Ok(preheader) = preheader(function, &natural) else {
+            continue;
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in (0..function.blocks.len()).filter(|&index| natural.body[index]) {
+                let insts = std::mem::take(&mut function.blocks[index].insts);
+                for inst in insts {
+                    let is_invariant = inst
+                        .uses()
+                        .iter()
+                        .all(|vreg| def_blocks[vreg.0].map_or(true, |block| !natural.body[block]));
+                    if is_invariant && is_hoistable(&inst) {
+                        for vreg in inst.defs() {
+                            def_blocks[vreg.0] = Some(preheader.0);
+                        }
+                        function.blocks[preheader.0].insts.push(inst);
+                        changed = true;
+                    } else {
+                        function.blocks[index].insts.push(inst);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Whether `inst` has no effect but its result, and can't trap, so it can run even if the loop
+// doesn't.
+fn is_hoistable(inst: &Inst) -> bool {
+    match inst {
+        Inst::Binary { op, .. } => !matches!(op, BinaryOp::SDiv | BinaryOp::UDiv),
+        Inst::Copy { .. }
+        | Inst::Cmp { .. }
+        | Inst::Neg { .. }
+        | Inst::Extend { .. }
+        | Inst::Addr { .. }
+        | Inst::FunctionAddr { .. } => true,
+        _ => false,
+    }
+}
+
+// The loops of each header, which go back to it from the blocks it dominates.
+fn loops(function: &Function) -> Vec<Loop> {
+    let dominators = Dominators::new(function);
+    let predecessors = cfg::predecessors(function);
+    let mut loops = vec![];
+    for (index, predecessors_of_header) in predecessors.iter().enumerate() {
+        let header = BlockId(index);
+        let mut stack: Vec<BlockId> = predecessors_of_header
+            .iter()
+            .copied()
+            .filter(|&latch| dominators.dominates(header, latch))
+            .collect();
+        if stack.is_empty() {
+            continue;
+        }
+
+        let mut body = vec![false; function.blocks.len()];
+        body[header.0] = true;
+        while let Some(block) = stack.pop() {
+            if !body[block.0] {
+                body[block.0] = true;
+                stack.extend(&predecessors[block.0]);
+            }
+        }
+        loops.push(Loop { header, body });
+    }
+    loops
+}
+
+// The block which only goes to the loop, and is the only one outside it which does. Otherwise,
+// the block outside it which goes to it, if there's only one.
+fn preheader(function: &Function, natural: &Loop) -> Result<BlockId, Option<BlockId>> {
+    let predecessors = cfg::predecessors(function);
+    let outside: Vec<BlockId> = predecessors[natural.header.0]
+        .iter()
+        .copied()
+        .filter(|block| !natural.body[block.0])
+        .collect();
+    match outside[..] {
+        [block] if function.blocks[block.0].terminator == Terminator::Jump(natural.header) => {
+            Ok(block)
+        }
+        [block] => Err(Some(block)),
+        _ => Err(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_hoist() {
+        assert_eq!(
+            passes_str(
+                "long f(long a, long n) { long s = 0; while (n) { s = s + a * 3; n = n - 1; } return s; }",
+                &[Pass::Mem2Reg, Pass::Licm]
+            ),
+            "f:
+bb0:
+  %10 = load.i64 [local 8]
+  %11 = load.i64 [local 16]
+  %1 = copy %10
+  %2 = mul %1, 3
+  jmp bb1
+bb1:
+  %8 = phi [bb0: %11], [bb2: %6]
+  %9 = phi [bb0: 0], [bb2: %4]
+  %0 = copy %8
+  br %0, bb2, bb3
+bb2:
+  %3 = copy %9
+  %4 = add %3, %2
+  %5 = copy %8
+  %6 = sub %5, 1
+  jmp bb1
+bb3:
+  %7 = copy %9
+  ret %7
+"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use crate::ir::{Address, AsmPlace, Base, BlockId, Function, Inst, Operand, VReg};
+
+use super::cfg::{self, Dominators};
+
+/// Moves the variables in the frame which are only loaded and stored whole into virtual registers,
+/// with phis where their definitions meet, which puts the function in SSA form.
+pub fn run(function: &mut Function) {
+    cfg::remove_unreachable(function);
+
+    let sizes = promotable(function);
+    if sizes.is_empty() {
+        return;
+    }
+    let mut variables: Vec<usize> = sizes.keys().copied().collect();
+    variables.sort();
+    let index: HashMap<usize, usize> = variables
+        .iter()
+        .enumerate()
+        .map(|(index, &offset)| (offset, index))
+        .collect();
+
+    let dominators = Dominators::new(function);
+    let frontiers = dominators.frontiers(function);
+
+    // A phi is placed where a store to the variable meets another definition, which is itself one.
+    let mut phis: Vec<Vec<usize>> = vec![vec![]; function.blocks.len()];
+    for (variable, &offset) in variables.iter().enumerate() {
+        let mut has_phi = vec![false; function.blocks.len()];
+        let mut worklist: Vec<BlockId> = (0..function.blocks.len())
+            .map(BlockId)
+            .filter(|block| {
+                function.blocks[block.0]
+                    .insts
+                    .iter()
+                    .any(|inst| local(inst) == Some(offset) && matches!(inst, Inst::Store { .. }))
+            })
+            .collect();
+        while let Some(block) = worklist.pop() {
+            for &frontier in &frontiers[block.0] {
+                if !has_phi[frontier.0] {
+                    has_phi[frontier.0] = true;
+                    phis[frontier.0].push(variable);
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+    let mut phi_dests = vec![vec![]; function.blocks.len()];
+    for (block, variables) in phis.iter().enumerate() {
+        let dests: Vec<VReg> = variables.iter().map(|_| new_vreg(function)).collect();
+        let insts = dests.iter().map(|&dest| Inst::Phi { dest, args: vec![] });
+        function.blocks[block].insts.splice(0..0, insts);
+        phi_dests[block] = dests;
+    }
+
+    // A parameter starts as the argument stored in the prologue, and anything else as 0, since
+    // reading it before it's set is undefined.
+    let mut initial = vec![];
+    let mut stacks = vec![];
+    for &offset in &variables {
+        let value = match function.params.iter().any(|param| param.offset == offset) {
+            true => {
+                let dest = new_vreg(function);
+                initial.push(Inst::Load {
+                    dest,
+                    address: Address {
+                        base: Base::Local(offset),
+                        offset: 0,
+                    },
+                    size: sizes[&offset],
+                    is_unsigned: false,
+                    is_volatile: false,
+                });
+                Operand::Reg(dest)
+            }
+            false => Operand::Imm(0),
+        };
+        stacks.push(vec![value]);
+    }
+
+    // Each block is renamed with the values at its end in the blocks dominating it.
+    let children = dominators.children();
+    let mut worklist = vec![(BlockId(0), true)];
+    let mut pushed: Vec<Vec<usize>> = vec![vec![]; function.blocks.len()];
+    while let Some((block, is_entry)) = worklist.pop() {
+        if !is_entry {
+            for &variable in &pushed[block.0] {
+                stacks[variable].pop();
+            }
+            continue;
+        }
+
+        for (&variable, &dest) in phis[block.0].iter().zip(&phi_dests[block.0]) {
+            stacks[variable].push(Operand::Reg(dest));
+            pushed[block.0].push(variable);
+        }
+        let insts = std::mem::take(&mut function.blocks[block.0].insts);
+        for inst in insts {
+            let Some(&variable) = local(&inst).and_then(|offset| index.get(&offset)) else {
+                function.blocks[block.0].insts.push(inst);
+                continue;
+            };
+            let value = stacks[variable].last().unwrap().clone();
+            match inst {
+                Inst::Load { dest, size: 8, .. } => function.blocks[block.0]
+                    .insts
+                    .push(Inst::Copy { dest, src: value }),
+                // Only the stored bytes are read back.
+                Inst::Load {
+                    dest,
+                    size,
+                    is_unsigned,
+                    ..
+                } => function.blocks[block.0].insts.push(Inst::Extend {
+                    dest,
+                    src: value,
+                    size,
+                    is_unsigned,
+                }),
+                Inst::Store { src, .. } => {
+                    stacks[variable].push(src);
+                    pushed[block.0].push(variable);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        let successors = function.blocks[block.0].terminator.successors();
+        for (index, successor) in successors.iter().enumerate() {
+            if successors[..index].contains(successor) {
+                continue;
+            }
+            for (position, &variable) in phis[successor.0].iter().enumerate() {
+                let value = stacks[variable].last().unwrap().clone();
+                if let Inst::Phi { args, .. } = &mut function.blocks[successor.0].insts[position] {
+                    args.push((block, value));
+                }
+            }
+        }
+
+        worklist.push((block, false));
+        worklist.extend(children[block.0].iter().rev().map(|&child| (child, true)));
+    }
+
+    function.blocks[0].insts.splice(0..0, initial);
+}
+
+// The offset of the variable a promotable load or store accesses.
+fn local(inst: &Inst) -> Option<usize> {
+    match inst {
+        Inst::Load { address, .. } | Inst::Store { address, .. } => match address.base {
+            Base::Local(offset) => Some(offset),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The size of each variable whose address is never taken, and which is only loaded and stored
+// whole and not volatile.
+fn promotable(function: &Function) -> HashMap<usize, usize> {
+    let mut sizes: HashMap<usize, Option<usize>> = HashMap::new();
+    for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+        match inst {
+            Inst::Load {
+                address,
+                size,
+                is_volatile: false,
+                ..
+            }
+            | Inst::Store {
+                address,
+                size,
+                is_volatile: false,
+                ..
+            } if address.offset == 0 => {
+                if let Base::Local(offset) = address.base {
+                    let entry = sizes.entry(offset).or_insert(Some(*size));
+                    if *entry != Some(*size) {
+                        *entry = None;
+                    }
+                }
+            }
+            Inst::Addr { address, .. }
+            | Inst::Load { address, .. }
+            | Inst::Store { address, .. } => {
+                if let Base::Local(offset) = address.base {
+                    sizes.insert(offset, None);
+                }
+            }
+            Inst::Call {
+                temporary: Some(offset),
+                ..
+            } => {
+                sizes.insert(*offset, None);
+            }
+            Inst::Asm(asm) => {
+                for operand in &asm.operands {
+                    if let AsmPlace::Variable(Address {
+                        base: Base::Local(offset),
+                        ..
+                    }) = operand.place
+                    {
+                        sizes.insert(offset, None);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(offset) = function.return_pointer {
+        sizes.insert(offset, None);
+    }
+    sizes
+        .into_iter()
+        .filter_map(|(offset, size)| Some((offset, size?)))
+        .collect()
+}
+
+fn new_vreg(function: &mut Function) -> VReg {
+    function.vregs += 1;
+    VReg(function.vregs - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_phis() {
+        assert_eq!(
+            passes_str(
+                "int f(int a) { int x = 1; if (a) x = 2; return x; }",
+                &[Pass::Mem2Reg]
+            ),
+            "f:
+bb0:
+  %3 = load.i32 [local 4]
+  %0 = ext.i32 %3
+  br %0, bb1, bb2
+bb1:
+  jmp bb2
+bb2:
+  %2 = phi [bb0: 1], [bb1: 2]
+  %1 = ext.i32 %2
+  ret %1
+"
+        );
+    }
+
+    #[test]
+    fn test_address_taken() {
+        assert_eq!(
+            passes_str(
+                "int f() { int x = 1; int *p = &x; *p = 2; return x; }",
+                &[Pass::Mem2Reg]
+            ),
+            "f:
+bb0:
+  store.i32 [local 4], 1
+  %0 = addr [local 4]
+  %1 = copy %0
+  store.i32 [%1], 2
+  %2 = load.i32 [local 4]
+  ret %2
+"
+        );
+    }
+}
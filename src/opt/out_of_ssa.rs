@@ -0,0 +1,98 @@
+use crate::ir::{BlockId, Function, Inst, Operand, Terminator, VReg};
+
+/// Replaces each phi with copies, of its operand at the end of each predecessor to a new virtual
+/// register, and of that to its result where it was. The copies of one block's phis can't
+/// overwrite each other's operands, even when they're swapped.
+pub fn run(function: &mut Function) {
+    for index in 0..function.blocks.len() {
+        let mut copies: Vec<(BlockId, Inst)> = vec![];
+        for inst in &mut function.blocks[index].insts {
+            let Inst::Phi { dest, args } = inst else {
+                continue;
+            };
+            let dest = *dest;
+            function.vregs += 1;
+            let temporary = VReg(function.vregs - 1);
+            for (predecessor, arg) in args.drain(..) {
+                copies.push((
+                    predecessor,
+                    Inst::Copy {
+                        dest: temporary,
+                        src: arg,
+                    },
+                ));
+            }
+            *inst = Inst::Copy {
+                dest,
+                src: Operand::Reg(temporary),
+            };
+        }
+
+        for (predecessor, copy) in copies {
+            let block = &mut function.blocks[predecessor.0];
+            // Before a comparison only branched on, which sets the flags for the branch.
+            let position = match (block.insts.last(), &block.terminator) {
+                (
+                    Some(Inst::Cmp { dest, .. }),
+                    Terminator::Branch {
+                        cond: Operand::Reg(cond),
+                        ..
+                    },
+                ) if dest == cond && !copy.uses().contains(dest) => block.insts.len() - 1,
+                _ => block.insts.len(),
+            };
+            block.insts.insert(position, copy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_swap() {
+        assert_eq!(
+            passes_str(
+                "long f(long n) {
+                    long a = 0;
+                    long b = 1;
+                    long t;
+                    while (n) { t = a; a = b; b = t; n = n - 1; }
+                    return a;
+                }",
+                &[Pass::Mem2Reg, Pass::OutOfSsa]
+            ),
+            "f:
+bb0:
+  %11 = load.i64 [local 8]
+  %12 = copy %11
+  %13 = copy 0
+  %14 = copy 1
+  %15 = copy 0
+  jmp bb1
+bb1:
+  %7 = copy %12
+  %8 = copy %13
+  %9 = copy %14
+  %10 = copy %15
+  %0 = copy %7
+  br %0, bb2, bb3
+bb2:
+  %1 = copy %8
+  %2 = copy %9
+  %3 = copy %1
+  %4 = copy %7
+  %5 = sub %4, 1
+  %12 = copy %5
+  %13 = copy %2
+  %14 = copy %3
+  %15 = copy %1
+  jmp bb1
+bb3:
+  %6 = copy %8
+  ret %6
+"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::inline_asm::Register;
+
+/// Rewrites short sequences of the assembly code generation emits, until nothing changes: moves
+/// of a register to itself and jumps to the next line are removed, a load of what was just stored
+/// reads the register instead, and comparisons with 0 and moves of 0 are shortened. What an asm
+/// statement wrote, between `#APP` and `#NO_APP`, is left as it is.
+pub fn run(asm: &str) -> String {
+    let mut lines: Vec<String> = asm.lines().map(str::to_string).collect();
+    while let Some(simplified) = simplify(&lines) {
+        lines = simplified;
+    }
+    lines.join("\n") + "\n"
+}
+
+// The lines rewritten once, if anything changed.
+fn simplify(lines: &[String]) -> Option<Vec<String>> {
+    let mut simplified: Vec<String> = vec![];
+    let mut changed = false;
+    let mut is_asm = false;
+    for (index, line) in lines.iter().enumerate() {
+        match line.trim() {
+            "#APP" => is_asm = true,
+            "#NO_APP" => is_asm = false,
+            _ => {}
+        }
+        let Some((mnemonic, operands)) = instruction(line).filter(|_| !is_asm) else {
+            simplified.push(line.clone());
+            continue;
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+
+        let rewritten = match (mnemonic, &operands[..]) {
+            ("mov", [dest, src]) if dest == src && is_register64(dest) => None,
+            ("jmp", [label]) if lines.get(index + 1) == Some(&format!("{label}:")) => None,
+            // A load of what the previous line stored.
+            ("mov", [dest, src]) if src.starts_with("QWORD PTR") && is_register64(dest) => {
+                match simplified.last().and_then(|last| instruction(last)) {
+                    Some(("mov", stored)) => match stored[..] {
+                        [address, register] if address == *src && is_register64(register) => {
+                            (register != *dest).then(|| format!("mov {dest}, {register}"))
+                        }
+                        _ => Some(line.trim().to_string()),
+                    },
+                    _ => Some(line.trim().to_string()),
+                }
+            }
+            ("cmp", [register, "0"]) if Register::from_name(register).is_some() => {
+                Some(format!("test {register}, {register}"))
+            }
+            ("mov", [dest, "0"]) if is_register64(dest) && are_flags_dead(&lines[index + 1..]) => {
+                let register = Register::from_name(dest).unwrap().name(4);
+                Some(format!("xor {register}, {register}"))
+            }
+            _ => Some(line.trim().to_string()),
+        };
+        match rewritten {
+            Some(rewritten) if rewritten == line.trim() => simplified.push(line.clone()),
+            Some(rewritten) => {
+                simplified.push(format!("{indent}{rewritten}"));
+                changed = true;
+            }
+            None => changed = true,
+        }
+    }
+    changed.then_some(simplified)
+}
+
+// The mnemonic and the operands of a line which is an instruction.
+fn instruction(line: &str) -> Option<(&str, Vec<&str>)> {
+    if !line.starts_with(' ') {
+        return None;
+    }
+    let line = line.trim();
+    if line.starts_with('.') || line.starts_with('#') {
+        return None;
+    }
+    Some(match line.split_once(' ') {
+        Some((mnemonic, operands)) => (mnemonic, operands.split(", ").collect()),
+        None => (line, vec![]),
+    })
+}
+
+fn is_register64(operand: &str) -> bool {
+    Register::from_name(operand).is_some_and(|register| register.name(8) == operand)
+}
+
+// Whether the flags are set again before anything after these lines reads them.
+fn are_flags_dead(lines: &[String]) -> bool {
+    for line in lines {
+        if line.trim() == "#APP" {
+            return false;
+        }
+        let Some((mnemonic, _)) = instruction(line) else {
+            continue;
+        };
+        let reads = ["set", "cmov", "adc", "sbb"]
+            .iter()
+            .any(|prefix| mnemonic.starts_with(prefix))
+            || mnemonic.starts_with('j') && mnemonic != "jmp";
+        if reads {
+            return false;
+        }
+        // A shift by 0 leaves them as they were.
+        if [
+            "cmp", "test", "add", "sub", "and", "or", "xor", "neg", "imul", "mul", "idiv", "div",
+            "jmp", "call", "ret",
+        ]
+        .contains(&mnemonic)
+        {
+            return true;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplify() {
+        assert_eq!(
+            run("f:
+  mov rax, rax
+  mov QWORD PTR [rbp-8], rax
+  mov rcx, QWORD PTR [rbp-8]
+  mov rax, QWORD PTR [rbp-8]
+  cmp rcx, 0
+  jmp .L1
+.L1:
+  mov rdx, 0
+  ret
+"),
+            "f:
+  mov QWORD PTR [rbp-8], rax
+  mov rcx, rax
+  mov rax, QWORD PTR [rbp-8]
+  test rcx, rcx
+.L1:
+  xor edx, edx
+  ret
+"
+        );
+    }
+
+    #[test]
+    fn test_flags_live() {
+        // The move can't become a xor between the comparison and the jump.
+        let asm = "f:
+  cmp rax, rcx
+  mov rax, 0
+  jl .L1
+  ret
+";
+        assert_eq!(run(asm), asm);
+    }
+
+    #[test]
+    fn test_inline_asm() {
+        let asm = "f:
+#APP
+  mov rax, rax
+#NO_APP
+  ret
+";
+        assert_eq!(run(asm), asm);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use crate::ir::{BlockId, Function, Inst, Operand, Terminator, VReg};
+
+use super::{cfg, fold};
+
+// What's known of a virtual register, which only goes down from `Unknown` to `Varying`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    // Nothing, since none of its definitions has been reached.
+    Unknown,
+    Constant(i64),
+    Varying,
+}
+
+fn meet(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (Value::Unknown, value) | (value, Value::Unknown) => value,
+        (Value::Constant(a), Value::Constant(b)) if a == b => Value::Constant(a),
+        _ => Value::Varying,
+    }
+}
+
+/// Sparse conditional constant propagation, which finds the virtual registers that are constant
+/// on every path control can take, assuming a branch on a constant only goes one way. Their uses
+/// are replaced with the constants, and the blocks which are never reached are removed.
+pub fn run(function: &mut Function) {
+    let mut solver = Solver::new(function);
+    solver.solve();
+    let Solver {
+        values, executable, ..
+    } = solver;
+
+    let constant = |operand: &mut Operand| {
+        if let Operand::Reg(vreg) = *operand {
+            if let Value::Constant(value) = values[vreg.0] {
+                *operand = Operand::Imm(value);
+            }
+        }
+    };
+    for block in &mut function.blocks {
+        for inst in &mut block.insts {
+            inst.operands_mut().into_iter().for_each(constant);
+        }
+        if let Some(operand) = block.terminator.operand_mut() {
+            constant(operand);
+        }
+    }
+    for (index, &is_executable) in executable.iter().enumerate() {
+        if is_executable {
+            cfg::simplify_branch(function, BlockId(index));
+        }
+    }
+    cfg::remove_unreachable(function);
+}
+
+// Where a virtual register is read: an instruction of a block, or its terminator.
+type Site = (BlockId, Option<usize>);
+
+struct Solver<'a> {
+    function: &'a Function,
+    values: Vec<Value>,
+    executable: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    users: Vec<Vec<Site>>,
+    // The edges found executable, with none into the first block, and the virtual registers whose
+    // value went down, which are still to be followed.
+    flow: Vec<(Option<BlockId>, BlockId)>,
+    ssa: Vec<VReg>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(function: &'a Function) -> Self {
+        let mut users = vec![vec![]; function.vregs];
+        for (index, block) in function.blocks.iter().enumerate() {
+            for (position, inst) in block.insts.iter().enumerate() {
+                for vreg in inst.uses() {
+                    users[vreg.0].push((BlockId(index), Some(position)));
+                }
+            }
+            if let Some(vreg) = block.terminator.uses() {
+                users[vreg.0].push((BlockId(index), None));
+            }
+        }
+        Solver {
+            function,
+            values: vec![Value::Unknown; function.vregs],
+            executable: vec![false; function.blocks.len()],
+            edges: HashSet::new(),
+            users,
+            flow: vec![(None, BlockId(0))],
+            ssa: vec![],
+        }
+    }
+
+    fn solve(&mut self) {
+        loop {
+            if let Some((from, to)) = self.flow.pop() {
+                if from.is_some_and(|from| !self.edges.insert((from, to))) {
+                    continue;
+                }
+                let block = &self.function.blocks[to.0];
+                if self.executable[to.0] {
+                    // Only the phis see the new edge.
+                    for (position, inst) in block.insts.iter().enumerate() {
+                        if let Inst::Phi { .. } = inst {
+                            self.visit((to, Some(position)));
+                        }
+                    }
+                } else {
+                    self.executable[to.0] = true;
+                    for position in 0..block.insts.len() {
+                        self.visit((to, Some(position)));
+                    }
+                    self.visit((to, None));
+                }
+            } else if let Some(vreg) = self.ssa.pop() {
+                for index in 0..self.users[vreg.0].len() {
+                    let site = self.users[vreg.0][index];
+                    if self.executable[site.0 .0] {
+                        self.visit(site);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn value(&self, operand: &Operand) -> Value {
+        match operand {
+            Operand::Reg(vreg) => self.values[vreg.0],
+            Operand::Imm(value) => Value::Constant(*value),
+        }
+    }
+
+    fn visit(&mut self, (block, position): Site) {
+        let Some(position) = position else {
+            match &self.function.blocks[block.0].terminator {
+                Terminator::Jump(target) => self.flow.push((Some(block), *target)),
+                Terminator::Branch { cond, then, else_ } => match self.value(cond) {
+                    Value::Unknown => {}
+                    Value::Constant(value) => {
+                        let target = if value != 0 { then } else { else_ };
+                        self.flow.push((Some(block), *target));
+                    }
+                    Value::Varying => {
+                        self.flow.push((Some(block), *then));
+                        self.flow.push((Some(block), *else_));
+                    }
+                },
+                Terminator::Return(_) => {}
+            }
+            return;
+        };
+
+        let inst = &self.function.blocks[block.0].insts[position];
+        let value = self.evaluate(block, inst);
+        for vreg in inst.defs() {
+            if self.values[vreg.0] != value {
+                self.values[vreg.0] = value;
+                self.ssa.push(vreg);
+            }
+        }
+    }
+
+    fn evaluate(&self, block: BlockId, inst: &Inst) -> Value {
+        let unary = |src: &Operand, f: &dyn Fn(i64) -> i64| match self.value(src) {
+            Value::Constant(value) => Value::Constant(f(value)),
+            value => value,
+        };
+        let binary = |lhs: &Operand, rhs: &Operand, f: &dyn Fn(i64, i64) -> Option<i64>| match (
+            self.value(lhs),
+            self.value(rhs),
+        ) {
+            (Value::Constant(lhs), Value::Constant(rhs)) => {
+                f(lhs, rhs).map_or(Value::Varying, Value::Constant)
+            }
+            (Value::Varying, _) | (_, Value::Varying) => Value::Varying,
+            _ => Value::Unknown,
+        };
+        match inst {
+            // Only the operands from the edges which are executable so far.
+            Inst::Phi { args, .. } => args
+                .iter()
+                .filter(|(predecessor, _)| self.edges.contains(&(*predecessor, block)))
+                .fold(Value::Unknown, |value, (_, arg)| {
+                    meet(value, self.value(arg))
+                }),
+            Inst::Copy { src, .. } => self.value(src),
+            Inst::Binary { op, lhs, rhs, .. } => {
+                binary(lhs, rhs, &|lhs, rhs| fold::binary(*op, lhs, rhs))
+            }
+            Inst::Cmp {
+                op,
+                is_unsigned,
+                lhs,
+                rhs,
+                ..
+            } => binary(lhs, rhs, &|lhs, rhs| {
+                Some(fold::compare(*op, *is_unsigned, lhs, rhs) as i64)
+            }),
+            Inst::Neg { src, .. } => unary(src, &i64::wrapping_neg),
+            Inst::Extend {
+                src,
+                size,
+                is_unsigned,
+                ..
+            } => unary(src, &|value| fold::extend(value, *size, *is_unsigned)),
+            _ => Value::Varying,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_unreachable_definition() {
+        // Folding alone can't tell x stays 1, since the loop could reach the store.
+        assert_eq!(
+            passes_str(
+                "int f(int n) {
+                    int x = 1;
+                    int i = 0;
+                    while (i < n) { if (x != 1) x = 2; i = i + 1; }
+                    return x;
+                }",
+                &[Pass::Mem2Reg, Pass::Sccp]
+            ),
+            "f:
+bb0:
+  %12 = load.i32 [local 4]
+  jmp bb1
+bb1:
+  %9 = phi [bb0: 1], [bb3: 1]
+  %10 = phi [bb0: 0], [bb3: %7]
+  %0 = ext.i32 %12
+  %1 = ext.i32 %10
+  %2 = cmp lt %1, %0
+  br %2, bb2, bb4
+bb2:
+  %3 = ext.i32 1
+  %4 = cmp ne 1, 1
+  jmp bb3
+bb3:
+  %11 = phi [bb2: 1]
+  %5 = ext.i32 %10
+  %6 = add %5, 1
+  %7 = ext.i32 %6
+  jmp bb1
+bb4:
+  %8 = ext.i32 1
+  ret 1
+"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::ir::{BlockId, Function, Inst, Terminator};
+
+use super::cfg;
+
+/// Simplifies the control flow graph, until nothing changes: a branch which goes to the same
+/// block either way becomes a jump, a block only entered from one which jumps to it is merged
+/// into that one, and a jump to an empty block which only jumps on goes where it does.
+pub fn run(function: &mut Function) {
+    loop {
+        for index in 0..function.blocks.len() {
+            cfg::simplify_branch(function, BlockId(index));
+        }
+        cfg::remove_unreachable(function);
+        if !merge(function) && !forward(function) {
+            break;
+        }
+    }
+}
+
+// Merges a block into its only predecessor, if that jumps to it. Whether it found one.
+fn merge(function: &mut Function) -> bool {
+    let predecessors = cfg::predecessors(function);
+    let Some((block, successor)) = function
+        .blocks
+        .iter()
+        .enumerate()
+        .find_map(|(index, block)| match block.terminator {
+            Terminator::Jump(successor)
+                if successor.0 != 0
+                    && successor.0 != index
+                    && predecessors[successor.0] == [BlockId(index)] =>
+            {
+                Some((BlockId(index), successor))
+            }
+            _ => None,
+        })
+    else {
+        return false;
+    };
+
+    // Its phis have only this operand.
+    let insts = std::mem::take(&mut function.blocks[successor.0].insts);
+    let insts = insts.into_iter().map(|inst| match inst {
+        Inst::Phi { dest, mut args } => Inst::Copy {
+            dest,
+            src: args.remove(0).1,
+        },
+        inst => inst,
+    });
+    function.blocks[block.0].insts.extend(insts);
+    // The successor is left unreachable.
+    let terminator = std::mem::replace(
+        &mut function.blocks[successor.0].terminator,
+        Terminator::Return(None),
+    );
+    for next in terminator.successors() {
+        cfg::rename_predecessor(function, next, successor, block);
+    }
+    function.blocks[block.0].terminator = terminator;
+    true
+}
+
+// Makes the predecessors of an empty block which only jumps go where it does. A successor with
+// phis takes their operands from the predecessors instead, unless one already goes to it. Whether
+// it found such a block.
+fn forward(function: &mut Function) -> bool {
+    let predecessors = cfg::predecessors(function);
+    let found = function
+        .blocks
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find_map(|(index, block)| {
+            let Terminator::Jump(target) = block.terminator else {
+                return None;
+            };
+            let has_phis = matches!(
+                function.blocks[target.0].insts.first(),
+                Some(Inst::Phi { .. })
+            );
+            let conflicts = predecessors[index]
+                .iter()
+                .any(|predecessor| predecessors[target.0].contains(predecessor));
+            (block.insts.is_empty() && target.0 != index && !(has_phis && conflicts))
+                .then_some((BlockId(index), target))
+        });
+    let Some((block, target)) = found else {
+        return false;
+    };
+
+    for &predecessor in &predecessors[block.0] {
+        for successor in function.blocks[predecessor.0].terminator.successors_mut() {
+            if *successor == block {
+                *successor = target;
+            }
+        }
+    }
+    for inst in &mut function.blocks[target.0].insts {
+        if let Inst::Phi { args, .. } = inst {
+            if let Some(position) = args.iter().position(|(from, _)| *from == block) {
+                let (_, value) = args.remove(position);
+                for &predecessor in &predecessors[block.0] {
+                    args.push((predecessor, value.clone()));
+                }
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opt::{passes_str, Pass};
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            passes_str(
+                "long f(long a) { if (1) a = a + 1; else a = 0; while (a) {} return a; }",
+                &[Pass::Mem2Reg, Pass::SimplifyCfg]
+            ),
+            "f:
+bb0:
+  %5 = load.i64 [local 8]
+  %0 = copy %5
+  %1 = add %0, 1
+  %4 = copy %1
+  jmp bb1
+bb1:
+  %2 = copy %4
+  br %2, bb1, bb2
+bb2:
+  %3 = copy %4
+  ret %3
+"
+        );
+    }
+
+    #[test]
+    fn test_forward() {
+        // The empty block can't be skipped, since its phi would need two operands from bb1.
+        assert_eq!(
+            passes_str(
+                "long f(long a) { long x = 0; if (a) { if (a) x = 1; } return x; }",
+                &[Pass::Mem2Reg, Pass::SimplifyCfg]
+            ),
+            "f:
+bb0:
+  %5 = load.i64 [local 8]
+  %0 = copy %5
+  br %0, bb1, bb4
+bb1:
+  %1 = copy %5
+  br %1, bb2, bb3
+bb2:
+  jmp bb3
+bb3:
+  %3 = phi [bb1: 0], [bb2: 1]
+  jmp bb4
+bb4:
+  %4 = phi [bb0: 0], [bb3: %3]
+  %2 = copy %4
+  ret %2
+"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{bail, Result};
+
+use crate::{opt::Pass, types::Target, warning::WarningOptions};
+
+#[derive(Debug, PartialEq)]
+pub enum MacroDefinition {
+    // -DNAME or -DNAME=value
+    Define { name: String, value: String },
+    // -UNAME
+    Undefine { name: String },
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub enum ColorChoice {
+    // Only when stderr is a terminal.
+    #[default]
+    Auto,
+    // -fcolor-diagnostics
+    Always,
+    // -fno-color-diagnostics
+    Never,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub enum DiagnosticsFormat {
+    // -fdiagnostics-format=text
+    #[default]
+    Text,
+    // -fdiagnostics-format=json
+    Json,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub enum OptLevel {
+    // -O0
+    #[default]
+    O0,
+    // -O1 or -O
+    O1,
+    // -O2
+    O2,
+    // -Os, which is -O2 without what makes the code larger.
+    Os,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Options {
+    pub c_code: String,
+    // In command-line order, since a later -D/-U overrides an earlier one.
+    pub macro_definitions: Vec<MacroDefinition>,
+    // -E
+    pub preprocess_only: bool,
+    // -dM
+    pub dump_macros: bool,
+    pub color: ColorChoice,
+    pub diagnostics_format: DiagnosticsFormat,
+    // -W<name>, -Wno-<name>, -Wall, -Wextra, -Werror and -Werror=<name>
+    pub warnings: WarningOptions,
+    // -fsigned-char, -funsigned-char, -fPIC and -fno-PIC
+    pub target: Target,
+    pub opt_level: OptLevel,
+    // -ftime-report
+    pub time_report: bool,
+    // -fdump-ir-after=<pass>, which may be given for several passes.
+    pub dump_ir_after: Vec<Pass>,
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Self::default();
+        let mut c_code = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-E" => options.preprocess_only = true,
+                "-dM" => options.dump_macros = true,
+                "-fcolor-diagnostics" => options.color = ColorChoice::Always,
+                "-fno-color-diagnostics" => options.color = ColorChoice::Never,
+                "-fdiagnostics-format=text" => options.diagnostics_format = DiagnosticsFormat::Text,
+                "-fdiagnostics-format=json" => options.diagnostics_format = DiagnosticsFormat::Json,
+                "-fsigned-char" => options.target.unsigned_char = false,
+                "-funsigned-char" => options.target.unsigned_char = true,
+                "-fPIC" | "-fpic" => options.target.pic = true,
+                "-fno-PIC" | "-fno-pic" => options.target.pic = false,
+                "-O0" => options.opt_level = OptLevel::O0,
+                "-O" | "-O1" => options.opt_level = OptLevel::O1,
+                "-O2" => options.opt_level = OptLevel::O2,
+                "-Os" => options.opt_level = OptLevel::Os,
+                "-ftime-report" => options.time_report = true,
+                "-D" | "-U" => match args.next() {
+                    Some(value) => options.push_macro_definition(&arg, &value)?,
+                    None => bail!("Missing macro name after {arg}"),
+                },
+                _ if arg.starts_with("-D") || arg.starts_with("-U") => {
+                    let (flag, value) = arg.split_at(2);
+                    options.push_macro_definition(flag, value)?;
+                }
+                _ if arg.starts_with("-fdump-ir-after=") => {
+                    let name = &arg["-fdump-ir-after=".len()..];
+                    match Pass::from_name(name) {
+                        Some(pass) => options.dump_ir_after.push(pass),
+                        None => bail!("Unknown pass: {name}"),
+                    }
+                }
+                _ if arg.starts_with("-W") => options.warnings.apply_flag(&arg)?,
+                _ if arg.starts_with('-') && arg.len() > 1 => bail!("Unknown option: {arg}"),
+                _ => {
+                    if c_code.is_some() {
+                        bail!("Please provide only one C program");
+                    }
+                    c_code = Some(arg);
+                }
+            }
+        }
+
+        options.c_code = match c_code {
+            Some(c_code) => c_code,
+            // `kanic -dM -E` only prints the predefined macros, so no program is needed.
+            None if options.preprocess_only && options.dump_macros => String::new(),
+            None => bail!("Please provide a C program"),
+        };
+
+        Ok(options)
+    }
+
+    fn push_macro_definition(&mut self, flag: &str, value: &str) -> Result<()> {
+        let (name, value) = match (flag, value.split_once('=')) {
+            ("-D", Some((name, value))) => (name, value),
+            ("-D", None) => (value, "1"),
+            _ => (value, ""),
+        };
+
+        if !is_identifier(name) {
+            bail!("Macro name must be an identifier: {name:?}");
+        }
+
+        self.macro_definitions.push(match flag {
+            "-D" => MacroDefinition::Define {
+                name: name.to_string(),
+                value: value.to_string(),
+            },
+            _ => MacroDefinition::Undefine {
+                name: name.to_string(),
+            },
+        });
+
+        Ok(())
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some('a'..='z' | 'A'..='Z' | '_'))
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_ok_c_code_only() -> Result<()> {
+        assert_eq!(
+            parse(&["return 1;"])?,
+            Options {
+                c_code: "return 1;".to_string(),
+                ..Default::default()
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_macro_definitions() -> Result<()> {
+        let options = parse(&["-DFOO", "-DBAR=2", "-D", "BAZ=a + b", "-UFOO", "return 1;"])?;
+
+        assert_eq!(
+            options.macro_definitions,
+            vec![
+                MacroDefinition::Define {
+                    name: "FOO".to_string(),
+                    value: "1".to_string()
+                },
+                MacroDefinition::Define {
+                    name: "BAR".to_string(),
+                    value: "2".to_string()
+                },
+                MacroDefinition::Define {
+                    name: "BAZ".to_string(),
+                    value: "a + b".to_string()
+                },
+                MacroDefinition::Undefine {
+                    name: "FOO".to_string()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_dump_macros_without_c_code() -> Result<()> {
+        let options = parse(&["-dM", "-E"])?;
+
+        assert!(options.dump_macros);
+        assert!(options.preprocess_only);
+        assert_eq!(options.c_code, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_diagnostics_options() -> Result<()> {
+        let options = parse(&["-fno-color-diagnostics", "-fdiagnostics-format=json", "1;"])?;
+
+        assert_eq!(options.color, ColorChoice::Never);
+        assert_eq!(options.diagnostics_format, DiagnosticsFormat::Json);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_warning_options() -> Result<()> {
+        let options = parse(&["-Wall", "-Wno-unused-value", "-Werror=parentheses", "1;"])?;
+
+        let mut expected = WarningOptions::default();
+        expected.apply_flag("-Wall")?;
+        expected.apply_flag("-Wno-unused-value")?;
+        expected.apply_flag("-Werror=parentheses")?;
+        assert_eq!(options.warnings, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_char_signedness() -> Result<()> {
+        assert!(parse(&["-funsigned-char", "1;"])?.target.unsigned_char);
+        assert!(
+            !parse(&["-funsigned-char", "-fsigned-char", "1;"])?
+                .target
+                .unsigned_char
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_pic() -> Result<()> {
+        assert!(parse(&["-fPIC", "1;"])?.target.pic);
+        assert!(!parse(&["-fpic", "-fno-pic", "1;"])?.target.pic);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_optimization_options() -> Result<()> {
+        assert_eq!(parse(&["1;"])?.opt_level, OptLevel::O0);
+        assert_eq!(parse(&["-O", "1;"])?.opt_level, OptLevel::O1);
+        assert_eq!(parse(&["-O2", "-Os", "1;"])?.opt_level, OptLevel::Os);
+
+        let options = parse(&[
+            "-O2",
+            "-ftime-report",
+            "-fdump-ir-after=gvn",
+            "-fdump-ir-after=peephole",
+            "1;",
+        ])?;
+        assert_eq!(options.opt_level, OptLevel::O2);
+        assert!(options.time_report);
+        assert_eq!(options.dump_ir_after, vec![Pass::Gvn, Pass::Peephole]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ng_unknown_pass() {
+        assert!(parse(&["-fdump-ir-after=inline", "1;"]).is_err());
+    }
+
+    #[test]
+    fn test_ng_unknown_warning_option() {
+        assert!(parse(&["-Wfoo", "1;"]).is_err());
+    }
+
+    #[test]
+    fn test_ng_unknown_diagnostics_format() {
+        assert!(parse(&["-fdiagnostics-format=xml", "1;"]).is_err());
+    }
+
+    #[test]
+    fn test_ng_invalid_macro_name() {
+        assert!(parse(&["-D1FOO", "return 1;"]).is_err());
+        assert!(parse(&["-U", "return 1;"]).is_err());
+    }
+
+    #[test]
+    fn test_ng_unknown_option() {
+        assert!(parse(&["-Z", "return 1;"]).is_err());
+    }
+}
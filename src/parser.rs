@@ -1,36 +1,56 @@
 use crate::{
-    error_reporter,
-    lexer::{Symbol, Token, TokenKind, Tokens},
+    diagnostic::{Diagnostic, DiagnosticEngine},
+    lexer::{IntSuffix, Symbol, Token, TokenKind, Tokens},
+    source_map::Span,
+    types::{Qualifiers, Type},
 };
 
 use core::fmt;
-use std::{collections::HashMap, iter::Peekable};
+use std::{iter::Peekable, rc::Rc};
 
-#[derive(Debug, PartialEq)]
-pub enum Node {
-    Num(i64),
-    LocalVar {
-        // Local variable address = RBP - offset
-        offset: usize,
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+// Spans are ignored so that trees can be compared by their structure alone.
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    Num {
+        value: u64,
+        suffix: IntSuffix,
     },
+    Str(String),
+    Var {
+        name: String,
+    },
+    // An operand which couldn't be lexed, or a statement which couldn't be parsed, already
+    // reported.
+    Error,
     Ret {
-        value: NodeChild,
+        // None for `return;`
+        value: Option<NodeChild>,
+    },
+    Block {
+        stmts: Vec<Node>,
     },
     If {
-        label: String,
         cond: NodeChild,
         then: NodeChild,
         else_: Option<NodeChild>,
     },
     While {
-        start_label: String,
-        end_label: String,
         cond: NodeChild,
         then: NodeChild,
     },
     For {
-        start_label: String,
-        end_label: String,
         init: Option<NodeChild>,
         cond: Option<NodeChild>,
         update: Option<NodeChild>,
@@ -46,82 +66,486 @@ pub enum Node {
         lhs: NodeChild,
         rhs: NodeChild,
     },
+    // `lhs op= rhs`
+    CompoundAssign {
+        value: ArithOp,
+        lhs: NodeChild,
+        rhs: NodeChild,
+    },
+    Unary {
+        value: UnaryOp,
+        operand: NodeChild,
+    },
+    // `base[index]`
+    Index {
+        base: NodeChild,
+        index: NodeChild,
+    },
+    // `operand.member`, or `operand->member` if `arrow`.
+    Member {
+        operand: NodeChild,
+        member: Ident,
+        arrow: bool,
+    },
+    // `(ty) operand`
+    Cast {
+        ty: TypeName,
+        operand: NodeChild,
+    },
+    // `(ty){ ... }`
+    CompoundLiteral {
+        ty: TypeName,
+        init: Box<Initializer>,
+    },
+    // `sizeof operand`, whose operand isn't evaluated.
+    SizeofExpr {
+        operand: NodeChild,
+    },
+    // `sizeof(ty)`
+    SizeofType {
+        ty: TypeName,
+    },
+    // `_Alignof(ty)`
+    Alignof {
+        ty: TypeName,
+    },
+    // `_Generic(control, ty: value, ..., default: value)`, which is the value whose type is
+    // compatible with the type of `control`. `control` isn't evaluated.
+    Generic {
+        control: NodeChild,
+        associations: Vec<GenericAssociation>,
+    },
+    // `__builtin_offsetof(ty, member.member[index])`, where the first designator is a member.
+    Offsetof {
+        ty: TypeName,
+        designators: Vec<Designator>,
+    },
+    // `__builtin_types_compatible_p(lhs, rhs)`
+    TypesCompatible {
+        lhs: TypeName,
+        rhs: TypeName,
+    },
+    // `_Static_assert(cond, "message")`, at file or block scope or in a struct.
+    StaticAssert {
+        cond: NodeChild,
+        message: String,
+    },
+    // `asm("template" : outputs : inputs : clobbers)` at file scope or in a function. Basic asm,
+    // without any `:`, is emitted as is.
+    Asm {
+        template: Ident,
+        // With `:`, so that the template has operands like `%0`.
+        is_extended: bool,
+        outputs: Vec<AsmOperand>,
+        inputs: Vec<AsmOperand>,
+        clobbers: Vec<Ident>,
+    },
+    // `callee(args)`, where `callee` is a function or a pointer to one.
+    Call {
+        callee: NodeChild,
+        args: Vec<Node>,
+    },
+    // `({ stmts })`, whose value is that of its last statement if it's an expression.
+    StmtExpr {
+        body: NodeChild,
+    },
+    Decl {
+        // Also defines the struct in it, if there are no declarators.
+        declspec: TypeName,
+        storage: Option<StorageClass>,
+        // `_Thread_local`, which goes with `static` or `extern`, or alone at file scope.
+        is_thread_local: bool,
+        // Of the variables declared, which are aligned to the strictest.
+        alignas: Vec<Alignas>,
+        declarators: Vec<Declarator>,
+    },
+    Function {
+        // Only `static` or `extern` at file scope.
+        storage: Option<StorageClass>,
+        return_type: TypeName,
+        ident: Ident,
+        params: Vec<Param>,
+        // `...` after the parameters.
+        is_variadic: bool,
+        // Including `_Noreturn` and `inline`, and those of the specifiers.
+        attributes: Vec<Attribute>,
+        // The name in `__asm__("name")`, which the function has in the assembly.
+        asm_label: Option<Ident>,
+        // None for a declaration without a body.
+        body: Option<NodeChild>,
+    },
 }
 
 type NodeChild = Box<Node>;
 
-impl Node {
-    fn num(value: i64) -> Self {
-        Self::Num(value)
+/// A name with where it's written.
+#[derive(Debug, Clone)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+// Like for `Node`, the span doesn't take part in comparisons.
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// A type as written. Array lengths are expressions, and struct tags are resolved by sema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeName {
+    // `void`, `char`, `short`, `int` or `long`.
+    Base(Type),
+    Pointer(Box<TypeName>),
+    Array {
+        element: Box<TypeName>,
+        // None for `[]`.
+        len: Option<NodeChild>,
+    },
+    Struct(Rc<StructSpec>),
+    Qualified {
+        qualifiers: Qualifiers,
+        ty: Box<TypeName>,
+    },
+    Function {
+        return_type: Box<TypeName>,
+        params: Vec<Param>,
+        is_variadic: bool,
+    },
+    // `typeof(expr)`, the type of an expression which isn't evaluated. `typeof(ty)` is just `ty`.
+    Typeof(NodeChild),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageClass {
+    Static,
+    Register,
+    Auto,
+    Extern,
+}
+
+impl fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                StorageClass::Static => "static",
+                StorageClass::Register => "register",
+                StorageClass::Auto => "auto",
+                StorageClass::Extern => "extern",
+            }
+        )
     }
+}
+
+/// `struct tag { members }` or `union tag { members }`, shared by every declarator of the
+/// declaration it's in.
+#[derive(Debug, PartialEq)]
+pub struct StructSpec {
+    pub is_union: bool,
+    pub tag: Option<Ident>,
+    // None for a use of the tag, without braces.
+    pub members: Option<Vec<MemberDecl>>,
+    // The `_Static_assert`s among the members.
+    pub static_asserts: Vec<Node>,
+    // After `struct` or the closing brace, like `packed`.
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MemberDecl {
+    pub ty: TypeName,
+    // Only a bit-field, or a struct or union whose members are found through it, can be unnamed.
+    pub ident: Option<Ident>,
+    // The width of a bit-field, after `:`.
+    pub width: Option<Node>,
+    pub alignas: Vec<Alignas>,
+    pub attributes: Vec<Attribute>,
+    // The specifiers, which are all there is to an anonymous struct or union.
+    pub span: Span,
+}
+
+/// `_Alignas(align)` or `_Alignas(ty)`, which is like `_Alignas(_Alignof(ty))`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alignas {
+    Expr(Node),
+    Type { ty: TypeName, span: Span },
+}
+
+/// `ty: value` or `default: value` in `_Generic`, where `span` is the type or `default`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericAssociation {
+    // None for `default`.
+    pub ty: Option<TypeName>,
+    pub value: Node,
+    pub span: Span,
+}
+
+/// `ident __asm__("label") __attribute__((...)) = init` in a declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declarator {
+    pub ty: TypeName,
+    pub ident: Ident,
+    pub init: Option<Initializer>,
+    // Including those of the specifiers, and `_Noreturn` and `inline` on a function.
+    pub attributes: Vec<Attribute>,
+    pub asm_label: Option<Ident>,
+}
+
+/// `name` or `name(args)` in `__attribute__((...))`, where `__name__` is the same as `name`. The
+/// arguments are expressions, so that identifiers like `printf` in `format` are variables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: Ident,
+    pub args: Vec<Node>,
+}
+
+/// `[name] "constraint" (expr)`, an operand of an extended asm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmOperand {
+    pub name: Option<Ident>,
+    pub constraint: Ident,
+    pub expr: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub ty: TypeName,
+    // Only `register`.
+    pub storage: Option<StorageClass>,
+    // Parameters of a function declaration don't need a name.
+    pub ident: Option<Ident>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Initializer {
+    Expr(Node),
+    // `{ item, ... }`
+    List { items: Vec<InitItem>, span: Span },
+}
+
+/// `designators = value` in an initializer list. Without designators, there is no `=`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitItem {
+    pub designators: Vec<Designator>,
+    pub value: Initializer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Designator {
+    // `[index]`
+    Index(Node),
+    // `.member`
+    Member(Ident),
+}
 
-    fn local_var(offset: usize) -> Self {
-        Self::LocalVar { offset }
+impl Attribute {
+    // An attribute without arguments, like the one `_Noreturn` stands for.
+    fn new(name: &str, span: Span) -> Self {
+        Self {
+            name: Ident {
+                name: name.to_string(),
+                span,
+            },
+            args: vec![],
+        }
     }
+}
 
-    fn ret(child: Node) -> Self {
-        Node::Ret {
-            value: Box::new(child),
+impl Alignas {
+    pub fn span(&self) -> Span {
+        match self {
+            Alignas::Expr(node) => node.span,
+            Alignas::Type { span, .. } => *span,
         }
     }
+}
 
-    fn if_(label: String, cond: Node, then: Node, else_: Option<Node>) -> Self {
-        Self::If {
-            label,
-            cond: Box::new(cond),
-            then: Box::new(then),
-            else_: else_.map(Box::new),
+impl Designator {
+    pub fn span(&self) -> Span {
+        match self {
+            Designator::Index(node) => node.span,
+            Designator::Member(ident) => ident.span,
         }
     }
+}
 
-    fn while_(start_label: String, end_label: String, cond: Node, then: Node) -> Self {
-        Self::While {
-            start_label,
-            end_label,
-            cond: Box::new(cond),
-            then: Box::new(then),
+impl Initializer {
+    pub fn span(&self) -> Span {
+        match self {
+            Initializer::Expr(node) => node.span,
+            Initializer::List { span, .. } => *span,
         }
     }
+}
+
+impl Node {
+    fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    fn num(value: u64, suffix: IntSuffix, span: Span) -> Self {
+        Self::new(NodeKind::Num { value, suffix }, span)
+    }
+
+    fn var(name: String, span: Span) -> Self {
+        Self::new(NodeKind::Var { name }, span)
+    }
+
+    fn ret(child: Option<Node>, span: Span) -> Self {
+        Self::new(
+            NodeKind::Ret {
+                value: child.map(Box::new),
+            },
+            span,
+        )
+    }
+
+    fn block(stmts: Vec<Node>, span: Span) -> Self {
+        Self::new(NodeKind::Block { stmts }, span)
+    }
+
+    fn if_(cond: Node, then: Node, else_: Option<Node>, span: Span) -> Self {
+        Self::new(
+            NodeKind::If {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                else_: else_.map(Box::new),
+            },
+            span,
+        )
+    }
+
+    fn while_(cond: Node, then: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::While {
+                cond: Box::new(cond),
+                then: Box::new(then),
+            },
+            span,
+        )
+    }
 
     fn for_(
-        start_label: String,
-        end_label: String,
         init: Option<Node>,
         cond: Option<Node>,
         update: Option<Node>,
         then: Node,
+        span: Span,
     ) -> Self {
-        Self::For {
-            start_label,
-            end_label,
-            init: init.map(Box::new),
-            cond: cond.map(Box::new),
-            update: update.map(Box::new),
-            then: Box::new(then),
-        }
+        Self::new(
+            NodeKind::For {
+                init: init.map(Box::new),
+                cond: cond.map(Box::new),
+                update: update.map(Box::new),
+                then: Box::new(then),
+            },
+            span,
+        )
     }
 
-    fn arith_op(value: ArithOp, lhs: Node, rhs: Node) -> Self {
-        Self::ArithOp {
-            value,
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
-        }
+    fn arith_op(value: ArithOp, lhs: Node, rhs: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::ArithOp {
+                value,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span,
+        )
     }
 
-    fn cmp_op(value: CmpOp, lhs: Node, rhs: Node) -> Self {
-        Self::CmpOp {
-            value,
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
-        }
+    fn cmp_op(value: CmpOp, lhs: Node, rhs: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::CmpOp {
+                value,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span,
+        )
+    }
+
+    fn compound_assign(value: ArithOp, lhs: Node, rhs: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::CompoundAssign {
+                value,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span,
+        )
+    }
+
+    fn cast(ty: TypeName, operand: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::Cast {
+                ty,
+                operand: Box::new(operand),
+            },
+            span,
+        )
+    }
+
+    fn sizeof_expr(operand: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::SizeofExpr {
+                operand: Box::new(operand),
+            },
+            span,
+        )
+    }
+
+    fn unary(value: UnaryOp, operand: Node, span: Span) -> Self {
+        Self::new(
+            NodeKind::Unary {
+                value,
+                operand: Box::new(operand),
+            },
+            span,
+        )
+    }
+
+    fn call(callee: Node, args: Vec<Node>, span: Span) -> Self {
+        Self::new(
+            NodeKind::Call {
+                callee: Box::new(callee),
+                args,
+            },
+            span,
+        )
+    }
+
+    fn decl(
+        declspec: TypeName,
+        storage: Option<StorageClass>,
+        is_thread_local: bool,
+        alignas: Vec<Alignas>,
+        declarators: Vec<Declarator>,
+        span: Span,
+    ) -> Self {
+        Self::new(
+            NodeKind::Decl {
+                declspec,
+                storage,
+                is_thread_local,
+                alignas,
+                declarators,
+            },
+            span,
+        )
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum CmpOp {
     Lt,
     Lte,
+    Gt,
+    Gte,
     Eq,
     Neq,
 }
@@ -134,6 +558,8 @@ impl fmt::Display for CmpOp {
             match self {
                 CmpOp::Lt => "setl",
                 CmpOp::Lte => "setle",
+                CmpOp::Gt => "setg",
+                CmpOp::Gte => "setge",
                 CmpOp::Eq => "sete",
                 CmpOp::Neq => "setne",
             }
@@ -146,6 +572,8 @@ impl From<&TokenKind> for CmpOp {
         match value {
             TokenKind::Symbol(Symbol::Lt) => Self::Lt,
             TokenKind::Symbol(Symbol::Lte) => Self::Lte,
+            TokenKind::Symbol(Symbol::Gt) => Self::Gt,
+            TokenKind::Symbol(Symbol::Gte) => Self::Gte,
             TokenKind::Symbol(Symbol::Eq) => Self::Eq,
             TokenKind::Symbol(Symbol::Neq) => Self::Neq,
             _ => {
@@ -155,7 +583,7 @@ impl From<&TokenKind> for CmpOp {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ArithOp {
     Add,
     Sub,
@@ -194,496 +622,3001 @@ impl From<&TokenKind> for ArithOp {
     }
 }
 
-struct ParserContext {
-    local_variables: HashMap<String, LocalVariable>,
-    current_label_number: usize,
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    Plus,
+    Neg,
+    // `&`
+    Addr,
+    // `*`
+    Deref,
+    PreInc,
+    PreDec,
+    PostInc,
+    PostDec,
 }
 
-impl ParserContext {
-    fn new() -> Self {
-        Self {
-            local_variables: HashMap::new(),
-            current_label_number: 0,
-        }
-    }
+struct ParserContext<'a> {
+    diags: &'a mut DiagnosticEngine,
+    // Where "Unexpected EOF" is reported: just past the last token.
+    eof_span: Span,
 }
 
-impl ParserContext {
-    fn new_label(&mut self) -> String {
-        let label = format!(".L{}", self.current_label_number);
-        self.current_label_number += 1;
-        label
-    }
-
-    fn stack_size(&self) -> usize {
-        self.local_variables.len() * 8
+impl<'a> ParserContext<'a> {
+    fn new(diags: &'a mut DiagnosticEngine, eof_span: Span) -> Self {
+        Self { diags, eof_span }
     }
 }
 
-struct LocalVariable {
-    offset: usize,
-}
-
-pub struct ParseResult {
-    pub nodes: Vec<Node>,
-    pub stack_size: usize,
-}
-
-pub fn parse(tokens: Tokens) -> ParseResult {
+// The diagnostic has already been emitted. The caller recovers by synchronizing.
+struct SyntaxError;
+
+/// Parses the whole program into function definitions, declarations and top-level statements.
+/// Syntax errors are reported to `diags`, and parsing resumes at the next statement, so a
+/// statement with errors is only an error node.
+pub fn parse(tokens: Tokens, diags: &mut DiagnosticEngine) -> Vec<Node> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    let eof_span = tokens.last().map_or_else(Span::default, |token| {
+        Span::new(token.span.file, token.span.end, token.span.end)
+    });
     let mut tokens = tokens.into_iter().peekable();
 
-    let mut parser_context = ParserContext::new();
-    let nodes = program(&mut tokens, &mut parser_context);
-
-    ParseResult {
-        nodes,
-        stack_size: parser_context.stack_size(),
-    }
+    let mut parser_context = ParserContext::new(diags, eof_span);
+    program(&mut tokens, &mut parser_context)
 }
 
-fn program<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Vec<Node>
+fn program<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Vec<Node>
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
     let mut nodes = vec![];
 
-    while tokens.peek().is_some() {
-        nodes.push(stmt(tokens, ctx));
+    loop {
+        skip_extension(tokens);
+        let Some(token) = tokens.peek() else {
+            break;
+        };
+        if token.value == TokenKind::Symbol(Symbol::RBrace) {
+            let span = tokens.next().unwrap().span;
+            ctx.diags.emit(
+                Diagnostic::error(span, "Unmatched '}'")
+                    .with_fix_it(span, "")
+                    .with_help("Remove this '}'"),
+            );
+            continue;
+        }
+
+        if is_type_name(token) {
+            match external_declaration(tokens, ctx) {
+                Ok(node) => nodes.push(node),
+                Err(SyntaxError) => synchronize(tokens),
+            }
+        } else {
+            nodes.push(stmt_or_error(tokens, ctx));
+        }
     }
 
     nodes
 }
 
-fn stmt<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+// Skips tokens up to the end of the current statement: past the next `;` or block at the same
+// nesting level, or up to (but not past) the `}` closing the enclosing block.
+fn synchronize<I>(tokens: &mut Peekable<I>)
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
-    let node = match tokens.peek() {
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::Ret),
-            ..
-        }) => {
-            tokens.next().unwrap();
-            Node::ret(expr(tokens, ctx))
-        }
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::If),
-            ..
-        }) => {
-            tokens.next().unwrap();
-
-            consume(tokens, TokenKind::Symbol(Symbol::LParen));
-
-            let cond = expr(tokens, ctx);
+    let mut depth = 0;
 
-            consume(tokens, TokenKind::Symbol(Symbol::RParen));
-
-            let then = stmt(tokens, ctx);
-
-            let else_ = match tokens.peek() {
-                Some(Token {
-                    value: TokenKind::Symbol(Symbol::Else),
-                    ..
-                }) => {
-                    tokens.next().unwrap();
-                    Some(stmt(tokens, ctx))
+    while let Some(token) = tokens.peek() {
+        match token.value {
+            TokenKind::Symbol(Symbol::SemiColon) if depth == 0 => {
+                tokens.next();
+                return;
+            }
+            TokenKind::Symbol(Symbol::LBrace) => depth += 1,
+            TokenKind::Symbol(Symbol::RBrace) => {
+                if depth == 0 {
+                    return;
                 }
-                _ => None,
-            };
 
-            return Node::if_(ctx.new_label(), cond, then, else_);
+                depth -= 1;
+                if depth == 0 {
+                    tokens.next();
+                    return;
+                }
+            }
+            _ => {}
         }
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::While),
-            ..
-        }) => {
-            tokens.next().unwrap();
 
-            consume(tokens, TokenKind::Symbol(Symbol::LParen));
+        tokens.next();
+    }
+}
 
-            let cond = expr(tokens, ctx);
+fn is_type_name(token: &Token) -> bool {
+    matches!(
+        token.value,
+        TokenKind::Symbol(
+            Symbol::Void
+                | Symbol::Char
+                | Symbol::Short
+                | Symbol::Int
+                | Symbol::Long
+                | Symbol::Bool
+                | Symbol::Signed
+                | Symbol::Unsigned
+                | Symbol::Struct
+                | Symbol::Union
+                | Symbol::Noreturn
+                | Symbol::Inline
+                | Symbol::Alignas
+                | Symbol::Atomic
+                | Symbol::Attribute
+                | Symbol::Typeof
+                | Symbol::Const
+                | Symbol::Volatile
+                | Symbol::Restrict
+                | Symbol::Static
+                | Symbol::Register
+                | Symbol::Auto
+                | Symbol::Extern
+                | Symbol::ThreadLocal
+        )
+    )
+}
 
-            consume(tokens, TokenKind::Symbol(Symbol::RParen));
+fn is_symbol(token: Option<&Token>, symbol: Symbol) -> bool {
+    token.is_some_and(|token| token.value == TokenKind::Symbol(symbol))
+}
 
-            let then = stmt(tokens, ctx);
+// The specifiers of a declaration, like `static const long int`.
+struct DeclSpec {
+    ty: TypeName,
+    storage: Option<(StorageClass, Span)>,
+    // Where `_Thread_local` is, if it's there.
+    thread_local: Option<Span>,
+    alignas: Vec<Alignas>,
+    // Where `_Noreturn` and `inline` are, if they're there.
+    noreturn: Option<Span>,
+    inline: Option<Span>,
+    // Those of every declarator.
+    attributes: Vec<Attribute>,
+    span: Span,
+}
 
-            return Node::while_(ctx.new_label(), ctx.new_label(), cond, then);
+impl DeclSpec {
+    // Reports `_Noreturn` and `inline` where no function is declared.
+    fn check_noreturn(&self, ctx: &mut ParserContext) {
+        if let Some(span) = self.noreturn {
+            ctx.diags
+                .error(span, "'_Noreturn' can only appear on functions");
         }
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::For),
-            ..
-        }) => {
-            tokens.next().unwrap();
-
-            consume(tokens, TokenKind::Symbol(Symbol::LParen));
-
-            let init = match tokens.peek() {
-                Some(Token {
-                    value: TokenKind::Symbol(Symbol::SemiColon),
-                    ..
-                }) => None,
-                _ => Some(expr(tokens, ctx)),
-            };
-            consume(tokens, TokenKind::Symbol(Symbol::SemiColon));
-
-            let cond = match tokens.peek() {
-                Some(Token {
-                    value: TokenKind::Symbol(Symbol::SemiColon),
-                    ..
-                }) => None,
-                _ => Some(expr(tokens, ctx)),
-            };
-            consume(tokens, TokenKind::Symbol(Symbol::SemiColon));
+        if let Some(span) = self.inline {
+            ctx.diags
+                .error(span, "'inline' can only appear on functions");
+        }
+    }
 
-            let update = match tokens.peek() {
-                Some(Token {
-                    value: TokenKind::Symbol(Symbol::RParen),
-                    ..
-                }) => None,
-                _ => Some(expr(tokens, ctx)),
-            };
-            consume(tokens, TokenKind::Symbol(Symbol::RParen));
+    // Gives `declarator` the attributes of the specifiers before its own, and `noreturn` for
+    // `_Noreturn` if it's a function.
+    fn attribute(&self, declarator: &mut Declarator) {
+        let mut attributes = self.attributes.clone();
+        if let (Some(span), TypeName::Function { .. }) = (self.noreturn, &declarator.ty) {
+            attributes.push(Attribute::new("noreturn", span));
+        }
+        attributes.append(&mut declarator.attributes);
+        declarator.attributes = attributes;
+    }
 
-            let then = stmt(tokens, ctx);
+    // Reports `_Alignas` where it can't be, like on a function.
+    fn check_alignas(&self, ctx: &mut ParserContext, message: &str) {
+        if let Some(alignas) = self.alignas.first() {
+            ctx.diags.error(alignas.span(), message);
+        }
+    }
 
-            return Node::for_(ctx.new_label(), ctx.new_label(), init, cond, update, then);
+    // Reports `_Thread_local` where no variable is declared.
+    fn check_thread_local(&self, ctx: &mut ParserContext) {
+        if let Some(span) = self.thread_local {
+            ctx.diags.error(span, THREAD_LOCAL_ON_NON_VARIABLE);
         }
-        _ => expr(tokens, ctx),
-    };
+    }
 
-    consume(tokens, TokenKind::Symbol(Symbol::SemiColon));
+    // A declaration of nothing but what its specifiers define, like `struct P { int x; };`.
+    fn declaration(self, ctx: &mut ParserContext, end: Span) -> Node {
+        self.check_noreturn(ctx);
+        self.check_alignas(ctx, ALIGNAS_ON_NOTHING);
+        self.check_thread_local(ctx);
+        let storage = self.storage.map(|(storage, _)| storage);
+        Node::decl(self.ty, storage, false, vec![], vec![], self.span.to(end))
+    }
 
-    node
+    // Reports a storage class where only `allowed` ones can be, like in a type name.
+    fn check_storage(
+        &self,
+        ctx: &mut ParserContext,
+        allowed: &[StorageClass],
+        message: &str,
+    ) -> Option<StorageClass> {
+        let (storage, span) = self.storage?;
+        if !allowed.contains(&storage) {
+            ctx.diags.error(span, message);
+        }
+        Some(storage)
+    }
 }
 
-fn expr<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+// A function definition or declaration, or a declaration of variables.
+fn external_declaration<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Node, SyntaxError>
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
-    assign(tokens, ctx)
-}
+    let spec = declspec(tokens, ctx)?;
+    if is_symbol(tokens.peek(), Symbol::SemiColon) {
+        let end = tokens.next().unwrap().span;
+        return Ok(spec.declaration(ctx, end));
+    }
 
-fn assign<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
-where
-    I: Iterator<Item = Token<'a>>,
-{
-    let mut node = equality(tokens, ctx);
+    let mut declarator = declarator(tokens, ctx, spec.ty.clone())?;
+    spec.attribute(&mut declarator);
 
-    while let Some(token) = tokens.peek() {
-        match token.value {
-            TokenKind::Symbol(Symbol::Assign) => {
-                tokens.next().unwrap();
-                node = Node::arith_op(ArithOp::Assign, node, assign(tokens, ctx));
-            }
-            _ => {
-                break;
-            }
-        }
+    // A function definition, or a declaration of only one function.
+    if matches!(declarator.ty, TypeName::Function { .. }) {
+        let storage = spec.check_storage(
+            ctx,
+            &[StorageClass::Static, StorageClass::Extern],
+            "Illegal storage class on function",
+        );
+
+        let (body, end) = if is_symbol(tokens.peek(), Symbol::LBrace) {
+            let body = compound_stmt(tokens, ctx)?;
+            let end = body.span;
+            (Some(Box::new(body)), end)
+        } else if is_symbol(tokens.peek(), Symbol::SemiColon) {
+            (None, tokens.next().unwrap().span)
+        } else {
+            // More declarators follow, like in `int f(void), g(void);`.
+            return declaration_rest(tokens, ctx, spec, declarator);
+        };
+        spec.check_alignas(ctx, ALIGNAS_ON_FUNCTION);
+        spec.check_thread_local(ctx);
+
+        let Declarator {
+            ty:
+                TypeName::Function {
+                    return_type,
+                    params,
+                    is_variadic,
+                },
+            ident,
+            attributes,
+            asm_label,
+            ..
+        } = declarator
+        else {
+            unreachable!()
+        };
+        return Ok(Node::new(
+            NodeKind::Function {
+                storage,
+                return_type: *return_type,
+                ident,
+                params,
+                is_variadic,
+                attributes,
+                asm_label,
+                body,
+            },
+            spec.span.to(end),
+        ));
     }
 
-    node
+    spec.check_storage(
+        ctx,
+        &[StorageClass::Static, StorageClass::Extern],
+        "Illegal storage class on file-scoped variable",
+    );
+    declaration_rest(tokens, ctx, spec, declarator)
 }
 
-fn equality<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+// The parameters of a function declarator after its `(`, up to and including the `)`, and whether
+// they end with `...`.
+fn params<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<(Vec<Param>, bool), SyntaxError>
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
-    let mut node = relational(tokens, ctx);
+    let mut params = vec![];
+    let mut is_variadic = false;
+    while !is_symbol(tokens.peek(), Symbol::RParen) {
+        if !params.is_empty() {
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+        }
 
-    while let Some(token) = tokens.peek() {
-        match token.value {
-            TokenKind::Symbol(Symbol::Eq | Symbol::Neq) => {
-                node = Node::cmp_op(
-                    CmpOp::from(&tokens.next().unwrap().value),
-                    node,
-                    relational(tokens, ctx),
-                )
-            }
-            _ => {
-                break;
+        if let Some(token) =
+            tokens.next_if(|token| token.value == TokenKind::Symbol(Symbol::Ellipsis))
+        {
+            if params.is_empty() {
+                ctx.diags
+                    .error(token.span, "ISO C requires a named parameter before '...'");
             }
+            is_variadic = true;
+            break;
+        }
+
+        let spec = declspec(tokens, ctx)?;
+        let storage = spec.check_storage(
+            ctx,
+            &[StorageClass::Register],
+            "Invalid storage class specifier in function declarator",
+        );
+        spec.check_noreturn(ctx);
+        spec.check_alignas(
+            ctx,
+            "'_Alignas' attribute cannot be applied to a function parameter",
+        );
+        spec.check_thread_local(ctx);
+        let (ty, ident) = derivation(tokens, ctx, Naming::Optional)?.apply(spec.ty);
+        // Attributes of parameters, like `unused`, don't do anything.
+        attributes(tokens, ctx)?;
+
+        // `(void)` means there are no parameters.
+        if params.is_empty()
+            && ty == TypeName::Base(Type::Void)
+            && ident.is_none()
+            && is_symbol(tokens.peek(), Symbol::RParen)
+        {
+            break;
         }
+
+        params.push(Param { ty, storage, ident });
     }
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
 
-    node
+    Ok((params, is_variadic))
 }
 
-fn relational<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+// Type specifiers such as `long int` or `struct P { ... }`, in any order with qualifiers and a
+// storage class.
+fn declspec<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<DeclSpec, SyntaxError>
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
-    let mut node = add(tokens, ctx);
+    let start = match tokens.peek() {
+        Some(token) if is_type_name(token) => token.span,
+        Some(Token { span, .. }) => {
+            ctx.diags.error(*span, "Expected a type");
+            return Err(SyntaxError);
+        }
+        None => {
+            ctx.diags
+                .error(ctx.eof_span, "Expected a type. Unexpected EOF");
+            return Err(SyntaxError);
+        }
+    };
 
-    while let Some(token) = tokens.peek() {
-        match token.value {
-            TokenKind::Symbol(Symbol::Lt | Symbol::Lte) => {
-                node = Node::cmp_op(
-                    CmpOp::from(&tokens.next().unwrap().value),
-                    node,
-                    add(tokens, ctx),
-                )
+    // How many times void, _Bool, char, short, int, long, signed and unsigned appear.
+    let mut counts = [0; 8];
+    let mut ty = None;
+    let mut qualifiers = Qualifiers::default();
+    let mut storage: Option<(StorageClass, Span)> = None;
+    let mut thread_local = None;
+    let mut alignas = vec![];
+    let mut noreturn = None;
+    let mut inline = None;
+    let mut attributes = vec![];
+    let mut end = start;
+    // The last type specifier, which can't be combined with `struct`, `union` or `typeof`.
+    let mut previous = None;
+
+    while let Some(token) = tokens.next_if(is_type_name) {
+        let TokenKind::Symbol(symbol) = token.value else {
+            unreachable!()
+        };
+        end = token.span;
+
+        match symbol {
+            Symbol::Const => qualifiers.is_const = true,
+            Symbol::Volatile => qualifiers.is_volatile = true,
+            Symbol::Restrict => qualifiers.is_restrict = true,
+            // Unless it's `_Atomic(type)`.
+            Symbol::Atomic if !is_symbol(tokens.peek(), Symbol::LParen) => {
+                qualifiers.is_atomic = true
             }
-            TokenKind::Symbol(Symbol::Gt) => {
-                tokens.next().unwrap();
-                node = Node::cmp_op(CmpOp::Lt, add(tokens, ctx), node);
+            Symbol::Noreturn => noreturn = Some(token.span),
+            Symbol::Inline => inline = Some(token.span),
+            Symbol::Attribute => end = attribute_list(tokens, ctx, &mut attributes)?,
+            Symbol::Alignas => {
+                consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+                alignas.push(if tokens.peek().is_some_and(is_type_name) {
+                    let ty = type_name(tokens, ctx)?;
+                    end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+                    Alignas::Type {
+                        ty,
+                        span: token.span.to(end),
+                    }
+                } else {
+                    let align = assign(tokens, ctx)?;
+                    end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+                    Alignas::Expr(align)
+                });
             }
-            TokenKind::Symbol(Symbol::Gte) => {
-                tokens.next().unwrap();
-                node = Node::cmp_op(CmpOp::Lte, add(tokens, ctx), node);
+            // It only goes with `static` and `extern`.
+            Symbol::ThreadLocal => {
+                if let Some((previous @ (StorageClass::Register | StorageClass::Auto), _)) = storage
+                {
+                    ctx.diags.error(
+                        token.span,
+                        format!("Cannot combine with previous '{previous}' declaration specifier"),
+                    );
+                    return Err(SyntaxError);
+                }
+                thread_local = Some(token.span);
             }
-            _ => {
-                break;
+            Symbol::Static | Symbol::Register | Symbol::Auto | Symbol::Extern => {
+                if let Some((previous, _)) = storage {
+                    ctx.diags.error(
+                        token.span,
+                        format!("Cannot combine with previous '{previous}' declaration specifier"),
+                    );
+                    return Err(SyntaxError);
+                }
+                if thread_local.is_some() && matches!(symbol, Symbol::Register | Symbol::Auto) {
+                    ctx.diags.error(
+                        token.span,
+                        "Cannot combine with previous '_Thread_local' declaration specifier",
+                    );
+                    return Err(SyntaxError);
+                }
+                storage = Some((
+                    match symbol {
+                        Symbol::Static => StorageClass::Static,
+                        Symbol::Register => StorageClass::Register,
+                        Symbol::Auto => StorageClass::Auto,
+                        _ => StorageClass::Extern,
+                    },
+                    token.span,
+                ));
+            }
+            _ if matches!(
+                previous,
+                Some(Symbol::Struct | Symbol::Union | Symbol::Typeof | Symbol::Atomic)
+            ) || (matches!(
+                symbol,
+                Symbol::Struct | Symbol::Union | Symbol::Typeof | Symbol::Atomic
+            ) && previous.is_some()) =>
+            {
+                ctx.diags.error(
+                    token.span,
+                    format!(
+                        "Cannot combine with previous '{}' declaration specifier",
+                        previous.unwrap()
+                    ),
+                );
+                return Err(SyntaxError);
+            }
+            Symbol::Struct | Symbol::Union => {
+                let spec = struct_spec(tokens, ctx, symbol == Symbol::Union, token.span)?;
+                end = spec.span;
+                ty = Some(TypeName::Struct(Rc::new(spec)));
+                previous = Some(symbol);
+            }
+            Symbol::Typeof => {
+                consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+                ty = Some(if tokens.peek().is_some_and(is_type_name) {
+                    type_name(tokens, ctx)?
+                } else {
+                    TypeName::Typeof(Box::new(expr(tokens, ctx)?))
+                });
+                end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+                previous = Some(symbol);
+            }
+            Symbol::Atomic => {
+                consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+                let atomic = Qualifiers {
+                    is_atomic: true,
+                    ..Qualifiers::default()
+                };
+                ty = Some(qualified(type_name(tokens, ctx)?, atomic));
+                end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+                previous = Some(symbol);
+            }
+            _ => {
+                counts[match &symbol {
+                    Symbol::Void => 0,
+                    Symbol::Bool => 1,
+                    Symbol::Char => 2,
+                    Symbol::Short => 3,
+                    Symbol::Int => 4,
+                    Symbol::Long => 5,
+                    Symbol::Signed => 6,
+                    _ => 7,
+                }] += 1;
+
+                // `int` is implied by `short`, `long`, `signed` and `unsigned`.
+                ty = Some(TypeName::Base(match counts {
+                    [1, 0, 0, 0, 0, 0, 0, 0] => Type::Void,
+                    [0, 1, 0, 0, 0, 0, 0, 0] => Type::Bool,
+                    [0, 0, 1, 0, 0, 0, 0, 0] => Type::Char,
+                    [0, 0, 1, 0, 0, 0, 1, 0] => Type::SChar,
+                    [0, 0, 1, 0, 0, 0, 0, 1] => Type::UChar,
+                    [0, 0, 0, 1, 0 | 1, 0, 0 | 1, 0] => Type::Short,
+                    [0, 0, 0, 1, 0 | 1, 0, 0, 1] => Type::UShort,
+                    [0, 0, 0, 0, 0 | 1, 0, 0 | 1, 0] => Type::Int,
+                    [0, 0, 0, 0, 0 | 1, 0, 0, 1] => Type::UInt,
+                    [0, 0, 0, 0, 0 | 1, 1, 0 | 1, 0] => Type::Long,
+                    [0, 0, 0, 0, 0 | 1, 1, 0, 1] => Type::ULong,
+                    [0, 0, 0, 0, 0 | 1, 2, 0 | 1, 0] => Type::LongLong,
+                    [0, 0, 0, 0, 0 | 1, 2, 0, 1] => Type::ULongLong,
+                    _ => {
+                        ctx.diags.error(
+                            token.span,
+                            format!(
+                                "Cannot combine with previous '{}' declaration specifier",
+                                previous.unwrap_or(symbol)
+                            ),
+                        );
+                        return Err(SyntaxError);
+                    }
+                }));
+                previous = Some(symbol);
             }
         }
     }
 
-    node
+    let span = start.to(end);
+    let ty = ty.unwrap_or_else(|| {
+        ctx.diags
+            .error(span, "Type specifier missing, defaults to 'int'");
+        TypeName::Base(Type::Int)
+    });
+
+    Ok(DeclSpec {
+        ty: qualified(ty, qualifiers),
+        storage,
+        thread_local,
+        alignas,
+        noreturn,
+        inline,
+        attributes,
+        span,
+    })
+}
+
+fn qualified(ty: TypeName, qualifiers: Qualifiers) -> TypeName {
+    if qualifiers.is_empty() {
+        ty
+    } else {
+        TypeName::Qualified {
+            qualifiers,
+            ty: Box::new(ty),
+        }
+    }
 }
 
-fn add<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+// `struct tag`, `struct tag { members }` or `struct { members }`, after the `struct` at `start`.
+fn struct_spec<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    is_union: bool,
+    start: Span,
+) -> Result<StructSpec, SyntaxError>
 where
-    I: Iterator<Item = Token<'a>>,
+    I: Iterator<Item = Token>,
 {
-    let mut node = mul(tokens, ctx);
+    let mut attributes = self::attributes(tokens, ctx)?;
+    let tag = match tokens.peek() {
+        Some(Token {
+            value: TokenKind::Ident(_),
+            ..
+        }) => Some(ident(tokens, ctx)?),
+        _ => None,
+    };
+    if let Some(tag) = &tag {
+        if !is_symbol(tokens.peek(), Symbol::LBrace) {
+            return Ok(StructSpec {
+                is_union,
+                span: start.to(tag.span),
+                tag: Some(tag.clone()),
+                members: None,
+                static_asserts: vec![],
+                attributes,
+            });
+        }
+    }
 
-    while let Some(token) = tokens.peek() {
-        match token.value {
-            TokenKind::Symbol(Symbol::Add | Symbol::Sub) => {
-                node = Node::arith_op(
-                    ArithOp::from(&tokens.next().unwrap().value),
-                    node,
-                    mul(tokens, ctx),
-                )
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LBrace))?;
+    let mut members = vec![];
+    let mut static_asserts = vec![];
+    let end = loop {
+        if is_symbol(tokens.peek(), Symbol::RBrace) {
+            break tokens.next().unwrap().span;
+        }
+        if is_symbol(tokens.peek(), Symbol::StaticAssert) {
+            static_asserts.push(static_assert(tokens, ctx)?);
+            continue;
+        }
+        skip_extension(tokens);
+
+        let spec = declspec(tokens, ctx)?;
+        spec.check_storage(ctx, &[], STORAGE_IN_TYPE_NAME);
+        spec.check_noreturn(ctx);
+        spec.check_thread_local(ctx);
+        // An anonymous struct or union, like `union { int i; char c; };`.
+        if is_symbol(tokens.peek(), Symbol::SemiColon) {
+            tokens.next();
+            members.push(MemberDecl {
+                ty: spec.ty,
+                ident: None,
+                width: None,
+                alignas: spec.alignas,
+                attributes: spec.attributes,
+                span: spec.span,
+            });
+            continue;
+        }
+        loop {
+            let mut attributes = spec.attributes.clone();
+            let (ty, ident) = if is_symbol(tokens.peek(), Symbol::Colon) {
+                (spec.ty.clone(), None)
+            } else {
+                let mut declarator = declarator(tokens, ctx, spec.ty.clone())?;
+                attributes.append(&mut declarator.attributes);
+                (declarator.ty, Some(declarator.ident))
+            };
+            let width = if is_symbol(tokens.peek(), Symbol::Colon) {
+                tokens.next();
+                let width = assign(tokens, ctx)?;
+                attributes.append(&mut self::attributes(tokens, ctx)?);
+                Some(width)
+            } else {
+                None
+            };
+            members.push(MemberDecl {
+                ty,
+                ident,
+                width,
+                alignas: spec.alignas.clone(),
+                attributes,
+                span: spec.span,
+            });
+
+            if !is_symbol(tokens.peek(), Symbol::Comma) {
+                break;
             }
-            _ => {
+            tokens.next();
+        }
+        consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+    };
+    attributes.append(&mut self::attributes(tokens, ctx)?);
+
+    Ok(StructSpec {
+        is_union,
+        tag,
+        members: Some(members),
+        static_asserts,
+        attributes,
+        span: start.to(end),
+    })
+}
+
+// `__attribute__((...))`s, if there are any.
+fn attributes<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Vec<Attribute>, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut attributes = vec![];
+    while tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::Attribute))
+        .is_some()
+    {
+        attribute_list(tokens, ctx, &mut attributes)?;
+    }
+    Ok(attributes)
+}
+
+// `((attribute, ...))` after `__attribute__`, returning the span of the last `)`. Attributes can
+// be empty, like in `__attribute__(())`.
+fn attribute_list<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    attributes: &mut Vec<Attribute>,
+) -> Result<Span, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    loop {
+        // Keywords like `const` are also attribute names.
+        let name = match tokens.peek() {
+            Some(Token {
+                value: TokenKind::Ident(_),
+                ..
+            }) => Some(ident(tokens, ctx)?),
+            Some(Token {
+                value: TokenKind::Symbol(symbol),
+                span,
+            }) if symbol
+                .to_string()
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                let name = symbol.to_string();
+                let span = *span;
+                tokens.next();
+                Some(Ident { name, span })
+            }
+            _ => None,
+        };
+
+        if let Some(mut name) = name {
+            if let Some(stripped) = name
+                .name
+                .strip_prefix("__")
+                .and_then(|name| name.strip_suffix("__"))
+            {
+                name.name = stripped.to_string();
+            }
+
+            let mut args = vec![];
+            if tokens
+                .next_if(|token| token.value == TokenKind::Symbol(Symbol::LParen))
+                .is_some()
+            {
+                while !is_symbol(tokens.peek(), Symbol::RParen) {
+                    if !args.is_empty() {
+                        consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+                    }
+                    args.push(assign(tokens, ctx)?);
+                }
+                tokens.next();
+            }
+            attributes.push(Attribute { name, args });
+        }
+
+        if tokens
+            .next_if(|token| token.value == TokenKind::Symbol(Symbol::Comma))
+            .is_none()
+        {
+            break;
+        }
+    }
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))
+}
+
+// `__asm__("label")` after a declarator, which names it in the assembly.
+fn asm_label<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Option<Ident>, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    if tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::Asm))
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let start = consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let label = string_literal(tokens, ctx)?;
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+    Ok(Some(Ident {
+        name: label.name,
+        span: start.to(end),
+    }))
+}
+
+// Adjacent string literals, which must not be empty, as one string.
+fn string_literal<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Ident, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut result: Option<Ident> = None;
+    while let Some(token) = tokens.next_if(|token| matches!(token.value, TokenKind::Str(_))) {
+        let TokenKind::Str(str) = token.value else {
+            unreachable!()
+        };
+        match &mut result {
+            Some(result) => {
+                result.name.push_str(&str);
+                result.span = result.span.to(token.span);
+            }
+            None => {
+                result = Some(Ident {
+                    name: str,
+                    span: token.span,
+                })
+            }
+        }
+    }
+
+    result.ok_or_else(|| {
+        let span = tokens.peek().map_or(ctx.eof_span, |token| token.span);
+        ctx.diags.error(span, "Expected string literal");
+        SyntaxError
+    })
+}
+
+// `asm volatile ("template" : outputs : inputs : clobbers)`, without the `;`.
+fn asm_stmt<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let start = consume(tokens, ctx, TokenKind::Symbol(Symbol::Asm))?;
+    // An asm is never removed or moved, so these don't change anything.
+    while tokens
+        .next_if(|token| {
+            matches!(
+                token.value,
+                TokenKind::Symbol(Symbol::Volatile | Symbol::Inline)
+            )
+        })
+        .is_some()
+    {}
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let template = string_literal(tokens, ctx)?;
+
+    let mut is_extended = false;
+    let mut outputs = vec![];
+    let mut inputs = vec![];
+    for operands in [&mut outputs, &mut inputs] {
+        if tokens
+            .next_if(|token| token.value == TokenKind::Symbol(Symbol::Colon))
+            .is_none()
+        {
+            break;
+        }
+        is_extended = true;
+        if matches!(
+            tokens.peek(),
+            Some(Token {
+                value: TokenKind::Symbol(Symbol::Colon | Symbol::RParen),
+                ..
+            })
+        ) {
+            continue;
+        }
+        loop {
+            operands.push(asm_operand(tokens, ctx)?);
+            if tokens
+                .next_if(|token| token.value == TokenKind::Symbol(Symbol::Comma))
+                .is_none()
+            {
+                break;
+            }
+        }
+    }
+
+    let mut clobbers = vec![];
+    if is_extended
+        && tokens
+            .next_if(|token| token.value == TokenKind::Symbol(Symbol::Colon))
+            .is_some()
+        && !is_symbol(tokens.peek(), Symbol::RParen)
+    {
+        loop {
+            clobbers.push(string_literal(tokens, ctx)?);
+            if tokens
+                .next_if(|token| token.value == TokenKind::Symbol(Symbol::Comma))
+                .is_none()
+            {
                 break;
             }
         }
     }
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    Ok(Node::new(
+        NodeKind::Asm {
+            template,
+            is_extended,
+            outputs,
+            inputs,
+            clobbers,
+        },
+        start.to(end),
+    ))
+}
+
+// `[name] "constraint" (expr)`
+fn asm_operand<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<AsmOperand, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let name = if tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::LBracket))
+        .is_some()
+    {
+        let name = ident(tokens, ctx)?;
+        consume(tokens, ctx, TokenKind::Symbol(Symbol::RBracket))?;
+        Some(name)
+    } else {
+        None
+    };
+    let constraint = string_literal(tokens, ctx)?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let expr = expr(tokens, ctx)?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    Ok(AsmOperand {
+        name,
+        constraint,
+        expr,
+    })
+}
+
+// `__extension__`s, which only silence pedantic warnings, before a declaration or expression.
+fn skip_extension<I>(tokens: &mut Peekable<I>)
+where
+    I: Iterator<Item = Token>,
+{
+    while tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::Extension))
+        .is_some()
+    {}
+}
+
+// `_Static_assert(cond, "message");`
+fn static_assert<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let start = consume(tokens, ctx, TokenKind::Symbol(Symbol::StaticAssert))?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let cond = assign(tokens, ctx)?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+
+    let mut message = String::new();
+    while let Some(token) = tokens.next_if(|token| matches!(token.value, TokenKind::Str(_))) {
+        let TokenKind::Str(str) = token.value else {
+            unreachable!()
+        };
+        message.push_str(&str);
+    }
+    if message.is_empty() && !is_symbol(tokens.peek(), Symbol::RParen) {
+        let span = tokens.peek().map_or(ctx.eof_span, |token| token.span);
+        ctx.diags.error(span, "Expected string literal");
+        return Err(SyntaxError);
+    }
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+    Ok(Node::new(
+        NodeKind::StaticAssert {
+            cond: Box::new(cond),
+            message,
+        },
+        start.to(end),
+    ))
+}
+
+// Whether a declarator has a name: declarations need one, parameters may have one, and type names
+// can't.
+#[derive(Clone, Copy, PartialEq)]
+enum Naming {
+    Required,
+    Optional,
+    Abstract,
+}
+
+// What a declarator derives from the type of its specifiers, as written: `*`s, then the name or a
+// parenthesized declarator, then `[len]`s and `(params)`s. In `int *(*p)[3]`, `(*p)` applies to
+// `int *[3]`, which is only known after it.
+struct Derivation {
+    // Each with its qualifiers, like in `* const`.
+    pointers: Vec<Qualifiers>,
+    nested: Option<Box<Derivation>>,
+    ident: Option<Ident>,
+    suffixes: Vec<Suffix>,
+}
+
+enum Suffix {
+    // `[len]`, where `[]` has no length.
+    Array(Option<NodeChild>),
+    // `(params)` or `(params, ...)`
+    Function(Vec<Param>, bool),
+}
+
+impl Derivation {
+    // The declared type and name, given the type of the specifiers.
+    fn apply(self, ty: TypeName) -> (TypeName, Option<Ident>) {
+        let ty = self.pointers.into_iter().fold(ty, |ty, qualifiers| {
+            qualified(TypeName::Pointer(Box::new(ty)), qualifiers)
+        });
+        // The first suffix is the outermost, like in `int a[2][3]`.
+        let ty = self
+            .suffixes
+            .into_iter()
+            .rev()
+            .fold(ty, |ty, suffix| match suffix {
+                Suffix::Array(len) => TypeName::Array {
+                    element: Box::new(ty),
+                    len,
+                },
+                Suffix::Function(params, is_variadic) => TypeName::Function {
+                    return_type: Box::new(ty),
+                    params,
+                    is_variadic,
+                },
+            });
+
+        match self.nested {
+            Some(nested) => nested.apply(ty),
+            None => (ty, self.ident),
+        }
+    }
+}
+
+fn derivation<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    naming: Naming,
+) -> Result<Derivation, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    // Attributes of the type, like in `(__attribute__((aligned)) *p)`, don't do anything.
+    attributes(tokens, ctx)?;
+    let mut derivation = Derivation {
+        pointers: pointers(tokens, ctx)?,
+        nested: None,
+        ident: None,
+        suffixes: vec![],
+    };
+
+    let is_ident = |token: Option<&Token>| {
+        naming != Naming::Abstract
+            && matches!(
+                token,
+                Some(Token {
+                    value: TokenKind::Ident(_),
+                    ..
+                })
+            )
+    };
+    if is_symbol(tokens.peek(), Symbol::LParen) {
+        tokens.next();
+        // Without a name, `(` can also start the parameters, like in `int (*)(int)`.
+        let next = tokens.peek();
+        if naming == Naming::Required
+            || is_symbol(next, Symbol::Mul)
+            || is_symbol(next, Symbol::LParen)
+            || is_ident(next)
+        {
+            derivation.nested = Some(Box::new(self::derivation(tokens, ctx, naming)?));
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+        } else {
+            let (params, is_variadic) = params(tokens, ctx)?;
+            derivation
+                .suffixes
+                .push(Suffix::Function(params, is_variadic));
+        }
+    } else if naming == Naming::Required || is_ident(tokens.peek()) {
+        derivation.ident = Some(ident(tokens, ctx)?);
+    }
+
+    loop {
+        if is_symbol(tokens.peek(), Symbol::LBracket) {
+            tokens.next();
+            let len = if is_symbol(tokens.peek(), Symbol::RBracket) {
+                None
+            } else {
+                Some(Box::new(assign(tokens, ctx)?))
+            };
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RBracket))?;
+            derivation.suffixes.push(Suffix::Array(len));
+        } else if is_symbol(tokens.peek(), Symbol::LParen) {
+            tokens.next();
+            let (params, is_variadic) = params(tokens, ctx)?;
+            derivation
+                .suffixes
+                .push(Suffix::Function(params, is_variadic));
+        } else {
+            return Ok(derivation);
+        }
+    }
+}
+
+// `*`s before the name of a declarator, each with its qualifiers like in `* const`. Attributes
+// among them are skipped.
+fn pointers<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Vec<Qualifiers>, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut pointers = vec![];
+    while tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::Mul))
+        .is_some()
+    {
+        let mut qualifiers = Qualifiers::default();
+        while let Some(token) = tokens.next_if(|token| {
+            matches!(
+                token.value,
+                TokenKind::Symbol(
+                    Symbol::Const
+                        | Symbol::Volatile
+                        | Symbol::Restrict
+                        | Symbol::Atomic
+                        | Symbol::Attribute
+                )
+            )
+        }) {
+            match token.value {
+                TokenKind::Symbol(Symbol::Const) => qualifiers.is_const = true,
+                TokenKind::Symbol(Symbol::Volatile) => qualifiers.is_volatile = true,
+                TokenKind::Symbol(Symbol::Restrict) => qualifiers.is_restrict = true,
+                TokenKind::Symbol(Symbol::Atomic) => qualifiers.is_atomic = true,
+                _ => {
+                    attribute_list(tokens, ctx, &mut vec![])?;
+                }
+            }
+        }
+
+        pointers.push(qualifiers);
+    }
+
+    Ok(pointers)
+}
+
+// The type and name of a declared variable, function or member, like `*(*p)[3]`, followed by its
+// asm label and attributes.
+fn declarator<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    declspec: TypeName,
+) -> Result<Declarator, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let (ty, ident) = derivation(tokens, ctx, Naming::Required)?.apply(declspec);
+    let asm_label = asm_label(tokens, ctx)?;
+    let attributes = attributes(tokens, ctx)?;
+    Ok(Declarator {
+        ty,
+        ident: ident.expect("A missing name is a syntax error"),
+        init: None,
+        attributes,
+        asm_label,
+    })
+}
+
+// A type without a name, like in casts and `sizeof`: `int *[3]` or `int (*)(int)`.
+fn type_name<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<TypeName, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let spec = declspec(tokens, ctx)?;
+    spec.check_storage(ctx, &[], STORAGE_IN_TYPE_NAME);
+    spec.check_noreturn(ctx);
+    spec.check_alignas(ctx, "'_Alignas' attribute cannot be applied to types");
+    spec.check_thread_local(ctx);
+    Ok(derivation(tokens, ctx, Naming::Abstract)?.apply(spec.ty).0)
+}
+
+const STORAGE_IN_TYPE_NAME: &str = "Type name does not allow storage class to be specified";
+const ALIGNAS_ON_FUNCTION: &str = "'_Alignas' attribute only applies to variables and fields";
+const THREAD_LOCAL_ON_NON_VARIABLE: &str =
+    "'_Thread_local' is only allowed on variable declarations";
+const ALIGNAS_ON_NOTHING: &str = "'_Alignas' attribute cannot be applied to a declaration that \
+                                  does not declare anything";
+
+fn declaration<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let spec = declspec(tokens, ctx)?;
+    if is_symbol(tokens.peek(), Symbol::SemiColon) {
+        let end = tokens.next().unwrap().span;
+        return Ok(spec.declaration(ctx, end));
+    }
+
+    let mut declarator = declarator(tokens, ctx, spec.ty.clone())?;
+    spec.attribute(&mut declarator);
+    // A block-scoped variable is thread-local only if it's not on the stack.
+    if let (Some(span), None | Some((StorageClass::Register | StorageClass::Auto, _))) =
+        (spec.thread_local, spec.storage)
+    {
+        if !matches!(declarator.ty, TypeName::Function { .. }) {
+            ctx.diags
+                .error(span, "'_Thread_local' variables must have global storage");
+        }
+    }
+    declaration_rest(tokens, ctx, spec, declarator)
+}
+
+// The rest of a declaration after the specifiers and the first declarator.
+fn declaration_rest<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    spec: DeclSpec,
+    first: Declarator,
+) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut declarators = vec![];
+    let mut declarator = first;
+
+    loop {
+        if is_symbol(tokens.peek(), Symbol::Assign) {
+            tokens.next();
+            declarator.init = Some(initializer(tokens, ctx)?);
+        }
+        declarators.push(declarator);
+
+        if !is_symbol(tokens.peek(), Symbol::Comma) {
+            break;
+        }
+        tokens.next();
+        declarator = self::declarator(tokens, ctx, spec.ty.clone())?;
+        spec.attribute(&mut declarator);
+    }
+
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+    let (functions, variables): (Vec<_>, Vec<_>) = declarators
+        .iter()
+        .partition(|declarator| matches!(declarator.ty, TypeName::Function { .. }));
+    if !variables.is_empty() {
+        spec.check_noreturn(ctx);
+    }
+    if !functions.is_empty() {
+        spec.check_alignas(ctx, ALIGNAS_ON_FUNCTION);
+        spec.check_thread_local(ctx);
+    }
+
+    let storage = spec.storage.map(|(storage, _)| storage);
+    Ok(Node::decl(
+        spec.ty,
+        storage,
+        spec.thread_local.is_some(),
+        spec.alignas,
+        declarators,
+        spec.span.to(end),
+    ))
+}
+
+// An expression, or `{ items }` with an optional trailing comma.
+fn initializer<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+) -> Result<Initializer, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    if !is_symbol(tokens.peek(), Symbol::LBrace) {
+        return Ok(Initializer::Expr(assign(tokens, ctx)?));
+    }
+    let start = tokens.next().unwrap().span;
+
+    let mut items = vec![];
+    let end = loop {
+        if is_symbol(tokens.peek(), Symbol::RBrace) {
+            break tokens.next().unwrap().span;
+        }
+        if !items.is_empty() {
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+            if is_symbol(tokens.peek(), Symbol::RBrace) {
+                break tokens.next().unwrap().span;
+            }
+        }
+
+        let mut designators = vec![];
+        self::designators(tokens, ctx, &mut designators)?;
+        if !designators.is_empty() {
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::Assign))?;
+        }
+
+        let value = initializer(tokens, ctx)?;
+        items.push(InitItem { designators, value });
+    };
+
+    Ok(Initializer::List {
+        items,
+        span: start.to(end),
+    })
+}
+
+// Any number of `[index]` and `.member`, added to `designators`.
+fn designators<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    designators: &mut Vec<Designator>,
+) -> Result<(), SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    loop {
+        if is_symbol(tokens.peek(), Symbol::LBracket) {
+            tokens.next();
+            designators.push(Designator::Index(assign(tokens, ctx)?));
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RBracket))?;
+        } else if is_symbol(tokens.peek(), Symbol::Dot) {
+            tokens.next();
+            designators.push(Designator::Member(ident(tokens, ctx)?));
+        } else {
+            return Ok(());
+        }
+    }
+}
+
+fn ident<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Ident, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    match tokens.peek() {
+        Some(Token {
+            value: TokenKind::Ident(_),
+            ..
+        }) => {
+            let Some(Token {
+                value: TokenKind::Ident(name),
+                span,
+            }) = tokens.next()
+            else {
+                unreachable!()
+            };
+            Ok(Ident { name, span })
+        }
+        Some(Token { span, .. }) => {
+            ctx.diags.error(*span, "Expected identifier");
+            Err(SyntaxError)
+        }
+        None => {
+            ctx.diags
+                .error(ctx.eof_span, "Expected identifier. Unexpected EOF");
+            Err(SyntaxError)
+        }
+    }
+}
+
+fn compound_stmt<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let start = consume(tokens, ctx, TokenKind::Symbol(Symbol::LBrace))?;
+
+    let mut stmts = vec![];
+    let end = loop {
+        match tokens.peek() {
+            Some(Token {
+                value: TokenKind::Symbol(Symbol::RBrace),
+                ..
+            }) => break tokens.next().unwrap().span,
+            None => {
+                let diagnostic = expected(tokens, ctx, TokenKind::Symbol(Symbol::RBrace))
+                    .with_label(start, "To match this '{'");
+                ctx.diags.emit(diagnostic);
+                return Err(SyntaxError);
+            }
+            Some(_) => stmts.push(stmt_or_error(tokens, ctx)),
+        }
+    };
+
+    Ok(Node::block(stmts, start.to(end)))
+}
+
+// A statement with a syntax error is skipped, but left in the block so that later passes know
+// they don't see all of it.
+fn stmt_or_error<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
+where
+    I: Iterator<Item = Token>,
+{
+    let start = tokens.peek().map_or(ctx.eof_span, |token| token.span);
+    stmt(tokens, ctx).unwrap_or_else(|SyntaxError| {
+        synchronize(tokens);
+        Node::new(NodeKind::Error, start)
+    })
+}
+
+fn stmt<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    skip_extension(tokens);
+    let node = match tokens.peek() {
+        Some(token) if is_type_name(token) => return declaration(tokens, ctx),
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::StaticAssert),
+            ..
+        }) => return static_assert(tokens, ctx),
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Asm),
+            ..
+        }) => asm_stmt(tokens, ctx)?,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Ret),
+            ..
+        }) => {
+            let start = tokens.next().unwrap().span;
+            let value = match tokens.peek() {
+                Some(Token {
+                    value: TokenKind::Symbol(Symbol::SemiColon),
+                    ..
+                }) => None,
+                _ => Some(expr(tokens, ctx)?),
+            };
+            let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+            return Ok(Node::ret(value, start.to(end)));
+        }
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::LBrace),
+            ..
+        }) => return compound_stmt(tokens, ctx),
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::If),
+            ..
+        }) => {
+            let start = tokens.next().unwrap().span;
+
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+
+            let cond = expr(tokens, ctx)?;
+
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+            let then = stmt(tokens, ctx)?;
+
+            let else_ = match tokens.peek() {
+                Some(Token {
+                    value: TokenKind::Symbol(Symbol::Else),
+                    ..
+                }) => {
+                    tokens.next().unwrap();
+                    Some(stmt(tokens, ctx)?)
+                }
+                _ => None,
+            };
+
+            let span = start.to(else_.as_ref().unwrap_or(&then).span);
+            return Ok(Node::if_(cond, then, else_, span));
+        }
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::While),
+            ..
+        }) => {
+            let start = tokens.next().unwrap().span;
+
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+
+            let cond = expr(tokens, ctx)?;
+
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+            let then = stmt(tokens, ctx)?;
+
+            let span = start.to(then.span);
+            return Ok(Node::while_(cond, then, span));
+        }
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::For),
+            ..
+        }) => {
+            let start = tokens.next().unwrap().span;
+
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+
+            let init = match tokens.peek() {
+                Some(Token {
+                    value: TokenKind::Symbol(Symbol::SemiColon),
+                    ..
+                }) => None,
+                _ => Some(expr(tokens, ctx)?),
+            };
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+            let cond = match tokens.peek() {
+                Some(Token {
+                    value: TokenKind::Symbol(Symbol::SemiColon),
+                    ..
+                }) => None,
+                _ => Some(expr(tokens, ctx)?),
+            };
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+            let update = match tokens.peek() {
+                Some(Token {
+                    value: TokenKind::Symbol(Symbol::RParen),
+                    ..
+                }) => None,
+                _ => Some(expr(tokens, ctx)?),
+            };
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+            let then = stmt(tokens, ctx)?;
+
+            let span = start.to(then.span);
+            return Ok(Node::for_(init, cond, update, then, span));
+        }
+        _ => expr(tokens, ctx)?,
+    };
+
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::SemiColon))?;
+
+    Ok(node)
+}
+
+fn expr<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    assign(tokens, ctx)
+}
+
+fn assign<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = equality(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        let op = match token.value {
+            TokenKind::Symbol(Symbol::Assign) => None,
+            TokenKind::Symbol(Symbol::AddAssign) => Some(ArithOp::Add),
+            TokenKind::Symbol(Symbol::SubAssign) => Some(ArithOp::Sub),
+            TokenKind::Symbol(Symbol::MulAssign) => Some(ArithOp::Mul),
+            TokenKind::Symbol(Symbol::DivAssign) => Some(ArithOp::Div),
+            _ => break,
+        };
+
+        tokens.next().unwrap();
+        let rhs = assign(tokens, ctx)?;
+        let span = node.span.to(rhs.span);
+        node = match op {
+            Some(op) => Node::compound_assign(op, node, rhs, span),
+            None => Node::arith_op(ArithOp::Assign, node, rhs, span),
+        };
+    }
+
+    Ok(node)
+}
+
+fn equality<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = relational(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        match token.value {
+            TokenKind::Symbol(Symbol::Eq | Symbol::Neq) => {
+                let op = CmpOp::from(&tokens.next().unwrap().value);
+                let rhs = relational(tokens, ctx)?;
+                let span = node.span.to(rhs.span);
+                node = Node::cmp_op(op, node, rhs, span)
+            }
+            _ => {
+                break;
+            }
+        }
+    }
+
+    Ok(node)
+}
+
+fn relational<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = add(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        match token.value {
+            TokenKind::Symbol(Symbol::Lt | Symbol::Lte | Symbol::Gt | Symbol::Gte) => {
+                let op = CmpOp::from(&tokens.next().unwrap().value);
+                let rhs = add(tokens, ctx)?;
+                let span = node.span.to(rhs.span);
+                node = Node::cmp_op(op, node, rhs, span)
+            }
+            _ => {
+                break;
+            }
+        }
+    }
+
+    Ok(node)
+}
+
+fn add<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = mul(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        match token.value {
+            TokenKind::Symbol(Symbol::Add | Symbol::Sub) => {
+                let op = ArithOp::from(&tokens.next().unwrap().value);
+                let rhs = mul(tokens, ctx)?;
+                let span = node.span.to(rhs.span);
+                node = Node::arith_op(op, node, rhs, span)
+            }
+            _ => {
+                break;
+            }
+        }
+    }
+
+    Ok(node)
+}
+
+fn mul<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = unary(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        match token.value {
+            TokenKind::Symbol(Symbol::Mul | Symbol::Div) => {
+                let op = ArithOp::from(&tokens.next().unwrap().value);
+                let rhs = unary(tokens, ctx)?;
+                let span = node.span.to(rhs.span);
+                node = Node::arith_op(op, node, rhs, span)
+            }
+            _ => {
+                return Ok(node);
+            }
+        }
+    }
+
+    Ok(node)
+}
+
+fn unary<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let op = match tokens.peek() {
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Add),
+            ..
+        }) => UnaryOp::Plus,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Sub),
+            ..
+        }) => UnaryOp::Neg,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Amp),
+            ..
+        }) => UnaryOp::Addr,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Mul),
+            ..
+        }) => UnaryOp::Deref,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Inc),
+            ..
+        }) => UnaryOp::PreInc,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Dec),
+            ..
+        }) => UnaryOp::PreDec,
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Sizeof | Symbol::Alignof),
+            ..
+        }) => return sizeof(tokens, ctx),
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Extension),
+            ..
+        }) => {
+            tokens.next();
+            return unary(tokens, ctx);
+        }
+        _ => return postfix(tokens, ctx),
+    };
+
+    let start = tokens.next().unwrap().span;
+    let operand = unary(tokens, ctx)?;
+    let span = start.to(operand.span);
+
+    Ok(Node::unary(op, operand, span))
+}
+
+// `sizeof unary`, `sizeof(type)` or `_Alignof(type)`.
+fn sizeof<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let Token { value, span: start } = tokens.next().unwrap();
+    let is_sizeof = value == TokenKind::Symbol(Symbol::Sizeof);
+
+    let lparen = match tokens.peek() {
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::LParen),
+            ..
+        }) => tokens.next().unwrap().span,
+        _ if is_sizeof => {
+            let operand = unary(tokens, ctx)?;
+            let span = start.to(operand.span);
+            return Ok(Node::sizeof_expr(operand, span));
+        }
+        _ => consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?,
+    };
+
+    if is_sizeof && !tokens.peek().is_some_and(is_type_name) {
+        let operand = parenthesized(tokens, ctx, lparen)?;
+        let span = start.to(operand.span);
+        return Ok(Node::sizeof_expr(operand, span));
+    }
+
+    let ty = type_name(tokens, ctx)?;
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    let kind = if is_sizeof {
+        NodeKind::SizeofType { ty }
+    } else {
+        NodeKind::Alignof { ty }
+    };
+    Ok(Node::new(kind, start.to(end)))
+}
+
+fn postfix<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = primary(tokens, ctx)?;
+
+    while let Some(token) = tokens.peek() {
+        let op = match token.value {
+            TokenKind::Symbol(Symbol::Inc) => UnaryOp::PostInc,
+            TokenKind::Symbol(Symbol::Dec) => UnaryOp::PostDec,
+            TokenKind::Symbol(Symbol::LBracket) => {
+                tokens.next();
+                let index = expr(tokens, ctx)?;
+                let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RBracket))?;
+
+                let span = node.span.to(end);
+                node = Node::new(
+                    NodeKind::Index {
+                        base: Box::new(node),
+                        index: Box::new(index),
+                    },
+                    span,
+                );
+                continue;
+            }
+            TokenKind::Symbol(Symbol::LParen) => {
+                node = call(tokens, ctx, node)?;
+                continue;
+            }
+            TokenKind::Symbol(Symbol::Dot | Symbol::Arrow) => {
+                let arrow = tokens.next().unwrap().value == TokenKind::Symbol(Symbol::Arrow);
+                let member = ident(tokens, ctx)?;
+
+                let span = node.span.to(member.span);
+                node = Node::new(
+                    NodeKind::Member {
+                        operand: Box::new(node),
+                        member,
+                        arrow,
+                    },
+                    span,
+                );
+                continue;
+            }
+            _ => break,
+        };
+
+        let end = tokens.next().unwrap().span;
+        let span = node.span.to(end);
+        node = Node::unary(op, node, span);
+    }
+
+    Ok(node)
+}
+
+fn primary<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    match tokens.peek() {
+        Some(Token {
+            value:
+                TokenKind::Num(..)
+                | TokenKind::Ident(_)
+                | TokenKind::Str(_)
+                | TokenKind::Error
+                | TokenKind::Symbol(Symbol::LParen),
+            ..
+        }) => {}
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::Generic),
+            ..
+        }) => return generic(tokens, ctx),
+        Some(Token { span, .. }) => {
+            ctx.diags.error(*span, "Invalid token");
+            return Err(SyntaxError);
+        }
+        None => {
+            ctx.diags.error(ctx.eof_span, "Unexpected EOF");
+            return Err(SyntaxError);
+        }
+    }
+
+    match tokens.next().unwrap() {
+        Token {
+            value: TokenKind::Num(num, suffix),
+            span,
+        } => Ok(Node::num(num, suffix, span)),
+        Token {
+            value: TokenKind::Error,
+            span,
+        } => Ok(Node::new(NodeKind::Error, span)),
+        Token {
+            value: TokenKind::Ident(name),
+            span,
+        } => match name.as_str() {
+            "__builtin_offsetof" | "__builtin_types_compatible_p" => {
+                type_builtin(tokens, ctx, &name, span)
+            }
+            _ => Ok(Node::var(name, span)),
+        },
+        Token {
+            value: TokenKind::Str(mut str),
+            mut span,
+        } => {
+            // Adjacent string literals are concatenated.
+            while let Some(token) = tokens.next_if(|token| matches!(token.value, TokenKind::Str(_)))
+            {
+                let TokenKind::Str(next) = token.value else {
+                    unreachable!()
+                };
+                str.push_str(&next);
+                span = span.to(token.span);
+            }
+
+            Ok(Node::new(NodeKind::Str(str), span))
+        }
+        // A statement expression, like `({ int x = f(); x * x; })`.
+        Token { span: start, .. } if is_symbol(tokens.peek(), Symbol::LBrace) => {
+            let body = compound_stmt(tokens, ctx)?;
+            let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+            Ok(Node::new(
+                NodeKind::StmtExpr {
+                    body: Box::new(body),
+                },
+                start.to(end),
+            ))
+        }
+        Token { span: start, .. } if tokens.peek().is_some_and(is_type_name) => {
+            let ty = type_name(tokens, ctx)?;
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+            if is_symbol(tokens.peek(), Symbol::LBrace) {
+                let init = initializer(tokens, ctx)?;
+                let span = start.to(init.span());
+                return Ok(Node::new(
+                    NodeKind::CompoundLiteral {
+                        ty,
+                        init: Box::new(init),
+                    },
+                    span,
+                ));
+            }
+
+            let operand = unary(tokens, ctx)?;
+            let span = start.to(operand.span);
+            Ok(Node::cast(ty, operand, span))
+        }
+        Token { span: start, .. } => parenthesized(tokens, ctx, start),
+    }
+}
+
+// `_Generic(control, ty: value, ..., default: value)`
+fn generic<I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let start = consume(tokens, ctx, TokenKind::Symbol(Symbol::Generic))?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let control = assign(tokens, ctx)?;
+
+    let mut associations = vec![];
+    while tokens
+        .next_if(|token| token.value == TokenKind::Symbol(Symbol::Comma))
+        .is_some()
+    {
+        let (ty, span) = match tokens.peek() {
+            Some(Token {
+                value: TokenKind::Symbol(Symbol::Default),
+                span,
+            }) => {
+                let span = *span;
+                tokens.next();
+                (None, span)
+            }
+            Some(Token { span, .. }) => {
+                let span = *span;
+                (Some(type_name(tokens, ctx)?), span)
+            }
+            None => {
+                ctx.diags
+                    .error(ctx.eof_span, "Expected a type. Unexpected EOF");
+                return Err(SyntaxError);
+            }
+        };
+        consume(tokens, ctx, TokenKind::Symbol(Symbol::Colon))?;
+        let value = assign(tokens, ctx)?;
+        associations.push(GenericAssociation { ty, value, span });
+    }
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    Ok(Node::new(
+        NodeKind::Generic {
+            control: Box::new(control),
+            associations,
+        },
+        start.to(end),
+    ))
+}
+
+// A builtin taking a type, which can't be parsed as a call, after its name at `start`. The others
+// are calls to names sema recognizes.
+fn type_builtin<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    name: &str,
+    start: Span,
+) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+    let ty = type_name(tokens, ctx)?;
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+
+    let kind = if name == "__builtin_offsetof" {
+        let mut designators = vec![Designator::Member(ident(tokens, ctx)?)];
+        self::designators(tokens, ctx, &mut designators)?;
+        NodeKind::Offsetof { ty, designators }
+    } else {
+        NodeKind::TypesCompatible {
+            lhs: ty,
+            rhs: type_name(tokens, ctx)?,
+        }
+    };
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    Ok(Node::new(kind, start.to(end)))
+}
+
+// `( expr )` after the `(` at `start`.
+fn parenthesized<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    start: Span,
+) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    let mut node = expr(tokens, ctx)?;
+
+    let end = match tokens.peek() {
+        Some(Token {
+            value: TokenKind::Symbol(Symbol::RParen),
+            ..
+        }) => tokens.next().unwrap().span,
+        Some(token) if token.value == TokenKind::Error => return Err(SyntaxError),
+        _ => {
+            let diagnostic = expected(tokens, ctx, TokenKind::Symbol(Symbol::RParen))
+                .with_label(start, "To match this '('");
+            ctx.diags.emit(diagnostic);
+            return Err(SyntaxError);
+        }
+    };
+
+    node.span = start.to(end);
+    Ok(node)
+}
+
+// `(args)` after `callee`.
+fn call<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    callee: Node,
+) -> Result<Node, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    consume(tokens, ctx, TokenKind::Symbol(Symbol::LParen))?;
+
+    let mut args = vec![];
+    while !is_symbol(tokens.peek(), Symbol::RParen) {
+        if !args.is_empty() {
+            consume(tokens, ctx, TokenKind::Symbol(Symbol::Comma))?;
+        }
+        args.push(assign(tokens, ctx)?);
+    }
+    let end = consume(tokens, ctx, TokenKind::Symbol(Symbol::RParen))?;
+
+    let span = callee.span.to(end);
+    Ok(Node::call(callee, args, span))
+}
+
+// The error for the next token not being `expected_token_kind`, suggesting to insert it.
+fn expected<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &ParserContext,
+    expected_token_kind: TokenKind,
+) -> Diagnostic
+where
+    I: Iterator<Item = Token>,
+{
+    let diagnostic = match tokens.peek() {
+        Some(Token { span, .. }) => {
+            Diagnostic::error(*span, format!("Expected '{expected_token_kind}'"))
+        }
+        None => Diagnostic::error(
+            ctx.eof_span,
+            format!("Expected '{expected_token_kind}'. Unexpected EOF"),
+        ),
+    };
+
+    let insertion = Span::new(
+        diagnostic.span.file,
+        diagnostic.span.start,
+        diagnostic.span.start,
+    );
+    diagnostic.with_fix_it(insertion, expected_token_kind.to_string())
+}
+
+// Consumes the next token, which must be `expected_token_kind`, and returns its span.
+fn consume<I>(
+    tokens: &mut Peekable<I>,
+    ctx: &mut ParserContext,
+    expected_token_kind: TokenKind,
+) -> Result<Span, SyntaxError>
+where
+    I: Iterator<Item = Token>,
+{
+    match tokens.peek() {
+        Some(token) if token.value == expected_token_kind => Ok(tokens.next().unwrap().span),
+        // The lexer has already reported it.
+        Some(token) if token.value == TokenKind::Error => Err(SyntaxError),
+        // The unexpected token is left for `synchronize`, in case it ends the statement.
+        _ => {
+            let diagnostic = expected(tokens, ctx, expected_token_kind);
+            ctx.diags.emit(diagnostic);
+            Err(SyntaxError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{lexer, source_map::SourceMap};
+
+    use super::*;
+
+    fn parse_str(c_code: &str) -> Result<Vec<Node>> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", c_code);
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = lexer::tokenize(&source_map, file, &mut diags);
+        let nodes = parse(tokens, &mut diags);
+
+        Ok(diags.finish(nodes)?.0)
+    }
+
+    fn parse_errors(c_code: &str) -> Vec<(usize, String)> {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("<input>", c_code);
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = lexer::tokenize(&source_map, file, &mut diags);
+        let nodes = parse(tokens, &mut diags);
+
+        diags
+            .finish(nodes)
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|diagnostic| (diagnostic.span.start, diagnostic.message))
+            .collect()
+    }
+
+    // Spans don't take part in comparisons, so the expected trees use a dummy one.
+
+    fn num(value: u64) -> Node {
+        Node::num(value, IntSuffix::default(), Span::default())
+    }
+
+    fn var(name: &str) -> Node {
+        Node::var(name.to_string(), Span::default())
+    }
+
+    fn ident(name: &str) -> Ident {
+        Ident {
+            name: name.to_string(),
+            span: Span::default(),
+        }
+    }
+
+    fn ret(child: Node) -> Node {
+        Node::ret(Some(child), Span::default())
+    }
+
+    fn block(stmts: Vec<Node>) -> Node {
+        Node::block(stmts, Span::default())
+    }
+
+    fn if_(cond: Node, then: Node, else_: Option<Node>) -> Node {
+        Node::if_(cond, then, else_, Span::default())
+    }
+
+    fn while_(cond: Node, then: Node) -> Node {
+        Node::while_(cond, then, Span::default())
+    }
+
+    fn for_(init: Option<Node>, cond: Option<Node>, update: Option<Node>, then: Node) -> Node {
+        Node::for_(init, cond, update, then, Span::default())
+    }
+
+    fn arith_op(value: ArithOp, lhs: Node, rhs: Node) -> Node {
+        Node::arith_op(value, lhs, rhs, Span::default())
+    }
+
+    fn cmp_op(value: CmpOp, lhs: Node, rhs: Node) -> Node {
+        Node::cmp_op(value, lhs, rhs, Span::default())
+    }
+
+    fn unary(value: UnaryOp, operand: Node) -> Node {
+        Node::unary(value, operand, Span::default())
+    }
+
+    fn decl(ty: Type, declarators: Vec<Declarator>) -> Node {
+        Node::decl(
+            TypeName::Base(ty),
+            None,
+            false,
+            vec![],
+            declarators,
+            Span::default(),
+        )
+    }
+
+    fn declarator(ty: Type, name: &str, init: Option<Node>) -> Declarator {
+        Declarator {
+            ty: TypeName::Base(ty),
+            ident: ident(name),
+            init: init.map(Initializer::Expr),
+            attributes: vec![],
+            asm_label: None,
+        }
+    }
+
+    fn function(return_type: Type, name: &str, params: Vec<Param>, body: Option<Node>) -> Node {
+        Node::new(
+            NodeKind::Function {
+                storage: None,
+                return_type: TypeName::Base(return_type),
+                ident: ident(name),
+                params,
+                is_variadic: false,
+                attributes: vec![],
+                asm_label: None,
+                body: body.map(Box::new),
+            },
+            Span::default(),
+        )
+    }
+
+    #[test]
+    fn test_ok_parse_single() -> Result<()> {
+        let actual = parse_str("1;")?;
+
+        assert_eq!(actual, vec![num(1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_parse_complex() -> Result<()> {
+        let actual = parse_str("(+1 + -2) * 3 - 4 / 5;")?;
+
+        assert_eq!(
+            actual,
+            vec![arith_op(
+                ArithOp::Sub,
+                arith_op(
+                    ArithOp::Mul,
+                    arith_op(
+                        ArithOp::Add,
+                        unary(UnaryOp::Plus, num(1)),
+                        unary(UnaryOp::Neg, num(2))
+                    ),
+                    num(3),
+                ),
+                arith_op(ArithOp::Div, num(4), num(5))
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_with_cmp() -> Result<()> {
+        let actual = parse_str("(1 + 2 * 3 > 4) != (5 < 6 == 7 >= 8);")?;
+
+        assert_eq!(
+            actual,
+            vec![cmp_op(
+                CmpOp::Neq,
+                cmp_op(
+                    CmpOp::Gt,
+                    arith_op(ArithOp::Add, num(1), arith_op(ArithOp::Mul, num(2), num(3))),
+                    num(4)
+                ),
+                cmp_op(
+                    CmpOp::Eq,
+                    cmp_op(CmpOp::Lt, num(5), num(6)),
+                    cmp_op(CmpOp::Gte, num(7), num(8))
+                )
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_with_assign() -> Result<()> {
+        let actual = parse_str("a = 1 + 2 * 3; bar = a; return bar;")?;
+
+        assert_eq!(
+            actual,
+            vec![
+                arith_op(
+                    ArithOp::Assign,
+                    var("a"),
+                    arith_op(ArithOp::Add, num(1), arith_op(ArithOp::Mul, num(2), num(3)))
+                ),
+                arith_op(ArithOp::Assign, var("bar"), var("a")),
+                ret(var("bar"))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_if() -> Result<()> {
+        let actual = parse_str("if (1) return 2; else return 3;")?;
+
+        assert_eq!(actual, vec![if_(num(1), ret(num(2)), Some(ret(num(3))))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_while() -> Result<()> {
+        let actual = parse_str("while (1) return 2;")?;
+
+        assert_eq!(actual, vec![while_(num(1), ret(num(2)))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_for() -> Result<()> {
+        let actual = parse_str("for (i = 0; i < 10; i = i + 1) return i;")?;
+
+        assert_eq!(
+            actual,
+            vec![for_(
+                Some(arith_op(ArithOp::Assign, var("i"), num(0))),
+                Some(cmp_op(CmpOp::Lt, var("i"), num(10))),
+                Some(arith_op(
+                    ArithOp::Assign,
+                    var("i"),
+                    arith_op(ArithOp::Add, var("i"), num(1))
+                )),
+                ret(var("i"))
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_spans() -> Result<()> {
+        let actual = parse_str("a = 1;\nreturn (a + 2) * 3;")?;
+
+        let spans: Vec<_> = actual
+            .iter()
+            .map(|node| (node.span.start, node.span.end))
+            .collect();
+        assert_eq!(spans, vec![(0, 5), (7, 26)]);
+
+        let NodeKind::Ret { value: Some(value) } = &actual[1].kind else {
+            panic!("Must be Ret: {:?}", actual[1]);
+        };
+        assert_eq!((value.span.start, value.span.end), (14, 25));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_block() -> Result<()> {
+        let actual = parse_str("{ 1; { } 2; }")?;
+
+        assert_eq!(actual, vec![block(vec![num(1), block(vec![]), num(2)])]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_declarations() -> Result<()> {
+        let actual = parse_str("long int a = 1, b; { char c; short int d = a; }")?;
+
+        assert_eq!(
+            actual,
+            vec![
+                decl(
+                    Type::Long,
+                    vec![
+                        declarator(Type::Long, "a", Some(num(1))),
+                        declarator(Type::Long, "b", None),
+                    ]
+                ),
+                block(vec![
+                    decl(Type::Char, vec![declarator(Type::Char, "c", None)]),
+                    decl(
+                        Type::Short,
+                        vec![declarator(Type::Short, "d", Some(var("a")))]
+                    ),
+                ]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_compound_assignment() -> Result<()> {
+        let actual = parse_str("a += b -= 2; -a++--; ++-b;")?;
+
+        assert_eq!(
+            actual,
+            vec![
+                Node::compound_assign(
+                    ArithOp::Add,
+                    var("a"),
+                    Node::compound_assign(ArithOp::Sub, var("b"), num(2), Span::default()),
+                    Span::default()
+                ),
+                unary(
+                    UnaryOp::Neg,
+                    unary(UnaryOp::PostDec, unary(UnaryOp::PostInc, var("a")))
+                ),
+                unary(UnaryOp::PreInc, unary(UnaryOp::Neg, var("b"))),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_casts_and_sizeof() -> Result<()> {
+        let actual =
+            parse_str("(long)-a; sizeof a + 1; sizeof(a); sizeof(short int); _Alignof(char);")?;
+
+        assert_eq!(
+            actual,
+            vec![
+                Node::cast(
+                    TypeName::Base(Type::Long),
+                    unary(UnaryOp::Neg, var("a")),
+                    Span::default()
+                ),
+                arith_op(
+                    ArithOp::Add,
+                    Node::sizeof_expr(var("a"), Span::default()),
+                    num(1)
+                ),
+                Node::sizeof_expr(var("a"), Span::default()),
+                Node::new(
+                    NodeKind::SizeofType {
+                        ty: TypeName::Base(Type::Short)
+                    },
+                    Span::default()
+                ),
+                Node::new(
+                    NodeKind::Alignof {
+                        ty: TypeName::Base(Type::Char)
+                    },
+                    Span::default()
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_functions() -> Result<()> {
+        let actual = parse_str("int f(void); long g(int a, char) { return f(); } g(1, 2);")?;
+
+        assert_eq!(
+            actual,
+            vec![
+                function(Type::Int, "f", vec![], None),
+                function(
+                    Type::Long,
+                    "g",
+                    vec![
+                        Param {
+                            ty: TypeName::Base(Type::Int),
+                            storage: None,
+                            ident: Some(ident("a")),
+                        },
+                        Param {
+                            ty: TypeName::Base(Type::Char),
+                            storage: None,
+                            ident: None,
+                        },
+                    ],
+                    Some(block(vec![ret(Node::call(
+                        var("f"),
+                        vec![],
+                        Span::default()
+                    ))]))
+                ),
+                Node::call(var("g"), vec![num(1), num(2)], Span::default()),
+            ]
+        );
 
-    node
-}
+        Ok(())
+    }
 
-fn mul<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
-where
-    I: Iterator<Item = Token<'a>>,
-{
-    let mut node = unary(tokens, ctx);
+    #[test]
+    fn test_ok_declarators() -> Result<()> {
+        let actual = parse_str("char *p[2], **q; struct P { int x, *y; } s; struct P;")?;
+        let pointer = |ty| TypeName::Pointer(Box::new(ty));
 
-    while let Some(token) = tokens.peek() {
-        match token.value {
-            TokenKind::Symbol(Symbol::Mul | Symbol::Div) => {
-                node = Node::arith_op(
-                    ArithOp::from(&tokens.next().unwrap().value),
-                    node,
-                    unary(tokens, ctx),
-                )
-            }
-            _ => {
-                return node;
+        let NodeKind::Decl { declarators, .. } = &actual[0].kind else {
+            panic!("Must be a declaration: {:?}", actual[0]);
+        };
+        assert_eq!(
+            declarators[0].ty,
+            TypeName::Array {
+                element: Box::new(pointer(TypeName::Base(Type::Char))),
+                len: Some(Box::new(num(2))),
             }
-        }
+        );
+        assert_eq!(
+            declarators[1].ty,
+            pointer(pointer(TypeName::Base(Type::Char)))
+        );
+
+        let NodeKind::Decl {
+            declspec: TypeName::Struct(spec),
+            declarators,
+            ..
+        } = &actual[1].kind
+        else {
+            panic!("Must be a struct declaration: {:?}", actual[1]);
+        };
+        let members = spec.members.as_ref().unwrap();
+        assert_eq!(spec.tag, Some(ident("P")));
+        assert_eq!(members[1].ty, pointer(TypeName::Base(Type::Int)));
+        assert_eq!(declarators[0].ident, ident("s"));
+
+        assert!(matches!(
+            &actual[2].kind,
+            NodeKind::Decl { declspec: TypeName::Struct(spec), declarators, .. }
+                if spec.members.is_none() && declarators.is_empty()
+        ));
+
+        Ok(())
     }
 
-    node
-}
+    #[test]
+    fn test_ok_function_pointers() -> Result<()> {
+        let actual =
+            parse_str("int (*ops[2])(int, char **); int (*pick(int))(void); (*ops[0])(1, 0);")?;
+        let pointer = |ty| TypeName::Pointer(Box::new(ty));
+        let function = |return_type, params: Vec<TypeName>| TypeName::Function {
+            return_type: Box::new(return_type),
+            is_variadic: false,
+            params: params
+                .into_iter()
+                .map(|ty| Param {
+                    ty,
+                    storage: None,
+                    ident: None,
+                })
+                .collect(),
+        };
+        let int = || TypeName::Base(Type::Int);
+
+        let NodeKind::Decl { declarators, .. } = &actual[0].kind else {
+            panic!("Must be a declaration: {:?}", actual[0]);
+        };
+        let handler = pointer(function(
+            int(),
+            vec![int(), pointer(pointer(TypeName::Base(Type::Char)))],
+        ));
+        assert_eq!(
+            declarators[0].ty,
+            TypeName::Array {
+                element: Box::new(handler),
+                len: Some(Box::new(num(2))),
+            }
+        );
 
-fn unary<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
-where
-    I: Iterator<Item = Token<'a>>,
-{
-    match tokens.peek() {
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::Add),
-            ..
-        }) => {
-            tokens.next().unwrap();
-            primary(tokens, ctx)
-        }
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::Sub),
+        let NodeKind::Function {
+            return_type,
+            ident: name,
+            params,
+            body: None,
             ..
-        }) => {
-            tokens.next().unwrap();
-            Node::arith_op(ArithOp::Sub, Node::num(0), primary(tokens, ctx))
-        }
-        _ => primary(tokens, ctx),
+        } = &actual[1].kind
+        else {
+            panic!("Must be a function declaration: {:?}", actual[1]);
+        };
+        assert_eq!(*name, ident("pick"));
+        assert_eq!(*return_type, pointer(function(int(), vec![])));
+        assert_eq!(params.len(), 1);
+
+        let NodeKind::Call { callee, args } = &actual[2].kind else {
+            panic!("Must be a call: {:?}", actual[2]);
+        };
+        assert!(matches!(
+            callee.kind,
+            NodeKind::Unary {
+                value: UnaryOp::Deref,
+                ..
+            }
+        ));
+        assert_eq!(*args, vec![num(1), num(0)]);
+
+        Ok(())
     }
-}
 
-fn primary<'a, I>(tokens: &mut Peekable<I>, ctx: &mut ParserContext) -> Node
-where
-    I: Iterator<Item = Token<'a>>,
-{
-    match tokens.next() {
-        Some(Token {
-            value: TokenKind::Num(num),
-            ..
-        }) => Node::num(num),
-        Some(Token {
-            value: TokenKind::Ident(ident),
-            ..
-        }) => {
-            let offset = {
-                let current_max_offset = ctx.local_variables.len() * 8;
-
-                ctx.local_variables
-                    .entry(ident.to_string())
-                    .or_insert_with(|| LocalVariable {
-                        offset: current_max_offset + 8,
-                    })
-                    .offset
-            };
-            Node::local_var(offset)
-        }
-        Some(Token {
-            value: TokenKind::Symbol(Symbol::LParen),
+    #[test]
+    fn test_ok_qualifiers_and_storage_classes() -> Result<()> {
+        let actual = parse_str("static const int *volatile p; int f(int register r);")?;
+        let qualifiers = |is_const, is_volatile| Qualifiers {
+            is_const,
+            is_volatile,
+            ..Qualifiers::default()
+        };
+
+        let NodeKind::Decl {
+            declspec,
+            storage,
+            declarators,
             ..
-        }) => {
-            let node = expr(tokens, ctx);
+        } = &actual[0].kind
+        else {
+            panic!("Must be a declaration: {:?}", actual[0]);
+        };
+        let const_int = TypeName::Qualified {
+            qualifiers: qualifiers(true, false),
+            ty: Box::new(TypeName::Base(Type::Int)),
+        };
+        assert_eq!(*declspec, const_int);
+        assert_eq!(*storage, Some(StorageClass::Static));
+        assert_eq!(
+            declarators[0].ty,
+            TypeName::Qualified {
+                qualifiers: qualifiers(false, true),
+                ty: Box::new(TypeName::Pointer(Box::new(const_int))),
+            }
+        );
 
-            consume(tokens, TokenKind::Symbol(Symbol::RParen));
+        assert!(matches!(
+            &actual[1].kind,
+            NodeKind::Function { params, .. }
+                if params[0].storage == Some(StorageClass::Register)
+        ));
 
-            node
-        }
-        token => {
-            invalid_token(token, None);
-        }
+        Ok(())
     }
-}
 
-fn invalid_token(token: Option<Token>, message: Option<&str>) -> ! {
-    match token {
-        Some(Token { metadata, .. }) => {
-            error_reporter::report(
-                metadata.user_input,
-                metadata.code_location,
-                match message {
-                    Some(message) => message,
-                    None => "Invalid token",
+    #[test]
+    fn test_ok_atomic() -> Result<()> {
+        let actual = parse_str("_Atomic int a; _Atomic(long) *volatile b; int *_Atomic c;")?;
+        let atomic = |ty| TypeName::Qualified {
+            qualifiers: Qualifiers {
+                is_atomic: true,
+                ..Qualifiers::default()
+            },
+            ty: Box::new(ty),
+        };
+        let types: Vec<_> = actual
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::Decl { declarators, .. } => declarators[0].ty.clone(),
+                _ => panic!("Must be a declaration: {node:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            types,
+            [
+                atomic(TypeName::Base(Type::Int)),
+                TypeName::Qualified {
+                    qualifiers: Qualifiers {
+                        is_volatile: true,
+                        ..Qualifiers::default()
+                    },
+                    ty: Box::new(TypeName::Pointer(Box::new(atomic(TypeName::Base(
+                        Type::Long
+                    ))))),
                 },
-            );
-        }
-        None => match message {
-            Some(message) => panic!("{}", message),
-            None => panic!("Unexpected EOF"),
-        },
+                atomic(TypeName::Pointer(Box::new(TypeName::Base(Type::Int)))),
+            ]
+        );
+        assert_eq!(
+            parse_errors("_Atomic(int) long a;"),
+            [(
+                13,
+                "Cannot combine with previous '_Atomic' declaration specifier".to_string()
+            )]
+        );
+
+        Ok(())
     }
-}
 
-fn consume<'a, I>(tokens: &mut Peekable<I>, expected_token_kind: TokenKind)
-where
-    I: Iterator<Item = Token<'a>>,
-{
-    let actual_token = match tokens.next() {
-        Some(token) => token,
-        None => {
-            invalid_token(
-                None,
-                Some(format!("Must be {:?}. Unexpected EOF", expected_token_kind).as_str()),
-            );
-        }
-    };
+    #[test]
+    fn test_ok_thread_local() -> Result<()> {
+        let actual = parse_str("_Thread_local int a; static __thread int b; extern int c;")?;
+        let decls: Vec<_> = actual
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::Decl {
+                    storage,
+                    is_thread_local,
+                    ..
+                } => (*storage, *is_thread_local),
+                _ => panic!("Must be a declaration: {node:?}"),
+            })
+            .collect();
 
-    if actual_token.value != expected_token_kind {
-        invalid_token(
-            Some(actual_token),
-            Some(format!("Must be {:?}", expected_token_kind).as_str()),
+        assert_eq!(
+            decls,
+            [
+                (None, true),
+                (Some(StorageClass::Static), true),
+                (Some(StorageClass::Extern), false)
+            ]
         );
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use anyhow::Result;
+    #[test]
+    fn test_ok_integer_types() -> Result<()> {
+        let actual = parse_str(
+            "unsigned a; long long int b; signed char c; _Bool d; short unsigned int e; \
+             long unsigned long f; signed g; 1ul;",
+        )?;
+        let declspecs: Vec<_> = actual
+            .iter()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Decl { declspec, .. } => Some(declspec.clone()),
+                _ => None,
+            })
+            .collect();
 
-    use crate::lexer;
+        assert_eq!(
+            declspecs,
+            [
+                Type::UInt,
+                Type::LongLong,
+                Type::SChar,
+                Type::Bool,
+                Type::UShort,
+                Type::ULongLong,
+                Type::Int
+            ]
+            .map(TypeName::Base)
+        );
+        assert_eq!(
+            actual[7],
+            Node::num(
+                1,
+                IntSuffix {
+                    is_unsigned: true,
+                    longs: 1
+                },
+                Span::default()
+            )
+        );
 
-    use super::*;
+        Ok(())
+    }
 
     #[test]
-    fn test_ok_parse_single() -> Result<()> {
-        let tokens = lexer::tokenize("1;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ok_bit_fields() -> Result<()> {
+        let actual = parse_str("struct { unsigned a : 3, : 0; int *p, b : 1 + 1; } s;")?;
 
-        assert_eq!(actual, vec![Node::num(1)]);
+        let NodeKind::Decl {
+            declspec: TypeName::Struct(spec),
+            ..
+        } = &actual[0].kind
+        else {
+            panic!("Must be a struct declaration: {:?}", actual[0]);
+        };
+        let members: Vec<_> = spec
+            .members
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|member| (member.ident.clone(), member.width.clone()))
+            .collect();
+        assert_eq!(
+            members,
+            [
+                (Some(ident("a")), Some(num(3))),
+                (None, Some(num(0))),
+                (Some(ident("p")), None),
+                (
+                    Some(ident("b")),
+                    Some(arith_op(ArithOp::Add, num(1), num(1)))
+                ),
+            ]
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_ok_parse_complex() -> Result<()> {
-        let tokens = lexer::tokenize("(+1 + -2) * 3 - 4 / 5;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ok_c11_declarations() -> Result<()> {
+        let actual = parse_str(
+            "_Noreturn void f(void); _Alignas(8) _Alignas(int) char c; \
+             union U { _Static_assert(1, \"a\" \"b\"); struct { int x; }; };",
+        )?;
+
+        let NodeKind::Function { attributes, .. } = &actual[0].kind else {
+            panic!("Must be a function: {:?}", actual[0]);
+        };
+        assert_eq!(attributes[0].name.name, "noreturn");
+
+        let NodeKind::Decl { alignas, .. } = &actual[1].kind else {
+            panic!("Must be a declaration: {:?}", actual[1]);
+        };
+        assert!(matches!(
+            &alignas[..],
+            [
+                Alignas::Expr(_),
+                Alignas::Type {
+                    ty: TypeName::Base(Type::Int),
+                    ..
+                }
+            ]
+        ));
+
+        let NodeKind::Decl {
+            declspec: TypeName::Struct(spec),
+            ..
+        } = &actual[2].kind
+        else {
+            panic!("Must be a union declaration: {:?}", actual[2]);
+        };
+        assert!(spec.is_union);
+        let [NodeKind::StaticAssert { message, .. }] = &spec.static_asserts[..]
+            .iter()
+            .map(|node| &node.kind)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Must be a static assertion: {:?}", spec.static_asserts);
+        };
+        assert_eq!(message, "ab");
+        let members = spec.members.as_ref().unwrap();
+        assert_eq!(members.len(), 1);
+        assert!(members[0].ident.is_none() && members[0].width.is_none());
 
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_generic() -> Result<()> {
+        let actual = parse_str("_Generic(x, int *: 1, default: 2);")?;
+
+        let NodeKind::Generic {
+            control,
+            associations,
+        } = &actual[0].kind
+        else {
+            panic!("Must be a generic selection: {:?}", actual[0]);
+        };
+        assert_eq!(**control, var("x"));
+        let associations: Vec<_> = associations
+            .iter()
+            .map(|association| (association.ty.clone(), association.value.clone()))
+            .collect();
         assert_eq!(
-            actual,
-            vec![Node::arith_op(
-                ArithOp::Sub,
-                Node::arith_op(
-                    ArithOp::Mul,
-                    Node::arith_op(
-                        ArithOp::Add,
-                        Node::num(1),
-                        Node::arith_op(ArithOp::Sub, Node::num(0), Node::num(2))
-                    ),
-                    Node::num(3),
+            associations,
+            [
+                (
+                    Some(TypeName::Pointer(Box::new(TypeName::Base(Type::Int)))),
+                    num(1)
                 ),
-                Node::arith_op(ArithOp::Div, Node::num(4), Node::num(5))
-            )]
+                (None, num(2)),
+            ]
         );
 
         Ok(())
     }
 
     #[test]
-    fn test_ok_with_cmp() -> Result<()> {
-        let tokens = lexer::tokenize("(1 + 2 * 3 > 4) != (5 < 6 == 7 >= 8);")?;
-        let actual = parse(tokens).nodes;
+    fn test_ok_type_builtins() -> Result<()> {
+        let actual = parse_str(
+            "__builtin_offsetof(struct S, a.b[1]); __builtin_types_compatible_p(int, long *);",
+        )?;
+
+        let NodeKind::Offsetof { ty, designators } = &actual[0].kind else {
+            panic!("Must be offsetof: {:?}", actual[0]);
+        };
+        assert!(matches!(ty, TypeName::Struct { .. }));
+        assert_eq!(
+            *designators,
+            [
+                Designator::Member(ident("a")),
+                Designator::Member(ident("b")),
+                Designator::Index(num(1)),
+            ]
+        );
+        assert_eq!(
+            actual[1].kind,
+            NodeKind::TypesCompatible {
+                lhs: TypeName::Base(Type::Int),
+                rhs: TypeName::Pointer(Box::new(TypeName::Base(Type::Long))),
+            }
+        );
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_ok_gnu_extensions() -> Result<()> {
+        let actual = parse_str(
+            "__attribute__((__noreturn__)) static inline void f(int, ...) __asm__(\"g\") \
+             __attribute__((cold, aligned(8))); \
+             __extension__ __typeof__(1 + 2) x = ({ int y = 1; y; });",
+        )?;
+
+        let NodeKind::Function {
+            storage,
+            params,
+            is_variadic,
+            attributes,
+            asm_label,
+            ..
+        } = &actual[0].kind
+        else {
+            panic!("Must be a function: {:?}", actual[0]);
+        };
+        assert_eq!(*storage, Some(StorageClass::Static));
+        assert_eq!(params.len(), 1);
+        assert!(is_variadic);
+        let names: Vec<_> = attributes.iter().map(|a| a.name.name.as_str()).collect();
+        assert_eq!(names, ["noreturn", "cold", "aligned"]);
+        assert_eq!(attributes[2].args, [num(8)]);
+        assert_eq!(asm_label.as_ref().unwrap().name, "g");
+
+        let NodeKind::Decl {
+            declspec,
+            declarators,
+            ..
+        } = &actual[1].kind
+        else {
+            panic!("Must be a declaration: {:?}", actual[1]);
+        };
         assert_eq!(
-            actual,
-            vec![Node::cmp_op(
-                CmpOp::Neq,
-                Node::cmp_op(
-                    CmpOp::Lt,
-                    Node::num(4),
-                    Node::arith_op(
-                        ArithOp::Add,
-                        Node::num(1),
-                        Node::arith_op(ArithOp::Mul, Node::num(2), Node::num(3))
-                    )
-                ),
-                Node::cmp_op(
-                    CmpOp::Eq,
-                    Node::cmp_op(CmpOp::Lt, Node::num(5), Node::num(6)),
-                    Node::cmp_op(CmpOp::Lte, Node::num(8), Node::num(7),)
-                )
-            )]
+            *declspec,
+            TypeName::Typeof(Box::new(arith_op(ArithOp::Add, num(1), num(2))))
+        );
+        let Some(Initializer::Expr(init)) = &declarators[0].init else {
+            panic!("Must be initialized: {:?}", declarators[0]);
+        };
+        assert!(
+            matches!(&init.kind, NodeKind::StmtExpr { body } if matches!(
+                &body.kind,
+                NodeKind::Block { stmts } if stmts.len() == 2
+            ))
         );
 
         Ok(())
     }
 
     #[test]
-    fn test_ok_with_assign() -> Result<()> {
-        let tokens = lexer::tokenize("a = 1 + 2 * 3; bar = a; return bar;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ok_asm() -> Result<()> {
+        let actual = parse_str(
+            "asm(\"nop\"); __asm__ __volatile__(\"mov %0, %[b]\" \"\\n\" : \"=r\"(a) \
+             : [b] \"ri\"(1), \"m\"(*p) : \"cc\", \"memory\"); asm(\"\" ::: \"rax\");",
+        )?;
+
+        let NodeKind::Asm {
+            template,
+            is_extended: false,
+            ..
+        } = &actual[0].kind
+        else {
+            panic!("Must be basic asm: {:?}", actual[0]);
+        };
+        assert_eq!(template.name, "nop");
+
+        let NodeKind::Asm {
+            template,
+            is_extended: true,
+            outputs,
+            inputs,
+            clobbers,
+        } = &actual[1].kind
+        else {
+            panic!("Must be extended asm: {:?}", actual[1]);
+        };
+        assert_eq!(template.name, "mov %0, %[b]\n");
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(
+            (outputs[0].constraint.name.as_str(), &outputs[0].expr),
+            ("=r", &var("a"))
+        );
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].name, Some(ident("b")));
+        assert_eq!(inputs[1].expr, unary(UnaryOp::Deref, var("p")));
+        let clobbers: Vec<_> = clobbers
+            .iter()
+            .map(|clobber| clobber.name.as_str())
+            .collect();
+        assert_eq!(clobbers, ["cc", "memory"]);
+
+        assert!(matches!(
+            &actual[2].kind,
+            NodeKind::Asm { is_extended: true, outputs, inputs, clobbers, .. }
+                if outputs.is_empty() && inputs.is_empty() && clobbers.len() == 1
+        ));
 
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_initializers() -> Result<()> {
+        let actual = parse_str("int a[] = {1, [2] = {3}, .x.y = 4,};")?;
+
+        let NodeKind::Decl { declarators, .. } = &actual[0].kind else {
+            panic!("Must be a declaration: {:?}", actual[0]);
+        };
+        let Some(Initializer::List { items, .. }) = &declarators[0].init else {
+            panic!("Must be an initializer list: {:?}", declarators[0]);
+        };
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].value, Initializer::Expr(num(1)));
+        assert_eq!(items[1].designators, vec![Designator::Index(num(2))]);
+        assert!(matches!(&items[1].value, Initializer::List { items, .. } if items.len() == 1));
         assert_eq!(
-            actual,
+            items[2].designators,
             vec![
-                Node::arith_op(
-                    ArithOp::Assign,
-                    Node::local_var(8),
-                    Node::arith_op(
-                        ArithOp::Add,
-                        Node::num(1),
-                        Node::arith_op(ArithOp::Mul, Node::num(2), Node::num(3))
-                    )
-                ),
-                Node::arith_op(ArithOp::Assign, Node::local_var(16), Node::local_var(8)),
-                Node::ret(Node::local_var(16))
+                Designator::Member(ident("x")),
+                Designator::Member(ident("y"))
             ]
         );
 
@@ -691,66 +3624,247 @@ mod tests {
     }
 
     #[test]
-    fn test_ok_if() -> Result<()> {
-        let tokens = lexer::tokenize("if (1) return 2; else return 3;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ok_postfix_and_literals() -> Result<()> {
+        let actual = parse_str("a[1].b->c; &*p; \"ab\" \"c\"; (int *){0};")?;
+        let member = |operand, name: &str, arrow| {
+            Node::new(
+                NodeKind::Member {
+                    operand: Box::new(operand),
+                    member: ident(name),
+                    arrow,
+                },
+                Span::default(),
+            )
+        };
+        let index = Node::new(
+            NodeKind::Index {
+                base: Box::new(var("a")),
+                index: Box::new(num(1)),
+            },
+            Span::default(),
+        );
 
+        assert_eq!(actual[0], member(member(index, "b", false), "c", true));
         assert_eq!(
-            actual,
-            vec![Node::if_(
-                ".L0".to_string(),
-                Node::num(1),
-                Node::ret(Node::num(2)),
-                Some(Node::ret(Node::num(3)))
-            )]
+            actual[1],
+            unary(UnaryOp::Addr, unary(UnaryOp::Deref, var("p")))
+        );
+        assert_eq!(
+            actual[2],
+            Node::new(NodeKind::Str("abc".to_string()), Span::default())
         );
+        assert!(matches!(
+            &actual[3].kind,
+            NodeKind::CompoundLiteral { ty: TypeName::Pointer(_), init }
+                if matches!(**init, Initializer::List { .. })
+        ));
 
         Ok(())
     }
 
     #[test]
-    fn test_ok_while() -> Result<()> {
-        let tokens = lexer::tokenize("while (1) return 2;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ng_declarations() {
+        assert_eq!(
+            parse_errors("int 1; char long c; int f(int a b); struct {; int struct P p; int a[2;"),
+            vec![
+                (4, "Expected identifier".to_string()),
+                (
+                    12,
+                    "Cannot combine with previous 'char' declaration specifier".to_string()
+                ),
+                (32, "Expected ','".to_string()),
+                (44, "Expected a type".to_string()),
+                (
+                    50,
+                    "Cannot combine with previous 'int' declaration specifier".to_string()
+                ),
+                (69, "Expected ']'".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_errors("signed unsigned a; long long long b; unsigned _Bool c;"),
+            vec![
+                (
+                    7,
+                    "Cannot combine with previous 'signed' declaration specifier".to_string()
+                ),
+                (
+                    29,
+                    "Cannot combine with previous 'long' declaration specifier".to_string()
+                ),
+                (
+                    46,
+                    "Cannot combine with previous 'unsigned' declaration specifier".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            parse_errors("int (*)(int); int (*fp(int); extern int g(extern int);"),
+            vec![
+                (6, "Expected identifier".to_string()),
+                (27, "Expected ')'".to_string()),
+                (
+                    42,
+                    "Invalid storage class specifier in function declarator".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_storage_classes() {
+        assert_eq!(
+            parse_errors(
+                "auto int a; static register int b; register int f(void); \
+                 int g(static int c); const d; int h(void) { return sizeof(static int); }"
+            ),
+            vec![
+                (
+                    0,
+                    "Illegal storage class on file-scoped variable".to_string()
+                ),
+                (
+                    19,
+                    "Cannot combine with previous 'static' declaration specifier".to_string()
+                ),
+                (35, "Illegal storage class on function".to_string()),
+                (
+                    63,
+                    "Invalid storage class specifier in function declarator".to_string()
+                ),
+                (78, "Type specifier missing, defaults to 'int'".to_string()),
+                (
+                    115,
+                    "Type name does not allow storage class to be specified".to_string()
+                ),
+            ]
+        );
+    }
 
+    #[test]
+    fn test_ng_c11_specifiers() {
         assert_eq!(
-            actual,
-            vec![Node::while_(
-                ".L0".to_string(),
-                ".L1".to_string(),
-                Node::num(1),
-                Node::ret(Node::num(2))
-            )]
+            parse_errors(
+                "_Noreturn int a; _Alignas(4) int f(void); int g(_Alignas(4) int b); \
+                 _Alignas(4) struct S { int c; }; _Static_assert(1); int n = sizeof(_Noreturn int);"
+            ),
+            vec![
+                (0, "'_Noreturn' can only appear on functions".to_string()),
+                (
+                    26,
+                    "'_Alignas' attribute only applies to variables and fields".to_string()
+                ),
+                (
+                    57,
+                    "'_Alignas' attribute cannot be applied to a function parameter".to_string()
+                ),
+                (
+                    77,
+                    "'_Alignas' attribute cannot be applied to a declaration that does not \
+                     declare anything"
+                        .to_string()
+                ),
+                (117, "Expected ','".to_string()),
+                (135, "'_Noreturn' can only appear on functions".to_string()),
+            ]
         );
+    }
 
-        Ok(())
+    #[test]
+    fn test_ng_thread_local() {
+        assert_eq!(
+            parse_errors(
+                "__thread int f(void); int g(__thread int a); struct S { __thread int m; }; \
+                 _Thread_local struct T { int t; }; int h(void) { _Thread_local int b; \
+                 auto __thread int c; return sizeof(_Thread_local int); }"
+            ),
+            vec![
+                (0, THREAD_LOCAL_ON_NON_VARIABLE.to_string()),
+                (28, THREAD_LOCAL_ON_NON_VARIABLE.to_string()),
+                (56, THREAD_LOCAL_ON_NON_VARIABLE.to_string()),
+                (75, THREAD_LOCAL_ON_NON_VARIABLE.to_string()),
+                (
+                    124,
+                    "'_Thread_local' variables must have global storage".to_string()
+                ),
+                (
+                    150,
+                    "Cannot combine with previous 'auto' declaration specifier".to_string()
+                ),
+                (180, THREAD_LOCAL_ON_NON_VARIABLE.to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn test_ok_for() -> Result<()> {
-        let tokens = lexer::tokenize("for (i = 0; i < 10; i = i + 1) return i;")?;
-        let actual = parse(tokens).nodes;
+    fn test_ng_gnu_extensions() {
+        assert_eq!(
+            parse_errors(
+                "int f(...); inline int a; int g(void) __asm__(1); int h __attribute__((x(;"
+            ),
+            vec![
+                (
+                    6,
+                    "ISO C requires a named parameter before '...'".to_string()
+                ),
+                (12, "'inline' can only appear on functions".to_string()),
+                (46, "Expected string literal".to_string()),
+                (73, "Invalid token".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_reports_every_syntax_error() {
+        let actual = parse_errors("a = 1 b = 2;\nc = ;\n{ d = (1; e = 3; }\nreturn 4");
 
         assert_eq!(
             actual,
-            vec![Node::for_(
-                ".L0".to_string(),
-                ".L1".to_string(),
-                Some(Node::arith_op(
-                    ArithOp::Assign,
-                    Node::local_var(8),
-                    Node::num(0)
-                )),
-                Some(Node::cmp_op(CmpOp::Lt, Node::local_var(8), Node::num(10))),
-                Some(Node::arith_op(
-                    ArithOp::Assign,
-                    Node::local_var(8),
-                    Node::arith_op(ArithOp::Add, Node::local_var(8), Node::num(1))
-                )),
-                Node::ret(Node::local_var(8))
-            )]
+            vec![
+                (6, "Expected ';'".to_string()),
+                (17, "Invalid token".to_string()),
+                (27, "Expected ')'".to_string()),
+                (46, "Expected ';'. Unexpected EOF".to_string()),
+            ]
         );
+    }
 
-        Ok(())
+    #[test]
+    fn test_ng_invalid_tokens() {
+        // They are operands which the parser doesn't report again, and the lexer's errors are
+        // sorted with the parser's.
+        assert_eq!(
+            parse_errors("f(1x, 2); a = ; 1 @ 2; 3y;"),
+            vec![
+                (2, "Invalid suffix 'x' on integer constant".to_string()),
+                (14, "Invalid token".to_string()),
+                (18, "Invalid token".to_string()),
+                (23, "Invalid suffix 'y' on integer constant".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_invalid_tokens_in_parentheses() {
+        assert_eq!(
+            parse_errors("return (3 @ 2);"),
+            vec![(10, "Invalid token".to_string())]
+        );
+        assert_eq!(
+            parse_errors("g((1 @ 2));"),
+            vec![(5, "Invalid token".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_ng_unmatched_braces() {
+        assert_eq!(
+            parse_errors("1; } 2;"),
+            vec![(3, "Unmatched '}'".to_string())]
+        );
+        assert_eq!(
+            parse_errors("{ 1;"),
+            vec![(4, "Expected '}'. Unexpected EOF".to_string())]
+        );
     }
 }
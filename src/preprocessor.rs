@@ -0,0 +1,489 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    diagnostic::DiagnosticEngine,
+    lexer::{self, Symbol, Token, TokenKind, Tokens},
+    options::{OptLevel, Options},
+    source_map::{FileId, SourceMap, Span},
+    warning::{DiagnosticPragma, Warning, WarningLevel},
+};
+
+// Macros every translation unit sees, matching what gcc predefines for x86-64 Linux.
+const PREDEFINED_MACROS: &[(&str, &str)] = &[
+    ("__kanic__", "1"),
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201112L"),
+    ("__STDC_HOSTED__", "1"),
+    ("__x86_64__", "1"),
+    ("__x86_64", "1"),
+    ("__amd64__", "1"),
+    ("__amd64", "1"),
+    ("__linux__", "1"),
+    ("__linux", "1"),
+    ("__gnu_linux__", "1"),
+    ("__unix__", "1"),
+    ("__unix", "1"),
+    ("__ELF__", "1"),
+    ("__LP64__", "1"),
+    ("_LP64", "1"),
+    ("__CHAR_BIT__", "8"),
+    ("__SIZEOF_SHORT__", "2"),
+    ("__SIZEOF_INT__", "4"),
+    ("__SIZEOF_LONG__", "8"),
+    ("__SIZEOF_LONG_LONG__", "8"),
+    ("__SIZEOF_POINTER__", "8"),
+    ("__SIZEOF_SIZE_T__", "8"),
+    ("__SIZEOF_PTRDIFF_T__", "8"),
+    ("__SCHAR_MAX__", "127"),
+    ("__SHRT_MAX__", "32767"),
+    ("__INT_MAX__", "2147483647"),
+    ("__LONG_MAX__", "9223372036854775807L"),
+    ("__LONG_LONG_MAX__", "9223372036854775807LL"),
+    ("__ORDER_LITTLE_ENDIAN__", "1234"),
+    ("__ORDER_BIG_ENDIAN__", "4321"),
+    ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"),
+    ("__ATOMIC_RELAXED", "0"),
+    ("__ATOMIC_CONSUME", "1"),
+    ("__ATOMIC_ACQUIRE", "2"),
+    ("__ATOMIC_RELEASE", "3"),
+    ("__ATOMIC_ACQ_REL", "4"),
+    ("__ATOMIC_SEQ_CST", "5"),
+];
+
+/// Object-like macros, keyed by name.
+#[derive(Debug)]
+pub struct MacroTable {
+    macros: BTreeMap<String, Macro>,
+}
+
+#[derive(Debug)]
+struct Macro {
+    // The replacement list as written.
+    value: String,
+    // The file of the replacement list in the source map, and its tokens.
+    file: FileId,
+    tokens: Vec<Token>,
+}
+
+impl MacroTable {
+    /// The predefined macros, with those describing the options the code is compiled with.
+    pub fn new(
+        source_map: &mut SourceMap,
+        diags: &mut DiagnosticEngine,
+        options: &Options,
+    ) -> Self {
+        let mut macros = Self {
+            macros: BTreeMap::new(),
+        };
+
+        let mut predefined = PREDEFINED_MACROS.to_vec();
+        if options.target.unsigned_char {
+            predefined.push(("__CHAR_UNSIGNED__", "1"));
+        }
+        if options.target.pic {
+            predefined.extend([("__PIC__", "2"), ("__pic__", "2")]);
+        }
+        if options.opt_level != OptLevel::O0 {
+            predefined.push(("__OPTIMIZE__", "1"));
+        }
+        if options.opt_level == OptLevel::Os {
+            predefined.push(("__OPTIMIZE_SIZE__", "1"));
+        }
+        for (name, value) in predefined {
+            macros.define_in(source_map, diags, "<built-in>", name, value);
+        }
+
+        macros
+    }
+
+    /// Defines a macro given on the command line.
+    pub fn define(
+        &mut self,
+        source_map: &mut SourceMap,
+        diags: &mut DiagnosticEngine,
+        name: &str,
+        value: &str,
+    ) {
+        self.define_in(source_map, diags, "<command-line>", name, value)
+    }
+
+    fn define_in(
+        &mut self,
+        source_map: &mut SourceMap,
+        diags: &mut DiagnosticEngine,
+        file_name: &str,
+        name: &str,
+        value: &str,
+    ) {
+        let file = source_map.add_file(file_name, value);
+        let tokens = lexer::tokenize(source_map, file, diags)
+            .into_iter()
+            .collect();
+
+        self.macros.insert(
+            name.to_string(),
+            Macro {
+                value: value.to_string(),
+                file,
+                tokens,
+            },
+        );
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.macros.remove(name);
+    }
+
+    /// Every macro as a `#define` line, in the format of `gcc -dM -E`.
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+
+        for (name, Macro { value, .. }) in &self.macros {
+            dump += &format!("#define {name} {value}\n");
+        }
+
+        dump
+    }
+}
+
+/// Runs the directives, i.e. lines starting with `#`, and replaces every identifier that names a
+/// macro with its (recursively expanded) replacement list.
+pub fn preprocess(
+    tokens: Tokens,
+    macros: &MacroTable,
+    source_map: &mut SourceMap,
+    diags: &mut DiagnosticEngine,
+) -> Tokens {
+    let line_of = |token: &Token| (token.span.file, source_map.location(token.span).line);
+
+    let mut text = vec![];
+    let mut previous_line = None;
+
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let line = line_of(&token);
+        let at_line_start = previous_line != Some(line);
+        previous_line = Some(line);
+
+        if at_line_start && token.value == TokenKind::Symbol(Symbol::Hash) {
+            let mut directive = vec![];
+            while let Some(token) = tokens.next_if(|token| line_of(token) == line) {
+                directive.push(token);
+            }
+
+            run_directive(token.span, &directive, diags);
+        } else {
+            text.push(token);
+        }
+    }
+
+    let mut expander = Expander {
+        macros,
+        source_map,
+        diags,
+        expanding: vec![],
+        expanded: vec![],
+    };
+    expander.expand(text);
+
+    expander.expanded.into_iter().collect()
+}
+
+// `tokens` is everything after the `#` on its line.
+fn run_directive(hash: Span, tokens: &[Token], diags: &mut DiagnosticEngine) {
+    match tokens {
+        // The null directive does nothing.
+        [] => {}
+        [name, rest @ ..] if name.value == TokenKind::Ident("pragma".to_string()) => {
+            run_pragma(hash, rest, diags)
+        }
+        [name, ..] => diags.error(name.span, "Invalid preprocessing directive"),
+    }
+}
+
+fn run_pragma(hash: Span, tokens: &[Token], diags: &mut DiagnosticEngine) {
+    let ident = |token: &Token| match &token.value {
+        TokenKind::Ident(ident) => Some(ident.clone()),
+        _ => None,
+    };
+
+    // Like other compilers, pragmas meant for someone else are ignored.
+    let [namespace, diagnostic, rest @ ..] = tokens else {
+        return;
+    };
+    if !matches!(ident(namespace).as_deref(), Some("GCC" | "clang" | "kanic"))
+        || ident(diagnostic).as_deref() != Some("diagnostic")
+    {
+        return;
+    }
+
+    let Some((kind, rest)) = rest.split_first() else {
+        diags.error(
+            diagnostic.span,
+            "Expected 'push', 'pop', 'ignored', 'warning' or 'error'",
+        );
+        return;
+    };
+
+    let level = match ident(kind).as_deref() {
+        Some("push") => return diags.pragma(hash, DiagnosticPragma::Push),
+        Some("pop") => return diags.pragma(hash, DiagnosticPragma::Pop),
+        Some("ignored") => WarningLevel::Ignored,
+        Some("warning") => WarningLevel::Warning,
+        Some("error") => WarningLevel::Error,
+        _ => {
+            diags.error(
+                kind.span,
+                "Expected 'push', 'pop', 'ignored', 'warning' or 'error'",
+            );
+            return;
+        }
+    };
+
+    match rest {
+        [Token {
+            value: TokenKind::Str(option),
+            ..
+        }] => {
+            // Warnings kanic doesn't implement can't fire, so there is nothing to configure.
+            if let Some(warning) = option.strip_prefix("-W").and_then(Warning::from_name) {
+                diags.pragma(hash, DiagnosticPragma::Set(warning, level));
+            }
+        }
+        _ => diags.error(
+            kind.span
+                .to(rest.last().map_or(kind.span, |token| token.span)),
+            "Expected a warning option such as \"-Wunused-variable\"",
+        ),
+    }
+}
+
+struct Expander<'a> {
+    macros: &'a MacroTable,
+    source_map: &'a mut SourceMap,
+    diags: &'a mut DiagnosticEngine,
+    // Macros currently being expanded. A macro is never expanded within itself.
+    expanding: Vec<&'a str>,
+    expanded: Vec<Token>,
+}
+
+impl<'a> Expander<'a> {
+    // Each expansion of a macro is a copy of its replacement list in a file of its own, which
+    // diagnostics trace back to where the macro is used.
+    fn expand<I>(&mut self, tokens: I)
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        for token in tokens {
+            let replacement = match &token.value {
+                TokenKind::Ident(ident) => self
+                    .macros
+                    .macros
+                    .get_key_value(ident)
+                    .filter(|(name, _)| !self.expanding.contains(&name.as_str())),
+                _ => None,
+            };
+
+            match replacement {
+                Some((name, Macro { file, tokens, .. })) => {
+                    let expansion = self.source_map.copy_file(*file);
+                    self.diags.expansion(expansion, name, token.span);
+
+                    let tokens = tokens.iter().map(|token| Token {
+                        span: Span {
+                            file: expansion,
+                            ..token.span
+                        },
+                        ..token.clone()
+                    });
+                    self.expanding.push(name);
+                    self.expand(tokens.collect::<Vec<_>>());
+                    self.expanding.pop();
+                }
+                None => self.expanded.push(token),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    fn expand_to_string(
+        source_map: &mut SourceMap,
+        c_code: &str,
+        macros: &MacroTable,
+    ) -> Result<String> {
+        let file = source_map.add_file("<input>", c_code);
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = lexer::tokenize(source_map, file, &mut diags);
+        let tokens = preprocess(tokens, macros, source_map, &mut diags);
+
+        Ok(diags
+            .finish(tokens)?
+            .0
+            .into_iter()
+            .map(|token| token.value.to_string())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    #[test]
+    fn test_ok_predefined() -> Result<()> {
+        let mut source_map = SourceMap::new();
+        let macros = MacroTable::new(
+            &mut source_map,
+            &mut DiagnosticEngine::default(),
+            &Options::default(),
+        );
+
+        assert_eq!(
+            expand_to_string(
+                &mut source_map,
+                "return __SIZEOF_POINTER__ * __CHAR_BIT__;",
+                &macros
+            )?,
+            "return 8 * 8 ;"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_define_and_undefine() -> Result<()> {
+        let mut source_map = SourceMap::new();
+        let mut diags = DiagnosticEngine::default();
+        let mut macros = MacroTable::new(&mut source_map, &mut diags, &Options::default());
+        macros.define(&mut source_map, &mut diags, "FOO", "1 + 2");
+        macros.define(&mut source_map, &mut diags, "BAR", "FOO * 3");
+        macros.undefine("__kanic__");
+
+        assert_eq!(
+            expand_to_string(&mut source_map, "BAR; __kanic__;", &macros)?,
+            "1 + 2 * 3 ; __kanic__ ;"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_self_referential() -> Result<()> {
+        let mut source_map = SourceMap::new();
+        let mut diags = DiagnosticEngine::default();
+        let mut macros = MacroTable::new(&mut source_map, &mut diags, &Options::default());
+        macros.define(&mut source_map, &mut diags, "a", "a + 1");
+
+        assert_eq!(expand_to_string(&mut source_map, "a;", &macros)?, "a + 1 ;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_dump() -> Result<()> {
+        let mut source_map = SourceMap::new();
+        let mut diags = DiagnosticEngine::default();
+        let mut macros = MacroTable::new(&mut source_map, &mut diags, &Options::default());
+        macros.define(&mut source_map, &mut diags, "FOO", "42");
+
+        let dump = macros.dump();
+
+        assert!(dump.contains("#define FOO 42\n"));
+        assert!(dump.contains("#define __LP64__ 1\n"));
+        assert!(dump.contains("#define __INT_MAX__ 2147483647\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ok_predefined_by_options() {
+        let mut source_map = SourceMap::new();
+        let mut options = Options::default();
+        let dump =
+            MacroTable::new(&mut source_map, &mut DiagnosticEngine::default(), &options).dump();
+        assert!(!dump.contains("__CHAR_UNSIGNED__"));
+        assert!(!dump.contains("__PIC__"));
+        assert!(!dump.contains("__OPTIMIZE__"));
+
+        options.target.unsigned_char = true;
+        options.target.pic = true;
+        options.opt_level = OptLevel::Os;
+        let dump =
+            MacroTable::new(&mut source_map, &mut DiagnosticEngine::default(), &options).dump();
+        assert!(dump.contains("#define __CHAR_UNSIGNED__ 1\n"));
+        assert!(dump.contains("#define __PIC__ 2\n"));
+        assert!(dump.contains("#define __pic__ 2\n"));
+        assert!(dump.contains("#define __OPTIMIZE__ 1\n"));
+        assert!(dump.contains("#define __OPTIMIZE_SIZE__ 1\n"));
+    }
+
+    #[test]
+    fn test_ok_pragmas() -> Result<()> {
+        let mut source_map = SourceMap::new();
+        let macros = MacroTable::new(
+            &mut source_map,
+            &mut DiagnosticEngine::default(),
+            &Options::default(),
+        );
+
+        assert_eq!(
+            expand_to_string(
+                &mut source_map,
+                "#pragma GCC diagnostic push\n\
+                 #pragma GCC diagnostic ignored \"-Wunused-variable\"\n\
+                 a = 1; #pragma once\n\
+                 #pragma once\n\
+                 #\n\
+                 #pragma GCC diagnostic pop\n\
+                 return a;",
+                &macros
+            )?,
+            "a = 1 ; # pragma once return a ;"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ng_directives() {
+        let mut source_map = SourceMap::new();
+        let macros = MacroTable::new(
+            &mut source_map,
+            &mut DiagnosticEngine::default(),
+            &Options::default(),
+        );
+        let file = source_map.add_file(
+            "<input>",
+            "#foo\n#pragma GCC diagnostic ignored\n#pragma GCC diagnostic foo",
+        );
+
+        let mut diags = DiagnosticEngine::default();
+        let tokens = lexer::tokenize(&source_map, file, &mut diags);
+        let tokens = preprocess(tokens, &macros, &mut source_map, &mut diags);
+
+        let messages: Vec<_> = diags
+            .finish(tokens)
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|diagnostic| (diagnostic.span.start, diagnostic.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Invalid preprocessing directive".to_string()),
+                (
+                    28,
+                    "Expected a warning option such as \"-Wunused-variable\"".to_string()
+                ),
+                (
+                    59,
+                    "Expected 'push', 'pop', 'ignored', 'warning' or 'error'".to_string()
+                ),
+            ]
+        );
+    }
+}
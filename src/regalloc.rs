@@ -0,0 +1,391 @@
+use std::collections::HashSet;
+
+use crate::{
+    builtin::BitOp,
+    inline_asm::Register,
+    ir::{BinaryOp, Function, Inst, Operand, VReg},
+    parser::ArithOp,
+    types::Target,
+};
+
+// The registers given to virtual registers, in the order they're tried. rax and r11 are left for
+// the instructions themselves. Those which calls don't clobber come last, since they have to be
+// saved in the prologue.
+const CALLER_SAVED: [Register; 7] = [
+    Register::RCX,
+    Register::RDX,
+    Register::RSI,
+    Register::RDI,
+    Register::R8,
+    Register::R9,
+    Register::R10,
+];
+const CALLEE_SAVED: [Register; 5] = [
+    Register::RBX,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+/// Where a virtual register is kept for its whole life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(Register),
+    // The index of an eightbyte in the frame.
+    Stack(usize),
+}
+
+#[derive(Debug)]
+pub struct Allocation {
+    pub locations: Vec<Location>,
+    // How many eightbytes spilled virtual registers take in the frame.
+    pub stack_slots: usize,
+    // The callee-saved registers it uses, which the function saves and restores.
+    pub callee_saved: Vec<Register>,
+}
+
+// The positions where a virtual register is live, from its first definition or the start of a
+// block it's live into, to its last use or the end of a block it's live out of. Each instruction
+// reads its operands at an even position, and writes its result at the next one.
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    vreg: VReg,
+    start: usize,
+    end: usize,
+}
+
+impl Interval {
+    fn contains(&self, position: usize) -> bool {
+        self.start <= position && position <= self.end
+    }
+}
+
+// The registers an instruction overwrites, which nothing live across it may be in.
+struct Clobber {
+    position: usize,
+    registers: Vec<Register>,
+    // Whether what it only reads may not be in them either. Code generation moves the arguments
+    // of a call into place as if all at once, so they may.
+    includes_operands: bool,
+    // Whether its results may not be in them either.
+    includes_results: bool,
+}
+
+impl Clobber {
+    fn forbids(&self, interval: &Interval) -> bool {
+        let is_live = interval.contains(self.position)
+            && (self.includes_operands || interval.end > self.position);
+        is_live || self.includes_results && interval.contains(self.position + 1)
+    }
+}
+
+/// Assigns registers to the virtual registers of `function` by linear scan, over the blocks in
+/// order. A virtual register which none is left for is spilled to the frame.
+pub fn allocate(function: &Function, target: Target) -> Allocation {
+    let (intervals, clobbers) = intervals(function, target);
+
+    let mut locations = vec![None; function.vregs];
+    let mut stack_slots = 0;
+    let mut callee_saved = vec![];
+    // Those which are in registers, by their end.
+    let mut active: Vec<(Interval, Register)> = vec![];
+    for interval in intervals {
+        active.retain(|(active, _)| active.end >= interval.start);
+
+        // The clobbers are sorted by position, and only those the interval covers matter.
+        let first = clobbers.partition_point(|clobber| clobber.position + 1 < interval.start);
+        let forbidden: Vec<Register> = clobbers[first..]
+            .iter()
+            .take_while(|clobber| clobber.position <= interval.end)
+            .filter(|clobber| clobber.forbids(&interval))
+            .flat_map(|clobber| clobber.registers.iter().copied())
+            .collect();
+        let is_free = |register: &Register| {
+            !forbidden.contains(register) && !active.iter().any(|(_, used)| used == register)
+        };
+
+        let register = match CALLER_SAVED
+            .iter()
+            .chain(&CALLEE_SAVED)
+            .find(|r| is_free(r))
+        {
+            Some(&register) => Some(register),
+            // The one whose end is furthest is spilled, which frees a register for longest.
+            None => {
+                let furthest = active
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, register))| !forbidden.contains(register))
+                    .max_by_key(|(_, (active, _))| active.end)
+                    .filter(|(_, (active, _))| active.end > interval.end)
+                    .map(|(index, _)| index);
+                furthest.map(|index| {
+                    let (spilled, register) = active.remove(index);
+                    locations[spilled.vreg.0] = Some(Location::Stack(stack_slots));
+                    stack_slots += 1;
+                    register
+                })
+            }
+        };
+
+        match register {
+            Some(register) => {
+                if CALLEE_SAVED.contains(&register) && !callee_saved.contains(&register) {
+                    callee_saved.push(register);
+                }
+                locations[interval.vreg.0] = Some(Location::Register(register));
+                active.push((interval, register));
+            }
+            None => {
+                locations[interval.vreg.0] = Some(Location::Stack(stack_slots));
+                stack_slots += 1;
+            }
+        }
+    }
+
+    Allocation {
+        // Those which appear nowhere are never used, and go nowhere.
+        locations: locations
+            .into_iter()
+            .map(|location| location.unwrap_or(Location::Stack(0)))
+            .collect(),
+        stack_slots,
+        callee_saved,
+    }
+}
+
+// The live interval of each virtual register, by start, and the clobbers, by position.
+fn intervals(function: &Function, target: Target) -> (Vec<Interval>, Vec<Clobber>) {
+    let blocks = &function.blocks;
+
+    // What each block reads before writing it, and what it writes.
+    let mut exposed = vec![];
+    let mut defined = vec![];
+    for block in blocks {
+        let mut reads = vec![];
+        let mut writes = HashSet::new();
+        for inst in &block.insts {
+            reads.extend(
+                inst.uses()
+                    .into_iter()
+                    .filter(|vreg| !writes.contains(vreg)),
+            );
+            writes.extend(inst.defs());
+        }
+        reads.extend(
+            block
+                .terminator
+                .uses()
+                .filter(|vreg| !writes.contains(vreg)),
+        );
+        exposed.push(sorted(reads.into_iter().map(|vreg| vreg.0).collect()));
+        defined.push(sorted(writes.into_iter().map(|vreg| vreg.0).collect()));
+    }
+
+    // Which virtual registers are live into and out of each block, as sorted sets, found by
+    // iterating to a fixed point.
+    let mut live_in: Vec<Vec<usize>> = vec![vec![]; blocks.len()];
+    let mut live_out: Vec<Vec<usize>> = vec![vec![]; blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, block) in blocks.iter().enumerate().rev() {
+            let out = sorted(
+                block
+                    .terminator
+                    .successors()
+                    .into_iter()
+                    .flat_map(|successor| live_in[successor.0].iter().copied())
+                    .collect(),
+            );
+            let mut live: Vec<usize> = out
+                .iter()
+                .copied()
+                .filter(|vreg| defined[index].binary_search(vreg).is_err())
+                .chain(exposed[index].iter().copied())
+                .collect();
+            live = sorted(live);
+            live_out[index] = out;
+            if live != live_in[index] {
+                live_in[index] = live;
+                changed = true;
+            }
+        }
+    }
+
+    let mut ranges = vec![(usize::MAX, 0); function.vregs];
+    let mut extend = |vreg: VReg, position: usize| {
+        let (start, end) = &mut ranges[vreg.0];
+        *start = position.min(*start);
+        *end = position.max(*end);
+    };
+    let mut clobbers = vec![];
+    let mut position = 0;
+    for (index, block) in blocks.iter().enumerate() {
+        for &vreg in &live_in[index] {
+            extend(VReg(vreg), position);
+        }
+        for inst in &block.insts {
+            for vreg in inst.uses() {
+                extend(vreg, position);
+            }
+            for vreg in inst.defs() {
+                extend(vreg, position + 1);
+            }
+            let registers = self::clobbers(inst, target);
+            if !registers.is_empty() {
+                clobbers.push(Clobber {
+                    position,
+                    registers,
+                    includes_operands: !matches!(inst, Inst::Call { .. }),
+                    includes_results: matches!(inst, Inst::Asm(_)),
+                });
+            }
+            position += 2;
+        }
+        if let Some(vreg) = block.terminator.uses() {
+            extend(vreg, position);
+        }
+        for &vreg in &live_out[index] {
+            extend(VReg(vreg), position + 1);
+        }
+        position += 2;
+    }
+
+    let mut intervals: Vec<_> = ranges
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (start, _))| *start != usize::MAX)
+        .map(|(vreg, (start, end))| Interval {
+            vreg: VReg(vreg),
+            start,
+            end,
+        })
+        .collect();
+    intervals.sort_by_key(|interval| interval.start);
+    (intervals, clobbers)
+}
+
+fn sorted(mut vregs: Vec<usize>) -> Vec<usize> {
+    vregs.sort_unstable();
+    vregs.dedup();
+    vregs
+}
+
+// The registers code generation uses for `inst`, besides rax and r11.
+fn clobbers(inst: &Inst, target: Target) -> Vec<Register> {
+    match inst {
+        // rdx:rax is the dividend.
+        Inst::Binary {
+            op: BinaryOp::SDiv | BinaryOp::UDiv,
+            ..
+        } => vec![Register::RDX],
+        // A variable count is in cl.
+        Inst::Binary {
+            op: BinaryOp::Shl | BinaryOp::LShr | BinaryOp::AShr,
+            rhs: Operand::Reg(_),
+            ..
+        } => vec![Register::RCX],
+        Inst::Overflow {
+            op: ArithOp::Mul,
+            is_unsigned: true,
+            ..
+        } => vec![Register::RDX],
+        // The masks of the bit count.
+        Inst::BitOp {
+            op: BitOp::Popcount,
+            ..
+        } => vec![Register::RCX],
+        Inst::MemCopy { .. } => vec![Register::RDI, Register::RSI, Register::RCX],
+        Inst::MemSet { .. } => vec![Register::RDI, Register::RCX],
+        Inst::Atomic { .. } => vec![Register::RDI, Register::RSI, Register::RDX],
+        Inst::Call { .. } => CALLER_SAVED.to_vec(),
+        // Through `__tls_get_addr`.
+        Inst::ThreadLocalAddr { .. } if target.pic => CALLER_SAVED.to_vec(),
+        // The caller-saved registers it clobbers aren't known, and those it saves are pushed
+        // around it, so its operands are kept out of all of them.
+        Inst::Asm(asm) => CALLER_SAVED.iter().chain(&asm.saved).copied().collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::lower_str;
+
+    use super::*;
+
+    // The IR and the allocation of the first function.
+    fn allocate_str(c_code: &str) -> (Function, Allocation) {
+        let function = lower_str(c_code).functions.remove(0);
+        let allocation = allocate(&function, Target::default());
+        (function, allocation)
+    }
+
+    #[test]
+    fn test_allocate() {
+        // Short intervals share a register.
+        let (_, allocation) = allocate_str("int f(int a) { return a + 1 + 2; }");
+        assert!(allocation
+            .locations
+            .iter()
+            .all(|&location| location == Location::Register(Register::RCX)));
+        assert_eq!(allocation.stack_slots, 0);
+        assert!(allocation.callee_saved.is_empty());
+    }
+
+    #[test]
+    fn test_fixed_registers() {
+        // %0 is only read by the call, so it needn't survive it, and the rest only live between
+        // instructions.
+        let (function, allocation) = allocate_str("int g(int); int f(int a) { return a + g(a); }");
+        assert_eq!(
+            function.to_string(),
+            "f:
+bb0:
+  %0 = load.i32 [local 4]
+  %1 = call @g(%0)
+  %2 = load.i32 [local 4]
+  %3 = add %2, %1
+  %4 = ext.i32 %3
+  ret %4
+"
+        );
+        assert_eq!(
+            allocation.locations,
+            [
+                Location::Register(Register::RCX),
+                Location::Register(Register::RCX),
+                Location::Register(Register::RDX),
+                Location::Register(Register::RCX),
+                Location::Register(Register::RCX),
+            ]
+        );
+        assert!(allocation.callee_saved.is_empty());
+
+        // The rhs is evaluated first, and live across the call.
+        let (_, allocation) = allocate_str("int g(int); int f(int a) { return g(2) + (a + 1); }");
+        assert_eq!(allocation.callee_saved, [Register::RBX]);
+
+        // The divisor isn't in rdx.
+        let (_, allocation) = allocate_str("long f(long a, long b) { return a / (b + 1); }");
+        assert!(!allocation
+            .locations
+            .contains(&Location::Register(Register::RDX)));
+    }
+
+    #[test]
+    fn test_spill() {
+        // The rhs of each addition is evaluated first, so every result but the first is live
+        // across a call.
+        let mut c_code = "g(a)".to_string();
+        for _ in 0..13 {
+            c_code = format!("({c_code} + g(a))");
+        }
+        let c_code = format!("long g(long); long f(long a) {{ return {c_code}; }}");
+        let (_, allocation) = allocate_str(&c_code);
+        assert!(allocation.stack_slots > 0);
+        assert_eq!(allocation.callee_saved, CALLEE_SAVED);
+    }
+}
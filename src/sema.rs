@@ -0,0 +1,6393 @@
+use core::fmt;
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use crate::{
+    abi,
+    atomic::{self, AtomicOp, Family, FetchOp, MemoryOrder, Orders},
+    builtin::{self, BitOp, Builtin},
+    const_eval::{self, Address, Constant, NotConstant},
+    diagnostic::{Diagnostic, DiagnosticEngine},
+    format::{self, Style},
+    inline_asm::{self, Piece, Register},
+    lexer::IntSuffix,
+    parser::{
+        self, Alignas, ArithOp, Attribute, CmpOp, Declarator, Designator, GenericAssociation,
+        Ident, InitItem, Initializer, MemberDecl, Node, NodeKind, Param, StorageClass, StructSpec,
+        TypeName, UnaryOp,
+    },
+    source_map::Span,
+    types::{BitField, Field, Qualifiers, StructRef, Target, Type},
+    warning::Warning,
+};
+
+// The largest alignment `_Alignas` can request, like in clang.
+const MAX_ALIGNMENT: i64 = 1 << 28;
+
+// The largest alignment of any type, which is what `aligned` without an argument requests.
+const BIGGEST_ALIGNMENT: usize = 16;
+
+/// A name-resolved and typed program, ready for code generation.
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub globals: Vec<Global>,
+    // How the symbols with attributes are emitted, by their name in C. Those that are only declared
+    // are also here.
+    pub symbols: BTreeMap<String, SymbolAttributes>,
+    // Basic asm at file scope, in order.
+    pub asm: Vec<String>,
+}
+
+/// What the attributes of a function or a variable with static storage duration change in the
+/// assembly, merged over its declarations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolAttributes {
+    // From `__asm__("label")`, the name of the symbol instead of the name in C.
+    pub label: Option<String>,
+    pub is_weak: bool,
+    pub visibility: Option<Visibility>,
+    pub section: Option<String>,
+    // The priorities of a function called before or after `main`. Lower ones run first for
+    // constructors, and last for destructors.
+    pub constructor: Option<u16>,
+    pub destructor: Option<u16>,
+}
+
+/// `visibility("...")`, for what other modules of a shared library can see the symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Default,
+    Hidden,
+    Protected,
+    Internal,
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Visibility::Default => "default",
+                Visibility::Hidden => "hidden",
+                Visibility::Protected => "protected",
+                Visibility::Internal => "internal",
+            }
+        )
+    }
+}
+
+impl SymbolAttributes {
+    // Adds those of a later declaration.
+    fn merge(&mut self, other: SymbolAttributes) {
+        self.label = other.label.or(self.label.take());
+        self.is_weak |= other.is_weak;
+        self.visibility = other.visibility.or(self.visibility);
+        self.section = other.section.or(self.section.take());
+        self.constructor = other.constructor.or(self.constructor);
+        self.destructor = other.destructor.or(self.destructor);
+    }
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    // Where the arguments are stored, in order.
+    pub params: Vec<Local>,
+    pub return_type: Type,
+    // Where the hidden pointer to the return value is stored, if it's returned in memory.
+    pub return_pointer: Option<usize>,
+    pub body: Vec<Stmt>,
+    // A multiple of 16, to keep calls aligned.
+    pub stack_size: usize,
+    // Not visible outside the file.
+    pub is_static: bool,
+}
+
+/// A variable in a function's frame, at `rbp - offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub offset: usize,
+    pub ty: Type,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub ty: Type,
+    // The bytes of the initial value, or None for a zero-initialized global.
+    pub init: Option<Vec<u8>>,
+    // Addresses of other globals in `init`, which are filled in by the linker.
+    pub relocations: Vec<Relocation>,
+    // Not visible outside the file, like string literals.
+    pub is_static: bool,
+    // `_Thread_local`, so each thread has its own copy.
+    pub is_thread_local: bool,
+    // The alignment of `ty`, unless `_Alignas` raises it.
+    pub align: usize,
+}
+
+/// `label + addend` as the 8 bytes at `offset` in the initial value of a global.
+#[derive(Debug, PartialEq)]
+pub struct Relocation {
+    pub offset: usize,
+    pub label: String,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expr(Expr),
+    Return(Option<Expr>),
+    // Also the initializers of a declaration.
+    Block(Vec<Stmt>),
+    // A block declaring variable length arrays, which are freed at its end by restoring the stack
+    // pointer saved at its start.
+    StackBlock {
+        stmts: Vec<Stmt>,
+    },
+    If {
+        cond: Expr,
+        then: Box<Stmt>,
+        else_: Option<Box<Stmt>>,
+    },
+    While {
+        cond: Expr,
+        then: Box<Stmt>,
+    },
+    For {
+        init: Option<Expr>,
+        cond: Option<Expr>,
+        update: Option<Expr>,
+        then: Box<Stmt>,
+    },
+    Asm(InlineAsm),
+}
+
+/// An asm statement in a function, with a place chosen for each operand.
+#[derive(Debug, Clone)]
+pub struct InlineAsm {
+    pub template: Vec<Piece>,
+    // The outputs, then the inputs.
+    pub operands: Vec<AsmOperand>,
+    // The callee-saved registers it uses or clobbers, which are restored after it.
+    pub saved: Vec<Register>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsmOperand {
+    // An lvalue for outputs and memory operands, and a value for other inputs.
+    pub expr: Expr,
+    pub place: AsmPlace,
+    pub is_output: bool,
+    // Inputs, and outputs with `+`.
+    pub is_read: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmPlace {
+    Register(Register),
+    // The address of a variable is written as is, and any other one is put in `base`.
+    Memory { base: Option<Register> },
+    Immediate(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Num(i64),
+    Local {
+        offset: usize,
+    },
+    Global {
+        name: String,
+    },
+    // A thread-local variable, whose address depends on the thread.
+    ThreadLocal {
+        name: String,
+    },
+    Assign {
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    // Both operands have the type of the expression, except for pointer arithmetic where the rhs
+    // is an offset in bytes as a `long`. Never `ArithOp::Assign`.
+    ArithOp {
+        value: ArithOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    // Both operands have the same type.
+    CmpOp {
+        value: CmpOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Neg {
+        operand: Box<Expr>,
+    },
+    // `&operand`, or an array decayed to a pointer to its first element.
+    Addr {
+        operand: Box<Expr>,
+    },
+    // `*operand`
+    Deref {
+        operand: Box<Expr>,
+    },
+    // A member of a struct, or an element of an array, `offset` bytes into the operand. A bit-field
+    // is in the storage unit there.
+    Member {
+        operand: Box<Expr>,
+        offset: usize,
+        bit_field: Option<BitField>,
+    },
+    // Sets every byte of the lvalue operand to 0, for initializers.
+    MemZero {
+        operand: Box<Expr>,
+    },
+    // Evaluates lhs for its side effects, then rhs. Made for compound literals and temporaries.
+    Comma {
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    // Converts the operand to the type of the expression.
+    Cast {
+        operand: Box<Expr>,
+    },
+    // A function designator, whose address is the function's.
+    Function {
+        name: String,
+    },
+    // Allocates `size` bytes on the stack until the function returns, or until the end of the block
+    // if it declares variable length arrays. The value is their address.
+    Alloca {
+        size: Box<Expr>,
+    },
+    // `({ ... })`, where the value is left by the last statement.
+    StmtExpr {
+        body: Box<Stmt>,
+    },
+    // The callee is a function designator for a direct call, and a pointer to a function otherwise.
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+        // The offset of the frame's temporary holding a returned struct, which is the value.
+        temporary: Option<usize>,
+    },
+    // An atomic operation on the object pointed to by the first argument, which the operands of
+    // `op` follow. A fence has no arguments.
+    Atomic {
+        op: AtomicOp,
+        order: MemoryOrder,
+        args: Vec<Expr>,
+    },
+    // `__builtin_trap` or `__builtin_unreachable`.
+    Trap,
+    // The type is that of the operand for `BitOp::Bswap`, and `int` otherwise.
+    BitOp {
+        op: BitOp,
+        operand: Box<Expr>,
+    },
+    // Stores `lhs op rhs` where `result` points to, and is whether it didn't fit there. The
+    // operands have the type the operation is exact in, `long` or `unsigned long`.
+    Overflow {
+        op: ArithOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+        result: Box<Expr>,
+    },
+    // Copies `size` bytes from `src` to `dest`, which is the value.
+    MemCopy {
+        dest: Box<Expr>,
+        src: Box<Expr>,
+        size: Box<Expr>,
+    },
+    // Sets `size` bytes at `dest` to `value`, and is `dest`.
+    MemSet {
+        dest: Box<Expr>,
+        value: Box<Expr>,
+        size: Box<Expr>,
+    },
+}
+
+impl Expr {
+    fn new(kind: ExprKind, ty: Type, span: Span) -> Self {
+        Self { kind, ty, span }
+    }
+
+    // Stands in for an expression with errors, so that analysis can go on.
+    fn error(span: Span) -> Self {
+        Self::new(ExprKind::Num(0), Type::Error, span)
+    }
+
+    // Whether the expression has errors which have already been reported.
+    fn is_error(&self) -> bool {
+        self.ty == Type::Error
+    }
+
+    /// Whether the expression designates an object, as opposed to only being a value.
+    pub fn is_lvalue(&self) -> bool {
+        match &self.kind {
+            ExprKind::Local { .. }
+            | ExprKind::Global { .. }
+            | ExprKind::ThreadLocal { .. }
+            | ExprKind::Deref { .. } => true,
+            ExprKind::Member { operand, .. } => operand.is_lvalue(),
+            ExprKind::Comma { rhs, .. } => rhs.is_lvalue(),
+            _ => false,
+        }
+    }
+
+    /// Whether the expression designates a bit-field, which has no address.
+    pub fn is_bit_field(&self) -> bool {
+        matches!(
+            self.kind,
+            ExprKind::Member {
+                bit_field: Some(_),
+                ..
+            }
+        )
+    }
+}
+
+/// Resolves names and types every expression, inserting the implicit conversions. Top-level
+/// statements become the body of `main`, where assigning to an undeclared name declares an `int`.
+pub fn analyze(nodes: &[Node], target: Target, diags: &mut DiagnosticEngine) -> Program {
+    let mut sema = Sema {
+        diags,
+        target,
+        globals: HashMap::new(),
+        program: Program {
+            functions: vec![],
+            globals: vec![],
+            symbols: BTreeMap::new(),
+            asm: vec![],
+        },
+        function: FunctionContext::default(),
+        script: FunctionContext {
+            name: "main".to_string(),
+            return_type: Type::Int,
+            scopes: vec![HashMap::new()],
+            implicit_declarations: true,
+            ..FunctionContext::default()
+        },
+        script_body: vec![],
+        script_span: None,
+        tags: vec![HashMap::new()],
+        structs: HashMap::new(),
+        anonymous: 0,
+        variable_sizes: Err("Variable length array declaration not allowed at file scope"),
+    };
+
+    // Functions can be called before they are declared.
+    for node in nodes {
+        if let NodeKind::Function {
+            storage,
+            return_type,
+            ident,
+            params,
+            is_variadic,
+            attributes,
+            asm_label,
+            body,
+        } = &node.kind
+        {
+            let ty = sema.function_type(return_type, params, *is_variadic, ident.span);
+            let is_static = *storage == Some(StorageClass::Static);
+            sema.declare_function(ty, ident, attributes, asm_label, body.is_some(), is_static);
+        }
+    }
+
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Function {
+                ident,
+                params,
+                body: Some(body),
+                ..
+            } => sema.function(ident, params, body),
+            NodeKind::Function { body: None, .. } => {}
+            NodeKind::Decl {
+                declspec,
+                storage,
+                is_thread_local,
+                alignas,
+                declarators,
+            } => {
+                sema.global_declaration(declspec, *storage, *is_thread_local, alignas, declarators)
+            }
+            NodeKind::StaticAssert { .. } => sema.static_assert(node),
+            NodeKind::Asm {
+                template,
+                is_extended: false,
+                ..
+            } => sema.program.asm.push(template.name.clone()),
+            _ => {
+                sema.script_span.get_or_insert(node.span);
+
+                std::mem::swap(&mut sema.function, &mut sema.script);
+                let stmt = sema.stmt(node);
+                std::mem::swap(&mut sema.function, &mut sema.script);
+
+                sema.script_body.push(stmt);
+            }
+        }
+    }
+
+    if let Some(script_span) = sema.script_span {
+        match sema.globals.get("main") {
+            Some(Symbol::Function(signature)) if signature.defined => {
+                let main_span = signature.span;
+                sema.diags.emit(
+                    Diagnostic::error(
+                        script_span,
+                        "Top-level statements can't be used when 'main' is defined",
+                    )
+                    .with_note(Some(main_span), "'main' is defined here"),
+                );
+            }
+            _ => {
+                let body = std::mem::take(&mut sema.script_body);
+                std::mem::swap(&mut sema.function, &mut sema.script);
+                sema.finish_function(body, script_span, false);
+            }
+        }
+    }
+
+    for (name, symbol) in &sema.globals {
+        let attributes = match symbol {
+            Symbol::Variable { attributes, .. } => attributes,
+            Symbol::Function(signature) => &signature.attributes,
+        };
+        if *attributes != SymbolAttributes::default() {
+            sema.program
+                .symbols
+                .insert(name.clone(), attributes.clone());
+        }
+    }
+
+    sema.program
+}
+
+struct Sema<'a> {
+    diags: &'a mut DiagnosticEngine,
+    target: Target,
+    // File scope.
+    globals: HashMap<String, Symbol>,
+    program: Program,
+    // The function being analyzed.
+    function: FunctionContext,
+    // `main` made of the top-level statements, while it isn't being analyzed.
+    script: FunctionContext,
+    script_body: Vec<Stmt>,
+    // The first top-level statement.
+    script_span: Option<Span>,
+    // Struct tags by block scope, innermost last. The first is the file scope.
+    tags: Vec<HashMap<String, StructRef>>,
+    // The struct each specifier resolved to, since declarators share their specifier.
+    structs: HashMap<*const StructSpec, StructRef>,
+    // For the unique names of string literals, compound literals at file scope and static locals.
+    anonymous: usize,
+    // Where the sizes of variable length arrays are computed while resolving a type, or why they
+    // aren't allowed there.
+    variable_sizes: Result<Vec<Expr>, &'static str>,
+}
+
+enum Symbol {
+    Variable {
+        ty: Type,
+        span: Span,
+        attributes: SymbolAttributes,
+        is_thread_local: bool,
+    },
+    Function(Signature),
+}
+
+struct Signature {
+    return_type: Type,
+    params: Vec<Type>,
+    is_variadic: bool,
+    span: Span,
+    defined: bool,
+    is_static: bool,
+    // Declared `_Noreturn` or `noreturn` by any of its declarations.
+    is_noreturn: bool,
+    format: Option<Format>,
+    attributes: SymbolAttributes,
+}
+
+impl Signature {
+    fn ty(&self) -> Type {
+        Type::Function {
+            return_type: Box::new(self.return_type.clone()),
+            params: self.params.clone(),
+            is_variadic: self.is_variadic,
+        }
+    }
+}
+
+// What the attributes of a declaration mean.
+#[derive(Default)]
+struct Attributes {
+    is_noreturn: bool,
+    // `aligned`, which can only raise the alignment.
+    aligned: Option<usize>,
+    packed: bool,
+    is_unused: bool,
+    format: Option<Format>,
+    symbol: SymbolAttributes,
+}
+
+// `format(style, string, first)` on a function, where the indices are those of its arguments.
+#[derive(Clone, Copy)]
+struct Format {
+    style: Style,
+    string: usize,
+    // None for a function taking the arguments as a `va_list`, like `vprintf`.
+    first: Option<usize>,
+}
+
+// What a declaration with attributes declares, which decides the attributes it can have.
+#[derive(Clone, Copy)]
+enum AttributeTarget<'a> {
+    Function { ty: &'a Type, is_static: bool },
+    // A variable with static storage duration.
+    Variable { is_static: bool },
+    // A variable with automatic storage duration.
+    Local,
+    Field,
+    Struct,
+}
+
+// GCC attributes which only help optimizations or diagnostics, and are accepted without effect.
+const IGNORED_ATTRIBUTES: [&str; 32] = [
+    "access",
+    "alloc_align",
+    "alloc_size",
+    "always_inline",
+    "artificial",
+    "cold",
+    "common",
+    "const",
+    "deprecated",
+    "error",
+    "externally_visible",
+    "fallthrough",
+    "flatten",
+    "format_arg",
+    "gnu_inline",
+    "hot",
+    "leaf",
+    "malloc",
+    "may_alias",
+    "no_instrument_function",
+    "nocommon",
+    "noinline",
+    "nonnull",
+    "nonstring",
+    "nothrow",
+    "pure",
+    "returns_nonnull",
+    "returns_twice",
+    "sentinel",
+    "used",
+    "warn_unused_result",
+    "warning",
+];
+
+#[derive(Default)]
+struct FunctionContext {
+    name: String,
+    return_type: Type,
+    // Block scopes, innermost last. Each maps a name to an index in `locals`.
+    scopes: Vec<HashMap<String, usize>>,
+    locals: Vec<LocalVariable>,
+    stack_size: usize,
+    return_pointer: Option<usize>,
+    // Whether an undeclared name declares a variable, like in top-level statements.
+    implicit_declarations: bool,
+    is_noreturn: bool,
+    // Whether a syntax error was reported in the body.
+    has_syntax_errors: bool,
+}
+
+struct LocalVariable {
+    name: String,
+    ty: Type,
+    span: Span,
+    offset: usize,
+    // The global holding a static local, or the global declared by `extern` in a block, which isn't
+    // in the frame.
+    label: Option<String>,
+    // Whether that global is thread-local.
+    is_thread_local: bool,
+    // Whether it's declared `register`, so its address can't be taken.
+    is_register: bool,
+    read: bool,
+    param: bool,
+}
+
+// What a name refers to.
+enum Resolved<'a> {
+    Local(usize),
+    Global(&'a Symbol),
+}
+
+// A subobject's initial value, with braces and designators resolved.
+#[derive(Debug)]
+enum InitValue {
+    // Not initialized explicitly, so zero.
+    Zero,
+    // Already converted to the type of the subobject.
+    Expr(Expr),
+    // Elements of an array or members of a struct, in order. Missing ones are zero.
+    Aggregate(Vec<InitValue>),
+}
+
+// The items of an initializer list, where elided braces consume items of the enclosing list.
+struct InitCursor<'a> {
+    items: &'a [InitItem],
+    pos: usize,
+    // The value of the next item, if it was analyzed to see whether it initializes a whole struct.
+    pending: Option<Expr>,
+    // Whether the designators of the next item are already applied.
+    designated: bool,
+}
+
+impl<'a> InitCursor<'a> {
+    fn peek(&self) -> Option<&'a InitItem> {
+        self.items.get(self.pos)
+    }
+
+    fn advance(&mut self) {
+        self.pos += 1;
+        self.pending = None;
+        self.designated = false;
+    }
+}
+
+impl Sema<'_> {
+    fn declare_function(
+        &mut self,
+        ty: Type,
+        ident: &Ident,
+        attributes: &[Attribute],
+        asm_label: &Option<Ident>,
+        defined: bool,
+        is_static: bool,
+    ) {
+        let Attributes {
+            is_noreturn,
+            format,
+            symbol,
+            ..
+        } = self.attributes(
+            attributes,
+            asm_label,
+            AttributeTarget::Function { ty: &ty, is_static },
+        );
+        let Type::Function {
+            return_type,
+            params,
+            is_variadic,
+        } = ty
+        else {
+            unreachable!()
+        };
+
+        let signature = Signature {
+            return_type: *return_type,
+            params,
+            is_variadic,
+            span: ident.span,
+            defined,
+            is_static,
+            is_noreturn,
+            format,
+            attributes: symbol,
+        };
+
+        match self.globals.get_mut(&ident.name) {
+            None => {
+                self.globals
+                    .insert(ident.name.clone(), Symbol::Function(signature));
+            }
+            Some(Symbol::Function(previous)) => {
+                // Qualifiers of parameters only matter in the definition.
+                let unqualified = |params: &[Type]| -> Vec<Type> {
+                    params.iter().map(|ty| ty.unqualified().clone()).collect()
+                };
+                if previous.return_type != signature.return_type
+                    || unqualified(&previous.params) != unqualified(&signature.params)
+                    || previous.is_variadic != signature.is_variadic
+                {
+                    let previous_span = previous.span;
+                    self.diags.emit(
+                        Diagnostic::error(
+                            ident.span,
+                            format!("Conflicting types for '{}'", ident.name),
+                        )
+                        .with_note(Some(previous_span), "Previous declaration is here"),
+                    );
+                    return;
+                } else if previous.defined && defined {
+                    let previous_span = previous.span;
+                    self.diags.emit(
+                        Diagnostic::error(ident.span, format!("Redefinition of '{}'", ident.name))
+                            .with_note(Some(previous_span), "Previous definition is here"),
+                    );
+                    return;
+                } else if is_static && !previous.is_static {
+                    let previous_span = previous.span;
+                    self.static_follows_non_static(ident, previous_span);
+                    return;
+                }
+
+                previous.is_noreturn |= signature.is_noreturn;
+                previous.format = signature.format.or(previous.format);
+                previous.attributes.merge(signature.attributes);
+                if defined {
+                    // A later declaration without `static` keeps the linkage.
+                    previous.params = signature.params;
+                    previous.span = signature.span;
+                    previous.defined = true;
+                }
+            }
+            Some(Symbol::Variable { span, .. }) => {
+                let previous_span = *span;
+                self.redefinition_as_different_kind(ident, previous_span);
+            }
+        }
+    }
+
+    fn redefinition_as_different_kind(&mut self, ident: &Ident, previous_span: Span) {
+        self.diags.emit(
+            Diagnostic::error(
+                ident.span,
+                format!(
+                    "Redefinition of '{}' as a different kind of symbol",
+                    ident.name
+                ),
+            )
+            .with_note(Some(previous_span), "Previous definition is here"),
+        );
+    }
+
+    fn static_follows_non_static(&mut self, ident: &Ident, previous_span: Span) {
+        self.diags.emit(
+            Diagnostic::error(
+                ident.span,
+                format!(
+                    "Static declaration of '{}' follows non-static declaration",
+                    ident.name
+                ),
+            )
+            .with_note(Some(previous_span), "Previous declaration is here"),
+        );
+    }
+
+    fn function(&mut self, ident: &Ident, params: &[Param], body: &Node) {
+        let (return_type, param_types, is_static, is_noreturn) = match self.globals.get(&ident.name)
+        {
+            Some(Symbol::Function(signature)) if signature.params.len() == params.len() => (
+                signature.return_type.clone(),
+                signature.params.clone(),
+                signature.is_static,
+                signature.is_noreturn,
+            ),
+            _ => (Type::Int, vec![Type::Int; params.len()], false, false),
+        };
+        if !return_type.is_complete() && return_type != Type::Void {
+            self.diags.error(
+                ident.span,
+                format!("Incomplete result type '{return_type}' in function definition"),
+            );
+        }
+
+        self.function = FunctionContext {
+            name: ident.name.clone(),
+            return_type: return_type.clone(),
+            scopes: vec![HashMap::new()],
+            is_noreturn,
+            ..FunctionContext::default()
+        };
+        if abi::is_memory(&return_type) {
+            self.function.return_pointer = Some(self.allocate(&Type::pointer_to(return_type)));
+        }
+        self.tags.push(HashMap::new());
+
+        for (param, ty) in params.iter().zip(param_types) {
+            match &param.ident {
+                Some(param_ident) => {
+                    let ty = self.object_type(ty, param_ident);
+                    let index = self.declare_local(param_ident, ty, true);
+                    self.function.locals[index].is_register =
+                        param.storage == Some(StorageClass::Register);
+                }
+                None => self.diags.error(ident.span, "Parameter name omitted"),
+            }
+        }
+
+        // The body shares the scope of the parameters.
+        let NodeKind::Block { stmts } = &body.kind else {
+            unreachable!("A function body is a block")
+        };
+        let body_stmts = stmts.iter().map(|stmt| self.stmt(stmt)).collect();
+        self.tags.pop();
+
+        let end = Span::new(body.span.file, body.span.end - 1, body.span.end);
+        self.finish_function(body_stmts, end, is_static);
+    }
+
+    // `end` is where control reaches the end of the function.
+    fn finish_function(&mut self, body: Vec<Stmt>, end: Span, is_static: bool) {
+        let function = std::mem::take(&mut self.function);
+
+        // A statement dropped for a syntax error may have returned, or read any variable.
+        let falls_through =
+            !function.has_syntax_errors && body.iter().all(|stmt| self.falls_through(stmt));
+        if function.is_noreturn && falls_through {
+            self.diags.emit(Diagnostic::warning(
+                Warning::InvalidNoreturn,
+                end,
+                "Function declared 'noreturn' should not return",
+            ));
+        // Reaching the end of `main` returns 0.
+        } else if function.return_type != Type::Void && function.name != "main" && falls_through {
+            self.diags.emit(Diagnostic::warning(
+                Warning::ReturnType,
+                end,
+                format!(
+                    "Non-void function '{}' does not return a value",
+                    function.name
+                ),
+            ));
+        }
+
+        for local in function
+            .locals
+            .iter()
+            .filter(|local| !local.read && !local.param && !function.has_syntax_errors)
+        {
+            self.diags.emit(Diagnostic::warning(
+                Warning::UnusedVariable,
+                local.span,
+                format!("Unused variable '{}'", local.name),
+            ));
+        }
+
+        self.program.functions.push(Function {
+            name: function.name,
+            params: function
+                .locals
+                .iter()
+                .filter(|local| local.param)
+                .map(|local| Local {
+                    offset: local.offset,
+                    ty: local.ty.clone(),
+                })
+                .collect(),
+            return_type: function.return_type,
+            return_pointer: function.return_pointer,
+            body,
+            stack_size: function.stack_size.next_multiple_of(16),
+            is_static,
+        });
+    }
+
+    // Whether control can reach the end of `stmt`.
+    fn falls_through(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Return(_) => false,
+            Stmt::Block(stmts) | Stmt::StackBlock { stmts } => {
+                stmts.iter().all(|stmt| self.falls_through(stmt))
+            }
+            Stmt::If { then, else_, .. } => {
+                self.falls_through(then)
+                    || else_
+                        .as_ref()
+                        .map_or(true, |else_| self.falls_through(else_))
+            }
+            // There is no `break` yet, so only a loop that can't end doesn't fall through.
+            Stmt::While { cond, .. } => !is_always_true(cond),
+            Stmt::For { cond, .. } => cond.as_ref().is_some_and(|cond| !is_always_true(cond)),
+            Stmt::Expr(Expr {
+                kind: ExprKind::Trap,
+                ..
+            }) => false,
+            // A call to a `_Noreturn` function doesn't return.
+            Stmt::Expr(Expr {
+                kind: ExprKind::Call { callee, .. },
+                ..
+            }) => match &callee.kind {
+                ExprKind::Function { name } => !matches!(
+                    self.globals.get(name),
+                    Some(Symbol::Function(signature)) if signature.is_noreturn
+                ),
+                _ => true,
+            },
+            Stmt::Expr(_) | Stmt::Asm(_) => true,
+        }
+    }
+
+    fn global_declaration(
+        &mut self,
+        declspec: &TypeName,
+        storage: Option<StorageClass>,
+        is_thread_local: bool,
+        alignas: &[Alignas],
+        declarators: &[Declarator],
+    ) {
+        // Defines the struct in it, when there are no declarators to do it.
+        if declarators.is_empty() {
+            self.resolve_type(declspec, Span::default());
+        }
+
+        let is_static = storage == Some(StorageClass::Static);
+        for declarator in declarators {
+            let Declarator {
+                ty,
+                ident,
+                init,
+                attributes,
+                asm_label,
+            } = declarator;
+            if let TypeName::Function { .. } = ty {
+                self.function_declaration(declarator, is_static);
+                continue;
+            }
+
+            // Without an initializer, `extern` only declares a variable defined elsewhere, which can
+            // have an incomplete type.
+            let is_extern = storage == Some(StorageClass::Extern) && init.is_none();
+            let ty = self.resolve_type(ty, ident.span);
+
+            // Only constants are allowed, so there are no variables to see. The variable itself is
+            // in scope, with the type it has so far, so it can hold its own address.
+            let (ty, init) = match init {
+                Some(init) => {
+                    let is_new = !self.globals.contains_key(&ident.name);
+                    if is_new {
+                        self.globals.insert(
+                            ident.name.clone(),
+                            Symbol::Variable {
+                                ty: ty.clone(),
+                                span: ident.span,
+                                attributes: SymbolAttributes::default(),
+                                is_thread_local,
+                            },
+                        );
+                    }
+                    let previous = std::mem::take(&mut self.function);
+                    let (ty, value) = self.initializer(init, ty);
+                    self.function = previous;
+                    if is_new {
+                        self.globals.remove(&ident.name);
+                    }
+                    (ty, Some(value))
+                }
+                None => (ty, None),
+            };
+            let ty = if is_extern {
+                ty
+            } else {
+                self.object_type(ty, ident)
+            };
+            let attributes = self.attributes(
+                attributes,
+                asm_label,
+                AttributeTarget::Variable { is_static },
+            );
+            let align = self
+                .alignment(alignas, &ty)
+                .max(attributes.aligned.unwrap_or(1));
+            let init = init.map(|value| self.global_data(&value, &ty));
+
+            match self.globals.get_mut(&ident.name) {
+                None => {
+                    self.globals.insert(
+                        ident.name.clone(),
+                        Symbol::Variable {
+                            ty: ty.clone(),
+                            span: ident.span,
+                            attributes: attributes.symbol,
+                            is_thread_local,
+                        },
+                    );
+                    if !is_extern {
+                        self.define_global(ident, ty, init, is_static, is_thread_local, align);
+                    }
+                }
+                Some(Symbol::Variable {
+                    ty: previous_ty,
+                    span,
+                    attributes: previous_attributes,
+                    is_thread_local: was_thread_local,
+                }) => {
+                    let previous_span = *span;
+                    let was_thread_local = *was_thread_local;
+                    previous_attributes.merge(attributes.symbol);
+                    if *previous_ty != ty {
+                        self.diags.emit(
+                            Diagnostic::error(
+                                ident.span,
+                                format!("Redefinition of '{}' with a different type", ident.name),
+                            )
+                            .with_note(Some(previous_span), "Previous definition is here"),
+                        );
+                        continue;
+                    }
+                    if was_thread_local != is_thread_local {
+                        self.thread_local_mismatch(ident, previous_span, is_thread_local);
+                        continue;
+                    }
+
+                    // Keeps the linkage of the previous declaration.
+                    if is_extern {
+                        continue;
+                    }
+
+                    let Some(position) = self
+                        .program
+                        .globals
+                        .iter()
+                        .position(|global| global.name == ident.name)
+                    else {
+                        // Only declared `extern` so far.
+                        if is_static {
+                            self.static_follows_non_static(ident, previous_span);
+                        } else {
+                            self.define_global(ident, ty, init, is_static, is_thread_local, align);
+                        }
+                        continue;
+                    };
+                    if self.program.globals[position].is_static != is_static {
+                        if is_static {
+                            self.static_follows_non_static(ident, previous_span);
+                        } else {
+                            self.diags.emit(
+                                Diagnostic::error(
+                                    ident.span,
+                                    format!(
+                                        "Non-static declaration of '{}' follows static declaration",
+                                        ident.name
+                                    ),
+                                )
+                                .with_note(Some(previous_span), "Previous declaration is here"),
+                            );
+                        }
+                        continue;
+                    }
+
+                    // Tentative definitions without an initializer can be repeated.
+                    let global = &mut self.program.globals[position];
+                    global.align = global.align.max(align);
+                    match (&global.init, init) {
+                        (Some(_), Some(_)) => self.diags.emit(
+                            Diagnostic::error(
+                                ident.span,
+                                format!("Redefinition of '{}'", ident.name),
+                            )
+                            .with_note(Some(previous_span), "Previous definition is here"),
+                        ),
+                        (None, Some((init, relocations))) => {
+                            global.init = Some(init);
+                            global.relocations = relocations;
+                        }
+                        (_, None) => {}
+                    }
+                }
+                Some(Symbol::Function(signature)) => {
+                    let previous_span = signature.span;
+                    self.redefinition_as_different_kind(ident, previous_span);
+                }
+            }
+        }
+    }
+
+    fn define_global(
+        &mut self,
+        ident: &Ident,
+        ty: Type,
+        init: Option<(Vec<u8>, Vec<Relocation>)>,
+        is_static: bool,
+        is_thread_local: bool,
+        align: usize,
+    ) {
+        let (init, relocations) = init.unzip();
+        self.program.globals.push(Global {
+            name: ident.name.clone(),
+            ty,
+            init,
+            relocations: relocations.unwrap_or_default(),
+            is_static,
+            is_thread_local,
+            align,
+        });
+    }
+
+    fn thread_local_mismatch(&mut self, ident: &Ident, previous_span: Span, is_thread_local: bool) {
+        let message = if is_thread_local {
+            "Thread-local declaration of '{}' follows non-thread-local declaration"
+        } else {
+            "Non-thread-local declaration of '{}' follows thread-local declaration"
+        };
+        self.diags.emit(
+            Diagnostic::error(ident.span, message.replace("{}", &ident.name))
+                .with_note(Some(previous_span), "Previous declaration is here"),
+        );
+    }
+
+    // A declarator of a function type in a declaration, which can't have an initializer.
+    fn function_declaration(&mut self, declarator: &Declarator, is_static: bool) {
+        let Declarator {
+            ty,
+            ident,
+            init,
+            attributes,
+            asm_label,
+        } = declarator;
+        if let Some(init) = init {
+            self.diags.error(
+                init.span(),
+                "Illegal initializer (only variables can be initialized)",
+            );
+        }
+        let ty = self.resolve_type(ty, ident.span);
+        self.declare_function(ty, ident, attributes, asm_label, false, is_static);
+    }
+
+    // A global only referred to by its generated name, like a string literal.
+    fn anonymous_global(
+        &mut self,
+        kind: &str,
+        ty: Type,
+        (init, relocations): (Vec<u8>, Vec<Relocation>),
+    ) -> String {
+        self.anonymous += 1;
+        let name = format!(".L.{kind}.{}", self.anonymous);
+
+        self.program.globals.push(Global {
+            name: name.clone(),
+            align: ty.align(),
+            ty,
+            init: Some(init),
+            relocations,
+            is_static: true,
+            is_thread_local: false,
+        });
+        name
+    }
+
+    // The bytes of a global's initial value, with the addresses in it.
+    fn global_data(&mut self, value: &InitValue, ty: &Type) -> (Vec<u8>, Vec<Relocation>) {
+        let mut bytes = vec![0; ty.size()];
+        let mut relocations = vec![];
+        self.write_global_data(value, ty, 0, &mut bytes, &mut relocations);
+
+        (bytes, relocations)
+    }
+
+    fn write_global_data(
+        &mut self,
+        value: &InitValue,
+        ty: &Type,
+        offset: usize,
+        bytes: &mut [u8],
+        relocations: &mut Vec<Relocation>,
+    ) {
+        match value {
+            InitValue::Zero => {}
+            InitValue::Aggregate(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    let (ty, member_offset, bit_field) = subobject(ty, index).unwrap();
+                    let offset = offset + member_offset;
+                    match (bit_field, value) {
+                        (Some(bit_field), InitValue::Expr(expr)) => {
+                            self.write_bit_field(expr, &ty, bit_field, &mut bytes[offset..]);
+                        }
+                        _ => self.write_global_data(value, &ty, offset, bytes, relocations),
+                    }
+                }
+            }
+            InitValue::Expr(expr) => match self.constant(expr) {
+                Address {
+                    label: Some(label),
+                    offset: addend,
+                } => {
+                    if ty.size() == 8 {
+                        relocations.push(Relocation {
+                            offset,
+                            label,
+                            addend,
+                        });
+                    } else {
+                        self.diags.error(
+                            expr.span,
+                            "Initializer element is not a compile-time constant",
+                        );
+                    }
+                }
+                Address {
+                    label: None,
+                    offset: value,
+                } => {
+                    let size = ty.size();
+                    bytes[offset..offset + size].copy_from_slice(&value.to_le_bytes()[..size]);
+                }
+            },
+        }
+    }
+
+    // Sets the bits of a bit-field in its storage unit, at the start of `bytes`.
+    fn write_bit_field(&mut self, expr: &Expr, ty: &Type, bit_field: BitField, bytes: &mut [u8]) {
+        let value = match self.constant(expr) {
+            Address {
+                label: None,
+                offset,
+            } => offset as u64,
+            Address { .. } => {
+                self.diags.error(
+                    expr.span,
+                    "Initializer element is not a compile-time constant",
+                );
+                return;
+            }
+        };
+
+        let size = ty.size();
+        let mut unit = [0; 8];
+        unit[..size].copy_from_slice(&bytes[..size]);
+        let mask = (u64::MAX >> (64 - bit_field.width)) << bit_field.offset;
+        let unit = u64::from_le_bytes(unit) & !mask | (value << bit_field.offset) & mask;
+        bytes[..size].copy_from_slice(&unit.to_le_bytes()[..size]);
+    }
+
+    // The value of an initializer of a global, or 0 after reporting why it isn't a constant.
+    fn constant(&mut self, expr: &Expr) -> Address {
+        match const_eval::eval_address(expr, self.target) {
+            Ok(address) => {
+                if let Ok(Constant {
+                    overflow: Some(span),
+                    ..
+                }) = const_eval::eval(expr, self.target)
+                {
+                    self.constant_overflow(span);
+                }
+                address
+            }
+            Err(NotConstant::Runtime(_)) => {
+                self.diags.error(
+                    expr.span,
+                    "Initializer element is not a compile-time constant",
+                );
+                Address::default()
+            }
+            Err(NotConstant::DivisionByZero(span)) => {
+                self.constant_division_by_zero(span);
+                Address::default()
+            }
+        }
+    }
+
+    // The value of an integer constant expression, like an array designator.
+    fn integer_constant(&mut self, node: &Node) -> Option<i64> {
+        let expr = self.rvalue(node);
+        if expr.is_error() {
+            return None;
+        }
+
+        match const_eval::eval(&expr, self.target) {
+            Ok(constant) if expr.ty.is_integer() => {
+                if let Some(span) = constant.overflow {
+                    self.constant_overflow(span);
+                }
+                Some(constant.value)
+            }
+            Err(NotConstant::DivisionByZero(span)) => {
+                self.constant_division_by_zero(span);
+                None
+            }
+            _ => {
+                self.diags.error(
+                    expr.span,
+                    "Expression is not an integer constant expression",
+                );
+                None
+            }
+        }
+    }
+
+    // Where a constant expression is required, overflow and division by zero are errors, which
+    // replace the warnings the operation had.
+    fn constant_overflow(&mut self, span: Span) {
+        self.diags.retract(Warning::IntegerOverflow, span);
+        self.diags.error(span, "Overflow in a constant expression");
+    }
+
+    fn constant_division_by_zero(&mut self, span: Span) {
+        self.diags.retract(Warning::DivByZero, span);
+        self.diags
+            .error(span, "Division by zero in a constant expression");
+    }
+
+    // Warns if `expr` is constant, but its own operation overflows.
+    fn check_overflow(&mut self, expr: Expr) -> Expr {
+        if let Ok(Constant {
+            value,
+            overflow: Some(span),
+        }) = const_eval::eval(&expr, self.target)
+        {
+            if span == expr.span {
+                self.diags.emit(Diagnostic::warning(
+                    Warning::IntegerOverflow,
+                    span,
+                    format!(
+                        "Overflow in expression; result is {value} with type '{}'",
+                        expr.ty
+                    ),
+                ));
+            }
+        }
+
+        expr
+    }
+
+    // The type of a variable, which must be complete.
+    fn object_type(&mut self, ty: Type, ident: &Ident) -> Type {
+        if ty.is_complete() {
+            ty
+        } else {
+            self.diags.error(
+                ident.span,
+                format!("Variable '{}' has incomplete type '{ty}'", ident.name),
+            );
+            Type::Int
+        }
+    }
+
+    // The alignment of an object of type `ty`, raised by the strictest of `alignas`.
+    fn alignment(&mut self, alignas: &[Alignas], ty: &Type) -> usize {
+        let mut align = ty.align();
+        for alignas in alignas {
+            let (requested, span) = match alignas {
+                Alignas::Expr(node) => match self.integer_constant(node) {
+                    Some(requested) => (requested, node.span),
+                    None => continue,
+                },
+                Alignas::Type { ty, span } => {
+                    let ty = self.resolve_fixed_type(
+                        ty,
+                        *span,
+                        "'_Alignas' cannot be applied to a variable length array type",
+                    );
+                    if !ty.is_complete() {
+                        self.diags.error(
+                            *span,
+                            format!(
+                                "Invalid application of '_Alignas' to an incomplete type '{ty}'"
+                            ),
+                        );
+                        continue;
+                    }
+                    (ty.align() as i64, *span)
+                }
+            };
+
+            // Zero has no effect.
+            if requested == 0 {
+                continue;
+            }
+            if requested < 0 || (requested as u64).count_ones() != 1 {
+                self.diags
+                    .error(span, "Requested alignment is not a power of 2");
+            } else if requested > MAX_ALIGNMENT {
+                self.diags.error(
+                    span,
+                    format!("Requested alignment must be {MAX_ALIGNMENT} bytes or smaller"),
+                );
+            } else if (requested as usize) < ty.align() {
+                self.diags.error(
+                    span,
+                    format!(
+                        "Requested alignment is less than minimum alignment of {} for type '{ty}'",
+                        ty.align()
+                    ),
+                );
+            } else {
+                align = align.max(requested as usize);
+            }
+        }
+        align
+    }
+
+    // Like `alignment`, also raised by `aligned`, and limited by the alignment of the frame.
+    fn local_alignment(
+        &mut self,
+        alignas: &[Alignas],
+        aligned: Option<usize>,
+        ty: &Type,
+        ident: &Ident,
+    ) -> usize {
+        let align = self.alignment(alignas, ty).max(aligned.unwrap_or(1));
+        if align > 16 {
+            self.diags.error(
+                ident.span,
+                format!(
+                    "Alignment of {align} bytes is not supported for local variable '{}'",
+                    ident.name
+                ),
+            );
+            return ty.align();
+        }
+        align
+    }
+
+    // What the attributes of a declaration mean, after reporting those that don't apply to
+    // `target` or are invalid.
+    fn attributes(
+        &mut self,
+        attributes: &[Attribute],
+        asm_label: &Option<Ident>,
+        target: AttributeTarget,
+    ) -> Attributes {
+        let mut result = Attributes::default();
+        let is_symbol = matches!(
+            target,
+            AttributeTarget::Function { .. } | AttributeTarget::Variable { .. }
+        );
+        match asm_label {
+            Some(label) if is_symbol => result.symbol.label = Some(label.name.clone()),
+            Some(label) => self.diags.emit(Diagnostic::warning(
+                Warning::IgnoredAttributes,
+                label.span,
+                "Asm label on a variable with automatic storage is ignored",
+            )),
+            None => {}
+        }
+
+        for attribute in attributes {
+            let Ident { name, span } = &attribute.name;
+            let (applies, applies_to) = match name.as_str() {
+                "noreturn" | "format" | "constructor" | "destructor" => (
+                    matches!(target, AttributeTarget::Function { .. }),
+                    "functions",
+                ),
+                "aligned" => (
+                    !matches!(target, AttributeTarget::Function { .. }),
+                    "variables, fields and structs",
+                ),
+                "packed" => (
+                    matches!(target, AttributeTarget::Field | AttributeTarget::Struct),
+                    "structs and fields",
+                ),
+                "weak" | "visibility" | "section" => (
+                    is_symbol,
+                    "functions and variables with static storage duration",
+                ),
+                "unused" => (true, ""),
+                name if IGNORED_ATTRIBUTES.contains(&name) => continue,
+                _ => {
+                    self.diags.emit(Diagnostic::warning(
+                        Warning::UnknownAttributes,
+                        *span,
+                        format!("Unknown attribute '{name}' ignored"),
+                    ));
+                    continue;
+                }
+            };
+            if !applies {
+                self.diags.emit(Diagnostic::warning(
+                    Warning::IgnoredAttributes,
+                    *span,
+                    format!("'{name}' attribute only applies to {applies_to}"),
+                ));
+                continue;
+            }
+
+            let (min, max) = match name.as_str() {
+                "aligned" | "constructor" | "destructor" => (0, 1),
+                "visibility" | "section" => (1, 1),
+                "format" => (3, 3),
+                _ => (0, 0),
+            };
+            let count = attribute.args.len();
+            if count < min || count > max {
+                let expected = match (min, max) {
+                    (0, 0) => "takes no arguments".to_string(),
+                    (1, 1) => "takes one argument".to_string(),
+                    (0, _) => format!("takes no more than {max} argument"),
+                    _ => format!("requires exactly {max} arguments"),
+                };
+                self.diags
+                    .error(*span, format!("'{name}' attribute {expected}"));
+                continue;
+            }
+
+            match (name.as_str(), target) {
+                ("noreturn", _) => result.is_noreturn = true,
+                ("packed", _) => result.packed = true,
+                ("unused", _) => result.is_unused = true,
+                (
+                    "weak",
+                    AttributeTarget::Function {
+                        is_static: true, ..
+                    }
+                    | AttributeTarget::Variable { is_static: true },
+                ) => self
+                    .diags
+                    .error(*span, "Weak declaration cannot have internal linkage"),
+                ("weak", _) => result.symbol.is_weak = true,
+                ("aligned", _) => {
+                    let align = match attribute.args.first() {
+                        Some(node) => self.attribute_alignment(node),
+                        None => Some(BIGGEST_ALIGNMENT),
+                    };
+                    if let Some(align) = align {
+                        result.aligned = Some(result.aligned.unwrap_or(1).max(align));
+                    }
+                }
+                ("visibility", _) => {
+                    let Some((visibility, span)) = self.string_argument(attribute) else {
+                        continue;
+                    };
+                    result.symbol.visibility = match visibility.as_str() {
+                        "default" => Some(Visibility::Default),
+                        "hidden" => Some(Visibility::Hidden),
+                        "protected" => Some(Visibility::Protected),
+                        "internal" => Some(Visibility::Internal),
+                        _ => {
+                            self.diags.emit(Diagnostic::warning(
+                                Warning::IgnoredAttributes,
+                                span,
+                                format!("Unknown visibility '{visibility}'"),
+                            ));
+                            continue;
+                        }
+                    };
+                }
+                ("section", _) => {
+                    if let Some((section, _)) = self.string_argument(attribute) {
+                        result.symbol.section = Some(section);
+                    }
+                }
+                ("constructor" | "destructor", _) => {
+                    let priority = match attribute.args.first() {
+                        Some(node) => match self.integer_constant(node).map(u16::try_from) {
+                            Some(Ok(priority)) => priority,
+                            Some(Err(_)) => {
+                                self.diags.error(
+                                    node.span,
+                                    format!(
+                                        "'{name}' attribute requires integer constant between 0 \
+                                         and 65535 inclusive"
+                                    ),
+                                );
+                                continue;
+                            }
+                            None => continue,
+                        },
+                        None => u16::MAX,
+                    };
+                    if name == "constructor" {
+                        result.symbol.constructor = Some(priority);
+                    } else {
+                        result.symbol.destructor = Some(priority);
+                    }
+                }
+                ("format", AttributeTarget::Function { ty, .. }) => {
+                    result.format = self.format_attribute(attribute, ty);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        result
+    }
+
+    // The alignment requested by `aligned(align)`, if it's valid.
+    fn attribute_alignment(&mut self, node: &Node) -> Option<usize> {
+        let requested = self.integer_constant(node)?;
+        if requested <= 0 || (requested as u64).count_ones() != 1 {
+            self.diags
+                .error(node.span, "Requested alignment is not a power of 2");
+            return None;
+        }
+        if requested > MAX_ALIGNMENT {
+            self.diags.error(
+                node.span,
+                format!("Requested alignment must be {MAX_ALIGNMENT} bytes or smaller"),
+            );
+            return None;
+        }
+        Some(requested as usize)
+    }
+
+    // The string literal that is the only argument of `attribute`, with its span.
+    fn string_argument(&mut self, attribute: &Attribute) -> Option<(String, Span)> {
+        let node = &attribute.args[0];
+        match &node.kind {
+            NodeKind::Str(str) => Some((str.clone(), node.span)),
+            _ => {
+                self.diags.error(
+                    node.span,
+                    format!("'{}' attribute requires a string", attribute.name.name),
+                );
+                None
+            }
+        }
+    }
+
+    // `format(style, string, first)` on a function of type `ty`, if it's valid.
+    fn format_attribute(&mut self, attribute: &Attribute, ty: &Type) -> Option<Format> {
+        let Type::Function {
+            params,
+            is_variadic,
+            ..
+        } = ty
+        else {
+            unreachable!()
+        };
+        let [style, string, first] = &attribute.args[..] else {
+            unreachable!()
+        };
+
+        let NodeKind::Var { name } = &style.kind else {
+            self.diags.error(
+                style.span,
+                "'format' attribute requires parameter 1 to be an identifier",
+            );
+            return None;
+        };
+        let name = name
+            .strip_prefix("__")
+            .and_then(|name| name.strip_suffix("__"))
+            .unwrap_or(name);
+        let style = match name {
+            "printf" | "gnu_printf" => Style::Printf,
+            "scanf" | "gnu_scanf" => Style::Scanf,
+            // Known, but not checked.
+            "strftime" | "gnu_strftime" | "strfmon" => return None,
+            _ => {
+                self.diags.emit(Diagnostic::warning(
+                    Warning::IgnoredAttributes,
+                    style.span,
+                    format!("'format' attribute argument not supported: {name}"),
+                ));
+                return None;
+            }
+        };
+
+        let index = self.integer_constant(string)?;
+        if index < 1 || index as usize > params.len() {
+            self.diags.error(
+                string.span,
+                "'format' attribute parameter 2 is out of bounds",
+            );
+            return None;
+        }
+        let index = index as usize - 1;
+        if !matches!(params[index].unqualified(), Type::Pointer(base) if *base.unqualified() == Type::Char)
+        {
+            self.diags
+                .error(string.span, "Format argument not a string type");
+            return None;
+        }
+
+        let first = match self.integer_constant(first)? {
+            0 => None,
+            _ if !is_variadic => {
+                self.diags
+                    .error(first.span, "'format' attribute requires variadic function");
+                return None;
+            }
+            value if value as usize != params.len() + 1 => {
+                self.diags.error(
+                    first.span,
+                    "'format' attribute parameter 3 is out of bounds",
+                );
+                return None;
+            }
+            value => Some(value as usize - 1),
+        };
+
+        Some(Format {
+            style,
+            string: index,
+            first,
+        })
+    }
+
+    // The type of a parameter, where arrays and functions decay to pointers. `span` is for errors.
+    fn param_type(&mut self, ty: &TypeName, span: Span) -> Type {
+        let ty = match ty {
+            // The length is ignored.
+            TypeName::Array { element, .. } => Type::pointer_to(self.resolve_type(element, span)),
+            ty => self.resolve_type(ty, span),
+        };
+
+        match ty.unqualified() {
+            Type::Function { .. } => Type::pointer_to(ty),
+            Type::Void => {
+                self.diags.error(span, "Parameter can't have type 'void'");
+                Type::Int
+            }
+            _ => ty,
+        }
+    }
+
+    // The type of a function declarator. `span` is where errors are reported, unless a parameter
+    // has a name.
+    fn function_type(
+        &mut self,
+        return_type: &TypeName,
+        params: &[Param],
+        is_variadic: bool,
+        span: Span,
+    ) -> Type {
+        // Qualifiers of the returned value don't matter.
+        let return_type = self.resolve_type(return_type, span).unqualified().clone();
+        if let Type::Array { .. } | Type::Function { .. } = return_type {
+            let kind = match return_type {
+                Type::Array { .. } => "array",
+                _ => "function",
+            };
+            self.diags.error(
+                span,
+                format!("Function cannot return {kind} type '{return_type}'"),
+            );
+        }
+
+        let params: Vec<_> = params
+            .iter()
+            .map(|param| {
+                let span = param.ident.as_ref().map_or(span, |ident| ident.span);
+                self.param_type(&param.ty, span)
+            })
+            .collect();
+
+        Type::Function {
+            return_type: Box::new(return_type),
+            params,
+            is_variadic,
+        }
+    }
+
+    // The type that a type name denotes. `span` is where errors about array lengths are reported.
+    fn resolve_type(&mut self, ty: &TypeName, span: Span) -> Type {
+        match ty {
+            TypeName::Base(ty) => ty.clone(),
+            TypeName::Pointer(base) => Type::pointer_to(self.resolve_type(base, span)),
+            TypeName::Array { element, len } => {
+                let mut element = self.resolve_type(element, span);
+                if !element.is_complete() {
+                    self.diags.error(
+                        span,
+                        format!("Array has incomplete element type '{element}'"),
+                    );
+                    element = Type::Int;
+                }
+
+                let len = match len.as_ref().map(|len| self.array_len(len)) {
+                    Some(Ok(len)) => Some(len),
+                    Some(Err(len)) => return self.variable_array(element, len, span),
+                    None => None,
+                };
+                // Each element has a variable length, so the array too.
+                match (&element, len) {
+                    (Type::VariableArray { .. }, Some(len)) => {
+                        let len = Expr::new(ExprKind::Num(len as i64), Type::ULong, span);
+                        self.variable_array(element, len, span)
+                    }
+                    _ => Type::array_of(element, len),
+                }
+            }
+            TypeName::Struct(spec) => Type::Struct(self.struct_type(spec)),
+            TypeName::Qualified { qualifiers, ty } => {
+                let ty = self.resolve_type(ty, span);
+                if qualifiers.is_restrict && !matches!(ty.unqualified(), Type::Pointer(_)) {
+                    self.diags.error(
+                        span,
+                        format!("Restrict requires a pointer ('{ty}' is invalid)"),
+                    );
+                    return ty;
+                }
+                // Only scalars can be operated on by a single instruction.
+                if qualifiers.is_atomic && !ty.is_scalar() {
+                    self.diags.error(
+                        span,
+                        format!("_Atomic cannot be applied to non-scalar type '{ty}'"),
+                    );
+                    return ty;
+                }
+
+                Type::qualified(ty, *qualifiers)
+            }
+            TypeName::Function {
+                return_type,
+                params,
+                is_variadic,
+            } => self.function_type(return_type, params, *is_variadic, span),
+            // Like with `sizeof`, the expression isn't evaluated.
+            TypeName::Typeof(node) => self.expr(node).ty,
+        }
+    }
+
+    // The length of an array, or the expression computing it if it's only known at runtime. 1 after
+    // reporting an error.
+    fn array_len(&mut self, node: &Node) -> Result<usize, Expr> {
+        let expr = self.rvalue(node);
+        if expr.is_error() {
+            return Ok(1);
+        }
+        if !expr.ty.is_integer() {
+            self.diags.error(
+                expr.span,
+                format!("Size of array has non-integer type '{}'", expr.ty),
+            );
+            return Ok(1);
+        }
+
+        match const_eval::eval(&expr, self.target) {
+            Ok(Constant {
+                overflow: Some(span),
+                ..
+            }) => {
+                self.constant_overflow(span);
+                Ok(1)
+            }
+            Ok(Constant { value, .. }) if value < 0 => {
+                self.diags.error(expr.span, "Array has negative size");
+                Ok(1)
+            }
+            Ok(Constant { value, .. }) => Ok(value as usize),
+            Err(NotConstant::Runtime(_)) => Err(expr),
+            Err(NotConstant::DivisionByZero(span)) => {
+                self.constant_division_by_zero(span);
+                Ok(1)
+            }
+        }
+    }
+
+    // An array of `len` elements of `element`, if variable length arrays are allowed where the type
+    // is. Its size is computed into a new frame slot.
+    fn variable_array(&mut self, element: Type, len: Expr, span: Span) -> Type {
+        if let Err(message) = self.variable_sizes {
+            self.diags.error(len.span, message);
+            return Type::array_of(element, Some(1));
+        }
+
+        let element_size = self.size_of(&element, "sizeof", span);
+        let size = self.arith_op(ArithOp::Mul, convert(len, &Type::ULong), element_size, span);
+        let offset = self.allocate(&Type::ULong);
+        let slot = Expr::new(ExprKind::Local { offset }, Type::ULong, span);
+        let assignment = self.assign(slot, size, span);
+        self.variable_sizes.as_mut().unwrap().push(assignment);
+
+        Type::VariableArray {
+            element: Box::new(element),
+            size: offset,
+        }
+    }
+
+    // Like `resolve_type` in a block, where arrays can have a variable length. Also returns the
+    // expressions computing their sizes, which must be evaluated before the type is used.
+    fn resolve_block_type(&mut self, ty: &TypeName, span: Span) -> (Type, Vec<Expr>) {
+        if self.function.scopes.is_empty() {
+            return (self.resolve_type(ty, span), vec![]);
+        }
+
+        let outer = std::mem::replace(&mut self.variable_sizes, Ok(vec![]));
+        let ty = self.resolve_type(ty, span);
+        let sizes = std::mem::replace(&mut self.variable_sizes, outer).unwrap();
+        (ty, sizes)
+    }
+
+    // Like `resolve_type` where arrays can't have a variable length, for the reason in `message`.
+    fn resolve_fixed_type(&mut self, ty: &TypeName, span: Span, message: &'static str) -> Type {
+        let outer = std::mem::replace(&mut self.variable_sizes, Err(message));
+        let ty = self.resolve_type(ty, span);
+        self.variable_sizes = outer;
+        ty
+    }
+
+    // The struct a specifier refers to, defining it if it has members.
+    fn struct_type(&mut self, spec: &Rc<StructSpec>) -> StructRef {
+        let key = Rc::as_ptr(spec);
+        if let Some(struct_ref) = self.structs.get(&key) {
+            return struct_ref.clone();
+        }
+
+        let new = |tag: &Ident| {
+            if spec.is_union {
+                StructRef::new_union(Some(tag.name.clone()))
+            } else {
+                StructRef::new(Some(tag.name.clone()))
+            }
+        };
+        let struct_ref = match (&spec.tag, &spec.members) {
+            (Some(tag), None) => {
+                let declared = self
+                    .tags
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.get(&tag.name))
+                    .cloned();
+
+                // Using an undeclared tag declares it in the current scope.
+                match declared {
+                    Some(declared) if declared.is_union() != spec.is_union => {
+                        self.tag_mismatch(tag);
+                        new(tag)
+                    }
+                    Some(declared) => declared,
+                    None => self.declare_tag(new(tag)),
+                }
+            }
+            (Some(tag), Some(_)) => match self.tags.last().unwrap().get(&tag.name) {
+                Some(declared) if declared.is_union() != spec.is_union => {
+                    self.tag_mismatch(tag);
+                    new(tag)
+                }
+                // The definition completes a declaration in the same scope.
+                Some(declared) if declared.borrow().members.is_none() => declared.clone(),
+                Some(declared) => {
+                    let message = format!("Redefinition of '{} {}'", declared.kind(), tag.name);
+                    self.diags.error(tag.span, message);
+                    new(tag)
+                }
+                None => self.declare_tag(new(tag)),
+            },
+            (None, _) if spec.is_union => StructRef::new_union(None),
+            (None, _) => StructRef::new(None),
+        };
+        self.structs.insert(key, struct_ref.clone());
+
+        if let Some(members) = &spec.members {
+            let struct_attributes =
+                self.attributes(&spec.attributes, &None, AttributeTarget::Struct);
+            let mut fields = vec![];
+            let mut spans = HashMap::new();
+
+            for MemberDecl {
+                ty,
+                ident,
+                width,
+                alignas,
+                attributes,
+                span,
+            } in members
+            {
+                let span = match (ident, width) {
+                    (Some(ident), _) => ident.span,
+                    (None, Some(width)) => width.span,
+                    (None, None) => *span,
+                };
+                let ty = self.resolve_fixed_type(ty, span, "Fields must have a constant size");
+                let width = match width {
+                    Some(width) => match self.bit_field_width(width, &ty, ident.as_ref()) {
+                        Some(width) => Some(width),
+                        // Already reported, so it's only kept if it can be a member.
+                        None if ident.is_none() || !ty.is_complete() => continue,
+                        None => None,
+                    },
+                    None => None,
+                };
+                let align = match (alignas.first(), width) {
+                    (None, _) => None,
+                    (Some(alignas), Some(_)) => {
+                        self.diags.error(
+                            alignas.span(),
+                            "'_Alignas' attribute cannot be applied to a bit-field",
+                        );
+                        None
+                    }
+                    (Some(_), None) if ty.is_complete() => Some(self.alignment(alignas, &ty)),
+                    (Some(_), None) => None,
+                };
+                let attributes = self.attributes(attributes, &None, AttributeTarget::Field);
+                let align = match (align, attributes.aligned) {
+                    (Some(align), Some(aligned)) => Some(align.max(aligned)),
+                    (align, aligned) => align.or(aligned),
+                };
+                let packed = struct_attributes.packed || attributes.packed;
+                let Some(ident) = ident else {
+                    if width.is_some() {
+                        fields.push(Field {
+                            name: None,
+                            ty,
+                            width,
+                            align,
+                            packed,
+                        });
+                        continue;
+                    }
+
+                    // The members of an anonymous struct or union are found through this one.
+                    match ty.unqualified() {
+                        Type::Struct(inner) if inner.borrow().tag.is_none() => {
+                            for name in inner.member_names() {
+                                if let Some(&previous_span) = spans.get(&name) {
+                                    self.diags.emit(
+                                        Diagnostic::error(
+                                            span,
+                                            format!("Duplicate member '{name}'"),
+                                        )
+                                        .with_note(
+                                            Some(previous_span),
+                                            "Previous declaration is here",
+                                        ),
+                                    );
+                                } else {
+                                    spans.insert(name, span);
+                                }
+                            }
+                            fields.push(Field {
+                                name: None,
+                                ty,
+                                width: None,
+                                align,
+                                packed,
+                            });
+                        }
+                        _ => self.diags.emit(Diagnostic::warning(
+                            Warning::MissingDeclarations,
+                            span,
+                            "Declaration does not declare anything",
+                        )),
+                    }
+                    continue;
+                };
+
+                if !ty.is_complete() {
+                    self.diags.error(
+                        ident.span,
+                        format!("Field '{}' has incomplete type '{ty}'", ident.name),
+                    );
+                } else if let Some(&previous_span) = spans.get(&ident.name) {
+                    self.diags.emit(
+                        Diagnostic::error(ident.span, format!("Duplicate member '{}'", ident.name))
+                            .with_note(Some(previous_span), "Previous declaration is here"),
+                    );
+                } else {
+                    spans.insert(ident.name.clone(), ident.span);
+                    fields.push(Field {
+                        width,
+                        align,
+                        packed,
+                        ..Field::new(&ident.name, ty)
+                    });
+                }
+            }
+
+            let names: Vec<_> = fields.iter().map(|field| field.name.clone()).collect();
+            if let Err(index) = struct_ref.define(fields) {
+                let name = names[index].as_deref().unwrap_or("<anonymous>");
+                self.diags.error(
+                    spans.get(name).copied().unwrap_or(spec.span),
+                    format!(
+                        "Packed bit-field '{name}' crosses its storage unit, which is not supported"
+                    ),
+                );
+            }
+            if let Some(aligned) = struct_attributes.aligned {
+                struct_ref.align_to(aligned);
+            }
+        }
+        for node in &spec.static_asserts {
+            self.static_assert(node);
+        }
+
+        struct_ref
+    }
+
+    // Reports a `_Static_assert` whose condition is zero.
+    fn static_assert(&mut self, node: &Node) {
+        let NodeKind::StaticAssert { cond, message } = &node.kind else {
+            unreachable!()
+        };
+        if self.integer_constant(cond) == Some(0) {
+            self.diags
+                .error(cond.span, format!("Static assertion failed: {message}"));
+        }
+    }
+
+    fn tag_mismatch(&mut self, tag: &Ident) {
+        self.diags.error(
+            tag.span,
+            format!(
+                "Use of '{}' with tag type that does not match previous declaration",
+                tag.name
+            ),
+        );
+    }
+
+    // The width of a bit-field of a `ty`, or None after reporting why it's invalid.
+    fn bit_field_width(&mut self, node: &Node, ty: &Type, ident: Option<&Ident>) -> Option<usize> {
+        let (subject, span) = match ident {
+            Some(ident) => (format!("bit-field '{}'", ident.name), ident.span),
+            None => ("anonymous bit-field".to_string(), node.span),
+        };
+        let capitalized = format!("{}{}", subject[..1].to_uppercase(), &subject[1..]);
+
+        if !ty.is_integer() {
+            self.diags
+                .error(span, format!("{capitalized} has non-integral type '{ty}'"));
+            return None;
+        }
+        // It has no address to operate on.
+        if ty.qualifiers().is_atomic {
+            self.diags
+                .error(span, format!("{capitalized} has atomic type '{ty}'"));
+            return None;
+        }
+        let width = self.integer_constant(node)?;
+
+        let bits = if *ty.unqualified() == Type::Bool {
+            1
+        } else {
+            ty.size() as i64 * 8
+        };
+        if width < 0 {
+            self.diags.error(
+                node.span,
+                format!("{capitalized} has negative width ({width})"),
+            );
+            return None;
+        }
+        if width == 0 && ident.is_some() {
+            self.diags
+                .error(node.span, format!("Named {subject} has zero width"));
+            return None;
+        }
+        if width > bits {
+            let plural = |bits| if bits == 1 { "bit" } else { "bits" };
+            self.diags.error(
+                node.span,
+                format!(
+                    "Width of {subject} ({width} {}) exceeds the width of its type ({bits} {})",
+                    plural(width),
+                    plural(bits)
+                ),
+            );
+            return None;
+        }
+
+        Some(width as usize)
+    }
+
+    fn declare_tag(&mut self, struct_ref: StructRef) -> StructRef {
+        let tag = struct_ref.borrow().tag.clone().unwrap();
+        self.tags
+            .last_mut()
+            .unwrap()
+            .insert(tag, struct_ref.clone());
+
+        struct_ref
+    }
+
+    fn declare_local(&mut self, ident: &Ident, ty: Type, param: bool) -> usize {
+        let align = ty.align();
+        self.declare_aligned_local(ident, ty, param, align)
+    }
+
+    fn declare_aligned_local(
+        &mut self,
+        ident: &Ident,
+        ty: Type,
+        param: bool,
+        align: usize,
+    ) -> usize {
+        // A variable length array is allocated where it's declared, and its slot holds its address.
+        let offset = match ty {
+            Type::VariableArray { .. } => self.allocate(&Type::pointer_to(ty.clone())),
+            _ => self.allocate_aligned(ty.size(), align),
+        };
+
+        self.declare(LocalVariable {
+            name: ident.name.clone(),
+            ty,
+            span: ident.span,
+            offset,
+            label: None,
+            is_thread_local: false,
+            is_register: false,
+            read: false,
+            param,
+        })
+    }
+
+    // Adds a variable to the innermost scope, and returns its index in `locals`.
+    fn declare(&mut self, variable: LocalVariable) -> usize {
+        if let Some(&previous) = self.function.scopes.last().unwrap().get(&variable.name) {
+            let previous_span = self.function.locals[previous].span;
+            self.diags.emit(
+                Diagnostic::error(
+                    variable.span,
+                    format!("Redefinition of '{}'", variable.name),
+                )
+                .with_note(Some(previous_span), "Previous definition is here"),
+            );
+            return previous;
+        }
+
+        let shadowed = match self.resolve(&variable.name) {
+            Some(Resolved::Local(index)) => {
+                Some(("a local variable", self.function.locals[index].span))
+            }
+            // Unless it's the global itself, declared `extern` in a block.
+            Some(Resolved::Global(Symbol::Variable { span, .. }))
+                if variable.label.as_ref() != Some(&variable.name) =>
+            {
+                Some(("a variable in the global scope", *span))
+            }
+            _ => None,
+        };
+        if let Some((kind, previous_span)) = shadowed {
+            self.diags.emit(
+                Diagnostic::warning(
+                    Warning::Shadow,
+                    variable.span,
+                    format!("Declaration shadows {kind}"),
+                )
+                .with_note(Some(previous_span), "Previous declaration is here"),
+            );
+        }
+
+        let index = self.function.locals.len();
+        self.function
+            .scopes
+            .last_mut()
+            .unwrap()
+            .insert(variable.name.clone(), index);
+        self.function.locals.push(variable);
+
+        index
+    }
+
+    // Reserves space for a `ty` in the frame, and returns its offset.
+    fn allocate(&mut self, ty: &Type) -> usize {
+        self.allocate_aligned(ty.size(), ty.align())
+    }
+
+    // The frame pointer is only aligned to 16 bytes, so `align` can't be larger.
+    fn allocate_aligned(&mut self, size: usize, align: usize) -> usize {
+        self.function.stack_size = (self.function.stack_size + size).next_multiple_of(align);
+        self.function.stack_size
+    }
+
+    fn resolve(&self, name: &str) -> Option<Resolved> {
+        self.function
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|&index| Resolved::Local(index))
+            .or_else(|| self.globals.get(name).map(Resolved::Global))
+    }
+
+    fn stmt(&mut self, node: &Node) -> Stmt {
+        match &node.kind {
+            NodeKind::Decl {
+                declspec,
+                storage,
+                is_thread_local,
+                alignas,
+                declarators,
+            } => {
+                if declarators.is_empty() {
+                    self.resolve_type(declspec, node.span);
+                }
+                if let (Some(alignas), Some(StorageClass::Register)) = (alignas.first(), storage) {
+                    self.diags.error(
+                        alignas.span(),
+                        "'_Alignas' attribute cannot be applied to a variable with 'register' \
+                         storage class",
+                    );
+                }
+
+                Stmt::Block(
+                    declarators
+                        .iter()
+                        .filter_map(|declarator| match (storage, &declarator.ty) {
+                            (_, TypeName::Function { .. }) => {
+                                if *storage == Some(StorageClass::Static) {
+                                    self.diags.error(
+                                        declarator.ident.span,
+                                        "Function declared in block scope cannot have 'static' \
+                                         storage class",
+                                    );
+                                }
+                                self.function_declaration(declarator, false);
+                                None
+                            }
+                            (Some(StorageClass::Static), _) => {
+                                self.static_local(declarator, *is_thread_local, alignas);
+                                None
+                            }
+                            (Some(StorageClass::Extern), _) => {
+                                self.extern_local(declarator, *is_thread_local);
+                                None
+                            }
+                            _ => self.local_declaration(
+                                declarator,
+                                alignas,
+                                *storage == Some(StorageClass::Register),
+                            ),
+                        })
+                        .collect(),
+                )
+            }
+            NodeKind::Ret { value } => {
+                if self.function.is_noreturn {
+                    self.diags.emit(Diagnostic::warning(
+                        Warning::InvalidNoreturn,
+                        node.span,
+                        format!(
+                            "Function '{}' declared 'noreturn' should not return",
+                            self.function.name
+                        ),
+                    ));
+                }
+                let return_type = self.function.return_type.clone();
+                match (value, return_type) {
+                    (Some(value), Type::Void) => {
+                        self.expr(value);
+                        self.diags.error(
+                            value.span,
+                            format!(
+                                "Void function '{}' should not return a value",
+                                self.function.name
+                            ),
+                        );
+                        Stmt::Return(None)
+                    }
+                    (Some(value), return_type) => {
+                        let value = self.rvalue(value);
+                        Stmt::Return(Some(self.convert_for_assignment(value, &return_type)))
+                    }
+                    (None, Type::Void) => Stmt::Return(None),
+                    (None, _) => {
+                        self.diags.error(
+                            node.span,
+                            format!(
+                                "Non-void function '{}' should return a value",
+                                self.function.name
+                            ),
+                        );
+                        Stmt::Return(None)
+                    }
+                }
+            }
+            NodeKind::Block { stmts } => {
+                self.function.scopes.push(HashMap::new());
+                self.tags.push(HashMap::new());
+                let stmts = stmts.iter().map(|stmt| self.stmt(stmt)).collect();
+                self.tags.pop();
+                let scope = self.function.scopes.pop().unwrap();
+
+                let declares_variable_arrays = scope.values().any(|&index| {
+                    matches!(self.function.locals[index].ty, Type::VariableArray { .. })
+                });
+                if declares_variable_arrays {
+                    Stmt::StackBlock { stmts }
+                } else {
+                    Stmt::Block(stmts)
+                }
+            }
+            NodeKind::If { cond, then, else_ } => Stmt::If {
+                cond: self.cond(cond),
+                then: Box::new(self.stmt(then)),
+                else_: else_.as_ref().map(|else_| Box::new(self.stmt(else_))),
+            },
+            NodeKind::While { cond, then } => Stmt::While {
+                cond: self.cond(cond),
+                then: Box::new(self.stmt(then)),
+            },
+            NodeKind::For {
+                init,
+                cond,
+                update,
+                then,
+            } => Stmt::For {
+                init: init.as_ref().map(|init| self.expr(init)),
+                cond: cond.as_ref().map(|cond| self.cond(cond)),
+                update: update.as_ref().map(|update| self.expr(update)),
+                then: Box::new(self.stmt(then)),
+            },
+            NodeKind::Function { .. } => {
+                unreachable!("Functions are only defined at the top level")
+            }
+            NodeKind::StaticAssert { .. } => {
+                self.static_assert(node);
+                Stmt::Block(vec![])
+            }
+            NodeKind::Asm {
+                template,
+                is_extended,
+                outputs,
+                inputs,
+                clobbers,
+            } => Stmt::Asm(self.inline_asm(template, *is_extended, outputs, inputs, clobbers)),
+            _ => Stmt::Expr(self.expr(node)),
+        }
+    }
+
+    // An asm statement, where each operand gets a register, a memory location or an immediate.
+    // Operands never share registers, unless an input is tied to an output.
+    fn inline_asm(
+        &mut self,
+        template: &Ident,
+        is_extended: bool,
+        outputs: &[parser::AsmOperand],
+        inputs: &[parser::AsmOperand],
+        clobbers: &[Ident],
+    ) -> InlineAsm {
+        if !is_extended {
+            return InlineAsm {
+                template: vec![Piece::Text(template.name.clone())],
+                operands: vec![],
+                saved: vec![],
+            };
+        }
+
+        let names: Vec<_> = outputs
+            .iter()
+            .chain(inputs)
+            .map(|operand| operand.name.as_ref().map(|name| name.name.as_str()))
+            .collect();
+        let pieces = inline_asm::template(&template.name, &names).unwrap_or_else(|message| {
+            self.diags.error(template.span, message);
+            vec![]
+        });
+
+        let mut clobbered = vec![];
+        for clobber in clobbers {
+            match Register::from_name(&clobber.name) {
+                Some(Register::RSP | Register::RBP) => self.diags.error(
+                    clobber.span,
+                    format!("Clobbering '{}' is not supported", clobber.name),
+                ),
+                Some(register) => clobbered.push(register),
+                // Vector and x87 registers aren't used around the asm.
+                None if ["memory", "cc", "flags", "dirflag", "fpsr", "fpcr"]
+                    .contains(&clobber.name.as_str())
+                    || ["xmm", "ymm", "zmm", "st", "mm"]
+                        .iter()
+                        .any(|prefix| clobber.name.starts_with(prefix)) => {}
+                None => self.diags.error(
+                    clobber.span,
+                    format!("Unknown register name '{}' in asm", clobber.name),
+                ),
+            }
+        }
+
+        // The fixed registers of the outputs and the inputs. An input can be in the same register
+        // as an output, since it's read before the output is written.
+        let mut fixed = [vec![], vec![]];
+        // Operands with the `r` constraint, which get a register once the fixed ones are known.
+        let mut unassigned = vec![];
+        // Inputs in the place of an output, by their indices.
+        let mut tied = vec![];
+        let mut operands = vec![];
+        for (index, operand) in outputs.iter().chain(inputs).enumerate() {
+            let is_output = index < outputs.len();
+            let Some((expr, place)) = self.asm_operand(operand, is_output, &operands) else {
+                continue;
+            };
+            let constraint = inline_asm::constraint(&operand.constraint.name).unwrap_or_default();
+            match place {
+                _ if constraint.tied.is_some() => {
+                    tied.push((operands.len(), constraint.tied.unwrap()));
+                }
+                Some(AsmPlace::Register(register)) => {
+                    let fixed = &mut fixed[usize::from(!is_output)];
+                    if clobbered.contains(&register) {
+                        self.diags.error(
+                            operand.constraint.span,
+                            format!(
+                                "Asm operand in register '{register}' conflicts with the \
+                                 clobber list"
+                            ),
+                        );
+                    } else if fixed.contains(&register) {
+                        self.diags.error(
+                            operand.constraint.span,
+                            format!("Register '{register}' is used by more than one asm operand"),
+                        );
+                    }
+                    fixed.push(register);
+                }
+                Some(AsmPlace::Memory { base: None }) if !is_simple_address(&expr) => {
+                    unassigned.push(operands.len());
+                }
+                None => unassigned.push(operands.len()),
+                _ => {}
+            }
+            operands.push(AsmOperand {
+                expr,
+                place: place.unwrap_or(AsmPlace::Register(Register::RAX)),
+                is_output,
+                is_read: !is_output || constraint.is_read_write,
+            });
+        }
+
+        let [mut taken, fixed_inputs] = fixed;
+        taken.extend(fixed_inputs);
+        taken.extend(clobbered);
+        let free: Vec<_> = Register::ALLOCATABLE
+            .into_iter()
+            .filter(|register| !taken.contains(register))
+            .collect();
+        let mut free = free.into_iter();
+        for index in unassigned {
+            let Some(register) = free.next() else {
+                self.diags.error(
+                    template.span,
+                    "Inline asm needs more registers than are available",
+                );
+                break;
+            };
+            taken.push(register);
+            let operand = &mut operands[index];
+            operand.place = match operand.place {
+                AsmPlace::Memory { .. } => AsmPlace::Memory {
+                    base: Some(register),
+                },
+                _ => AsmPlace::Register(register),
+            };
+        }
+        for (index, output) in tied {
+            operands[index].place = operands[output].place.clone();
+        }
+
+        let mut saved = vec![];
+        for register in taken {
+            if register.is_callee_saved() && !saved.contains(&register) {
+                saved.push(register);
+            }
+        }
+        InlineAsm {
+            template: pieces,
+            operands,
+            saved,
+        }
+    }
+
+    // The expression of an asm operand, and where it is unless it needs a register, or None
+    // after reporting why it's invalid. `previous` are the operands before it.
+    fn asm_operand(
+        &mut self,
+        parser::AsmOperand {
+            constraint, expr, ..
+        }: &parser::AsmOperand,
+        is_output: bool,
+        previous: &[AsmOperand],
+    ) -> Option<(Expr, Option<AsmPlace>)> {
+        let kind = if is_output { "output" } else { "input" };
+        let invalid = |this: &mut Self| {
+            this.diags.error(
+                constraint.span,
+                format!("Invalid {kind} constraint '{}' in asm", constraint.name),
+            );
+        };
+        let Ok(parsed) = inline_asm::constraint(&constraint.name) else {
+            invalid(self);
+            return None;
+        };
+        if is_output != (parsed.is_output || parsed.is_read_write)
+            || (is_output && parsed.tied.is_some())
+        {
+            invalid(self);
+            return None;
+        }
+
+        if let Some(index) = parsed.tied {
+            let value = self.rvalue(expr);
+            // The place of an output with the `r` constraint isn't known yet.
+            return match previous.get(index) {
+                Some(AsmOperand {
+                    place: AsmPlace::Register(_),
+                    is_output: true,
+                    ..
+                }) => Some((self.asm_register_operand(value)?, None)),
+                _ => {
+                    invalid(self);
+                    None
+                }
+            };
+        }
+
+        if is_output {
+            let lvalue = self.modifiable_lvalue(expr)?;
+            return match parsed.register {
+                Some(register) => Some((
+                    self.asm_register_operand(lvalue)?,
+                    register.map(AsmPlace::Register),
+                )),
+                None if parsed.memory && lvalue.is_bit_field() => {
+                    self.diags
+                        .error(expr.span, "Address of bit-field requested");
+                    None
+                }
+                None if parsed.memory => Some((lvalue, Some(AsmPlace::Memory { base: None }))),
+                None => {
+                    invalid(self);
+                    None
+                }
+            };
+        }
+
+        let value = self.rvalue(expr);
+        if parsed.immediate && value.ty.is_integer() {
+            if let Ok(constant) = const_eval::eval(&value, self.target) {
+                return Some((value, Some(AsmPlace::Immediate(constant.value))));
+            }
+        }
+        if let Some(register) = parsed.register {
+            return Some((
+                self.asm_register_operand(value)?,
+                register.map(AsmPlace::Register),
+            ));
+        }
+        if parsed.memory {
+            let lvalue = self.expr(expr);
+            if !lvalue.is_lvalue() || lvalue.is_bit_field() {
+                self.diags.error(
+                    expr.span,
+                    format!(
+                        "Invalid lvalue in asm input for constraint '{}'",
+                        constraint.name
+                    ),
+                );
+                return None;
+            }
+            return Some((lvalue, Some(AsmPlace::Memory { base: None })));
+        }
+        if parsed.immediate {
+            self.diags.error(
+                expr.span,
+                format!(
+                    "Constraint '{}' expects an integer constant expression",
+                    constraint.name
+                ),
+            );
+            return None;
+        }
+        invalid(self);
+        None
+    }
+
+    // An operand in a register, which must be a scalar.
+    fn asm_register_operand(&mut self, expr: Expr) -> Option<Expr> {
+        if expr.ty.is_integer() || matches!(expr.ty.unqualified(), Type::Pointer(_)) {
+            return Some(expr);
+        }
+        self.diags.error(
+            expr.span,
+            format!("Asm operand of type '{}' can't be in a register", expr.ty),
+        );
+        None
+    }
+
+    // A global with a unique name, only visible in its scope. It's initialized like a global.
+    fn static_local(
+        &mut self,
+        Declarator {
+            ty,
+            ident,
+            init,
+            attributes,
+            asm_label,
+        }: &Declarator,
+        is_thread_local: bool,
+        alignas: &[Alignas],
+    ) {
+        let ty = self.resolve_fixed_type(
+            ty,
+            ident.span,
+            "Variable length array declaration cannot have 'static' storage duration",
+        );
+        self.anonymous += 1;
+        let label = format!("{}.{}.{}", self.function.name, ident.name, self.anonymous);
+        // In scope in its own initializer, with the type it has so far.
+        let index = self.declare(LocalVariable {
+            name: ident.name.clone(),
+            ty: ty.clone(),
+            span: ident.span,
+            offset: 0,
+            label: Some(label.clone()),
+            is_thread_local,
+            is_register: false,
+            read: false,
+            param: false,
+        });
+
+        let (ty, value) = match init {
+            Some(init) => {
+                let (ty, value) = self.initializer(init, ty);
+                (ty, Some(value))
+            }
+            None => (ty, None),
+        };
+        let ty = self.object_type(ty, ident);
+        self.function.locals[index].ty = ty.clone();
+        let attributes = self.attributes(
+            attributes,
+            asm_label,
+            AttributeTarget::Variable { is_static: true },
+        );
+        let align = self
+            .alignment(alignas, &ty)
+            .max(attributes.aligned.unwrap_or(1));
+        let (init, relocations) = value.map(|value| self.global_data(&value, &ty)).unzip();
+
+        if attributes.symbol != SymbolAttributes::default() {
+            self.program
+                .symbols
+                .insert(label.clone(), attributes.symbol);
+        }
+        self.program.globals.push(Global {
+            name: label.clone(),
+            ty: ty.clone(),
+            init,
+            relocations: relocations.unwrap_or_default(),
+            is_static: true,
+            is_thread_local,
+            align,
+        });
+    }
+
+    // A global declared in a block, only visible in its scope.
+    fn extern_local(
+        &mut self,
+        Declarator {
+            ty,
+            ident,
+            init,
+            attributes,
+            asm_label,
+        }: &Declarator,
+        is_thread_local: bool,
+    ) {
+        if let Some(init) = init {
+            self.diags.error(
+                init.span(),
+                "Declaration of block scope identifier with linkage cannot have an initializer",
+            );
+        }
+
+        let ty = self.resolve_fixed_type(
+            ty,
+            ident.span,
+            "Variable length array declaration cannot have 'extern' linkage",
+        );
+        let attributes = self.attributes(
+            attributes,
+            asm_label,
+            AttributeTarget::Variable { is_static: false },
+        );
+        match self.globals.get_mut(&ident.name) {
+            None => {
+                self.globals.insert(
+                    ident.name.clone(),
+                    Symbol::Variable {
+                        ty: ty.clone(),
+                        span: ident.span,
+                        attributes: attributes.symbol,
+                        is_thread_local,
+                    },
+                );
+            }
+            Some(Symbol::Variable {
+                ty: previous_ty,
+                span,
+                attributes: previous_attributes,
+                is_thread_local: was_thread_local,
+            }) => {
+                let previous_span = *span;
+                let was_thread_local = *was_thread_local;
+                previous_attributes.merge(attributes.symbol);
+                if *previous_ty != ty {
+                    self.diags.emit(
+                        Diagnostic::error(
+                            ident.span,
+                            format!("Redefinition of '{}' with a different type", ident.name),
+                        )
+                        .with_note(Some(previous_span), "Previous definition is here"),
+                    );
+                    return;
+                }
+                if was_thread_local != is_thread_local {
+                    self.thread_local_mismatch(ident, previous_span, is_thread_local);
+                    return;
+                }
+            }
+            Some(Symbol::Function(signature)) => {
+                let previous_span = signature.span;
+                self.redefinition_as_different_kind(ident, previous_span);
+                return;
+            }
+        }
+
+        self.declare(LocalVariable {
+            name: ident.name.clone(),
+            ty,
+            span: ident.span,
+            offset: 0,
+            label: Some(ident.name.clone()),
+            is_thread_local,
+            is_register: false,
+            // Like globals, it isn't reported as unused.
+            read: true,
+            param: false,
+        });
+    }
+
+    // The initialization of a local variable, if it has an initializer.
+    fn local_declaration(
+        &mut self,
+        Declarator {
+            ty,
+            ident,
+            init,
+            attributes,
+            asm_label,
+        }: &Declarator,
+        alignas: &[Alignas],
+        is_register: bool,
+    ) -> Option<Stmt> {
+        let attributes = self.attributes(attributes, asm_label, AttributeTarget::Local);
+        let (ty, sizes) = self.resolve_block_type(ty, ident.span);
+        let mut stmts: Vec<_> = sizes.into_iter().map(Stmt::Expr).collect();
+
+        if let Type::VariableArray { size, .. } = ty {
+            if let Some(init) = init {
+                self.diags
+                    .error(init.span(), "Variable-sized object may not be initialized");
+            }
+
+            // Its slot holds its address.
+            let index = self.declare_local(ident, ty.clone(), false);
+            self.function.locals[index].is_register = is_register;
+            self.function.locals[index].read |= attributes.is_unused;
+            let span = ident.span;
+            let address = Expr::new(
+                ExprKind::Local {
+                    offset: self.function.locals[index].offset,
+                },
+                Type::pointer_to(ty),
+                span,
+            );
+            let size = Expr::new(ExprKind::Local { offset: size }, Type::ULong, span);
+            let alloca = Expr::new(
+                ExprKind::Alloca {
+                    size: Box::new(size),
+                },
+                address.ty.clone(),
+                span,
+            );
+            stmts.push(Stmt::Expr(self.assign(address, alloca, span)));
+            return Some(Stmt::Block(stmts));
+        }
+
+        // The length of `int a[] = { ... }` is only known after its initializer.
+        let (index, value) = match (init, &ty) {
+            (Some(init), Type::Array { len: None, .. }) => {
+                let (ty, value) = self.initializer(init, ty);
+                let ty = self.object_type(ty, ident);
+                let align = self.local_alignment(alignas, attributes.aligned, &ty, ident);
+                (
+                    self.declare_aligned_local(ident, ty, false, align),
+                    Some(value),
+                )
+            }
+            _ => {
+                let ty = self.object_type(ty, ident);
+                let align = self.local_alignment(alignas, attributes.aligned, &ty, ident);
+                let index = self.declare_aligned_local(ident, ty.clone(), false, align);
+                (
+                    index,
+                    init.as_ref().map(|init| self.initializer(init, ty).1),
+                )
+            }
+        };
+
+        self.function.locals[index].is_register = is_register;
+        self.function.locals[index].read |= attributes.is_unused;
+        let local = &self.function.locals[index];
+        let lhs = Expr::new(
+            ExprKind::Local {
+                offset: local.offset,
+            },
+            local.ty.clone(),
+            ident.span,
+        );
+        if let (Some(init), Some(value)) = (init, value) {
+            let span = ident.span.to(init.span());
+            stmts.extend(initialization(lhs, value, span).into_iter().map(Stmt::Expr));
+        }
+
+        (!stmts.is_empty()).then_some(Stmt::Block(stmts))
+    }
+
+    // The initial value of a `ty`, and `ty` with the length of an unsized array inferred from it.
+    fn initializer(&mut self, init: &Initializer, ty: Type) -> (Type, InitValue) {
+        let value = self.init_value(init, &ty);
+
+        match (ty, &value) {
+            (Type::Array { element, len: None }, InitValue::Aggregate(values)) => {
+                (Type::array_of(*element, Some(values.len())), value)
+            }
+            (ty, _) => (ty, value),
+        }
+    }
+
+    fn init_value(&mut self, init: &Initializer, ty: &Type) -> InitValue {
+        if let Some((str, span)) = string_literal(init, ty) {
+            return self.string_init(str, ty, span);
+        }
+
+        let items = match init {
+            Initializer::Expr(node) if matches!(ty.unqualified(), Type::Array { .. }) => {
+                self.diags.error(
+                    node.span,
+                    "Array initializer must be an initializer list or string literal",
+                );
+                return InitValue::Zero;
+            }
+            Initializer::Expr(node) => {
+                let expr = self.rvalue(node);
+                return InitValue::Expr(self.convert_for_assignment(expr, ty));
+            }
+            Initializer::List { items, .. } => items,
+        };
+
+        let mut cursor = InitCursor {
+            items,
+            pos: 0,
+            pending: None,
+            designated: false,
+        };
+        let (value, kind) = match ty.unqualified() {
+            Type::Array { .. } => (self.aggregate(&mut cursor, ty, true), "array"),
+            Type::Struct(struct_ref) => (self.aggregate(&mut cursor, ty, true), struct_ref.kind()),
+            // `int x = { 1 };`
+            _ => match cursor.peek() {
+                Some(item) => {
+                    if let Some(designator) = item.designators.first() {
+                        self.diags.error(
+                            designator.span(),
+                            format!("Designator in initializer for scalar type '{ty}'"),
+                        );
+                    }
+                    cursor.advance();
+                    (self.init_value(&item.value, ty), "scalar")
+                }
+                None => (InitValue::Zero, "scalar"),
+            },
+        };
+
+        if let Some(item) = cursor.peek() {
+            self.diags.error(
+                item.value.span(),
+                format!("Excess elements in {kind} initializer"),
+            );
+        }
+        value
+    }
+
+    // `"abc"` for an array of `char`, with the null character if it fits.
+    fn string_init(&mut self, str: &str, ty: &Type, span: Span) -> InitValue {
+        let mut bytes = str.as_bytes().to_vec();
+        if let Type::Array { len: Some(len), .. } = ty {
+            if bytes.len() > *len {
+                self.diags
+                    .error(span, "Initializer-string for char array is too long");
+            }
+            bytes.resize(*len, 0);
+        } else {
+            bytes.push(0);
+        }
+
+        let element = ty.base().unwrap().unqualified().clone();
+        let is_unsigned = self.target.is_unsigned(&element);
+        InitValue::Aggregate(
+            bytes
+                .into_iter()
+                .map(|byte| {
+                    let value = if is_unsigned {
+                        i64::from(byte)
+                    } else {
+                        i64::from(byte as i8)
+                    };
+                    InitValue::Expr(Expr::new(ExprKind::Num(value), element.clone(), span))
+                })
+                .collect(),
+        )
+    }
+
+    // The elements or members of `ty` from the items at the cursor. With braces, designators are
+    // applied and items are consumed up to the end of the list. Otherwise the braces are elided,
+    // and only as many items as there are subobjects are consumed.
+    fn aggregate(&mut self, cursor: &mut InitCursor, ty: &Type, braced: bool) -> InitValue {
+        let mut values = vec![];
+        self.fill(cursor, ty, &mut values, 0, braced);
+
+        InitValue::Aggregate(values)
+    }
+
+    // Like `aggregate`, starting at the subobject at `index`.
+    fn fill(
+        &mut self,
+        cursor: &mut InitCursor,
+        ty: &Type,
+        values: &mut Vec<InitValue>,
+        mut index: usize,
+        braced: bool,
+    ) {
+        while let Some(item) = cursor.peek() {
+            if !item.designators.is_empty() && !cursor.designated {
+                // A designator refers to the innermost braces.
+                if !braced {
+                    break;
+                }
+
+                match self.designation(cursor, &item.designators, ty, values) {
+                    Some(next) => index = next,
+                    None => cursor.advance(),
+                }
+                continue;
+            }
+
+            // Only the first member of a union is initialized without a designator.
+            let Some((ty, ..)) = subobject(ty, index).filter(|_| index == 0 || !is_union(ty))
+            else {
+                break;
+            };
+            let pos = cursor.pos;
+            let value = self.subobject_value(cursor, &ty);
+            set_init_value(values, index, value);
+            index += 1;
+
+            // Only an empty struct consumes no items.
+            if cursor.pos == pos {
+                break;
+            }
+        }
+    }
+
+    // Initializes the subobject of `ty` that the designators of the next item refer to, and
+    // returns the index after it.
+    fn designation(
+        &mut self,
+        cursor: &mut InitCursor,
+        designators: &[Designator],
+        ty: &Type,
+        values: &mut Vec<InitValue>,
+    ) -> Option<usize> {
+        let path = self.designator_path(&designators[0], ty)?;
+        self.designate(cursor, &path, &designators[1..], ty, values)
+    }
+
+    // Like `designation`, where `path` is the indices of the subobjects the first designator refers
+    // to, which are nested when it's a member of an anonymous struct or union.
+    fn designate(
+        &mut self,
+        cursor: &mut InitCursor,
+        path: &[usize],
+        designators: &[Designator],
+        ty: &Type,
+        values: &mut Vec<InitValue>,
+    ) -> Option<usize> {
+        let index = path[0];
+        let (subobject_ty, ..) = subobject(ty, index).unwrap();
+
+        // Only one member of a union is initialized.
+        if is_union(ty) {
+            for (other, value) in values.iter_mut().enumerate() {
+                if other != index {
+                    *value = InitValue::Zero;
+                }
+            }
+        }
+
+        let ty = subobject_ty;
+        if path.len() == 1 && designators.is_empty() {
+            cursor.designated = true;
+            let value = self.subobject_value(cursor, &ty);
+            set_init_value(values, index, value);
+        } else {
+            // The rest of the designators refer into the subobject, keeping its other values.
+            if values.len() <= index {
+                values.resize_with(index + 1, || InitValue::Zero);
+            }
+            if !matches!(values[index], InitValue::Aggregate(_)) {
+                values[index] = InitValue::Aggregate(vec![]);
+            }
+            let InitValue::Aggregate(values) = &mut values[index] else {
+                unreachable!()
+            };
+            let next = if path.len() > 1 {
+                self.designate(cursor, &path[1..], designators, &ty, values)?
+            } else {
+                self.designation(cursor, designators, &ty, values)?
+            };
+
+            // The next items initialize the subobjects after the designated one.
+            self.fill(cursor, &ty, values, next, false);
+        }
+
+        Some(index + 1)
+    }
+
+    fn designator_path(&mut self, designator: &Designator, ty: &Type) -> Option<Vec<usize>> {
+        match (designator, ty.unqualified()) {
+            (Designator::Index(node), Type::Array { len, .. }) => {
+                let index = self.integer_constant(node)?;
+                if index < 0 {
+                    self.diags.error(
+                        node.span,
+                        format!("Array designator value '{index}' is negative"),
+                    );
+                    return None;
+                }
+
+                match len {
+                    Some(len) if index as usize >= *len => {
+                        self.diags.error(
+                            node.span,
+                            format!(
+                                "Array designator index ({index}) exceeds array bounds ({len})"
+                            ),
+                        );
+                        None
+                    }
+                    _ => Some(vec![index as usize]),
+                }
+            }
+            (Designator::Member(ident), Type::Struct(struct_ref)) => {
+                let position = member_path(struct_ref, &ident.name);
+
+                if position.is_none() {
+                    self.diags.error(
+                        ident.span,
+                        format!(
+                            "Field designator '{}' does not refer to any field in type '{ty}'",
+                            ident.name
+                        ),
+                    );
+                }
+                position
+            }
+            (Designator::Index(_), _) => {
+                self.diags.error(
+                    designator.span(),
+                    format!("Array designator cannot initialize non-array type '{ty}'"),
+                );
+                None
+            }
+            (Designator::Member(_), _) => {
+                self.diags.error(
+                    designator.span(),
+                    format!("Field designator cannot initialize non-struct type '{ty}'"),
+                );
+                None
+            }
+        }
+    }
+
+    // The value of a subobject from the next item, or from the next items if its braces are elided.
+    fn subobject_value(&mut self, cursor: &mut InitCursor, ty: &Type) -> InitValue {
+        let item = cursor.peek().unwrap();
+        let node = match &item.value {
+            Initializer::Expr(node) if string_literal(&item.value, ty).is_none() => node,
+            value => {
+                cursor.advance();
+                return self.init_value(value, ty);
+            }
+        };
+
+        let expr = match cursor.pending.take() {
+            Some(expr) => expr,
+            None => self.rvalue(node),
+        };
+        match ty.unqualified() {
+            // A struct can be initialized by a whole struct.
+            Type::Struct(_) if expr.ty == *ty.unqualified() => {}
+            Type::Array { .. } | Type::Struct(_) => {
+                cursor.pending = Some(expr);
+                return self.aggregate(cursor, ty, false);
+            }
+            _ => {}
+        }
+
+        cursor.advance();
+        InitValue::Expr(self.convert_for_assignment(expr, ty))
+    }
+
+    // The association of `_Generic` whose type is compatible with the type of `control`, which isn't
+    // evaluated.
+    fn generic(&mut self, control: &Node, associations: &[GenericAssociation], span: Span) -> Expr {
+        let control = self.rvalue(control);
+        let mut default: Option<(usize, Span)> = None;
+        let mut types: Vec<(Type, Span)> = vec![];
+        let mut selected = None;
+
+        for (index, association) in associations.iter().enumerate() {
+            let Some(ty) = &association.ty else {
+                if let Some((_, previous_span)) = default {
+                    self.diags.emit(
+                        Diagnostic::error(
+                            association.span,
+                            "Duplicate default generic association",
+                        )
+                        .with_note(
+                            Some(previous_span),
+                            "Previous default generic association is here",
+                        ),
+                    );
+                } else {
+                    default = Some((index, association.span));
+                }
+                continue;
+            };
+
+            let ty = self.resolve_fixed_type(
+                ty,
+                association.span,
+                "Type in generic association cannot be a variably modified type",
+            );
+            if !ty.is_complete() || matches!(ty, Type::Function { .. }) {
+                let kind = if ty.is_complete() {
+                    "not an object type"
+                } else {
+                    "incomplete"
+                };
+                self.diags.error(
+                    association.span,
+                    format!("Type '{ty}' in generic association {kind}"),
+                );
+                continue;
+            }
+            if let Some((previous, previous_span)) = types
+                .iter()
+                .find(|(previous, _)| previous.is_compatible(&ty))
+            {
+                self.diags.emit(
+                    Diagnostic::error(
+                        association.span,
+                        format!(
+                            "Type '{ty}' in generic association compatible with previously \
+                             specified type '{previous}'"
+                        ),
+                    )
+                    .with_note(Some(*previous_span), "Compatible type is here"),
+                );
+                continue;
+            }
+            if control.ty.is_compatible(&ty) {
+                selected = Some(index);
+            }
+            types.push((ty, association.span));
+        }
+
+        // Every association is analyzed, though only the selected one is evaluated.
+        let selected = selected.or(default.map(|(index, _)| index));
+        let mut result = None;
+        for (index, association) in associations.iter().enumerate() {
+            let expr = self.expr(&association.value);
+            if selected == Some(index) {
+                result = Some(expr);
+            }
+        }
+
+        result.unwrap_or_else(|| {
+            if control.is_error() {
+                return Expr::error(span);
+            }
+            self.diags.error(
+                control.span,
+                format!(
+                    "Controlling expression type '{}' not compatible with any generic \
+                     association type",
+                    control.ty
+                ),
+            );
+            Expr::error(span)
+        })
+    }
+
+    // An expression whose value is used, so it can't be `void`. Arrays decay to pointers to their
+    // first element and functions to pointers to them, and the value of an lvalue has no qualifiers.
+    fn rvalue(&mut self, node: &Node) -> Expr {
+        let expr = self.expr(node);
+        if let Type::Array { .. } = expr.ty {
+            self.check_register(node, expr.span);
+        }
+
+        self.value(expr)
+    }
+
+    // The value of an analyzed expression, like `rvalue`.
+    fn value(&mut self, expr: Expr) -> Expr {
+        let ty = match &expr.ty {
+            Type::Void => {
+                self.diags
+                    .error(expr.span, "Void value not ignored as it ought to be");
+                return Expr::error(expr.span);
+            }
+            Type::Array { element, .. } | Type::VariableArray { element, .. } => {
+                Type::pointer_to((**element).clone())
+            }
+            Type::Function { .. } => Type::pointer_to(expr.ty.clone()),
+            _ => return promote_bit_field(unqualified(expr)),
+        };
+
+        let span = expr.span;
+        Expr::new(
+            ExprKind::Addr {
+                operand: Box::new(expr),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // The condition of `if` or a loop, which must be a scalar.
+    fn cond(&mut self, node: &Node) -> Expr {
+        let expr = self.rvalue(node);
+
+        if expr.ty.is_scalar() || expr.is_error() {
+            expr
+        } else {
+            self.diags.error(
+                expr.span,
+                format!(
+                    "Statement requires expression of scalar type ('{}' invalid)",
+                    expr.ty
+                ),
+            );
+            Expr::error(expr.span)
+        }
+    }
+
+    fn expr(&mut self, node: &Node) -> Expr {
+        let span = node.span;
+
+        match &node.kind {
+            NodeKind::Num { value, suffix } => Expr::new(
+                ExprKind::Num(*value as i64),
+                literal_type(*value, *suffix),
+                span,
+            ),
+            NodeKind::Str(str) => {
+                let mut bytes = str.as_bytes().to_vec();
+                bytes.push(0);
+
+                let ty = Type::array_of(Type::Char, Some(bytes.len()));
+                let name = self.anonymous_global("str", ty.clone(), (bytes, vec![]));
+                Expr::new(ExprKind::Global { name }, ty, span)
+            }
+            NodeKind::Var { name } => self.var(name, span, true),
+            NodeKind::Error => {
+                self.function.has_syntax_errors = true;
+                Expr::error(span)
+            }
+            NodeKind::ArithOp {
+                value: ArithOp::Assign,
+                lhs,
+                rhs,
+            } => {
+                let lhs = self.modifiable_lvalue(lhs);
+                let rhs = self.rvalue(rhs);
+                let Some(lhs) = lhs else {
+                    return Expr::error(span);
+                };
+
+                self.assign(lhs, rhs, span)
+            }
+            NodeKind::CompoundAssign { value, lhs, rhs } => {
+                let lhs = self.modifiable_lvalue(lhs);
+                let rhs = self.rvalue(rhs);
+                let Some(lhs) = lhs else {
+                    return Expr::error(span);
+                };
+
+                self.compound_assign(*value, lhs, rhs, span)
+            }
+            NodeKind::ArithOp { value, lhs, rhs } => {
+                let (lhs, rhs) = (self.rvalue(lhs), self.rvalue(rhs));
+                self.arith_op(*value, lhs, rhs, span)
+            }
+            NodeKind::CmpOp { value, lhs, rhs } => {
+                let (lhs, rhs) = (self.rvalue(lhs), self.rvalue(rhs));
+                if lhs.is_error() || rhs.is_error() {
+                    return Expr::error(span);
+                }
+                let ty = match (&lhs.ty, &rhs.ty) {
+                    (l, r) if l.is_integer() && r.is_integer() => {
+                        self.check_sign_compare(&lhs, &rhs, span);
+                        Type::common(l, r)
+                    }
+                    // Addresses are compared as unsigned integers.
+                    (Type::Pointer(_), Type::Pointer(_)) => Type::ULong,
+                    (Type::Pointer(_), _) if is_null_pointer_constant(&rhs) => Type::ULong,
+                    (_, Type::Pointer(_)) if is_null_pointer_constant(&lhs) => Type::ULong,
+                    _ => {
+                        self.invalid_operands(&lhs, &rhs, span);
+                        return Expr::error(span);
+                    }
+                };
+
+                Expr::new(
+                    ExprKind::CmpOp {
+                        value: *value,
+                        lhs: Box::new(convert(lhs, &ty)),
+                        rhs: Box::new(convert(rhs, &ty)),
+                    },
+                    Type::Int,
+                    span,
+                )
+            }
+            NodeKind::Unary {
+                value:
+                    value @ (UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec),
+                operand,
+            } => {
+                let Some(operand) = self.modifiable_lvalue(operand) else {
+                    return Expr::error(span);
+                };
+
+                let ty = operand.ty.unqualified().clone();
+                let one = || Expr::new(ExprKind::Num(1), Type::Int, span);
+                let (op, inverse) = match value {
+                    UnaryOp::PreInc | UnaryOp::PostInc => (ArithOp::Add, ArithOp::Sub),
+                    _ => (ArithOp::Sub, ArithOp::Add),
+                };
+
+                match value {
+                    UnaryOp::PreInc | UnaryOp::PreDec => {
+                        self.compound_assign(op, operand, one(), span)
+                    }
+                    // The stored value of a bit-field wraps around within its width, so the
+                    // previous one is kept: `(previous = a, a = previous + 1, previous)`.
+                    _ if operand.is_bit_field() => {
+                        let (address, operand) = self.reusable_lvalue(operand, span);
+                        let previous = Expr::new(
+                            ExprKind::Local {
+                                offset: self.allocate(&ty),
+                            },
+                            ty,
+                            span,
+                        );
+
+                        let value = self.value(operand.clone());
+                        let save = self.assign(previous.clone(), value, span);
+                        let updated = self.arith_op(op, previous.clone(), one(), span);
+                        let assignment = self.assign(operand, updated, span);
+                        let exprs = address.into_iter().chain([save, assignment]);
+                        exprs.rev().fold(previous, |rhs, lhs| comma(lhs, rhs, span))
+                    }
+                    // `a++` is `(a += 1) - 1`, converted back to the type of `a`.
+                    _ => {
+                        let updated = self.compound_assign(op, operand, one(), span);
+                        let previous = self.arith_op(inverse, updated, one(), span);
+                        convert(previous, &ty)
+                    }
+                }
+            }
+            NodeKind::Unary {
+                value: UnaryOp::Addr,
+                operand,
+            } => {
+                self.check_register(operand, span);
+                let operand_expr = self.expr(operand);
+                if operand_expr.is_error() {
+                    return Expr::error(span);
+                }
+                // A function designator isn't an lvalue, but has an address.
+                if !operand_expr.is_lvalue() && !matches!(operand_expr.ty, Type::Function { .. }) {
+                    // An unusable variable has already been reported.
+                    if !matches!(operand.kind, NodeKind::Var { .. }) {
+                        self.diags.error(
+                            span,
+                            format!(
+                                "Cannot take the address of an rvalue of type '{}'",
+                                operand_expr.ty
+                            ),
+                        );
+                    }
+                    return Expr::error(span);
+                }
+
+                if operand_expr.is_bit_field() {
+                    self.diags.error(span, "Address of bit-field requested");
+                    return Expr::error(span);
+                }
+
+                let ty = Type::pointer_to(operand_expr.ty.clone());
+                Expr::new(
+                    ExprKind::Addr {
+                        operand: Box::new(operand_expr),
+                    },
+                    ty,
+                    span,
+                )
+            }
+            NodeKind::Unary {
+                value: UnaryOp::Deref,
+                operand,
+            } => {
+                let operand = self.rvalue(operand);
+                self.deref(operand, span)
+            }
+            NodeKind::Unary { value, operand } => {
+                let operand = self.rvalue(operand);
+                if operand.is_error() {
+                    return Expr::error(span);
+                }
+                if !operand.ty.is_integer() {
+                    self.diags.error(
+                        span,
+                        format!("Invalid argument type '{}' to unary expression", operand.ty),
+                    );
+                    return Expr::error(span);
+                }
+
+                let ty = operand.ty.promoted();
+                let operand = convert(operand, &ty);
+                match value {
+                    UnaryOp::Neg => self.check_overflow(Expr::new(
+                        ExprKind::Neg {
+                            operand: Box::new(operand),
+                        },
+                        ty,
+                        span,
+                    )),
+                    _ => Expr { span, ..operand },
+                }
+            }
+            // `base[index]` is `*(base + index)`.
+            NodeKind::Index { base, index } => {
+                let (base, index) = (self.rvalue(base), self.rvalue(index));
+                if base.is_error() || index.is_error() {
+                    return Expr::error(span);
+                }
+                let valid = match (&base.ty, &index.ty) {
+                    (Type::Pointer(_), ty) | (ty, Type::Pointer(_)) => ty.is_integer(),
+                    _ => false,
+                };
+                if !valid {
+                    self.diags
+                        .error(span, "Subscripted value is not an array or pointer");
+                    return Expr::error(span);
+                }
+
+                let address = self.arith_op(ArithOp::Add, base, index, span);
+                self.deref(address, span)
+            }
+            NodeKind::Member {
+                operand,
+                member,
+                arrow,
+            } => {
+                let operand = if *arrow {
+                    let pointer = self.rvalue(operand);
+                    if pointer.is_error() {
+                        return Expr::error(span);
+                    }
+                    if !matches!(pointer.ty, Type::Pointer(_)) {
+                        self.diags.error(
+                            pointer.span,
+                            format!("Member reference type '{}' is not a pointer", pointer.ty),
+                        );
+                        return Expr::error(span);
+                    }
+                    let pointer_span = pointer.span;
+                    self.deref(pointer, pointer_span)
+                } else {
+                    self.expr(operand)
+                };
+
+                self.member(operand, member, span)
+            }
+            NodeKind::Cast { ty, operand } => {
+                // The result is a value, so qualifiers don't matter.
+                let (ty, sizes) = self.resolve_block_type(ty, span);
+                let ty = ty.unqualified().clone();
+
+                // Only `void` can be converted to `void`.
+                if ty == Type::Void {
+                    let operand = self.expr(operand);
+                    return Expr {
+                        span,
+                        ..convert(operand, &ty)
+                    };
+                }
+
+                let operand = self.rvalue(operand);
+                if operand.is_error() {
+                    return Expr::error(span);
+                }
+                let invalid = if !ty.is_scalar() {
+                    Some(("Used type", &ty))
+                } else if !operand.ty.is_scalar() {
+                    Some(("Operand of type", &operand.ty))
+                } else {
+                    None
+                };
+                if let Some((what, invalid)) = invalid {
+                    self.diags.error(
+                        span,
+                        format!("{what} '{invalid}' where arithmetic or pointer type is required"),
+                    );
+                    return Expr::error(span);
+                }
+
+                let cast = Expr {
+                    span,
+                    ..convert(operand, &ty)
+                };
+                sizes
+                    .into_iter()
+                    .rev()
+                    .fold(cast, |rhs, lhs| comma(lhs, rhs, span))
+            }
+            NodeKind::CompoundLiteral { ty, init } => {
+                let ty = self.resolve_fixed_type(
+                    ty,
+                    span,
+                    "Compound literal cannot be of variable-length array type",
+                );
+                let (ty, value) = self.initializer(init, ty);
+                if !ty.is_complete() {
+                    self.diags
+                        .error(span, format!("Compound literal has incomplete type '{ty}'"));
+                    return Expr::error(span);
+                }
+
+                // Outside of functions, it's static like a global.
+                if self.function.scopes.is_empty() {
+                    let data = self.global_data(&value, &ty);
+                    let name = self.anonymous_global("compound", ty.clone(), data);
+                    return Expr::new(ExprKind::Global { name }, ty, span);
+                }
+
+                let offset = self.allocate(&ty);
+                let object = Expr::new(ExprKind::Local { offset }, ty, span);
+                initialization(object.clone(), value, span)
+                    .into_iter()
+                    .rev()
+                    .fold(object, |rhs, lhs| comma(lhs, rhs, span))
+            }
+            NodeKind::SizeofExpr { operand } => {
+                let operand = self.expr(operand);
+                if operand.is_bit_field() {
+                    self.diags
+                        .error(span, "Invalid application of 'sizeof' to bit-field");
+                    return Expr::new(ExprKind::Num(0), Type::ULong, span);
+                }
+                self.size_of(&operand.ty, "sizeof", span)
+            }
+            NodeKind::SizeofType { ty } => {
+                let (ty, sizes) = self.resolve_block_type(ty, span);
+                let size = self.size_of(&ty, "sizeof", span);
+                sizes
+                    .into_iter()
+                    .rev()
+                    .fold(size, |rhs, lhs| comma(lhs, rhs, span))
+            }
+            NodeKind::Alignof { ty } => {
+                let (ty, _) = self.resolve_block_type(ty, span);
+                self.size_of(&ty, "_Alignof", span)
+            }
+            NodeKind::Call { callee, args } => self.call(callee, args, span),
+            NodeKind::StmtExpr { body } => self.stmt_expr(body, span),
+            NodeKind::Generic {
+                control,
+                associations,
+            } => self.generic(control, associations, span),
+            NodeKind::Offsetof { ty, designators } => self.offsetof(ty, designators, span),
+            // Top-level qualifiers are ignored.
+            NodeKind::TypesCompatible { lhs, rhs } => {
+                let (lhs, _) = self.resolve_block_type(lhs, span);
+                let (rhs, _) = self.resolve_block_type(rhs, span);
+                let is_compatible = lhs.unqualified().is_compatible(rhs.unqualified());
+                Expr::new(ExprKind::Num(i64::from(is_compatible)), Type::Int, span)
+            }
+            NodeKind::Ret { .. }
+            | NodeKind::StaticAssert { .. }
+            | NodeKind::Asm { .. }
+            | NodeKind::Block { .. }
+            | NodeKind::If { .. }
+            | NodeKind::While { .. }
+            | NodeKind::For { .. }
+            | NodeKind::Decl { .. }
+            | NodeKind::Function { .. } => unreachable!("Statements never appear in expressions"),
+        }
+    }
+
+    // `sizeof` or `_Alignof` of a complete type, as a `size_t`, which is `unsigned long`.
+    fn size_of(&mut self, ty: &Type, operator: &str, span: Span) -> Expr {
+        if let Type::Function { .. } = ty {
+            self.diags.error(
+                span,
+                format!("Invalid application of '{operator}' to a function type"),
+            );
+            return Expr::new(ExprKind::Num(0), Type::ULong, span);
+        }
+        if let Type::VariableArray { size, .. } = ty {
+            if operator == "sizeof" {
+                return Expr::new(ExprKind::Local { offset: *size }, Type::ULong, span);
+            }
+        }
+        // Like gcc, `void` has a size of 1.
+        if !ty.is_complete() && *ty != Type::Void {
+            self.diags.error(
+                span,
+                format!("Invalid application of '{operator}' to an incomplete type '{ty}'"),
+            );
+            return Expr::new(ExprKind::Num(0), Type::ULong, span);
+        }
+
+        let value = if operator == "sizeof" {
+            ty.size()
+        } else {
+            ty.align()
+        };
+        Expr::new(ExprKind::Num(value as i64), Type::ULong, span)
+    }
+
+    // `*pointer`, where `pointer` is an rvalue.
+    fn deref(&mut self, pointer: Expr, span: Span) -> Expr {
+        if pointer.is_error() {
+            return Expr::error(span);
+        }
+        let Type::Pointer(base) = &pointer.ty else {
+            self.diags.error(
+                span,
+                format!(
+                    "Indirection requires pointer operand ('{}' invalid)",
+                    pointer.ty
+                ),
+            );
+            return Expr::error(span);
+        };
+
+        let ty = (**base).clone();
+        Expr::new(
+            ExprKind::Deref {
+                operand: Box::new(pointer),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // `operand.member`, with the qualifiers of the operand.
+    fn member(&mut self, operand: Expr, member: &Ident, span: Span) -> Expr {
+        if operand.is_error() {
+            return Expr::error(span);
+        }
+        let Type::Struct(struct_ref) = operand.ty.unqualified() else {
+            self.diags.error(
+                operand.span,
+                format!(
+                    "Member reference base type '{}' is not a structure",
+                    operand.ty
+                ),
+            );
+            return Expr::error(span);
+        };
+        if !operand.ty.is_complete() {
+            self.diags.error(
+                operand.span,
+                format!("Incomplete definition of type '{}'", operand.ty),
+            );
+            return Expr::error(span);
+        }
+
+        let Some(found) = struct_ref.member(&member.name) else {
+            self.diags.error(
+                member.span,
+                format!("No member named '{}' in '{}'", member.name, operand.ty),
+            );
+            return Expr::error(span);
+        };
+        let ty = Type::qualified(found.ty, operand.ty.qualifiers());
+        Expr::new(
+            ExprKind::Member {
+                operand: Box::new(operand),
+                offset: found.offset,
+                bit_field: found.bit_field,
+            },
+            ty,
+            span,
+        )
+    }
+
+    // The operand of `=`, `op=`, `++` or `--`, which must designate a modifiable object.
+    fn modifiable_lvalue(&mut self, node: &Node) -> Option<Expr> {
+        let expr = match &node.kind {
+            // Writing to a variable doesn't read it.
+            NodeKind::Var { name } => {
+                let expr = self.var(name, node.span, false);
+                // An unusable variable has already been reported, unlike a function.
+                if !expr.is_lvalue() && !matches!(expr.ty, Type::Function { .. }) {
+                    return None;
+                }
+                expr
+            }
+            _ => {
+                let expr = self.expr(node);
+                if expr.is_error() {
+                    return None;
+                }
+                if !expr.is_lvalue() {
+                    self.diags.error(node.span, "Expression is not assignable");
+                    return None;
+                }
+                expr
+            }
+        };
+
+        if let Type::Array { .. } | Type::VariableArray { .. } = expr.ty {
+            self.diags.error(
+                node.span,
+                format!("Array type '{}' is not assignable", expr.ty),
+            );
+            return None;
+        }
+        if let Type::Function { .. } = expr.ty {
+            self.diags.error(
+                node.span,
+                format!("Non-object type '{}' is not assignable", expr.ty),
+            );
+            return None;
+        }
+        if expr.ty.is_const() {
+            let message = match &node.kind {
+                _ if !expr.ty.qualifiers().is_const => {
+                    format!(
+                        "Cannot assign to '{}' with a const-qualified member",
+                        expr.ty
+                    )
+                }
+                NodeKind::Var { name } => {
+                    format!(
+                        "Cannot assign to variable '{name}' with const-qualified type '{}'",
+                        expr.ty
+                    )
+                }
+                _ => "Read-only variable is not assignable".to_string(),
+            };
+            self.diags.error(node.span, message);
+            return None;
+        }
+        Some(expr)
+    }
+
+    // Integers are converted to their common type, and pointer arithmetic is in bytes.
+    fn arith_op(&mut self, value: ArithOp, lhs: Expr, rhs: Expr, span: Span) -> Expr {
+        if lhs.is_error() || rhs.is_error() {
+            return Expr::error(span);
+        }
+        match (value, &lhs.ty, &rhs.ty) {
+            (_, l, r) if l.is_integer() && r.is_integer() => {}
+            (ArithOp::Add | ArithOp::Sub, Type::Pointer(_), r) if r.is_integer() => {
+                return self.pointer_offset(value, lhs, rhs, span);
+            }
+            (ArithOp::Add, l, Type::Pointer(_)) if l.is_integer() => {
+                return self.pointer_offset(value, rhs, lhs, span);
+            }
+            // The number of elements between two pointers.
+            (ArithOp::Sub, Type::Pointer(l), Type::Pointer(r))
+                if l.unqualified().is_compatible(r.unqualified()) =>
+            {
+                let size = self.pointee_size(&lhs.ty, span);
+                let bytes = Expr::new(
+                    ExprKind::ArithOp {
+                        value,
+                        lhs: Box::new(convert(lhs, &Type::Long)),
+                        rhs: Box::new(convert(rhs, &Type::Long)),
+                    },
+                    Type::Long,
+                    span,
+                );
+                return Expr::new(
+                    ExprKind::ArithOp {
+                        value: ArithOp::Div,
+                        lhs: Box::new(bytes),
+                        rhs: Box::new(size),
+                    },
+                    Type::Long,
+                    span,
+                );
+            }
+            _ => {
+                self.invalid_operands(&lhs, &rhs, span);
+                return Expr::error(span);
+            }
+        }
+
+        let ty = Type::common(&lhs.ty, &rhs.ty);
+        let (lhs, rhs) = (convert(lhs, &ty), convert(rhs, &ty));
+
+        if value == ArithOp::Div && const_eval::is_zero(&rhs) == Some(true) {
+            self.diags.emit(Diagnostic::warning(
+                Warning::DivByZero,
+                rhs.span,
+                "Division by zero is undefined",
+            ));
+        }
+
+        self.check_overflow(Expr::new(
+            ExprKind::ArithOp {
+                value,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            ty,
+            span,
+        ))
+    }
+
+    // `pointer + offset` or `pointer - offset`, with the offset scaled to bytes.
+    fn pointer_offset(&mut self, value: ArithOp, pointer: Expr, offset: Expr, span: Span) -> Expr {
+        let bytes = self.offset_in_bytes(&pointer.ty, offset, span);
+        let ty = pointer.ty.clone();
+        Expr::new(
+            ExprKind::ArithOp {
+                value,
+                lhs: Box::new(pointer),
+                rhs: Box::new(bytes),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // An integer `offset` from a pointer of type `ty`, in bytes as a `long`.
+    fn offset_in_bytes(&mut self, ty: &Type, offset: Expr, span: Span) -> Expr {
+        let size = self.pointee_size(ty, span);
+        let offset_span = offset.span;
+        Expr::new(
+            ExprKind::ArithOp {
+                value: ArithOp::Mul,
+                lhs: Box::new(convert(offset, &Type::Long)),
+                rhs: Box::new(size),
+            },
+            Type::Long,
+            offset_span,
+        )
+    }
+
+    // The size of what a pointer points to as a `long`, which must be complete to do arithmetic on
+    // it.
+    fn pointee_size(&mut self, ty: &Type, span: Span) -> Expr {
+        let base = ty.base().unwrap();
+        // Like gcc, arithmetic on `void *` is in bytes.
+        if !base.is_complete() && *base.unqualified() != Type::Void {
+            self.diags.error(
+                span,
+                format!("Arithmetic on a pointer to an incomplete type '{base}'"),
+            );
+        }
+
+        match base {
+            Type::VariableArray { size, .. } => convert(
+                Expr::new(ExprKind::Local { offset: *size }, Type::ULong, span),
+                &Type::Long,
+            ),
+            _ => Expr::new(ExprKind::Num(base.size() as i64), Type::Long, span),
+        }
+    }
+
+    // Warns if a comparison converts an operand which can be negative to unsigned, where a negative
+    // value compares like a large one.
+    fn check_sign_compare(&mut self, lhs: &Expr, rhs: &Expr, span: Span) {
+        if !Type::common(&lhs.ty, &rhs.ty).is_unsigned() {
+            return;
+        }
+
+        let can_be_negative = |operand: &Expr| {
+            !self.target.is_unsigned(&operand.ty)
+                && !const_eval::eval(operand, self.target).is_ok_and(|constant| constant.value >= 0)
+        };
+        if can_be_negative(lhs) || can_be_negative(rhs) {
+            self.diags.emit(Diagnostic::warning(
+                Warning::SignCompare,
+                span,
+                format!(
+                    "Comparison of integers of different signs: '{}' and '{}'",
+                    lhs.ty, rhs.ty
+                ),
+            ));
+        }
+    }
+
+    fn invalid_operands(&mut self, lhs: &Expr, rhs: &Expr, span: Span) {
+        self.diags.error(
+            span,
+            format!(
+                "Invalid operands to binary expression ('{}' and '{}')",
+                lhs.ty, rhs.ty
+            ),
+        );
+    }
+
+    // `lhs op= rhs` is `lhs = lhs op rhs`. If evaluating `lhs` could have side effects, like in
+    // `a[i++] += 1`, its address is evaluated once into a temporary.
+    fn compound_assign(&mut self, value: ArithOp, lhs: Expr, rhs: Expr, span: Span) -> Expr {
+        if lhs.ty.qualifiers().is_atomic {
+            return self.atomic_compound_assign(value, lhs, rhs, span);
+        }
+
+        let (address, lhs) = self.reusable_lvalue(lhs, span);
+        let lhs_value = self.value(lhs.clone());
+        let result = self.arith_op(value, lhs_value, rhs, span);
+        let assignment = self.assign(lhs, result, span);
+
+        match address {
+            Some(address) => comma(address, assignment, span),
+            None => assignment,
+        }
+    }
+
+    // `lhs op= rhs` on an `_Atomic` object. `+=` and `-=` on an integer or a pointer is a single
+    // `lock xadd`, and anything else a loop until a compare-exchange stores the result:
+    // `({ p = &lhs; operand = rhs; old = *p; while (!cas(p, &old, new = old op operand)); new; })`
+    fn atomic_compound_assign(&mut self, value: ArithOp, lhs: Expr, rhs: Expr, span: Span) -> Expr {
+        let ty = lhs.ty.unqualified().clone();
+        let pointer = address_of(lhs, span);
+
+        let op = match value {
+            ArithOp::Add => Some(FetchOp::Add),
+            ArithOp::Sub => Some(FetchOp::Sub),
+            _ => None,
+        };
+        if let Some(op) = op.filter(|_| rhs.ty.is_integer() && ty != Type::Bool) {
+            let operand = match ty {
+                Type::Pointer(_) => self.offset_in_bytes(&ty, rhs, span),
+                _ => convert(rhs, &ty),
+            };
+            return Expr::new(
+                ExprKind::Atomic {
+                    op: AtomicOp::Fetch {
+                        op,
+                        returns_new: true,
+                    },
+                    order: MemoryOrder::SeqCst,
+                    args: vec![pointer, operand],
+                },
+                ty,
+                span,
+            );
+        }
+
+        let mut temporary = |ty: &Type| {
+            let offset = self.allocate(ty);
+            Expr::new(ExprKind::Local { offset }, ty.clone(), span)
+        };
+        let p = temporary(&pointer.ty);
+        let operand = temporary(&rhs.ty);
+        let old = temporary(&ty);
+        let new = temporary(&ty);
+
+        let object = self.deref(p.clone(), span);
+        let object = self.value(object);
+        let mut stmts = vec![
+            Stmt::Expr(self.assign(p.clone(), pointer, span)),
+            Stmt::Expr(self.assign(operand.clone(), rhs, span)),
+            Stmt::Expr(self.assign(old.clone(), object, span)),
+        ];
+        let result = self.arith_op(value, old.clone(), operand, span);
+        let desired = self.assign(new.clone(), result, span);
+        let exchange = Expr::new(
+            ExprKind::Atomic {
+                op: AtomicOp::CompareExchange {
+                    through_pointer: true,
+                    returns_previous: false,
+                },
+                order: MemoryOrder::SeqCst,
+                args: vec![p, address_of(old, span), desired],
+            },
+            Type::Bool,
+            span,
+        );
+        let failed = Expr::new(
+            ExprKind::CmpOp {
+                value: CmpOp::Eq,
+                lhs: Box::new(exchange),
+                rhs: Box::new(Expr::new(ExprKind::Num(0), Type::Bool, span)),
+            },
+            Type::Int,
+            span,
+        );
+        stmts.push(Stmt::While {
+            cond: failed,
+            then: Box::new(Stmt::Block(vec![])),
+        });
+        stmts.push(Stmt::Expr(new));
+
+        Expr::new(
+            ExprKind::StmtExpr {
+                body: Box::new(Stmt::Block(stmts)),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // An lvalue designating the same object as `lhs` that can be evaluated more than once, after
+    // the expression storing its address in a temporary if that has side effects. A bit-field has
+    // no address, so the one of its struct is stored.
+    fn reusable_lvalue(&mut self, lhs: Expr, span: Span) -> (Option<Expr>, Expr) {
+        if !has_side_effects(&lhs) {
+            return (None, lhs);
+        }
+
+        let (object, bit_field) = match lhs.kind {
+            ExprKind::Member {
+                operand,
+                offset,
+                bit_field: Some(bit_field),
+            } => (*operand, Some((offset, bit_field, lhs.ty))),
+            kind => (Expr { kind, ..lhs }, None),
+        };
+
+        let address = address_of(object, span);
+        let temporary = Expr::new(
+            ExprKind::Local {
+                offset: self.allocate(&address.ty),
+            },
+            address.ty.clone(),
+            span,
+        );
+        let address = self.assign(temporary.clone(), address, span);
+
+        let object = self.deref(temporary, span);
+        let lhs = match bit_field {
+            Some((offset, bit_field, ty)) => Expr::new(
+                ExprKind::Member {
+                    operand: Box::new(object),
+                    offset,
+                    bit_field: Some(bit_field),
+                },
+                ty,
+                span,
+            ),
+            None => object,
+        };
+        (Some(address), lhs)
+    }
+
+    // `lhs = rhs`, where `lhs` is a modifiable lvalue.
+    fn assign(&mut self, lhs: Expr, rhs: Expr, span: Span) -> Expr {
+        let ty = lhs.ty.unqualified().clone();
+        let rhs = self.convert_for_assignment(rhs, &ty);
+
+        // Like every access to an `_Atomic` object, it's sequentially consistent.
+        if lhs.ty.qualifiers().is_atomic {
+            return Expr::new(
+                ExprKind::Atomic {
+                    op: AtomicOp::Store,
+                    order: MemoryOrder::SeqCst,
+                    args: vec![address_of(lhs, span), rhs],
+                },
+                ty,
+                span,
+            );
+        }
+
+        Expr::new(
+            ExprKind::Assign {
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // `expr` converted to `ty` like by an assignment, which converts between integers, to and from
+    // `void *`, from a null pointer constant to a pointer, and from a pointer to `_Bool`. Pointers
+    // can gain qualifiers.
+    fn convert_for_assignment(&mut self, expr: Expr, ty: &Type) -> Expr {
+        let ty = ty.unqualified();
+        let message = match (ty, &expr.ty) {
+            (to, from) if to == from || (to.is_integer() && from.is_integer()) => None,
+            (_, Type::Error) => return expr,
+            (Type::Bool, Type::Pointer(_)) => None,
+            (Type::Pointer(to), Type::Pointer(from)) => {
+                let (to_base, from_base) = (to.unqualified(), from.unqualified());
+                let compatible = to_base.is_compatible(from_base)
+                    || *to_base == Type::Void
+                    || *from_base == Type::Void;
+
+                if compatible && !to.qualifiers().contains(from.qualifiers()) {
+                    self.diags.emit(Diagnostic::warning(
+                        Warning::DiscardedQualifiers,
+                        expr.span,
+                        format!("Converting '{}' to '{ty}' discards qualifiers", expr.ty),
+                    ));
+                }
+                (!compatible).then_some("Incompatible pointer types converting")
+            }
+            (Type::Pointer(_), _) if is_null_pointer_constant(&expr) => None,
+            (Type::Pointer(_), from) if from.is_integer() => {
+                Some("Incompatible integer to pointer conversion from")
+            }
+            (to, Type::Pointer(_)) if to.is_integer() => {
+                Some("Incompatible pointer to integer conversion from")
+            }
+            _ => Some("Incompatible types converting"),
+        };
+
+        if let Some(message) = message {
+            self.diags
+                .error(expr.span, format!("{message} '{}' to '{ty}'", expr.ty));
+        }
+        convert(expr, ty)
+    }
+
+    // `read` is false for the left-hand side of an assignment.
+    fn var(&mut self, name: &str, span: Span, read: bool) -> Expr {
+        let index = match self.resolve(name) {
+            Some(Resolved::Local(index)) => index,
+            Some(Resolved::Global(Symbol::Variable {
+                ty,
+                is_thread_local,
+                ..
+            })) => {
+                let name = name.to_string();
+                let kind = match is_thread_local {
+                    true => ExprKind::ThreadLocal { name },
+                    false => ExprKind::Global { name },
+                };
+                return Expr::new(kind, ty.clone(), span);
+            }
+            Some(Resolved::Global(Symbol::Function(signature))) => {
+                return Expr::new(
+                    ExprKind::Function {
+                        name: name.to_string(),
+                    },
+                    signature.ty(),
+                    span,
+                );
+            }
+            None if self.function.implicit_declarations => {
+                // Declared at the function level, wherever it's first used.
+                let scopes = std::mem::take(&mut self.function.scopes);
+                self.function.scopes = vec![HashMap::new()];
+                let index = self.declare_local(
+                    &Ident {
+                        name: name.to_string(),
+                        span,
+                    },
+                    Type::Int,
+                    false,
+                );
+                let implicit_scope = self.function.scopes.pop().unwrap();
+
+                self.function.scopes = scopes;
+                self.function.scopes[0].extend(implicit_scope);
+                index
+            }
+            None => {
+                self.diags
+                    .error(span, format!("Use of undeclared identifier '{name}'"));
+                return Expr::error(span);
+            }
+        };
+
+        let local = &mut self.function.locals[index];
+        local.read |= read;
+
+        let kind = match &local.label {
+            Some(name) if local.is_thread_local => ExprKind::ThreadLocal { name: name.clone() },
+            Some(name) => ExprKind::Global { name: name.clone() },
+            // A variable length array is where its slot points to.
+            None if matches!(local.ty, Type::VariableArray { .. }) => ExprKind::Deref {
+                operand: Box::new(Expr::new(
+                    ExprKind::Local {
+                        offset: local.offset,
+                    },
+                    Type::pointer_to(local.ty.clone()),
+                    span,
+                )),
+            },
+            None => ExprKind::Local {
+                offset: local.offset,
+            },
+        };
+        Expr::new(kind, local.ty.clone(), span)
+    }
+
+    // Reports taking the address of `node` if it's a variable declared `register`.
+    fn check_register(&mut self, node: &Node, span: Span) {
+        let NodeKind::Var { name } = &node.kind else {
+            return;
+        };
+        if let Some(Resolved::Local(index)) = self.resolve(name) {
+            if self.function.locals[index].is_register {
+                self.diags
+                    .error(span, "Address of register variable requested");
+            }
+        }
+    }
+
+    fn call(&mut self, callee: &Node, nodes: &[Node], span: Span) -> Expr {
+        let args: Vec<_> = nodes.iter().map(|arg| self.rvalue(arg)).collect();
+
+        if let NodeKind::Var { name } = &callee.kind {
+            if self.resolve(name).is_none() {
+                if let Some(builtin) = builtin::builtin(name) {
+                    return self.builtin(builtin, args, span);
+                }
+                if let Some(builtin) = atomic::builtin(name) {
+                    return self.atomic_builtin(builtin, args, span);
+                }
+                self.diags
+                    .error(callee.span, format!("Call to undeclared function '{name}'"));
+                return Expr::error(span);
+            }
+        }
+
+        // A function designator is called directly, and anything else through a pointer.
+        let callee = self.expr(callee);
+        let callee = match callee.kind {
+            ExprKind::Function { .. } => callee,
+            _ => self.value(callee),
+        };
+        if callee.is_error() {
+            return Expr::error(span);
+        }
+        let function_type = match (&callee.kind, &callee.ty) {
+            (ExprKind::Function { .. }, ty) => ty,
+            (_, Type::Pointer(base)) if matches!(**base, Type::Function { .. }) => base,
+            (_, ty) => {
+                self.diags.error(
+                    callee.span,
+                    format!("Called object type '{ty}' is not a function or function pointer"),
+                );
+                return Expr::error(span);
+            }
+        };
+        let Type::Function {
+            return_type,
+            params,
+            is_variadic,
+        } = function_type.clone()
+        else {
+            unreachable!()
+        };
+
+        if args.len() < params.len() || (args.len() > params.len() && !is_variadic) {
+            let mut diagnostic = Diagnostic::error(
+                span,
+                format!(
+                    "Too {} arguments to function call, expected {}, have {}",
+                    if args.len() > params.len() {
+                        "many"
+                    } else {
+                        "few"
+                    },
+                    params.len(),
+                    args.len()
+                ),
+            );
+            if let ExprKind::Function { name } = &callee.kind {
+                if let Some(Symbol::Function(signature)) = self.globals.get(name) {
+                    diagnostic = diagnostic
+                        .with_note(Some(signature.span), format!("'{name}' is declared here"));
+                }
+            }
+            self.diags.emit(diagnostic);
+            return Expr::error(span);
+        }
+
+        let temporary = match *return_type {
+            Type::Struct(_) if !return_type.is_complete() => {
+                self.diags.error(
+                    span,
+                    format!("Calling function with incomplete return type '{return_type}'"),
+                );
+                return Expr::error(span);
+            }
+            Type::Struct(_) => Some(self.allocate(&return_type)),
+            _ => None,
+        };
+
+        if let ExprKind::Function { name } = &callee.kind {
+            let format = match self.globals.get(name) {
+                Some(Symbol::Function(signature)) => signature.format,
+                _ => None,
+            };
+            if let Some(format) = format {
+                self.check_format(format, nodes, &args);
+            }
+        }
+        // The arguments matching `...` only have the default argument promotions.
+        let args: Vec<_> = args
+            .into_iter()
+            .enumerate()
+            .map(|(index, arg)| match params.get(index) {
+                Some(ty) => self.convert_for_assignment(arg, ty),
+                None => {
+                    let ty = arg.ty.promoted();
+                    convert(arg, &ty)
+                }
+            })
+            .collect();
+
+        Expr::new(
+            ExprKind::Call {
+                callee: Box::new(callee),
+                args,
+                temporary,
+            },
+            *return_type,
+            span,
+        )
+    }
+
+    // Reports the arguments of a call which don't match its format string, when it's a literal.
+    fn check_format(&mut self, format: Format, nodes: &[Node], args: &[Expr]) {
+        let node = &nodes[format.string];
+        let NodeKind::Str(string) = &node.kind else {
+            return;
+        };
+        let expected = match format::arguments(string, format.style) {
+            Ok(expected) => expected,
+            Err(message) => {
+                self.diags
+                    .emit(Diagnostic::warning(Warning::Format, node.span, message));
+                return;
+            }
+        };
+        let Some(first) = format.first else {
+            return;
+        };
+
+        let data = &args[first..];
+        for (expected, arg) in expected.iter().zip(data) {
+            if !expected.accepts(&arg.ty.promoted()) && !arg.is_error() {
+                self.diags.emit(Diagnostic::warning(
+                    Warning::Format,
+                    arg.span,
+                    format!(
+                        "Format specifies type '{expected}' but the argument has type '{}'",
+                        arg.ty
+                    ),
+                ));
+            }
+        }
+        if expected.len() > data.len() {
+            self.diags.emit(Diagnostic::warning(
+                Warning::Format,
+                node.span,
+                "More '%' conversions than data arguments",
+            ));
+        } else if let Some(arg) = data.get(expected.len()) {
+            self.diags.emit(Diagnostic::warning(
+                Warning::Format,
+                arg.span,
+                "Data argument not used by format string",
+            ));
+        }
+    }
+
+    // `({ ... })`, whose value is that of its last statement, if it's an expression. A struct is
+    // copied, since it could be changed before the value is used.
+    fn stmt_expr(&mut self, body: &Node, span: Span) -> Expr {
+        let mut body = self.stmt(body);
+        let (Stmt::Block(stmts) | Stmt::StackBlock { stmts }) = &mut body else {
+            unreachable!("The body of a statement expression is a block")
+        };
+
+        let ty = match stmts.last_mut() {
+            Some(Stmt::Expr(last)) if last.ty != Type::Void => {
+                let value = std::mem::replace(last, Expr::error(span));
+                let value = self.value(value);
+                if let Type::Struct(_) = value.ty {
+                    let offset = self.allocate(&value.ty);
+                    let temporary = Expr::new(ExprKind::Local { offset }, value.ty.clone(), span);
+                    let assign = self.assign(temporary.clone(), value, span);
+                    *last = comma(assign, temporary, span);
+                } else {
+                    *last = value;
+                }
+                last.ty.clone()
+            }
+            _ => Type::Void,
+        };
+
+        Expr::new(
+            ExprKind::StmtExpr {
+                body: Box::new(body),
+            },
+            ty,
+            span,
+        )
+    }
+
+    // A call to a builtin other than an atomic one, like `__builtin_clz(x)`.
+    fn builtin(&mut self, builtin: Builtin, args: Vec<Expr>, span: Span) -> Expr {
+        if !self.check_builtin_arity(&args, builtin.arity(), span)
+            || args.iter().any(Expr::is_error)
+        {
+            return Expr::error(span);
+        }
+
+        let mut args = args.into_iter();
+        let mut arg = |ty: &Type| {
+            let arg = args.next().unwrap();
+            Box::new(self.convert_for_assignment(arg, ty))
+        };
+        let void_pointer = Type::pointer_to(Type::Void);
+        let (kind, ty) = match builtin {
+            // `__builtin_alloca(size)` allocates `size` bytes in the caller's frame until it
+            // returns.
+            Builtin::Alloca => (
+                ExprKind::Alloca {
+                    size: arg(&Type::ULong),
+                },
+                void_pointer,
+            ),
+            // The expected value is only a hint.
+            Builtin::Expect => return *arg(&Type::Long),
+            Builtin::Trap => (ExprKind::Trap, Type::Void),
+            Builtin::Bit { op, operand } => {
+                let ty = match op {
+                    BitOp::Bswap => operand.clone(),
+                    _ => Type::Int,
+                };
+                let operand = arg(&operand);
+                (ExprKind::BitOp { op, operand }, ty)
+            }
+            Builtin::Overflow(op) => return self.overflow(op, args.collect(), span),
+            // The operand isn't evaluated.
+            Builtin::ConstantP => {
+                let operand = args.next().unwrap();
+                let is_constant =
+                    operand.ty.is_integer() && const_eval::eval(&operand, self.target).is_ok();
+                (ExprKind::Num(i64::from(is_constant)), Type::Int)
+            }
+            Builtin::Memcpy => {
+                let const_void = Type::qualified(
+                    Type::Void,
+                    Qualifiers {
+                        is_const: true,
+                        ..Qualifiers::default()
+                    },
+                );
+                (
+                    ExprKind::MemCopy {
+                        dest: arg(&void_pointer),
+                        src: arg(&Type::pointer_to(const_void)),
+                        size: arg(&Type::ULong),
+                    },
+                    void_pointer,
+                )
+            }
+            Builtin::Memset => (
+                ExprKind::MemSet {
+                    dest: arg(&void_pointer),
+                    value: arg(&Type::Int),
+                    size: arg(&Type::ULong),
+                },
+                void_pointer,
+            ),
+        };
+
+        Expr::new(kind, ty, span)
+    }
+
+    // `__builtin_add_overflow(lhs, rhs, result)` and the like. The operation is done in 64 bits,
+    // signed unless an operand is an `unsigned long`, in which case a negative operand is taken
+    // as unsigned too.
+    fn overflow(&mut self, op: ArithOp, args: Vec<Expr>, span: Span) -> Expr {
+        let [lhs, rhs, result] = <[Expr; 3]>::try_from(args).unwrap();
+        for operand in [&lhs, &rhs] {
+            if !operand.ty.is_integer() {
+                self.diags.error(
+                    operand.span,
+                    format!(
+                        "Operand argument to overflow builtin must be an integer ('{}' invalid)",
+                        operand.ty
+                    ),
+                );
+                return Expr::error(span);
+            }
+        }
+        let is_integer = |ty: &Type| {
+            ty.is_integer() && *ty.unqualified() != Type::Bool && !ty.qualifiers().is_const
+        };
+        if !result.ty.base().is_some_and(is_integer) {
+            self.diags.error(
+                result.span,
+                format!(
+                    "Result argument to overflow builtin must be a pointer to a non-const \
+                     integer ('{}' invalid)",
+                    result.ty
+                ),
+            );
+            return Expr::error(span);
+        }
+
+        let is_unsigned_long =
+            |operand: &Expr| operand.ty.size() == 8 && self.target.is_unsigned(&operand.ty);
+        let ty = if is_unsigned_long(&lhs) || is_unsigned_long(&rhs) {
+            Type::ULong
+        } else {
+            Type::Long
+        };
+        Expr::new(
+            ExprKind::Overflow {
+                op,
+                lhs: Box::new(convert(lhs, &ty)),
+                rhs: Box::new(convert(rhs, &ty)),
+                result: Box::new(result),
+            },
+            Type::Bool,
+            span,
+        )
+    }
+
+    // `__builtin_offsetof(ty, designators)`, an `unsigned long` which is constant unless an index
+    // isn't.
+    fn offsetof(&mut self, ty: &TypeName, designators: &[Designator], span: Span) -> Expr {
+        let mut ty = self.resolve_type(ty, span);
+        if !matches!(ty.unqualified(), Type::Struct(_)) {
+            self.diags.error(
+                span,
+                format!("Offsetof requires struct or union type, '{ty}' invalid"),
+            );
+            return Expr::new(ExprKind::Num(0), Type::ULong, span);
+        }
+
+        let mut offset = Expr::new(ExprKind::Num(0), Type::ULong, span);
+        for designator in designators {
+            let (term, next) = match (designator, ty.unqualified()) {
+                (Designator::Member(ident), Type::Struct(_)) if !ty.is_complete() => {
+                    self.diags
+                        .error(ident.span, format!("Incomplete definition of type '{ty}'"));
+                    return Expr::error(span);
+                }
+                (Designator::Member(ident), Type::Struct(struct_ref)) => {
+                    let Some(found) = struct_ref.member(&ident.name) else {
+                        self.diags.error(
+                            ident.span,
+                            format!("No member named '{}' in '{ty}'", ident.name),
+                        );
+                        return Expr::error(span);
+                    };
+                    if found.bit_field.is_some() {
+                        self.diags.error(
+                            ident.span,
+                            format!("Cannot compute offset of bit-field '{}'", ident.name),
+                        );
+                        return Expr::error(span);
+                    }
+                    let term =
+                        Expr::new(ExprKind::Num(found.offset as i64), Type::ULong, ident.span);
+                    (term, found.ty)
+                }
+                (Designator::Index(node), Type::Array { element, .. }) => {
+                    let element = (**element).clone();
+                    let index = self.rvalue(node);
+                    if index.is_error() {
+                        return Expr::error(span);
+                    }
+                    if !index.ty.is_integer() {
+                        self.diags
+                            .error(node.span, "Array subscript is not an integer");
+                        return Expr::error(span);
+                    }
+                    let term = self.offset_in_bytes(&ty, index, node.span);
+                    (convert(term, &Type::ULong), element)
+                }
+                (Designator::Member(ident), _) => {
+                    self.diags.error(
+                        ident.span,
+                        format!("Member reference base type '{ty}' is not a structure"),
+                    );
+                    return Expr::error(span);
+                }
+                (Designator::Index(node), _) => {
+                    self.diags
+                        .error(node.span, "Subscripted value is not an array");
+                    return Expr::error(span);
+                }
+            };
+            offset = Expr::new(
+                ExprKind::ArithOp {
+                    value: ArithOp::Add,
+                    lhs: Box::new(offset),
+                    rhs: Box::new(term),
+                },
+                Type::ULong,
+                span,
+            );
+            ty = next;
+        }
+        offset
+    }
+
+    // A call to an atomic builtin, like `__atomic_fetch_add(pointer, value, order)`.
+    fn atomic_builtin(
+        &mut self,
+        builtin: atomic::Builtin,
+        mut args: Vec<Expr>,
+        span: Span,
+    ) -> Expr {
+        let (operands, orders) = builtin.arity();
+        if !self.check_builtin_arity(&args, operands + orders, span)
+            || args.iter().any(Expr::is_error)
+        {
+            return Expr::error(span);
+        }
+
+        let orders = args.split_off(operands);
+        for order in &orders {
+            if !order.ty.is_integer() {
+                self.diags.error(
+                    order.span,
+                    format!("Memory order argument has non-integer type '{}'", order.ty),
+                );
+            }
+        }
+        // Only the first order, which is the one on success of a compare-exchange, matters.
+        let order = match builtin.orders {
+            Orders::Fixed(order) => order,
+            Orders::Ignored => MemoryOrder::Relaxed,
+            Orders::Taken => const_eval::eval(&orders[0], self.target)
+                .map_or(MemoryOrder::SeqCst, |constant| {
+                    MemoryOrder::from_value(constant.value)
+                }),
+        };
+
+        if builtin.op == AtomicOp::Fence {
+            return Expr::new(
+                ExprKind::Atomic {
+                    op: builtin.op,
+                    order,
+                    args: vec![],
+                },
+                Type::Void,
+                span,
+            );
+        }
+
+        let mut args = args.into_iter();
+        let pointer = args.next().unwrap();
+        let Some(ty) = self.atomic_object(builtin, &pointer) else {
+            return Expr::error(span);
+        };
+        let mut operand = |sema: &mut Self, ty: &Type| {
+            let arg = args.next().unwrap();
+            sema.convert_for_assignment(arg, ty)
+        };
+
+        let (operands, result_ty) = match builtin.op {
+            AtomicOp::Load => (vec![], ty),
+            AtomicOp::Store if builtin.family == Family::Sync => (
+                vec![convert(Expr::new(ExprKind::Num(0), Type::Int, span), &ty)],
+                Type::Void,
+            ),
+            AtomicOp::Store => (vec![operand(self, &ty)], Type::Void),
+            AtomicOp::Exchange => (vec![operand(self, &ty)], ty),
+            AtomicOp::CompareExchange {
+                through_pointer,
+                returns_previous,
+            } => {
+                let expected = match through_pointer {
+                    true => operand(self, &Type::pointer_to(ty.clone())),
+                    false => operand(self, &ty),
+                };
+                // A compare-exchange never fails spuriously, so a weak one is strong.
+                let desired = operand(self, &ty);
+                let result_ty = if returns_previous { ty } else { Type::Bool };
+                (vec![expected, desired], result_ty)
+            }
+            // Like gcc, only the `__c11_atomic` builtins scale the offset of a pointer.
+            AtomicOp::Fetch { .. } => match ty {
+                Type::Pointer(_) if builtin.family == Family::C11 => {
+                    let offset = operand(self, &Type::Long);
+                    (vec![self.offset_in_bytes(&ty, offset, span)], ty)
+                }
+                Type::Pointer(_) => (vec![operand(self, &Type::Long)], ty),
+                _ => (vec![operand(self, &ty)], ty),
+            },
+            AtomicOp::Fence => unreachable!(),
+        };
+
+        Expr::new(
+            ExprKind::Atomic {
+                op: builtin.op,
+                order,
+                args: [pointer].into_iter().chain(operands).collect(),
+            },
+            result_ty,
+            span,
+        )
+    }
+
+    // The unqualified type of the object an atomic builtin operates on through `pointer`, or None
+    // after reporting why it can't.
+    fn atomic_object(&mut self, builtin: atomic::Builtin, pointer: &Expr) -> Option<Type> {
+        let Type::Pointer(object) = &pointer.ty else {
+            self.atomic_object_error(pointer, "a pointer");
+            return None;
+        };
+        let is_bitwise = matches!(
+            builtin.op,
+            AtomicOp::Fetch { op, .. } if op != FetchOp::Add && op != FetchOp::Sub
+        );
+
+        let requirement = match object {
+            _ if builtin.family == Family::C11 && !object.qualifiers().is_atomic => {
+                "a pointer to _Atomic type"
+            }
+            _ if !object.is_scalar() || !object.is_complete() => "a pointer to integer or pointer",
+            _ if is_bitwise && !object.is_integer() => "a pointer to integer",
+            _ if object.is_const() && builtin.op != AtomicOp::Load => "a pointer to non-const type",
+            _ => return Some(object.unqualified().clone()),
+        };
+        self.atomic_object_error(pointer, requirement);
+        None
+    }
+
+    fn atomic_object_error(&mut self, pointer: &Expr, requirement: &str) {
+        self.diags.error(
+            pointer.span,
+            format!(
+                "Address argument to atomic operation must be {requirement} ('{}' invalid)",
+                pointer.ty
+            ),
+        );
+    }
+
+    // Reports a call to a builtin with other than `expected` arguments, if it is.
+    fn check_builtin_arity(&mut self, args: &[Expr], expected: usize, span: Span) -> bool {
+        if args.len() == expected {
+            return true;
+        }
+
+        self.diags.error(
+            span,
+            format!(
+                "Too {} arguments to function call, expected {expected}, have {}",
+                if args.len() > expected { "many" } else { "few" },
+                args.len()
+            ),
+        );
+        false
+    }
+}
+
+// `&lvalue`
+fn address_of(lvalue: Expr, span: Span) -> Expr {
+    let ty = Type::pointer_to(lvalue.ty.clone());
+    Expr::new(
+        ExprKind::Addr {
+            operand: Box::new(lvalue),
+        },
+        ty,
+        span,
+    )
+}
+
+// `lhs, rhs`
+fn comma(lhs: Expr, rhs: Expr, span: Span) -> Expr {
+    let ty = rhs.ty.clone();
+
+    Expr::new(
+        ExprKind::Comma {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+        ty,
+        span,
+    )
+}
+
+// Whether the address of an lvalue can be written in an asm operand without a register.
+fn is_simple_address(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Local { .. } | ExprKind::Global { .. })
+}
+
+fn has_side_effects(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Num(_)
+        | ExprKind::Local { .. }
+        | ExprKind::Global { .. }
+        | ExprKind::ThreadLocal { .. }
+        | ExprKind::Function { .. } => false,
+        ExprKind::Assign { .. }
+        | ExprKind::MemZero { .. }
+        | ExprKind::Call { .. }
+        | ExprKind::Alloca { .. }
+        | ExprKind::StmtExpr { .. }
+        | ExprKind::Atomic { .. }
+        | ExprKind::Trap
+        | ExprKind::Overflow { .. }
+        | ExprKind::MemCopy { .. }
+        | ExprKind::MemSet { .. } => true,
+        ExprKind::ArithOp { lhs, rhs, .. }
+        | ExprKind::CmpOp { lhs, rhs, .. }
+        | ExprKind::Comma { lhs, rhs } => has_side_effects(lhs) || has_side_effects(rhs),
+        ExprKind::Neg { operand }
+        | ExprKind::Cast { operand }
+        | ExprKind::Addr { operand }
+        | ExprKind::Deref { operand }
+        | ExprKind::Member { operand, .. }
+        | ExprKind::BitOp { operand, .. } => has_side_effects(operand),
+    }
+}
+
+// The type of an integer literal: the first type for its suffix which can represent it
+// (C11 6.4.4.1). Like gcc, a literal too large for `long long` is `unsigned long long`.
+fn literal_type(value: u64, suffix: IntSuffix) -> Type {
+    let candidates = match (suffix.is_unsigned, suffix.longs) {
+        (false, 0) => vec![Type::Int, Type::Long, Type::LongLong],
+        (false, 1) => vec![Type::Long, Type::LongLong],
+        (false, _) => vec![Type::LongLong],
+        (true, 0) => vec![Type::UInt, Type::ULong, Type::ULongLong],
+        (true, 1) => vec![Type::ULong, Type::ULongLong],
+        (true, _) => vec![Type::ULongLong],
+    };
+
+    candidates
+        .into_iter()
+        .find(|ty| {
+            let bits = ty.size() * 8 - usize::from(!ty.is_unsigned());
+            value <= u64::MAX >> (64 - bits)
+        })
+        .unwrap_or(Type::ULongLong)
+}
+
+// An integer constant expression with the value 0.
+fn is_null_pointer_constant(expr: &Expr) -> bool {
+    expr.ty.is_integer() && const_eval::is_zero(expr) == Some(true)
+}
+
+// The value of an lvalue, whose type has no qualifiers.
+fn unqualified(expr: Expr) -> Expr {
+    let ty = expr.ty.unqualified().clone();
+    Expr { ty, ..expr }
+}
+
+// Like a narrower integer, a bit-field whose values all fit in `int` is promoted to `int`.
+fn promote_bit_field(expr: Expr) -> Expr {
+    let ExprKind::Member {
+        bit_field: Some(BitField { width, .. }),
+        ..
+    } = expr.kind
+    else {
+        return expr;
+    };
+
+    if expr.ty.size() <= Type::Int.size() && (width < 32 || !expr.ty.is_unsigned()) {
+        convert(expr, &Type::Int)
+    } else {
+        expr
+    }
+}
+
+// `expr` converted to `ty`, with a cast if the types differ.
+fn convert(expr: Expr, ty: &Type) -> Expr {
+    if expr.ty == *ty {
+        expr
+    } else {
+        let span = expr.span;
+        Expr::new(
+            ExprKind::Cast {
+                operand: Box::new(expr),
+            },
+            ty.clone(),
+            span,
+        )
+    }
+}
+
+// The expressions storing `value` to the object `lhs`: the object is zeroed first, unless a single
+// value is assigned to it.
+fn initialization(lhs: Expr, value: InitValue, span: Span) -> Vec<Expr> {
+    let mut exprs = vec![];
+    if !matches!(value, InitValue::Expr(_)) {
+        exprs.push(Expr::new(
+            ExprKind::MemZero {
+                operand: Box::new(lhs.clone()),
+            },
+            Type::Void,
+            span,
+        ));
+    }
+
+    store_init_value(lhs, value, span, &mut exprs);
+    exprs
+}
+
+fn store_init_value(lhs: Expr, value: InitValue, span: Span, exprs: &mut Vec<Expr>) {
+    match value {
+        InitValue::Zero => {}
+        InitValue::Expr(rhs) => exprs.push(Expr::new(
+            ExprKind::Assign {
+                lhs: Box::new(lhs.clone()),
+                rhs: Box::new(rhs),
+            },
+            lhs.ty,
+            span,
+        )),
+        InitValue::Aggregate(values) => {
+            for (index, value) in values.into_iter().enumerate() {
+                let (ty, offset, bit_field) = subobject(&lhs.ty, index).unwrap();
+                let member = Expr::new(
+                    ExprKind::Member {
+                        operand: Box::new(lhs.clone()),
+                        offset,
+                        bit_field,
+                    },
+                    ty,
+                    span,
+                );
+                store_init_value(member, value, span, exprs);
+            }
+        }
+    }
+}
+
+// The type and offset of the element or member at `index`, if there is one, and where the bits
+// of a bit-field are.
+fn subobject(ty: &Type, index: usize) -> Option<(Type, usize, Option<BitField>)> {
+    match ty.unqualified() {
+        Type::Array { element, len } if len.map_or(true, |len| index < len) => {
+            Some(((**element).clone(), index * element.size(), None))
+        }
+        Type::Struct(struct_ref) => {
+            struct_ref
+                .borrow()
+                .members
+                .as_ref()?
+                .get(index)
+                .map(|member| {
+                    let ty = Type::qualified(member.ty.clone(), ty.qualifiers());
+                    (ty, member.offset, member.bit_field)
+                })
+        }
+        _ => None,
+    }
+}
+
+fn is_union(ty: &Type) -> bool {
+    matches!(ty.unqualified(), Type::Struct(struct_ref) if struct_ref.is_union())
+}
+
+// The indices of the member named `name`, through the anonymous structs and unions it's in.
+fn member_path(struct_ref: &StructRef, name: &str) -> Option<Vec<usize>> {
+    let this = struct_ref.borrow();
+    this.members
+        .as_ref()?
+        .iter()
+        .enumerate()
+        .find_map(
+            |(index, member)| match (&member.name, member.ty.unqualified()) {
+                (Some(member_name), _) if member_name == name => Some(vec![index]),
+                (None, Type::Struct(inner)) => member_path(inner, name).map(|mut path| {
+                    path.insert(0, index);
+                    path
+                }),
+                _ => None,
+            },
+        )
+}
+
+fn set_init_value(values: &mut Vec<InitValue>, index: usize, value: InitValue) {
+    if values.len() <= index {
+        values.resize_with(index + 1, || InitValue::Zero);
+    }
+    values[index] = value;
+}
+
+// The string literal initializing an array of `char`, which can be in braces.
+fn string_literal<'a>(init: &'a Initializer, ty: &Type) -> Option<(&'a str, Span)> {
+    if !ty.base().is_some_and(Type::is_char) || !matches!(ty, Type::Array { .. }) {
+        return None;
+    }
+
+    let node = match init {
+        Initializer::Expr(node) => node,
+        Initializer::List { items, .. } => match &items[..] {
+            [InitItem {
+                designators,
+                value: Initializer::Expr(node),
+            }] if designators.is_empty() => node,
+            _ => return None,
+        },
+    };
+    match &node.kind {
+        NodeKind::Str(str) => Some((str, node.span)),
+        _ => None,
+    }
+}
+
+fn is_always_true(cond: &Expr) -> bool {
+    const_eval::is_zero(cond) == Some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::parse_str;
+
+    use super::*;
+
+    // Every diagnostic as (start, message), and the program.
+    fn analyze_str(c_code: &str) -> (Vec<(usize, String)>, Program) {
+        let (nodes, mut diags) = parse_str(c_code, &["-Wall", "-Wshadow", "-Wsign-compare"]);
+        let program = analyze(&nodes, Target::default(), &mut diags);
+
+        let diagnostics = match diags.finish(()) {
+            Ok(((), warnings)) => warnings.0,
+            Err(errors) => errors.0,
+        };
+        (
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| (diagnostic.span.start, diagnostic.message))
+                .collect(),
+            program,
+        )
+    }
+
+    fn diagnostics(c_code: &str) -> Vec<(usize, String)> {
+        analyze_str(c_code).0
+    }
+
+    #[test]
+    fn test_conversions() {
+        let (diagnostics, program) = analyze_str("char c = 1; long l; l + c;");
+
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(
+            program.globals,
+            vec![
+                Global {
+                    name: "c".to_string(),
+                    ty: Type::Char,
+                    init: Some(vec![1]),
+                    relocations: vec![],
+                    is_static: false,
+                    is_thread_local: false,
+                    align: 1,
+                },
+                Global {
+                    name: "l".to_string(),
+                    ty: Type::Long,
+                    init: None,
+                    relocations: vec![],
+                    is_static: false,
+                    is_thread_local: false,
+                    align: 8,
+                },
+            ]
+        );
+
+        let [Stmt::Expr(expr)] = &program.functions[0].body[..] else {
+            panic!("Must be an expression: {:?}", program.functions[0].body);
+        };
+        let ExprKind::ArithOp { lhs, rhs, .. } = &expr.kind else {
+            panic!("Must be ArithOp: {expr:?}");
+        };
+        assert_eq!(expr.ty, Type::Long);
+        assert!(matches!(lhs.kind, ExprKind::Global { .. }));
+        // `char` is promoted and then converted to `long`.
+        assert!(matches!(rhs.kind, ExprKind::Cast { .. }));
+        assert_eq!(rhs.ty, Type::Long);
+    }
+
+    #[test]
+    fn test_integer_types() {
+        let (diagnostics, program) = analyze_str(
+            "1; 2147483648; 1u; 4294967296u; 1l; 1ull; 18446744073709551615; sizeof(int); \
+             (unsigned short)1 + (unsigned char)1; 1u + 1; 1u + 1l; 1ul + 1ll; 1 < 2u;",
+        );
+
+        assert_eq!(diagnostics, vec![]);
+        let types: Vec<_> = program.functions[0]
+            .body
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Expr(expr) => expr.ty.clone(),
+                _ => panic!("Must be an expression: {stmt:?}"),
+            })
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                Type::Int,
+                Type::Long,
+                Type::UInt,
+                Type::ULong,
+                Type::Long,
+                Type::ULongLong,
+                Type::ULongLong,
+                Type::ULong,
+                Type::Int,
+                Type::UInt,
+                Type::Long,
+                Type::ULongLong,
+                Type::Int,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locals() {
+        let (diagnostics, program) =
+            analyze_str("int f(char a, long b) { short c = a; { int d = c; return d + b; } }");
+
+        assert_eq!(diagnostics, vec![]);
+
+        let function = &program.functions[0];
+        assert_eq!(
+            function.params,
+            vec![
+                Local {
+                    offset: 1,
+                    ty: Type::Char
+                },
+                Local {
+                    offset: 16,
+                    ty: Type::Long
+                }
+            ]
+        );
+        assert_eq!(function.stack_size, 32);
+    }
+
+    #[test]
+    fn test_constant_value() {
+        let (_, program) = analyze_str("char c = 127 + 1; int i = 2 * (3 - 5) / 2 == -2;");
+
+        let inits: Vec<_> = program
+            .globals
+            .iter()
+            .map(|global| global.init.clone())
+            .collect();
+        assert_eq!(inits, vec![Some(vec![0x80]), Some(vec![1, 0, 0, 0])]);
+    }
+
+    #[test]
+    fn test_ng_syntax_errors_in_body() {
+        // The dropped statement read `x` and returned, and an invalid token is a syntax error too.
+        assert_eq!(
+            diagnostics(
+                "int f(void) { int x; if (x = 1) return 2 } int g(void) { int y; return 1 @ 2; }"
+            ),
+            vec![
+                (41, "Expected ';'".to_string()),
+                (73, "Invalid token".to_string()),
+            ]
+        );
+        assert_eq!(
+            diagnostics("int f(void) { int x; return 1; } int g(void) { int y; }"),
+            vec![
+                (18, "Unused variable 'x'".to_string()),
+                (51, "Unused variable 'y'".to_string()),
+                (
+                    54,
+                    "Non-void function 'g' does not return a value".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_names() {
+        assert_eq!(
+            diagnostics("int a; int a = 1; int a = 2; long a; int f() { b = 1; return a; } a();"),
+            vec![
+                (22, "Redefinition of 'a'".to_string()),
+                (34, "Redefinition of 'a' with a different type".to_string()),
+                (47, "Use of undeclared identifier 'b'".to_string()),
+                (
+                    66,
+                    "Called object type 'int' is not a function or function pointer".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics("int f(int a) { int a; { int a; } return g(); }"),
+            vec![
+                (19, "Redefinition of 'a'".to_string()),
+                (28, "Declaration shadows a local variable".to_string()),
+                (28, "Unused variable 'a'".to_string()),
+                (40, "Call to undeclared function 'g'".to_string()),
+            ]
+        );
+        // Nothing using an undeclared name is reported again.
+        assert_eq!(
+            diagnostics(
+                "int g(int); int f(int i) { int *p = &t[i]; p = &s->x; u.x = *v + -w; \
+                 return *p + g(x) + (y < 1); }"
+            ),
+            vec![
+                (37, "Use of undeclared identifier 't'".to_string()),
+                (48, "Use of undeclared identifier 's'".to_string()),
+                (54, "Use of undeclared identifier 'u'".to_string()),
+                (61, "Use of undeclared identifier 'v'".to_string()),
+                (66, "Use of undeclared identifier 'w'".to_string()),
+                (83, "Use of undeclared identifier 'x'".to_string()),
+                (89, "Use of undeclared identifier 'y'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_types() {
+        assert_eq!(
+            diagnostics(
+                "void v(void); void w(void) { return 1; } int f(int a) { return; } \
+                 int x = v(); void y; f(); 1 = 2;"
+            ),
+            vec![
+                (
+                    36,
+                    "Void function 'w' should not return a value".to_string()
+                ),
+                (
+                    56,
+                    "Non-void function 'f' should return a value".to_string()
+                ),
+                (74, "Void value not ignored as it ought to be".to_string()),
+                (84, "Variable 'y' has incomplete type 'void'".to_string()),
+                (
+                    87,
+                    "Too few arguments to function call, expected 1, have 0".to_string()
+                ),
+                (92, "Expression is not assignable".to_string()),
+            ]
+        );
+        assert_eq!(
+            diagnostics("int g; int x = g;"),
+            vec![(
+                15,
+                "Initializer element is not a compile-time constant".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_global_data() {
+        let (diagnostics, program) = analyze_str(
+            "struct P { char c; int i; } p = {.i = 2, .c = 1}; short a[] = {[2] = -1, 3}; \
+             short *q = &a[1] + 1; char s[4] = \"hi\";",
+        );
+        assert_eq!(diagnostics, vec![]);
+
+        let data: Vec<_> = program
+            .globals
+            .iter()
+            .map(|global| (global.ty.to_string(), global.init.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            data,
+            vec![
+                ("struct P".to_string(), vec![1, 0, 0, 0, 2, 0, 0, 0]),
+                ("short[4]".to_string(), vec![0, 0, 0, 0, 0xff, 0xff, 3, 0]),
+                ("short *".to_string(), vec![0; 8]),
+                ("char[4]".to_string(), vec![b'h', b'i', 0, 0]),
+            ]
+        );
+        assert_eq!(
+            program.globals[2].relocations,
+            vec![Relocation {
+                offset: 0,
+                label: "a".to_string(),
+                addend: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        let (diagnostics, program) = analyze_str(
+            "struct B { char c; unsigned a : 3, : 2; int b : 4; short s : 9; } g = { 1, 13, -1, -2 }; \
+             int f(struct B *p) { return p->a + p->b; }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(program.globals[0].init, Some(vec![0x01, 0xe5, 0xfd, 0x03]));
+
+        // Narrow bit-fields are promoted to `int`, even unsigned ones.
+        let Stmt::Return(Some(value)) = &program.functions[0].body[0] else {
+            panic!("Must be a return: {:?}", program.functions[0].body);
+        };
+        let ExprKind::ArithOp { lhs, .. } = &value.kind else {
+            panic!("Must be an addition: {value:?}");
+        };
+        assert_eq!((&value.ty, &lhs.ty), (&Type::Int, &Type::Int));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let (diagnostics, program) = analyze_str(
+            "struct __attribute__((packed)) P { char c; int i; short s : 12, t : 4; }; \
+             struct A { char c; } __attribute__((aligned(8))); \
+             struct M { char c; int i __attribute__((aligned(16))); }; \
+             _Static_assert(sizeof(struct P) == 7, \"P\"); _Static_assert(_Alignof(struct P) == 1, \"P\"); \
+             _Static_assert(sizeof(struct A) == 8, \"A\"); _Static_assert(sizeof(struct M) == 32, \"M\"); \
+             extern int v __asm__(\"w\") __attribute__((weak)); \
+             __attribute__((visibility(\"hidden\"), section(\"s\"))) int v; \
+             __attribute__((constructor(101))) void f(void) {}",
+        );
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(
+            program.symbols["v"],
+            SymbolAttributes {
+                label: Some("w".to_string()),
+                is_weak: true,
+                visibility: Some(Visibility::Hidden),
+                section: Some("s".to_string()),
+                constructor: None,
+                destructor: None,
+            }
+        );
+        assert_eq!(program.symbols["f"].constructor, Some(101));
+    }
+
+    #[test]
+    fn test_ng_attributes() {
+        assert_eq!(
+            diagnostics(
+                "int p(const char *f, ...) __attribute__((format(printf, 1, 2))); \
+                 __attribute__((foo)) int a; int b __attribute__((noreturn)); \
+                 static int c __attribute__((weak)); int d __attribute__((aligned(3))); \
+                 int e(int n) __attribute__((format(printf, 1, 0))); \
+                 struct __attribute__((packed)) S { char c : 7; int x : 30; }; \
+                 void f(void) { int u __attribute__((unused)); int v __asm__(\"v\") = 0; p(\"\", v); \
+                 p(\"%d %s\", 1); p(\"%s\", 1, 2); p(\"%q\"); }"
+            ),
+            vec![
+                (80, "Unknown attribute 'foo' ignored".to_string()),
+                (114, "'noreturn' attribute only applies to functions".to_string()),
+                (154, "Weak declaration cannot have internal linkage".to_string()),
+                (191, "Requested alignment is not a power of 2".to_string()),
+                (240, "Format argument not a string type".to_string()),
+                (
+                    300,
+                    "Packed bit-field 'x' crosses its storage unit, which is not supported"
+                        .to_string()
+                ),
+                (370, "Asm label on a variable with automatic storage is ignored".to_string()),
+                (387, "Data argument not used by format string".to_string()),
+                (393, "More '%' conversions than data arguments".to_string()),
+                (
+                    414,
+                    "Format specifies type 'char *' but the argument has type 'int'".to_string()
+                ),
+                (417, "Data argument not used by format string".to_string()),
+                (423, "Incomplete format specifier".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_asm() {
+        let (diagnostics, program) = analyze_str(
+            "asm(\".text\"); int g; \
+             int f(int x, int *p) { asm(\"%0 %1 %2 %3 %4\" : \"=r\"(x), \"=m\"(g) \
+             : \"0\"(x), \"i\"(1 + 2), \"m\"(*p) : \"rax\", \"rbx\"); return x; }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(program.asm, [".text"]);
+
+        let Stmt::Asm(asm) = &program.functions[0].body[0] else {
+            panic!("Must be an asm: {:?}", program.functions[0].body);
+        };
+        let places: Vec<_> = asm.operands.iter().map(|operand| &operand.place).collect();
+        // rax is clobbered, and p has to be in a register.
+        assert_eq!(
+            places,
+            [
+                &AsmPlace::Register(Register::RCX),
+                &AsmPlace::Memory { base: None },
+                &AsmPlace::Register(Register::RCX),
+                &AsmPlace::Immediate(3),
+                &AsmPlace::Memory {
+                    base: Some(Register::RDX)
+                },
+            ]
+        );
+        assert_eq!(asm.saved, [Register::RBX]);
+    }
+
+    #[test]
+    fn test_ng_inline_asm() {
+        assert_eq!(
+            diagnostics(
+                "int f(int x) { const int k = 1; \
+                 asm(\"%1\" : \"=r\"(x)); asm(\"\" : \"r\"(x) : \"x\"(x)); \
+                 asm(\"\" : \"=r\"(k), \"=a\"(x), \"=a\"(x) : \"m\"(x + 1), \"i\"(x)); \
+                 asm(\"\" : \"=a\"(x) : \"a\"(x) : \"rbp\", \"foo\", \"xmm0\", \"cc\"); \
+                 asm(\"\" :: \"b\"(x) : \"rbx\"); return x; }"
+            ),
+            vec![
+                // Writing to it isn't a use.
+                (25, "Unused variable 'k'".to_string()),
+                (
+                    36,
+                    "Invalid operand number 1 in inline asm string".to_string()
+                ),
+                (62, "Invalid output constraint 'r' in asm".to_string()),
+                (71, "Invalid input constraint 'x' in asm".to_string()),
+                (
+                    94,
+                    "Cannot assign to variable 'k' with const-qualified type 'const int'"
+                        .to_string()
+                ),
+                (
+                    107,
+                    "Register 'rax' is used by more than one asm operand".to_string()
+                ),
+                (
+                    121,
+                    "Invalid lvalue in asm input for constraint 'm'".to_string()
+                ),
+                (
+                    133,
+                    "Constraint 'i' expects an integer constant expression".to_string()
+                ),
+                (166, "Clobbering 'rbp' is not supported".to_string()),
+                (173, "Unknown register name 'foo' in asm".to_string()),
+                (
+                    205,
+                    "Asm operand in register 'rbx' conflicts with the clobber list".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_atomics() {
+        let (diagnostics, program) = analyze_str(
+            "_Atomic int a; int f(int *p) { a = 1; a += 2; a *= 3; \
+             return __atomic_fetch_add(p, 1, 2) - __sync_val_compare_and_swap(p, 1, 2); }",
+        );
+        assert_eq!(diagnostics, vec![]);
+
+        let atomic = |expr: &Expr| match &expr.kind {
+            ExprKind::Atomic { op, order, .. } => Some((*op, *order)),
+            _ => None,
+        };
+        let body = &program.functions[0].body;
+        let exprs: Vec<_> = body[..3]
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Expr(expr) => atomic(expr),
+                _ => panic!("Must be an expression: {stmt:?}"),
+            })
+            .collect();
+        // `*=` is a loop around a compare-exchange.
+        assert_eq!(
+            exprs,
+            [
+                Some((AtomicOp::Store, MemoryOrder::SeqCst)),
+                Some((
+                    AtomicOp::Fetch {
+                        op: FetchOp::Add,
+                        returns_new: true
+                    },
+                    MemoryOrder::SeqCst
+                )),
+                None,
+            ]
+        );
+
+        let Stmt::Return(Some(Expr {
+            kind: ExprKind::ArithOp { lhs, rhs, .. },
+            ..
+        })) = &body[3]
+        else {
+            panic!("Must be a subtraction: {:?}", body[3]);
+        };
+        assert_eq!(
+            atomic(lhs),
+            Some((
+                AtomicOp::Fetch {
+                    op: FetchOp::Add,
+                    returns_new: false
+                },
+                MemoryOrder::Acquire
+            ))
+        );
+        assert_eq!(
+            atomic(rhs),
+            Some((
+                AtomicOp::CompareExchange {
+                    through_pointer: false,
+                    returns_previous: true
+                },
+                MemoryOrder::SeqCst
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ng_atomics() {
+        assert_eq!(
+            diagnostics(
+                "struct S { _Atomic int b : 3; } s; _Atomic(int[3]) a; const int c; int *p; \
+                 int main() { __atomic_load_n(p); __atomic_fetch_add(1, 1, 5); \
+                 __c11_atomic_load(p, 5); __atomic_store_n(&c, 1, 5); __atomic_fetch_or(&p, 1, 5); \
+                 __atomic_load_n(&s, 5); __atomic_store_n(p, 1, p); return 0; }"
+            ),
+            vec![
+                (
+                    23,
+                    "Bit-field 'b' has atomic type '_Atomic int'".to_string()
+                ),
+                (
+                    51,
+                    "_Atomic cannot be applied to non-scalar type 'int[3]'".to_string()
+                ),
+                (
+                    88,
+                    "Too few arguments to function call, expected 2, have 1".to_string()
+                ),
+                (
+                    127,
+                    "Address argument to atomic operation must be a pointer ('int' invalid)"
+                        .to_string()
+                ),
+                (
+                    155,
+                    "Address argument to atomic operation must be a pointer to _Atomic type \
+                     ('int *' invalid)"
+                        .to_string()
+                ),
+                (
+                    179,
+                    "Address argument to atomic operation must be a pointer to non-const type \
+                     ('const int *' invalid)"
+                        .to_string()
+                ),
+                (
+                    208,
+                    "Address argument to atomic operation must be a pointer to integer \
+                     ('int **' invalid)"
+                        .to_string()
+                ),
+                (
+                    235,
+                    "Address argument to atomic operation must be a pointer to integer or \
+                     pointer ('struct S *' invalid)"
+                        .to_string()
+                ),
+                (
+                    266,
+                    "Memory order argument has non-integer type 'int *'".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thread_locals() {
+        let (diagnostics, program) = analyze_str(
+            "_Thread_local int a = 1; extern __thread int b; \
+             int f(void) { static __thread int c; return a + b + c; }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        let globals: Vec<_> = program
+            .globals
+            .iter()
+            .map(|global| (global.name.as_str(), global.is_thread_local))
+            .collect();
+        assert_eq!(globals, [("a", true), ("f.c.1", true)]);
+
+        let Stmt::Return(Some(expr)) = &program.functions[0].body[1] else {
+            panic!("Must be a return: {:?}", program.functions[0].body);
+        };
+        let ExprKind::ArithOp { lhs, rhs, .. } = &expr.kind else {
+            panic!("Must be ArithOp: {expr:?}");
+        };
+        assert!(matches!(&rhs.kind, ExprKind::ThreadLocal { name } if name == "f.c.1"));
+        assert!(matches!(&lhs.kind, ExprKind::ArithOp { .. }));
+    }
+
+    #[test]
+    fn test_ng_thread_locals() {
+        assert_eq!(
+            diagnostics(
+                "int x; _Thread_local int x; __thread int y; extern int y; _Thread_local int z; \
+                 int *p = &z; int f(void) { extern int z; return 0; }"
+            ),
+            vec![
+                (
+                    25,
+                    "Thread-local declaration of 'x' follows non-thread-local declaration"
+                        .to_string()
+                ),
+                (
+                    55,
+                    "Non-thread-local declaration of 'y' follows thread-local declaration"
+                        .to_string()
+                ),
+                (
+                    88,
+                    "Initializer element is not a compile-time constant".to_string()
+                ),
+                (
+                    117,
+                    "Non-thread-local declaration of 'z' follows thread-local declaration"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        let (diagnostics, program) = analyze_str(
+            "struct S { char a; struct { int b[4]; } s; } g; \
+             char c[] = { __builtin_offsetof(struct S, s.b[2]), __builtin_popcountl(-1), \
+             __builtin_clz(1), __builtin_bswap16(1), __builtin_types_compatible_p(int, const int), \
+             __builtin_types_compatible_p(int, long), __builtin_constant_p(g.a) }; \
+             int f(int *p, unsigned x) { if (__builtin_expect(x, 0)) __builtin_unreachable(); \
+             return __builtin_add_overflow(x, 1, p) + __builtin_ctz(x); }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(program.globals[1].init, Some(vec![12, 64, 31, 0, 1, 0, 0]));
+
+        // `__builtin_unreachable` doesn't fall through, so there's no implicit return.
+        let body = &program.functions[0].body;
+        assert!(matches!(
+            &body[0],
+            Stmt::If { then, .. } if matches!(&**then, Stmt::Expr(Expr { kind: ExprKind::Trap, .. }))
+        ));
+        let Stmt::Return(Some(Expr {
+            kind: ExprKind::ArithOp { lhs, rhs, .. },
+            ..
+        })) = &body[1]
+        else {
+            panic!("Must be an addition: {:?}", body[1]);
+        };
+        let ExprKind::Cast { operand: lhs } = &lhs.kind else {
+            panic!("Must be a cast: {lhs:?}");
+        };
+        assert!(matches!(
+            &lhs.kind,
+            ExprKind::Overflow { op: ArithOp::Add, lhs, .. } if lhs.ty == Type::Long
+        ));
+        assert!(matches!(&rhs.kind, ExprKind::BitOp { op: BitOp::Ctz, .. }));
+    }
+
+    #[test]
+    fn test_ng_builtins() {
+        assert_eq!(
+            diagnostics(
+                "struct S { int a : 3; int b[2]; } *p; const int c; \
+                 int main() { __builtin_add_overflow(p, 1, &c); __builtin_mul_overflow(1, 1, &c); \
+                 __builtin_offsetof(int, a); __builtin_offsetof(struct S, a); \
+                 __builtin_offsetof(struct S, b.c); __builtin_offsetof(struct S, b[p]); \
+                 __builtin_trap(1); return 0; }"
+            ),
+            vec![
+                (
+                    87,
+                    "Operand argument to overflow builtin must be an integer ('struct S *' invalid)"
+                        .to_string()
+                ),
+                (
+                    127,
+                    "Result argument to overflow builtin must be a pointer to a non-const \
+                     integer ('const int *' invalid)"
+                        .to_string()
+                ),
+                (
+                    132,
+                    "Offsetof requires struct or union type, 'int' invalid".to_string()
+                ),
+                (
+                    189,
+                    "Cannot compute offset of bit-field 'a'".to_string()
+                ),
+                (
+                    224,
+                    "Member reference base type 'int[2]' is not a structure".to_string()
+                ),
+                (259, "Array subscript is not an integer".to_string()),
+                (
+                    264,
+                    "Too many arguments to function call, expected 0, have 1".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unions_and_anonymous_members() {
+        let (diagnostics, program) = analyze_str(
+            "union U { char c; int i; } u = { .c = 1, .i = 258 }, v = { 3 }; \
+             struct S { char a; union { char b; short s; }; struct { char d; }; } s = { 1, .s = 2, 3 }; \
+             _Alignas(16) _Alignas(0) char c; \
+             int f(void) { return s.d + _Generic(s.b, char: 1, default: 2L); }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        let data: Vec<_> = program
+            .globals
+            .iter()
+            .map(|global| (global.init.clone(), global.align))
+            .collect();
+        assert_eq!(
+            data,
+            [
+                (Some(vec![2, 1, 0, 0]), 4),
+                (Some(vec![3, 0, 0, 0]), 4),
+                (Some(vec![1, 0, 2, 0, 3, 0]), 2),
+                (None, 16),
+            ]
+        );
+
+        // The selected association is the value of `_Generic`.
+        let Stmt::Return(Some(value)) = &program.functions[0].body[0] else {
+            panic!("Must be a return: {:?}", program.functions[0].body);
+        };
+        let ExprKind::ArithOp { rhs, .. } = &value.kind else {
+            panic!("Must be an addition: {value:?}");
+        };
+        assert!(matches!(rhs.kind, ExprKind::Num(1)));
+        assert_eq!(rhs.ty, Type::Int);
+    }
+
+    #[test]
+    fn test_ng_c11_declarations() {
+        assert_eq!(
+            diagnostics(
+                "_Static_assert(sizeof(int) == 8, \"64-bit int\"); struct A; union A *p; \
+                 struct S { int a; union { int a; }; int; _Static_assert(0, \"in struct\"); }; \
+                 _Alignas(3) int b; _Alignas(1) int c; _Alignas(struct A) int d; \
+                 int f(void) { _Alignas(32) int e; register _Alignas(8) int g; return e + g; }"
+            ),
+            vec![
+                (15, "Static assertion failed: 64-bit int".to_string()),
+                (
+                    64,
+                    "Use of 'A' with tag type that does not match previous declaration".to_string()
+                ),
+                (88, "Duplicate member 'a'".to_string()),
+                (106, "Declaration does not declare anything".to_string()),
+                (126, "Static assertion failed: in struct".to_string()),
+                (155, "Requested alignment is not a power of 2".to_string()),
+                (
+                    174,
+                    "Requested alignment is less than minimum alignment of 4 for type 'int'"
+                        .to_string()
+                ),
+                (
+                    184,
+                    "Invalid application of '_Alignas' to an incomplete type 'struct A'"
+                        .to_string()
+                ),
+                (
+                    241,
+                    "Alignment of 32 bytes is not supported for local variable 'e'".to_string()
+                ),
+                (
+                    262,
+                    "'_Alignas' attribute cannot be applied to a variable with 'register' storage \
+                     class"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_generic_and_noreturn() {
+        assert_eq!(
+            diagnostics(
+                "struct P; int m; int n = _Generic(m, long: 1, signed int: 2, int: 3, default: 4, \
+                 default: 5, struct P: 6) + _Generic(m, char: 1); \
+                 _Noreturn void stop(void); _Noreturn void f(int a) { if (a) return; } \
+                 _Noreturn void g(void) { stop(); } _Noreturn void h(void) { while (1) {} }"
+            ),
+            vec![
+                (
+                    61,
+                    "Type 'int' in generic association compatible with previously specified type \
+                     'int'"
+                        .to_string()
+                ),
+                (81, "Duplicate default generic association".to_string()),
+                (
+                    93,
+                    "Type 'struct P' in generic association incomplete".to_string()
+                ),
+                (
+                    117,
+                    "Controlling expression type 'int' not compatible with any generic \
+                     association type"
+                        .to_string()
+                ),
+                (
+                    190,
+                    "Function 'f' declared 'noreturn' should not return".to_string()
+                ),
+                (
+                    198,
+                    "Function declared 'noreturn' should not return".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_bit_fields() {
+        assert_eq!(
+            diagnostics(
+                "struct S { int *p : 1; long a : 65; _Bool b : 2; int c : 0; int : -1; int d : 2; } s; \
+                 int *q = &s.d; int n = sizeof s.d;"
+            ),
+            vec![
+                (16, "Bit-field 'p' has non-integral type 'int *'".to_string()),
+                (
+                    32,
+                    "Width of bit-field 'a' (65 bits) exceeds the width of its type (64 bits)"
+                        .to_string()
+                ),
+                (
+                    46,
+                    "Width of bit-field 'b' (2 bits) exceeds the width of its type (1 bit)"
+                        .to_string()
+                ),
+                (57, "Named bit-field 'c' has zero width".to_string()),
+                (66, "Anonymous bit-field has negative width (-1)".to_string()),
+                (95, "Address of bit-field requested".to_string()),
+                (109, "Invalid application of 'sizeof' to bit-field".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_initializers() {
+        assert_eq!(
+            diagnostics(
+                "int a[2] = {1, 2, 3}; char s[2] = \"ab\"; int b[2] = 1; int x = {1, 2}; \
+                 struct P { int m; } p = {.n = 1}; int c[2] = {[2] = 1, .m = 2};"
+            ),
+            vec![
+                (18, "Excess elements in array initializer".to_string()),
+                (
+                    51,
+                    "Array initializer must be an initializer list or string literal".to_string()
+                ),
+                (66, "Excess elements in scalar initializer".to_string()),
+                (
+                    96,
+                    "Field designator 'n' does not refer to any field in type 'struct P'"
+                        .to_string()
+                ),
+                (
+                    117,
+                    "Array designator index (2) exceeds array bounds (2)".to_string()
+                ),
+                (
+                    126,
+                    "Field designator cannot initialize non-struct type 'int[2]'".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_pointers_and_structs() {
+        assert_eq!(
+            diagnostics(
+                "struct P { int x; } p; struct Q *q; int a[2], *r; int main() { \
+                 a = r; r = p; *1; &1; p.y; q->x; 1[2]; p + 1; r - &p.x - q; \
+                 if (p) return 1; return sizeof(struct Q); }"
+            ),
+            vec![
+                (63, "Array type 'int[2]' is not assignable".to_string()),
+                (
+                    74,
+                    "Incompatible types converting 'struct P' to 'int *'".to_string()
+                ),
+                (
+                    77,
+                    "Indirection requires pointer operand ('int' invalid)".to_string()
+                ),
+                (
+                    81,
+                    "Cannot take the address of an rvalue of type 'int'".to_string()
+                ),
+                (87, "No member named 'y' in 'struct P'".to_string()),
+                (90, "Incomplete definition of type 'struct Q'".to_string()),
+                (
+                    96,
+                    "Subscripted value is not an array or pointer".to_string()
+                ),
+                (
+                    102,
+                    "Invalid operands to binary expression ('struct P' and 'int')".to_string()
+                ),
+                (
+                    109,
+                    "Invalid operands to binary expression ('long' and 'struct Q *')".to_string()
+                ),
+                (
+                    127,
+                    "Statement requires expression of scalar type ('struct P' invalid)".to_string()
+                ),
+                (
+                    147,
+                    "Invalid application of 'sizeof' to an incomplete type 'struct Q'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics(
+                "struct P { int x, x; struct P p; }; struct P { int y; }; int n; int v[n];"
+            ),
+            vec![
+                (18, "Duplicate member 'x'".to_string()),
+                (30, "Field 'p' has incomplete type 'struct P'".to_string()),
+                (43, "Redefinition of 'struct P'".to_string()),
+                (
+                    70,
+                    "Variable length array declaration not allowed at file scope".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_not_assignable() {
+        let c_code =
+            "int f(void); a = 1; (a + 1) = 2; ++3; a++ = 1; f() += 1; f = 1; --(a = 1); a--;";
+        assert_eq!(
+            diagnostics(c_code),
+            vec![
+                (20, "Expression is not assignable".to_string()),
+                (35, "Expression is not assignable".to_string()),
+                (38, "Expression is not assignable".to_string()),
+                (47, "Expression is not assignable".to_string()),
+                (
+                    57,
+                    "Non-object type 'int (void)' is not assignable".to_string()
+                ),
+                (66, "Expression is not assignable".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_qualifiers() {
+        assert_eq!(
+            diagnostics(
+                "const int c = 1; int main() { const int *p = &c; int *q = p; \
+                 struct S { const int m; } s, t; s = t; c = 2; *p = 3; \
+                 int *const r = q; r++; const int *volatile v = q; return *v + *r + s.m; }"
+            ),
+            vec![
+                (
+                    58,
+                    "Converting 'const int *' to 'int *' discards qualifiers".to_string()
+                ),
+                (
+                    93,
+                    "Cannot assign to 'struct S' with a const-qualified member".to_string()
+                ),
+                (
+                    100,
+                    "Cannot assign to variable 'c' with const-qualified type 'const int'"
+                        .to_string()
+                ),
+                (107, "Read-only variable is not assignable".to_string()),
+                (
+                    133,
+                    "Cannot assign to variable 'r' with const-qualified type 'int *const'"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_static_locals() {
+        let (diagnostics, program) = analyze_str(
+            "int f(void) { static int n = 1, *p = &n; return *p; } \
+             int g(void) { static int n; { static int m = 2; n = m; } return n; }",
+        );
+        assert_eq!(diagnostics, vec![]);
+
+        let globals: Vec<_> = program
+            .globals
+            .iter()
+            .map(|global| {
+                (
+                    global.name.as_str(),
+                    global.init.is_some(),
+                    global.is_static,
+                )
+            })
+            .collect();
+        assert_eq!(
+            globals,
+            vec![
+                ("f.n.1", true, true),
+                ("f.p.2", true, true),
+                ("g.n.3", false, true),
+                ("g.m.4", true, true),
+            ]
+        );
+        assert_eq!(program.globals[1].relocations[0].label, "f.n.1");
+    }
+
+    #[test]
+    fn test_function_pointers() {
+        let (diagnostics, program) = analyze_str(
+            "int f(int x) { return x; } int (*p)(int) = f, (*q[1])(int) = { &f }; \
+             int g(int (*h)(int)) { return h(1) + (*h)(2) + p(3) + f(4); }",
+        );
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(program.globals[0].relocations[0].label, "f");
+        assert_eq!(program.globals[1].relocations[0].label, "f");
+
+        let Some(Stmt::Return(Some(expr))) = program.functions[1].body.last() else {
+            panic!("Must be a return statement");
+        };
+        let mut callees: Vec<&Expr> = vec![];
+        let mut sum = expr;
+        while let ExprKind::ArithOp { lhs, rhs, .. } = &sum.kind {
+            callees.push(rhs);
+            sum = lhs;
+        }
+        callees.push(sum);
+
+        let direct: Vec<_> = callees
+            .iter()
+            .map(|call| match &call.kind {
+                ExprKind::Call { callee, .. } => matches!(callee.kind, ExprKind::Function { .. }),
+                _ => panic!("Must be a call: {call:?}"),
+            })
+            .collect();
+        assert_eq!(direct, vec![true, false, false, false]);
+    }
+
+    #[test]
+    fn test_ng_function_pointers() {
+        assert_eq!(
+            diagnostics(
+                "int f(int); int (*p)(int) = f; long (*q)(int) = f; int g(void)[2]; \
+                 int h(void) { return sizeof f + p(1, 2) + (*p)() + (&f)(1); }"
+            ),
+            vec![
+                (
+                    48,
+                    "Incompatible pointer types converting 'int (*)(int)' to 'long (*)(int)'"
+                        .to_string()
+                ),
+                (55, "Function cannot return array type 'int[2]'".to_string()),
+                (
+                    88,
+                    "Invalid application of 'sizeof' to a function type".to_string()
+                ),
+                (
+                    99,
+                    "Too many arguments to function call, expected 1, have 2".to_string()
+                ),
+                (
+                    109,
+                    "Too few arguments to function call, expected 1, have 0".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics(
+                "extern int a; int b(void) { extern int a, c; c = 1; return a + c; } int a; \
+                 extern long c; static int a;"
+            ),
+            vec![
+                (87, "Redefinition of 'c' with a different type".to_string()),
+                (
+                    101,
+                    "Static declaration of 'a' follows non-static declaration".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_struct_values() {
+        assert_eq!(
+            diagnostics("struct S; struct S f(void); struct S g(struct S s) {} void h() { f(); }"),
+            vec![
+                (
+                    37,
+                    "Incomplete result type 'struct S' in function definition".to_string()
+                ),
+                (
+                    48,
+                    "Variable 's' has incomplete type 'struct S'".to_string()
+                ),
+                (
+                    52,
+                    "Non-void function 'g' does not return a value".to_string()
+                ),
+                (
+                    65,
+                    "Calling function with incomplete return type 'struct S'".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_storage_classes() {
+        assert_eq!(
+            diagnostics(
+                "int a; static int a; static int b; int b; int f(void); static int f(void); \
+                 int g(register int x) { register int y[1]; return *&x + *y; }"
+            ),
+            vec![
+                (
+                    18,
+                    "Static declaration of 'a' follows non-static declaration".to_string()
+                ),
+                (
+                    39,
+                    "Non-static declaration of 'b' follows static declaration".to_string()
+                ),
+                (
+                    66,
+                    "Static declaration of 'f' follows non-static declaration".to_string()
+                ),
+                (126, "Address of register variable requested".to_string()),
+                (132, "Address of register variable requested".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_variable_arrays() {
+        assert_eq!(
+            diagnostics(
+                "int n = 3; int a[n]; int main(void) { static int b[n]; struct S { int x[n]; }; \
+                 int c[n] = {1}; int d[n]; d = 0; return b[0] + c[0] + (int[n]){1}[0] + __builtin_alloca(); }"
+            ),
+            vec![
+                (
+                    17,
+                    "Variable length array declaration not allowed at file scope".to_string()
+                ),
+                (
+                    51,
+                    "Variable length array declaration cannot have 'static' storage duration"
+                        .to_string()
+                ),
+                (72, "Fields must have a constant size".to_string()),
+                (90, "Variable-sized object may not be initialized".to_string()),
+                (99, "Unused variable 'd'".to_string()),
+                (105, "Array type 'int[*]' is not assignable".to_string()),
+                (
+                    138,
+                    "Compound literal cannot be of variable-length array type".to_string()
+                ),
+                (
+                    150,
+                    "Too few arguments to function call, expected 1, have 0".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_constant_expressions() {
+        assert_eq!(
+            diagnostics("int a = 1 / (2 - 2); int b = -(-2147483647 - 1) + 1; long c = (char)300;"),
+            vec![
+                (8, "Division by zero in a constant expression".to_string()),
+                (29, "Overflow in a constant expression".to_string()),
+            ]
+        );
+        // One error each, without the warnings of the operations.
+        assert_eq!(
+            diagnostics(
+                "_Static_assert((-2147483647 - 1) / -1, \"\"); int d[1 / 0]; \
+                 struct S { int e : 1 / 0; }; int f(void) { static int g = 65536 * 65536; return g; }"
+            ),
+            vec![
+                (15, "Overflow in a constant expression".to_string()),
+                (50, "Division by zero in a constant expression".to_string()),
+                (77, "Division by zero in a constant expression".to_string()),
+                (116, "Overflow in a constant expression".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        assert_eq!(
+            diagnostics(
+                "int f(int a) { if (a) return 1; } \
+                 int g(int a) { while (1) a = a + 1; } \
+                 a = 1; b = 2; b = a / (1 - 1);"
+            ),
+            vec![
+                (
+                    32,
+                    "Non-void function 'f' does not return a value".to_string()
+                ),
+                (79, "Unused variable 'b'".to_string()),
+                (94, "Division by zero is undefined".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sign_compare() {
+        assert_eq!(
+            diagnostics(
+                "int f(int a, unsigned b, unsigned char c, long d) { \
+                 return (a < b) + (b == 1) + (c < b) + (d > b) + (a < sizeof(int)); }"
+            ),
+            vec![
+                (
+                    59,
+                    "Comparison of integers of different signs: 'int' and 'unsigned int'"
+                        .to_string()
+                ),
+                (
+                    100,
+                    "Comparison of integers of different signs: 'int' and 'unsigned long'"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ng_main_and_top_level_statements() {
+        assert_eq!(
+            diagnostics("int main() { return 0; } return 1;"),
+            vec![(
+                25,
+                "Top-level statements can't be used when 'main' is defined".to_string()
+            )]
+        );
+    }
+}
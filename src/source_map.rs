@@ -0,0 +1,176 @@
+/// Identifies a file in a [`SourceMap`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+/// A byte range `start..end` in a file of a [`SourceMap`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`, which must be in the same file.
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset. The column counts bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    // Byte offset where each line starts. Always starts with 0.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        // The last line starting at or before `offset`.
+        let line_index = self.line_starts.partition_point(|&start| start <= offset) - 1;
+
+        Location {
+            line: line_index + 1,
+            column: offset - self.line_starts[line_index] + 1,
+        }
+    }
+
+    /// Text of the 1-based `line`, without its line terminator.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |&next_start| next_start - 1);
+
+        self.text[start..end].trim_end_matches('\r')
+    }
+}
+
+/// Every piece of source text the compiler has seen: the main file, and the bodies of macros.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, text: &str) -> FileId {
+        self.files
+            .push(SourceFile::new(name.to_string(), text.to_string()));
+
+        FileId(self.files.len() - 1)
+    }
+
+    /// A copy of `file`, so that the tokens of each expansion of a macro have their own spans.
+    pub fn copy_file(&mut self, file: FileId) -> FileId {
+        let file = self.file(file);
+        let (name, text) = (file.name.clone(), file.text.clone());
+        self.add_file(&name, &text)
+    }
+
+    pub fn file(&self, file: FileId) -> &SourceFile {
+        &self.files[file.0]
+    }
+
+    pub fn location(&self, span: Span) -> Location {
+        self.file(span.file).location(span.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = 1;\nb = 2;\n\nreturn a;");
+
+        let location = |offset| source_map.file(file).location(offset);
+
+        assert_eq!(location(0), Location { line: 1, column: 1 });
+        assert_eq!(location(4), Location { line: 1, column: 5 });
+        // The newline belongs to the line it terminates.
+        assert_eq!(location(6), Location { line: 1, column: 7 });
+        // The very start of a line.
+        assert_eq!(location(7), Location { line: 2, column: 1 });
+        assert_eq!(location(14), Location { line: 3, column: 1 });
+        assert_eq!(location(15), Location { line: 4, column: 1 });
+        // End of file.
+        assert_eq!(
+            location(24),
+            Location {
+                line: 4,
+                column: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_line() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("main.c", "a = 1;\r\nb = 2;\n\nreturn a;");
+
+        let source_file = source_map.file(file);
+
+        assert_eq!(source_file.name(), "main.c");
+        assert_eq!(source_file.line(1), "a = 1;");
+        assert_eq!(source_file.line(2), "b = 2;");
+        assert_eq!(source_file.line(3), "");
+        assert_eq!(source_file.line(4), "return a;");
+    }
+
+    #[test]
+    fn test_multiple_files() {
+        let mut source_map = SourceMap::new();
+        let main = source_map.add_file("main.c", "return FOO;");
+        let macro_body = source_map.add_file("<command-line>", "1 + 2");
+
+        assert_ne!(main, macro_body);
+        assert_eq!(source_map.file(macro_body).text(), "1 + 2");
+        assert_eq!(
+            source_map.location(Span::new(macro_body, 4, 5)),
+            Location { line: 1, column: 5 }
+        );
+    }
+}
@@ -0,0 +1,969 @@
+use core::fmt;
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Type {
+    Void,
+    Bool,
+    // Plain `char` is distinct from `signed char` and `unsigned char`, but has the range of one of
+    // them, chosen by `Target`.
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    // What an undeclared variable in a top-level statement is.
+    #[default]
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Pointer(Box<Type>),
+    Array {
+        element: Box<Type>,
+        // None for an incomplete array like `int a[]`.
+        len: Option<usize>,
+    },
+    // An array whose length is only known at runtime, like `int a[n]` in a block. Its size in bytes
+    // is computed where its type is declared, into the frame slot at offset `size`.
+    VariableArray {
+        element: Box<Type>,
+        size: usize,
+    },
+    Struct(StructRef),
+    // A function designator has this type, but it's converted to a pointer to it when used as a
+    // value, and there are no objects of it.
+    Function {
+        return_type: Box<Type>,
+        params: Vec<Type>,
+        // Whether more arguments can follow, like in `int printf(const char *, ...)`.
+        is_variadic: bool,
+    },
+    // Never nested, and never around an array: the elements are qualified instead.
+    Qualified {
+        qualifiers: Qualifiers,
+        ty: Box<Type>,
+    },
+    // What an expression with errors has. Every check accepts it silently, so that one mistake is
+    // reported once.
+    Error,
+}
+
+/// What C leaves to the implementation or the output depends on, chosen by options like
+/// -funsigned-char.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Target {
+    // Whether plain `char` is unsigned, as opposed to signed by default.
+    pub unsigned_char: bool,
+    // Whether the code may be in a shared library, which changes how thread-local variables are
+    // accessed.
+    pub pic: bool,
+}
+
+/// `const`, `volatile`, `restrict` and `_Atomic`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+    pub is_atomic: bool,
+}
+
+/// A struct or union type, shared by every use of its tag. It's completed in place by its
+/// definition.
+#[derive(Clone)]
+pub struct StructRef(Rc<RefCell<Struct>>);
+
+#[derive(Debug)]
+pub struct Struct {
+    // The members of a union all start at offset 0.
+    pub is_union: bool,
+    pub tag: Option<String>,
+    // None until the struct is defined.
+    pub members: Option<Vec<Member>>,
+    pub size: usize,
+    pub align: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    // None for an anonymous struct or union, whose members are found through it.
+    pub name: Option<String>,
+    pub ty: Type,
+    // For a bit-field, the offset of its storage unit, which is a `ty` at an aligned offset.
+    pub offset: usize,
+    pub bit_field: Option<BitField>,
+}
+
+/// Where the bits of a bit-field are in its storage unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitField {
+    // From the least significant bit.
+    pub offset: usize,
+    pub width: usize,
+}
+
+/// A member as declared, before the struct is laid out. Only a bit-field, which has a width, or an
+/// anonymous struct or union can be unnamed.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub width: Option<usize>,
+    // Raised by `_Alignas` above the alignment of `ty`.
+    pub align: Option<usize>,
+    // Declared `packed`, so it's only aligned to `align`, and a bit-field takes the very next bits.
+    pub packed: bool,
+}
+
+impl Field {
+    pub fn new(name: &str, ty: Type) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ty,
+            width: None,
+            align: None,
+            packed: false,
+        }
+    }
+}
+
+impl Type {
+    pub fn pointer_to(ty: Type) -> Type {
+        Type::Pointer(Box::new(ty))
+    }
+
+    pub fn array_of(element: Type, len: Option<usize>) -> Type {
+        Type::Array {
+            element: Box::new(element),
+            len,
+        }
+    }
+
+    /// `ty` with `qualifiers` added to the ones it has.
+    pub fn qualified(ty: Type, qualifiers: Qualifiers) -> Type {
+        match ty {
+            _ if qualifiers.is_empty() => ty,
+            Type::Array { element, len } => {
+                Type::array_of(Type::qualified(*element, qualifiers), len)
+            }
+            Type::VariableArray { element, size } => Type::VariableArray {
+                element: Box::new(Type::qualified(*element, qualifiers)),
+                size,
+            },
+            Type::Qualified {
+                qualifiers: inner,
+                ty,
+            } => Type::Qualified {
+                qualifiers: inner.union(qualifiers),
+                ty,
+            },
+            ty => Type::Qualified {
+                qualifiers,
+                ty: Box::new(ty),
+            },
+        }
+    }
+
+    /// The type without its top-level qualifiers, like the value of an lvalue.
+    pub fn unqualified(&self) -> &Type {
+        match self {
+            Type::Qualified { ty, .. } => ty,
+            ty => ty,
+        }
+    }
+
+    /// Whether the types are the same, except that an array whose length is only known at runtime
+    /// is compatible with any array of a compatible element type (C11 6.7.6.2).
+    pub fn is_compatible(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Pointer(lhs), Type::Pointer(rhs)) => lhs.is_compatible(rhs),
+            (
+                Type::Qualified {
+                    qualifiers: lhs_qualifiers,
+                    ty: lhs,
+                },
+                Type::Qualified {
+                    qualifiers: rhs_qualifiers,
+                    ty: rhs,
+                },
+            ) => lhs_qualifiers == rhs_qualifiers && lhs.is_compatible(rhs),
+            (
+                Type::Array {
+                    element: lhs,
+                    len: lhs_len,
+                },
+                Type::Array {
+                    element: rhs,
+                    len: rhs_len,
+                },
+            ) => lhs_len == rhs_len && lhs.is_compatible(rhs),
+            (
+                Type::Array { element: lhs, .. } | Type::VariableArray { element: lhs, .. },
+                Type::Array { element: rhs, .. } | Type::VariableArray { element: rhs, .. },
+            ) => lhs.is_compatible(rhs),
+            _ => self == other,
+        }
+    }
+
+    pub fn qualifiers(&self) -> Qualifiers {
+        match self {
+            Type::Qualified { qualifiers, .. } => *qualifiers,
+            _ => Qualifiers::default(),
+        }
+    }
+
+    /// Whether an object of the type can't be modified, including through one of its members.
+    pub fn is_const(&self) -> bool {
+        match self.unqualified() {
+            _ if self.qualifiers().is_const => true,
+            Type::Array { element, .. } | Type::VariableArray { element, .. } => element.is_const(),
+            Type::Struct(struct_ref) => struct_ref
+                .borrow()
+                .members
+                .iter()
+                .flatten()
+                .any(|member| member.ty.is_const()),
+            _ => false,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            // Like gcc, so that `sizeof(void)` is 1.
+            Type::Void | Type::Bool | Type::Char | Type::SChar | Type::UChar => 1,
+            Type::Short | Type::UShort => 2,
+            // Like `int`, which it stands in for.
+            Type::Int | Type::UInt | Type::Error => 4,
+            Type::Long | Type::ULong | Type::LongLong | Type::ULongLong | Type::Pointer(_) => 8,
+            Type::Array { element, len } => element.size() * len.unwrap_or(0),
+            // Only known at runtime.
+            Type::VariableArray { .. } => 0,
+            Type::Struct(struct_ref) => struct_ref.borrow().size,
+            // Never complete. Like `void`, it has a size of 1 so that arithmetic on pointers to it can go
+            // on after the error.
+            Type::Function { .. } => 1,
+            Type::Qualified { ty, .. } => ty.size(),
+        }
+    }
+
+    pub fn align(&self) -> usize {
+        match self {
+            Type::Array { element, .. } | Type::VariableArray { element, .. } => element.align(),
+            Type::Struct(struct_ref) => struct_ref.borrow().align,
+            Type::Qualified { ty, .. } => ty.align(),
+            _ => self.size(),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.rank() > 0
+    }
+
+    /// Whether the type is an unsigned integer. Plain `char` depends on the target, see
+    /// `Target::is_unsigned`.
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self.unqualified(),
+            Type::Bool | Type::UChar | Type::UShort | Type::UInt | Type::ULong | Type::ULongLong
+        )
+    }
+
+    /// Whether the type is `char`, `signed char` or `unsigned char`, which string literals can
+    /// initialize arrays of.
+    pub fn is_char(&self) -> bool {
+        matches!(self.unqualified(), Type::Char | Type::SChar | Type::UChar)
+    }
+
+    /// Whether the type can be a condition or be compared.
+    pub fn is_scalar(&self) -> bool {
+        self.is_integer() || matches!(self.unqualified(), Type::Pointer(_))
+    }
+
+    /// The type pointed to by a pointer, or of the elements of an array.
+    pub fn base(&self) -> Option<&Type> {
+        match self.unqualified() {
+            Type::Pointer(base)
+            | Type::Array { element: base, .. }
+            | Type::VariableArray { element: base, .. } => Some(base),
+            _ => None,
+        }
+    }
+
+    /// Whether the size is known. `void` and functions are never complete.
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Type::Void | Type::Function { .. } => false,
+            Type::Array { element, len } => len.is_some() && element.is_complete(),
+            Type::Struct(struct_ref) => struct_ref.borrow().members.is_some(),
+            Type::Qualified { ty, .. } => ty.is_complete(),
+            _ => true,
+        }
+    }
+
+    // Integer conversion rank (C11 6.3.1.1), which is the same for signed and unsigned types.
+    // 0 for anything but integers.
+    fn rank(&self) -> usize {
+        match self.unqualified() {
+            Type::Bool => 1,
+            Type::Char | Type::SChar | Type::UChar => 2,
+            Type::Short | Type::UShort => 3,
+            Type::Int | Type::UInt => 4,
+            Type::Long | Type::ULong => 5,
+            Type::LongLong | Type::ULongLong => 6,
+            _ => 0,
+        }
+    }
+
+    // The unsigned integer type with the same rank.
+    fn to_unsigned(&self) -> Type {
+        match self.unqualified() {
+            Type::Char | Type::SChar => Type::UChar,
+            Type::Short => Type::UShort,
+            Type::Int => Type::UInt,
+            Type::Long => Type::ULong,
+            Type::LongLong => Type::ULongLong,
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type after the integer promotions: anything ranked below `int` becomes `int`, which
+    /// can represent all of its values.
+    pub fn promoted(&self) -> Type {
+        if self.is_integer() && self.rank() < Type::Int.rank() {
+            Type::Int
+        } else {
+            self.unqualified().clone()
+        }
+    }
+
+    /// The common type of the usual arithmetic conversions (C11 6.3.1.8). Both must be integers.
+    pub fn common(lhs: &Type, rhs: &Type) -> Type {
+        let (lhs, rhs) = (lhs.promoted(), rhs.promoted());
+        let (signed, unsigned) = match (lhs.is_unsigned(), rhs.is_unsigned()) {
+            (false, true) => (lhs, rhs),
+            (true, false) => (rhs, lhs),
+            // With the same signedness, the higher rank wins.
+            _ if lhs.rank() >= rhs.rank() => return lhs,
+            _ => return rhs,
+        };
+
+        if unsigned.rank() >= signed.rank() {
+            unsigned
+        } else if signed.size() > unsigned.size() {
+            // The signed type can represent every value of the unsigned one.
+            signed
+        } else {
+            signed.to_unsigned()
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_declarator(f, String::new())
+    }
+}
+
+impl Type {
+    // Writes the type like a declaration of `declarator`, which is what derives from the type,
+    // e.g. `int (*)[3]` is `int` with the declarator `(*)[3]`.
+    fn fmt_declarator(&self, f: &mut fmt::Formatter<'_>, declarator: String) -> fmt::Result {
+        // Suffixes bind tighter than `*`, so a pointer to an array or a function needs parentheses.
+        let suffixed = |suffix: String| {
+            if declarator.starts_with('*') {
+                format!("({declarator}){suffix}")
+            } else {
+                format!("{declarator}{suffix}")
+            }
+        };
+
+        let name = match self {
+            Type::Void => "void",
+            Type::Bool => "_Bool",
+            Type::Char => "char",
+            Type::SChar => "signed char",
+            Type::UChar => "unsigned char",
+            Type::Short => "short",
+            Type::UShort => "unsigned short",
+            Type::Int => "int",
+            Type::UInt => "unsigned int",
+            Type::Long => "long",
+            Type::ULong => "unsigned long",
+            Type::LongLong => "long long",
+            Type::ULongLong => "unsigned long long",
+            Type::Error => "<error type>",
+            Type::Pointer(base) => return base.fmt_declarator(f, format!("*{declarator}")),
+            Type::Array { element, len } => {
+                let len = len.map(|len| len.to_string()).unwrap_or_default();
+                return element.fmt_declarator(f, suffixed(format!("[{len}]")));
+            }
+            // Like a VLA of unspecified size in a prototype.
+            Type::VariableArray { element, .. } => {
+                return element.fmt_declarator(f, suffixed("[*]".to_string()));
+            }
+            Type::Function {
+                return_type,
+                params,
+                is_variadic,
+            } => {
+                let mut params: Vec<_> = params.iter().map(Type::to_string).collect();
+                if *is_variadic {
+                    params.push("...".to_string());
+                }
+                let params = if params.is_empty() {
+                    "void".to_string()
+                } else {
+                    params.join(", ")
+                };
+                return return_type.fmt_declarator(f, suffixed(format!("({params})")));
+            }
+            Type::Struct(struct_ref) => {
+                let kind = struct_ref.kind();
+                match &struct_ref.borrow().tag {
+                    Some(tag) => write!(f, "{kind} {tag}")?,
+                    None => write!(f, "{kind} (anonymous)")?,
+                }
+                return Type::fmt_space(f, &declarator);
+            }
+            // `const int`, but `int *const`.
+            Type::Qualified { qualifiers, ty } => {
+                return match **ty {
+                    Type::Pointer(_) if declarator.is_empty() => {
+                        ty.fmt_declarator(f, qualifiers.to_string())
+                    }
+                    Type::Pointer(_) => ty.fmt_declarator(f, format!("{qualifiers} {declarator}")),
+                    _ => {
+                        write!(f, "{qualifiers} ")?;
+                        ty.fmt_declarator(f, declarator)
+                    }
+                };
+            }
+        };
+
+        write!(f, "{name}")?;
+        Type::fmt_space(f, &declarator)
+    }
+
+    // Writes the declarator after the specifiers, separated by a space unless it's a suffix of
+    // an array like in `int[3]`.
+    fn fmt_space(f: &mut fmt::Formatter<'_>, declarator: &str) -> fmt::Result {
+        match declarator {
+            "" => Ok(()),
+            _ if declarator.starts_with('[') => write!(f, "{declarator}"),
+            _ => write!(f, " {declarator}"),
+        }
+    }
+}
+
+impl Target {
+    /// Whether `ty` is an unsigned integer, including plain `char` with -funsigned-char.
+    pub fn is_unsigned(&self, ty: &Type) -> bool {
+        ty.is_unsigned() || (self.unsigned_char && *ty.unqualified() == Type::Char)
+    }
+}
+
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Qualifiers::default()
+    }
+
+    pub fn union(self, other: Qualifiers) -> Qualifiers {
+        Qualifiers {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+            is_atomic: self.is_atomic || other.is_atomic,
+        }
+    }
+
+    /// Whether every qualifier of `other` is also in `self`.
+    pub fn contains(self, other: Qualifiers) -> bool {
+        self.union(other) == self
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+            (self.is_atomic, "_Atomic"),
+        ];
+        let names: Vec<_> = names
+            .into_iter()
+            .filter_map(|(present, name)| present.then_some(name))
+            .collect();
+
+        write!(f, "{}", names.join(" "))
+    }
+}
+
+impl StructRef {
+    /// A struct that isn't defined yet.
+    pub fn new(tag: Option<String>) -> Self {
+        Self::declare(tag, false)
+    }
+
+    /// A union that isn't defined yet.
+    pub fn new_union(tag: Option<String>) -> Self {
+        Self::declare(tag, true)
+    }
+
+    fn declare(tag: Option<String>, is_union: bool) -> Self {
+        Self(Rc::new(RefCell::new(Struct {
+            is_union,
+            tag,
+            members: None,
+            size: 0,
+            align: 1,
+        })))
+    }
+
+    pub fn borrow(&self) -> std::cell::Ref<Struct> {
+        self.0.borrow()
+    }
+
+    pub fn is_union(&self) -> bool {
+        self.borrow().is_union
+    }
+
+    /// `struct` or `union`, like in diagnostics.
+    pub fn kind(&self) -> &'static str {
+        if self.is_union() {
+            "union"
+        } else {
+            "struct"
+        }
+    }
+
+    /// Lays out `fields` in order like gcc, each at the next offset aligned for its type. A
+    /// bit-field takes the next bits, unless they would cross a boundary aligned for its type,
+    /// and a zero-width one only aligns the next field. Unnamed bit-fields aren't members and don't
+    /// affect the alignment of the struct. In a union, every field starts at offset 0.
+    ///
+    /// A packed bit-field is in a storage unit starting at the byte its first bit is in. Fails
+    /// with the index of the first one that doesn't fit in it, which is laid out as if it wasn't
+    /// packed.
+    pub fn define(&self, fields: Vec<Field>) -> Result<(), usize> {
+        let is_union = self.is_union();
+        let mut bits: usize = 0;
+        let mut size = 0;
+        let mut align = 1;
+        let mut members = vec![];
+        let mut straddling = None;
+
+        for (
+            index,
+            Field {
+                name,
+                ty,
+                width,
+                align: field_align,
+                packed,
+            },
+        ) in fields.into_iter().enumerate()
+        {
+            if is_union {
+                bits = 0;
+            }
+            let field_align = field_align
+                .unwrap_or(1)
+                .max(if packed { 1 } else { ty.align() });
+            let unit = ty.align() * 8;
+            let bit_field = match width {
+                None => {
+                    bits = bits.next_multiple_of(field_align * 8);
+                    None
+                }
+                Some(0) => {
+                    bits = bits.next_multiple_of(unit);
+                    continue;
+                }
+                Some(width) if packed && bits % 8 + width <= ty.size() * 8 => Some(BitField {
+                    offset: bits % 8,
+                    width,
+                }),
+                Some(width) => {
+                    if packed {
+                        straddling.get_or_insert(index);
+                    }
+                    if bits / unit != (bits + width - 1) / unit {
+                        bits = bits.next_multiple_of(unit);
+                    }
+                    Some(BitField {
+                        offset: bits % unit,
+                        width,
+                    })
+                }
+            };
+
+            let offset = (bits - bit_field.map_or(0, |bit_field| bit_field.offset)) / 8;
+            bits += width.unwrap_or(ty.size() * 8);
+            size = size.max(bits);
+            if name.is_some() || width.is_none() {
+                align = align.max(field_align);
+                members.push(Member {
+                    name,
+                    ty,
+                    offset,
+                    bit_field,
+                });
+            }
+        }
+
+        let mut this = self.0.borrow_mut();
+        this.members = Some(members);
+        this.size = size.div_ceil(8).next_multiple_of(align);
+        this.align = align;
+        straddling.map_or(Ok(()), Err)
+    }
+
+    /// Raises the alignment of a defined struct to `align`, like `aligned` on it does, padding its
+    /// size to a multiple of it.
+    pub fn align_to(&self, align: usize) {
+        let mut this = self.0.borrow_mut();
+        this.align = this.align.max(align);
+        this.size = this.size.next_multiple_of(this.align);
+    }
+
+    /// The member named `name`, which can be in an anonymous struct or union, with its offset from
+    /// the start of this one.
+    pub fn member(&self, name: &str) -> Option<Member> {
+        self.borrow().members.as_ref()?.iter().find_map(|member| {
+            match (&member.name, member.ty.unqualified()) {
+                (Some(member_name), _) if member_name == name => Some(member.clone()),
+                (None, Type::Struct(inner)) => inner.member(name).map(|found| Member {
+                    ty: Type::qualified(found.ty, member.ty.qualifiers()),
+                    offset: member.offset + found.offset,
+                    ..found
+                }),
+                _ => None,
+            }
+        })
+    }
+
+    /// The names of the members, including those of anonymous structs and unions in it.
+    pub fn member_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        for member in self.borrow().members.iter().flatten() {
+            match (&member.name, member.ty.unqualified()) {
+                (Some(name), _) => names.push(name.clone()),
+                (None, Type::Struct(inner)) => names.extend(inner.member_names()),
+                _ => {}
+            }
+        }
+        names
+    }
+}
+
+// Each struct definition is a distinct type, even with the same members.
+impl PartialEq for StructRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StructRef {}
+
+// Members can point back to the struct, so they aren't printed.
+impl fmt::Debug for StructRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Type::Struct(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promoted() {
+        assert_eq!(Type::Char.promoted(), Type::Int);
+        assert_eq!(Type::Short.promoted(), Type::Int);
+        assert_eq!(Type::Int.promoted(), Type::Int);
+        assert_eq!(Type::Long.promoted(), Type::Long);
+    }
+
+    #[test]
+    fn test_common() {
+        assert_eq!(Type::common(&Type::Char, &Type::Char), Type::Int);
+        assert_eq!(Type::common(&Type::Short, &Type::Long), Type::Long);
+        assert_eq!(Type::common(&Type::Long, &Type::Int), Type::Long);
+    }
+
+    #[test]
+    fn test_common_unsigned() {
+        assert_eq!(Type::common(&Type::UChar, &Type::UShort), Type::Int);
+        assert_eq!(Type::common(&Type::Bool, &Type::Bool), Type::Int);
+        assert_eq!(Type::common(&Type::Int, &Type::UInt), Type::UInt);
+        assert_eq!(Type::common(&Type::UInt, &Type::Long), Type::Long);
+        assert_eq!(Type::common(&Type::ULong, &Type::Int), Type::ULong);
+        assert_eq!(Type::common(&Type::LongLong, &Type::ULong), Type::ULongLong);
+        assert_eq!(Type::common(&Type::ULong, &Type::LongLong), Type::ULongLong);
+        assert_eq!(Type::common(&Type::Long, &Type::LongLong), Type::LongLong);
+    }
+
+    #[test]
+    fn test_signedness() {
+        assert!(Type::UInt.is_unsigned());
+        assert!(!Type::Char.is_unsigned());
+        assert!(!Target::default().is_unsigned(&Type::Char));
+
+        let target = Target {
+            unsigned_char: true,
+            ..Target::default()
+        };
+        assert!(target.is_unsigned(&Type::Char));
+        assert!(!target.is_unsigned(&Type::SChar));
+        assert_eq!(Type::ULongLong.to_string(), "unsigned long long");
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        let struct_ref = StructRef::new(Some("P".to_string()));
+        assert!(!Type::Struct(struct_ref.clone()).is_complete());
+
+        struct_ref
+            .define(vec![
+                Field::new("c", Type::Char),
+                Field::new("l", Type::Long),
+                Field::new("s", Type::array_of(Type::Short, Some(3))),
+            ])
+            .unwrap();
+
+        let ty = Type::Struct(struct_ref.clone());
+        assert_eq!((ty.size(), ty.align()), (24, 8));
+        assert_eq!(struct_ref.member("l").unwrap().offset, 8);
+        assert_eq!(struct_ref.member("s").unwrap().offset, 16);
+        assert_ne!(ty, Type::Struct(StructRef::new(Some("P".to_string()))));
+    }
+
+    #[test]
+    fn test_union_layout() {
+        let union_ref = StructRef::new_union(None);
+        union_ref
+            .define(vec![
+                Field::new("c", Type::array_of(Type::Char, Some(5))),
+                Field::new("i", Type::Int),
+            ])
+            .unwrap();
+        let union_ty = Type::Struct(union_ref.clone());
+        assert_eq!((union_ty.size(), union_ty.align()), (8, 4));
+        assert_eq!(union_ref.member("i").unwrap().offset, 0);
+        assert_eq!(union_ty.to_string(), "union (anonymous)");
+
+        // The members of an anonymous union are found through the struct, and `_Alignas` raises
+        // the alignment of a member.
+        let struct_ref = StructRef::new(Some("S".to_string()));
+        struct_ref
+            .define(vec![
+                Field::new("a", Type::Char),
+                Field {
+                    name: None,
+                    ..Field::new(
+                        "",
+                        Type::qualified(
+                            union_ty,
+                            Qualifiers {
+                                is_const: true,
+                                ..Qualifiers::default()
+                            },
+                        ),
+                    )
+                },
+                Field {
+                    align: Some(16),
+                    ..Field::new("b", Type::Char)
+                },
+            ])
+            .unwrap();
+        let ty = Type::Struct(struct_ref.clone());
+        assert_eq!((ty.size(), ty.align()), (32, 16));
+        let member = struct_ref.member("c").unwrap();
+        assert_eq!(member.offset, 4);
+        assert!(member.ty.is_const());
+        assert_eq!(struct_ref.member("b").unwrap().offset, 16);
+        assert_eq!(struct_ref.member_names(), ["a", "c", "i", "b"]);
+    }
+
+    #[test]
+    fn test_bit_field_layout() {
+        let bit_field = |name: Option<&str>, ty, width| Field {
+            name: name.map(str::to_string),
+            ty,
+            width: Some(width),
+            align: None,
+            packed: false,
+        };
+        let layout = |fields| {
+            let struct_ref = StructRef::new(None);
+            struct_ref.define(fields).unwrap();
+            let ty = Type::Struct(struct_ref.clone());
+            let members = struct_ref.borrow().members.clone().unwrap();
+            let offsets: Vec<_> = members
+                .into_iter()
+                .map(|member| {
+                    (
+                        member.offset,
+                        member.bit_field.map(|bit_field| bit_field.offset),
+                    )
+                })
+                .collect();
+            (ty.size(), ty.align(), offsets)
+        };
+
+        // A bit-field that would cross a boundary of its type starts at the next one.
+        assert_eq!(
+            layout(vec![
+                bit_field(Some("a"), Type::UInt, 3),
+                bit_field(Some("b"), Type::UInt, 5),
+                bit_field(Some("c"), Type::UInt, 30),
+                Field::new("d", Type::Char),
+            ]),
+            (
+                12,
+                4,
+                vec![(0, Some(0)), (0, Some(3)), (4, Some(0)), (8, None)]
+            )
+        );
+        assert_eq!(
+            layout(vec![
+                Field::new("a", Type::Char),
+                bit_field(Some("b"), Type::Bool, 1),
+                bit_field(Some("c"), Type::Short, 9),
+            ]),
+            (4, 2, vec![(0, None), (1, Some(0)), (2, Some(0))])
+        );
+        assert_eq!(
+            layout(vec![
+                bit_field(Some("a"), Type::Long, 40),
+                bit_field(Some("b"), Type::Int, 30),
+            ]),
+            (16, 8, vec![(0, Some(0)), (8, Some(0))])
+        );
+
+        // Unnamed ones only take space, and zero-width ones align the next field.
+        assert_eq!(
+            layout(vec![
+                Field::new("a", Type::Char),
+                bit_field(Some("b"), Type::Int, 4),
+                bit_field(None, Type::Int, 0),
+                Field::new("c", Type::Char),
+            ]),
+            (8, 4, vec![(0, None), (0, Some(8)), (4, None)])
+        );
+        assert_eq!(
+            layout(vec![
+                Field::new("a", Type::Char),
+                bit_field(None, Type::Int, 4)
+            ]),
+            (2, 1, vec![(0, None)])
+        );
+    }
+
+    #[test]
+    fn test_qualified() {
+        let const_ = Qualifiers {
+            is_const: true,
+            ..Qualifiers::default()
+        };
+        let volatile = Qualifiers {
+            is_volatile: true,
+            ..Qualifiers::default()
+        };
+
+        let ty = Type::qualified(Type::array_of(Type::Int, Some(2)), const_);
+        assert_eq!(ty.to_string(), "const int[2]");
+        assert!(ty.is_const());
+        assert_eq!(ty.base().unwrap().qualifiers(), const_);
+
+        let ty = Type::qualified(Type::qualified(Type::Char, const_), volatile);
+        assert_eq!(ty.to_string(), "const volatile char");
+        assert_eq!(ty.unqualified(), &Type::Char);
+        assert_eq!(ty.promoted(), Type::Int);
+
+        let ty = Type::qualified(Type::pointer_to(ty), const_);
+        assert_eq!(ty.to_string(), "const volatile char *const");
+        assert_eq!(
+            Type::pointer_to(ty).to_string(),
+            "const volatile char *const *"
+        );
+
+        let struct_ref = StructRef::new(None);
+        struct_ref
+            .define(vec![Field::new("m", Type::qualified(Type::Int, const_))])
+            .unwrap();
+        assert!(Type::Struct(struct_ref).is_const());
+        assert!(!Type::pointer_to(Type::qualified(Type::Int, const_)).is_const());
+    }
+
+    #[test]
+    fn test_display() {
+        let ty = Type::array_of(
+            Type::array_of(Type::pointer_to(Type::pointer_to(Type::Char)), Some(3)),
+            None,
+        );
+        assert_eq!(ty.to_string(), "char **[][3]");
+
+        let handler = Type::Function {
+            return_type: Box::new(Type::Int),
+            params: vec![Type::Int, Type::pointer_to(Type::pointer_to(Type::Char))],
+            is_variadic: false,
+        };
+        assert_eq!(handler.to_string(), "int (int, char **)");
+        let handler = Type::pointer_to(handler);
+        assert_eq!(handler.to_string(), "int (*)(int, char **)");
+        assert_eq!(
+            Type::array_of(handler.clone(), Some(4)).to_string(),
+            "int (*[4])(int, char **)"
+        );
+        let lookup = Type::Function {
+            return_type: Box::new(handler),
+            params: vec![],
+            is_variadic: false,
+        };
+        assert_eq!(
+            Type::pointer_to(lookup).to_string(),
+            "int (*(*)(void))(int, char **)"
+        );
+        let printf = Type::Function {
+            return_type: Box::new(Type::Int),
+            params: vec![Type::pointer_to(Type::Char)],
+            is_variadic: true,
+        };
+        assert_eq!(printf.to_string(), "int (char *, ...)");
+        assert_eq!(
+            Type::pointer_to(Type::array_of(Type::Int, Some(3))).to_string(),
+            "int (*)[3]"
+        );
+        assert_eq!(
+            Type::Struct(StructRef::new(Some("P".to_string()))).to_string(),
+            "struct P"
+        );
+        let variable = Type::VariableArray {
+            element: Box::new(Type::array_of(Type::Int, Some(2))),
+            size: 8,
+        };
+        assert_eq!(Type::pointer_to(variable).to_string(), "int (*)[*][2]");
+    }
+
+    #[test]
+    fn test_variable_array_compatibility() {
+        let variable = |element, size| Type::VariableArray {
+            element: Box::new(element),
+            size,
+        };
+        assert!(variable(Type::Int, 8).is_compatible(&variable(Type::Int, 16)));
+        assert!(Type::array_of(Type::Int, Some(3)).is_compatible(&variable(Type::Int, 8)));
+        assert!(Type::pointer_to(variable(Type::Int, 8))
+            .is_compatible(&Type::pointer_to(Type::array_of(Type::Int, Some(3)))));
+        assert!(!variable(Type::Int, 8).is_compatible(&variable(Type::Long, 8)));
+        assert!(
+            !Type::array_of(Type::Int, Some(3)).is_compatible(&Type::array_of(Type::Int, Some(4)))
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+/// A warning group, enabled with `-W<name>` and disabled with `-Wno-<name>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Warning {
+    UnusedVariable,
+    UnusedValue,
+    ReturnType,
+    SignCompare,
+    // Accepted like gcc's, but nothing emits it until `switch` is supported.
+    ImplicitFallthrough,
+    Shadow,
+    Parentheses,
+    DivByZero,
+    IntegerOverflow,
+    DiscardedQualifiers,
+    InvalidNoreturn,
+    MissingDeclarations,
+    UnknownAttributes,
+    IgnoredAttributes,
+    Format,
+}
+
+impl Warning {
+    const ALL: [Warning; 15] = [
+        Warning::UnusedVariable,
+        Warning::UnusedValue,
+        Warning::ReturnType,
+        Warning::SignCompare,
+        Warning::ImplicitFallthrough,
+        Warning::Shadow,
+        Warning::Parentheses,
+        Warning::DivByZero,
+        Warning::IntegerOverflow,
+        Warning::DiscardedQualifiers,
+        Warning::InvalidNoreturn,
+        Warning::MissingDeclarations,
+        Warning::UnknownAttributes,
+        Warning::IgnoredAttributes,
+        Warning::Format,
+    ];
+
+    // Enabled without any -W flag.
+    const DEFAULT: [Warning; 9] = [
+        Warning::ReturnType,
+        Warning::DivByZero,
+        Warning::IntegerOverflow,
+        Warning::DiscardedQualifiers,
+        Warning::InvalidNoreturn,
+        Warning::MissingDeclarations,
+        Warning::UnknownAttributes,
+        Warning::IgnoredAttributes,
+        Warning::Format,
+    ];
+
+    // Enabled by -Wall.
+    const ALL_GROUP: [Warning; 12] = [
+        Warning::UnusedVariable,
+        Warning::UnusedValue,
+        Warning::ReturnType,
+        Warning::Parentheses,
+        Warning::DivByZero,
+        Warning::IntegerOverflow,
+        Warning::DiscardedQualifiers,
+        Warning::InvalidNoreturn,
+        Warning::MissingDeclarations,
+        Warning::UnknownAttributes,
+        Warning::IgnoredAttributes,
+        Warning::Format,
+    ];
+
+    // Enabled by -Wextra.
+    const EXTRA_GROUP: [Warning; 2] = [Warning::SignCompare, Warning::ImplicitFallthrough];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Warning::UnusedVariable => "unused-variable",
+            Warning::UnusedValue => "unused-value",
+            Warning::ReturnType => "return-type",
+            Warning::SignCompare => "sign-compare",
+            Warning::ImplicitFallthrough => "implicit-fallthrough",
+            Warning::Shadow => "shadow",
+            Warning::Parentheses => "parentheses",
+            Warning::DivByZero => "div-by-zero",
+            Warning::IntegerOverflow => "integer-overflow",
+            Warning::DiscardedQualifiers => "discarded-qualifiers",
+            Warning::InvalidNoreturn => "invalid-noreturn",
+            Warning::MissingDeclarations => "missing-declarations",
+            Warning::UnknownAttributes => "unknown-attributes",
+            Warning::IgnoredAttributes => "ignored-attributes",
+            Warning::Format => "format",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|warning| warning.name() == name)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-W{}", self.name())
+    }
+}
+
+/// What happens when a warning is emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningLevel {
+    Ignored,
+    Warning,
+    Error,
+}
+
+/// Warning levels selected on the command line. Later flags override earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarningOptions {
+    enabled: HashMap<Warning, bool>,
+    // Set by -Werror=<name> and -Wno-error=<name>, overriding -Werror.
+    errors: HashMap<Warning, bool>,
+    // -Werror
+    all_errors: bool,
+}
+
+impl Default for WarningOptions {
+    fn default() -> Self {
+        Self {
+            enabled: Warning::DEFAULT
+                .into_iter()
+                .map(|warning| (warning, true))
+                .collect(),
+            errors: HashMap::new(),
+            all_errors: false,
+        }
+    }
+}
+
+impl WarningOptions {
+    /// Applies a `-W...` flag.
+    pub fn apply_flag(&mut self, flag: &str) -> Result<()> {
+        let Some(name) = flag.strip_prefix("-W") else {
+            bail!("Unknown option: {flag}");
+        };
+
+        match name {
+            "all" => self.enable(Warning::ALL_GROUP),
+            "extra" => self.enable(Warning::EXTRA_GROUP),
+            "error" => self.all_errors = true,
+            "no-error" => self.all_errors = false,
+            _ => {
+                let (name, enabled, error) = if let Some(name) = name.strip_prefix("error=") {
+                    (name, Some(true), Some(true))
+                } else if let Some(name) = name.strip_prefix("no-error=") {
+                    (name, None, Some(false))
+                } else if let Some(name) = name.strip_prefix("no-") {
+                    (name, Some(false), None)
+                } else {
+                    (name, Some(true), None)
+                };
+
+                let Some(warning) = Warning::from_name(name) else {
+                    bail!("Unknown warning option: {flag}");
+                };
+
+                if let Some(enabled) = enabled {
+                    self.enabled.insert(warning, enabled);
+                }
+                if let Some(error) = error {
+                    self.errors.insert(warning, error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enable(&mut self, warnings: impl IntoIterator<Item = Warning>) {
+        for warning in warnings {
+            self.enabled.insert(warning, true);
+        }
+    }
+
+    pub fn level(&self, warning: Warning) -> WarningLevel {
+        if !self.enabled.get(&warning).copied().unwrap_or(false) {
+            WarningLevel::Ignored
+        } else if self
+            .errors
+            .get(&warning)
+            .copied()
+            .unwrap_or(self.all_errors)
+        {
+            WarningLevel::Error
+        } else {
+            WarningLevel::Warning
+        }
+    }
+}
+
+/// A `#pragma GCC diagnostic` directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticPragma {
+    Push,
+    Pop,
+    Set(Warning, WarningLevel),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(flags: &[&str]) -> Result<WarningOptions> {
+        let mut options = WarningOptions::default();
+        for flag in flags {
+            options.apply_flag(flag)?;
+        }
+
+        Ok(options)
+    }
+
+    #[test]
+    fn test_default() -> Result<()> {
+        let options = options(&[])?;
+
+        assert_eq!(options.level(Warning::DivByZero), WarningLevel::Warning);
+        assert_eq!(
+            options.level(Warning::UnusedVariable),
+            WarningLevel::Ignored
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_groups() -> Result<()> {
+        let options = options(&["-Wall", "-Wno-unused-value", "-Wshadow"])?;
+
+        assert_eq!(
+            options.level(Warning::UnusedVariable),
+            WarningLevel::Warning
+        );
+        assert_eq!(options.level(Warning::UnusedValue), WarningLevel::Ignored);
+        assert_eq!(options.level(Warning::Shadow), WarningLevel::Warning);
+        assert_eq!(options.level(Warning::SignCompare), WarningLevel::Ignored);
+
+        let options = super::tests::options(&["-Wextra"])?;
+
+        assert_eq!(options.level(Warning::SignCompare), WarningLevel::Warning);
+        assert_eq!(
+            options.level(Warning::ImplicitFallthrough),
+            WarningLevel::Warning
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let options = options(&["-Wall", "-Werror", "-Wno-error=unused-value"])?;
+
+        assert_eq!(options.level(Warning::UnusedVariable), WarningLevel::Error);
+        assert_eq!(options.level(Warning::UnusedValue), WarningLevel::Warning);
+        // -Werror doesn't enable anything.
+        assert_eq!(options.level(Warning::Shadow), WarningLevel::Ignored);
+
+        let options = super::tests::options(&["-Werror=shadow"])?;
+
+        assert_eq!(options.level(Warning::Shadow), WarningLevel::Error);
+        assert_eq!(options.level(Warning::DivByZero), WarningLevel::Warning);
+
+        // Accepted even though nothing emits it, like in build scripts written for gcc.
+        let options =
+            super::tests::options(&["-Wno-implicit-fallthrough", "-Werror=implicit-fallthrough"])?;
+
+        assert_eq!(
+            options.level(Warning::ImplicitFallthrough),
+            WarningLevel::Error
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ng_unknown_warning() {
+        assert!(options(&["-Wfoo"]).is_err());
+        assert!(options(&["-Werror=foo"]).is_err());
+    }
+}
@@ -1,10 +1,18 @@
+// Each program is compiled at every optimization level.
 fn assert_exit_code(c_code: &str, expected: i32) {
+    for level in ["-O0", "-O1", "-O2", "-Os"] {
+        assert_exit_code_with_options(c_code, &[level], expected);
+    }
+}
+
+fn assert_exit_code_with_options(c_code: &str, options: &[&str], expected: i32) {
     let res = std::process::Command::new("bin/run_arg")
         .arg(c_code)
+        .args(options)
         .output()
         .unwrap();
 
-    assert_eq!(res.status.code(), Some(expected));
+    assert_eq!(res.status.code(), Some(expected), "with {options:?}");
 }
 
 #[test]
@@ -65,6 +73,790 @@ fn test_for() {
     );
 }
 
+#[test]
+fn test_block() {
+    assert_exit_code(
+        "a = 0; b = 0; while (a < 10) { a = a + 1; b = b + 2; } return b;",
+        20,
+    );
+}
+
+#[test]
+fn test_if_without_else_falls_through() {
+    assert_exit_code("a = 1; if (a) a = 5; return a;", 5);
+    assert_exit_code("a = 0; if (a) a = 5; else a = 7; return a;", 7);
+}
+
+#[test]
+fn test_for_updates_after_body() {
+    assert_exit_code(
+        "a = 0; for (b = 0; b < 3; b = b + 1) a = a * 10 + b; return a;",
+        12,
+    );
+}
+
+#[test]
+fn test_functions() {
+    assert_exit_code(
+        "int add(int a, int b); \
+         int main() { return add(fib(10), 3) - sub6(1, 2, 3, 4, 5, 6); } \
+         int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } \
+         int add(int a, int b) { return a + b; } \
+         long sub6(long a, long b, long c, long d, long e, long f) { return a - b - c - d - e - f; }",
+        77,
+    );
+}
+
+#[test]
+fn test_register_pressure() {
+    // More values are live across calls than there are callee-saved registers.
+    assert_exit_code(
+        "long id(long x) { return x; } \
+         long deep(long a) { return (((((((((((((id(a) + id(a + 1) * 1) + id(a + 2) * 2) \
+         + id(a + 3) * 3) + id(a + 4) * 4) + id(a + 5) * 5) + id(a + 6) * 6) + id(a + 7) * 7) \
+         + id(a + 8) * 8) + id(a + 9) * 9) + id(a + 10) * 10) + id(a + 11) * 11) \
+         + id(a + 12) * 12) + id(a + 13) * 13); } \
+         int main() { long i; long s = 0; \
+         for (i = 0; i < 10; i = i + 1) s = s + deep(i) / (i + 1); return s - 900; }",
+        96,
+    );
+}
+
+#[test]
+fn test_constant_folding() {
+    // A signed division by a power of 2 rounds toward 0.
+    assert_exit_code(
+        "int main() { int a = -7; long b = -1; unsigned c = 4294967295; \
+         if (0) return 1; while (0) return 2; \
+         return (a / 4 == -1) + (a / 2 == -3) * 2 + (b / 8 == 0) * 4 + (c / 16 == 268435455) * 8 \
+         + (a * 8 == -56) * 16 + (a * 1 + 0 == a) * 32 + (5+20-4 == 21) * 64; }",
+        127,
+    );
+}
+
+#[test]
+fn test_declarations_and_scopes() {
+    assert_exit_code("int a = 3, b; { int a = 4; b = a; } return a * 10 + b;", 34);
+}
+
+#[test]
+fn test_narrow_types_wrap_around() {
+    assert_exit_code("char c = 127; c = c + 1; return c == -128;", 1);
+    assert_exit_code("short s = 32767; s = s + 2; return s == -32767;", 1);
+    assert_exit_code("long l = 4294967296 + 7; int i; i = l; return i;", 7);
+}
+
+#[test]
+fn test_globals() {
+    assert_exit_code(
+        "int g = 2 * 3; char h; long k = -1; \
+         void set(int value) { h = value; } \
+         set(300); return g + h + k;",
+        49,
+    );
+}
+
+#[test]
+fn test_compound_assignment_and_increments() {
+    assert_exit_code("a = 5; a += 3; a -= 1; a *= 4; a /= 2; return a;", 14);
+    assert_exit_code(
+        "a = 5; b = a++; c = ++a; return a * 100 + b * 10 + c - 700;",
+        57,
+    );
+    assert_exit_code(
+        "a = 5; b = a--; c = --a; return a * 100 + b * 10 + c - 300;",
+        53,
+    );
+    assert_exit_code("char c = 127; d = c++; return (d == 127) * (c == -128);", 1);
+    assert_exit_code("s = 0; for (i = 0; i < 5; i++) s += i; return s;", 10);
+}
+
+#[test]
+fn test_casts_and_sizeof() {
+    assert_exit_code("a = 300; return (char)a + sizeof(a) + sizeof(long);", 56);
+    assert_exit_code(
+        "long l = sizeof(int) * 4294967296; return l / 4294967296;",
+        4,
+    );
+}
+
+#[test]
+fn test_pointers_and_arrays() {
+    assert_exit_code("int a = 3; int *p = &a; *p = 5; return a;", 5);
+    assert_exit_code(
+        "int a[5]; int *p = a + 4; for (i = 0; i < 5; i++) a[i] = i * i; \
+         return *p * 10 + (p - a) + sizeof(a);",
+        184,
+    );
+    assert_exit_code(
+        "int a[3] = {1, 2, 3}; a[1] += 10; int *p = &a[2]; p--; return *p;",
+        12,
+    );
+    assert_exit_code(
+        "int a[3] = {1, 2, 3}; i = 0; a[i++] += 10; return a[0] * 10 + i;",
+        111,
+    );
+}
+
+#[test]
+fn test_structs() {
+    assert_exit_code(
+        "struct P { char c; long l; } p; struct P *q = &p; q->l = 7; p.c = 3; \
+         return p.l * 10 + q->c + sizeof(struct P);",
+        89,
+    );
+    assert_exit_code(
+        "struct P { int x, y; } a = {1, 2}, b; b = a; a.x = 5; return b.x * 10 + b.y;",
+        12,
+    );
+}
+
+#[test]
+fn test_initializers() {
+    assert_exit_code("int a[] = {1, 2, 3}; return sizeof(a) + a[2];", 15);
+    assert_exit_code(
+        "int a[2][3] = {1, 2, 3, 4}; return a[1][0] * 10 + a[1][1];",
+        40,
+    );
+    assert_exit_code(
+        "char s[] = \"abc\"; return sizeof(s) * 10 + (s[3] == 0) + s[1] - 97;",
+        42,
+    );
+    assert_exit_code(
+        "struct P { int x, y, z; } p = {.z = 5, .x = 2}; \
+         int a[5] = {[3] = 7, 8}; return p.x * 10 + p.y + p.z + a[3] + a[4] - 15;",
+        25,
+    );
+    assert_exit_code(
+        "struct S { int a; struct { int a, b; } in; int c; } s = {.in.a = 3, 4, 5}; \
+         return s.a * 100 + s.in.a * 10 + s.in.b + s.c;",
+        39,
+    );
+}
+
+#[test]
+fn test_global_initializers() {
+    assert_exit_code(
+        "int a[] = {10, 20, 30}; int *p = &a[1] + 1; char s[4] = \"hi\"; \
+         struct { char c; int *q; } t[] = {1, a, 2}; \
+         return *p + *t[0].q + (t[1].q == 0) + sizeof(t) + s[1] + s[3] - 105;",
+        73,
+    );
+    assert_exit_code(
+        "char *s = \"hello\"; return s[1] - 97 + sizeof(\"hello\");",
+        10,
+    ); // A global can hold its own address.
+    assert_exit_code(
+        "struct L { struct L *next; struct L *prev; }; void *gp = &gp; \
+         struct L head = { &head, &head }; \
+         int f(void) { static void *p = &p; static int n[] = { 4, 5 }; return (p == &p) + n[1]; } \
+         int main() { return (gp == &gp) + 2 * (head.prev->next == &head) + f(); }",
+        9,
+    );
+}
+
+#[test]
+fn test_compound_literals() {
+    assert_exit_code(
+        "struct P { int x, y; }; \
+         int main() { struct P p = (struct P){1, 2}; int *q = (int[]){3, 4, 5}; \
+         return p.x * 10 + p.y - q[2] + q[0]; }",
+        10,
+    );
+    assert_exit_code(
+        "struct P { int x, y; } *g = &(struct P){4, 5}; int main() { return g->x * g->y; }",
+        20,
+    );
+}
+
+#[test]
+fn test_static_locals() {
+    assert_exit_code(
+        "int count(void) { static int n = 10; return ++n; } \
+         int main() { count(); count(); return count(); }",
+        13,
+    );
+    assert_exit_code(
+        "int f(void) { static int n; return n += 2; } int g(void) { static int n = 100; return n++; } \
+         int main() { f(); return f() + g(); }",
+        104,
+    );
+}
+
+#[test]
+fn test_qualifiers() {
+    assert_exit_code(
+        "static int g = 5; static int twice(int register x) { return x * 2; } \
+         int main() { const volatile int c = 8; const int *p = &g; int *const q = &g; *q = 5; \
+         return twice(c) + *p - 3; }",
+        18,
+    );
+}
+
+#[test]
+fn test_unsigned_types() {
+    assert_exit_code(
+        "int main() { unsigned char c = 255; signed char d = 255; return c + d; }",
+        254,
+    );
+    assert_exit_code(
+        "int main() { unsigned short s = 65535; s++; unsigned u = 7; u -= 8; \
+         return s + (u == 4294967295u) + 2 * (sizeof(int) > -1); }",
+        1,
+    );
+    assert_exit_code(
+        "int main() { long long a = -3; unsigned long b = 2; \
+         return (a < b) + 2 * (-1 < 0u) + 4 * (-1L < 0u) + 8 * (4294967295u / 2 == 2147483647); }",
+        12,
+    );
+    assert_exit_code(
+        "unsigned long long f(unsigned x) { return x * 2ull; } \
+         int main() { return f(4294967295u) / 4294967296; }",
+        1,
+    );
+    assert_exit_code(
+        "unsigned long x = (0UL - 1) * (0UL - 1); \
+         int main() { return x + (18446744073709551615u * 18446744073709551615u > 0); }",
+        2,
+    );
+}
+
+#[test]
+fn test_bool() {
+    assert_exit_code(
+        "_Bool t(int x) { return x; } \
+         int main() { _Bool b = 256; _Bool *p = &b; _Bool q = p; return b + q + t(2) + t(0) + (t(5) == 1); }",
+        4,
+    );
+}
+
+#[test]
+fn test_char_signedness() {
+    let c_code = "int main() { char c = 200; return c < 0; }";
+    assert_exit_code(c_code, 1);
+    assert_exit_code_with_options(c_code, &["-funsigned-char"], 0);
+    assert_exit_code_with_options(c_code, &["-funsigned-char", "-fsigned-char"], 1);
+}
+
+#[test]
+fn test_function_pointers() {
+    assert_exit_code(
+        "int add(int a, int b) { return a + b; } int sub(int a, int b) { return a - b; } \
+         int (*ops[2])(int, int) = { add, sub }; \
+         int (*pick(int i))(int, int) { return ops[i]; } \
+         int apply(int (*f)(int, int), int x) { return f(x, 1) + (*f)(x, 2); } \
+         int main() { int (*fp)(int, int) = &add; int (*(*get)(int))(int, int) = pick; \
+         return fp(1, 2) + get(1)(10, 3) + apply(sub, 10) + ops[0](1, 1) + (fp == add); }",
+        30,
+    );
+}
+
+#[test]
+fn test_plugin_tables() {
+    assert_exit_code(
+        "int one(int argc, char **argv) { return argc; } \
+         int two(int argc, char **argv) { return argc * 2; } \
+         struct Plugin { char *name; int (*handler)(int, char **); } \
+         plugins[] = { { \"one\", one }, { \"two\", &two } }; \
+         int main() { int sum = 0; int i; struct Plugin *p = &plugins[1]; \
+         for (i = 0; i < 2; i++) sum += plugins[i].handler(i + 3, 0); \
+         return sum + p->handler(1, 0) + sizeof(plugins[0].handler); }",
+        21,
+    );
+}
+
+#[test]
+fn test_extern() {
+    assert_exit_code(
+        "extern int abs(int); int (*f)(int) = abs; \
+         int main() { int (*g)(int) = abs; extern int n; return f(-3) + g(-4) + n; } \
+         int n = 5;",
+        12,
+    );
+}
+
+#[test]
+fn test_struct_arguments() {
+    assert_exit_code(
+        "struct P { char x; char y; char z; }; struct Q { int a; int b; int c; }; \
+         struct L { long a; long b; long c; }; \
+         int p(struct P s) { return s.x + s.y * s.z; } \
+         int q(long a, long b, long c, long d, long e, struct Q s, long f) { return a + s.c + f; } \
+         long l(struct L s, int k) { s.a = k; return s.a + s.b + s.c; } \
+         int main() { struct P s; struct Q t; struct L u; s.x = 1; s.y = 2; s.z = 3; \
+         t.c = 4; u.a = 10; u.b = 20; u.c = 30; \
+         return p(s) + q(1, 2, 3, 4, 5, t, 6) + l(u, 1) - u.a; }",
+        59,
+    );
+}
+
+#[test]
+fn test_struct_return_values() {
+    assert_exit_code(
+        "struct P { char x; char y; char z; }; struct L { long a; long b; long c; }; \
+         struct P p(int x) { struct P s; s.x = x; s.y = x + 1; s.z = x + 2; return s; } \
+         struct L l(long a, long b, long c, long d, long e, long f, long g) \
+         { struct L s; s.a = a; s.b = f; s.c = g; return s; } \
+         int main() { struct P s = p(3); struct L t; t = l(1, 2, 3, 4, 5, 6, 7); \
+         return s.x + s.y + s.z + p(10).z + t.a + t.b + t.c + l(0, 0, 0, 0, 0, 0, 9).c; }",
+        47,
+    );
+}
+
+#[test]
+fn test_struct_interop() {
+    assert_exit_code(
+        "struct D { int quot; int rem; }; struct D div(int, int); \
+         struct LD { long quot; long rem; }; struct LD ldiv(long, long); \
+         int main() { struct D d = div(47, 10); return d.quot * 10 + d.rem + ldiv(-7, 2).rem; }",
+        46,
+    );
+}
+
+#[test]
+fn test_bit_fields() {
+    assert_exit_code(
+        "struct B { unsigned a : 3; int b : 5; long c : 40; _Bool d : 1; } s = { 9, -3, 5 }; \
+         int main() { struct B *p = &s; int x; p->a += 6; x = p->b++; s.d = 2; \
+         s.c = 1099511627775; s.b = s.b * 8; \
+         return s.a * 10 + x + (s.b == -16) + s.d + (s.c == -1) + p->c + (p->a - 4 < 0); }",
+        69,
+    );
+}
+
+#[test]
+fn test_register_maps() {
+    assert_exit_code(
+        "struct Ctrl { unsigned enable : 1, mode : 2, : 5; unsigned char prescale : 4; \
+         unsigned : 0; short irq : 3; }; \
+         int main() { struct Ctrl c = { 1, 3, 15 }; unsigned char *bytes = (unsigned char *)&c; int i; \
+         int sum = 0; c.irq = -2; c.mode = c.mode - 1; \
+         for (i = 0; i < sizeof c; i++) sum += bytes[i]; \
+         return sizeof c + sum + c.irq; }",
+        32,
+    );
+}
+
+#[test]
+fn test_variable_arrays() {
+    assert_exit_code(
+        "int id(int x) { return x; } \
+         int sum(int n, int *a) { int s = 0; int i; for (i = 0; i < n; i++) s += a[i]; return s; } \
+         int main() { int n = id(5); int m = id(3); int a[n]; int b[n][m]; int (*p)[m] = b; int i; \
+         for (i = 0; i < n; i++) { a[i] = i * i; b[i][m - 1] = i; } p++; \
+         return sizeof a + sizeof b + sizeof(int[n][2]) + sum(n, a) + p[0][2] + (p - b) + b[4][2]; }",
+        156,
+    );
+}
+
+#[test]
+fn test_variable_arrays_freed_at_scope_exit() {
+    // Without freeing each iteration's array, this would need 8GB of stack.
+    assert_exit_code(
+        "int main() { int n = 80000; long i; int sum = 0; \
+         for (i = 0; i < 100000; i++) { char big[n]; big[0] = 1; big[n - 1] = 2; sum = big[n - 1]; } \
+         return sum; }",
+        2,
+    );
+}
+
+#[test]
+fn test_alloca() {
+    assert_exit_code(
+        "int f(int a, char *p, int b) { p[15] = 3; return a + p[15] + b; } \
+         int main() { int x = 10; char *q = __builtin_alloca(x * 10); q[99] = 7; \
+         return f(1, __builtin_alloca(16), 2) + q[99] + x; }",
+        23,
+    );
+}
+
+#[test]
+fn test_unions_and_anonymous_members() {
+    assert_exit_code(
+        "union U { char c; int i; }; \
+         struct S { char a; union { short b; char c[3]; }; struct { char d, e; }; }; \
+         int main() { union U u; u.i = 258; struct S s = { 1, .c = \"xy\", 4 }; \
+         s.e = 5; struct S *p = &s; p->b = p->b + 1; \
+         return u.c + sizeof(union U) + sizeof(struct S) * 10 + s.c[0] + s.d + s.e; }",
+        // 2 + 4 + 80 + ('x' + 1) + 4 + 5
+        216,
+    );
+}
+
+#[test]
+fn test_generic_selection() {
+    assert_exit_code(
+        "int main() { long l = 0; char *p = 0; unsigned char c = 0; \
+         return _Generic(l, int: 1, long: 2) + _Generic(p, char *: 10, default: 20) \
+         + _Generic(c + c, int: 100, default: 0) + _Generic(l++, long: l); }",
+        112,
+    );
+}
+
+#[test]
+fn test_static_assert_and_alignas() {
+    assert_exit_code(
+        "_Static_assert(sizeof(long) == 8, \"LP64\"); \
+         struct A { char c; _Alignas(16) char d; }; char pad; _Alignas(64) char g; \
+         int main() { _Static_assert(_Alignof(struct A) == 16, \"aligned member\"); \
+         _Alignas(16) char x; char y; _Alignas(long) char z; \
+         return ((long)&g / 64 * 64 == (long)&g) + ((long)&x / 16 * 16 == (long)&x) * 2 \
+         + ((long)&z / 8 * 8 == (long)&z) * 4 + sizeof(struct A) + y * 0; }",
+        39,
+    );
+}
+
+#[test]
+fn test_noreturn() {
+    assert_exit_code(
+        "_Noreturn void exit(int code); \
+         _Noreturn void fail(int code) { exit(code + 1); } \
+         int main() { fail(41); }",
+        42,
+    );
+}
+
+#[test]
+fn test_gnu_attributes() {
+    assert_exit_code(
+        "struct __attribute__((packed)) P { char c; int i; short s : 12, t : 4; }; \
+         struct A { char c; } __attribute__((aligned(8))); \
+         extern int counter __asm__(\"real_counter\"); int real_counter = 1; \
+         __attribute__((weak)) int missing(void); \
+         __attribute__((constructor(101))) static void first(void) { counter = counter + 2; } \
+         __attribute__((constructor)) static void second(void) { counter = counter * 10; } \
+         __attribute__((section(\".text.hot\"), noinline)) int hot(void) { return 100; } \
+         int main() { struct P p; p.s = -3; p.t = 5; \
+         return sizeof(struct P) + sizeof(struct A) + counter + (missing == 0) + hot() + p.s + p.t; }",
+        // 7 + 8 + 30 + 1 + 100 - 3 + 5
+        148,
+    );
+}
+
+#[test]
+fn test_statement_expressions_and_typeof() {
+    assert_exit_code(
+        "struct S { int a, b; }; \
+         int main() { int x = ({ int y = 3; y * 2; }); typeof(x) *p = &x; \
+         __typeof__(struct S) s = ({ struct S t = { 4, 5 }; t; }); \
+         __extension__ long z = sizeof(typeof(z)) + ({ s.a = 1; }); \
+         return x + *p + s.a + s.b + z; }",
+        27,
+    );
+}
+
+#[test]
+fn test_variadic_calls() {
+    assert_exit_code(
+        "int printf(const char *format, ...) __attribute__((format(printf, 1, 2))); \
+         int main() { char c = 1; return printf(\"%d-%s-%ld\\n\", c, \"ab\", 123456789012); }",
+        18,
+    );
+}
+
+#[test]
+fn test_inline_asm() {
+    assert_exit_code(
+        "asm(\".globl answer\\nanswer:\\n\\tmov eax, 42\\n\\tret\"); int answer(void); \
+         int main() { int a = 5, b, c = 7, d; long q = 1; \
+         asm(\"lea %0, [%1+%2]\" : \"=&r\"(b) : \"r\"(a), \"ri\"(3)); \
+         asm volatile(\"add %q0, %[v]\" : \"+r\"(q) : [v] \"i\"(10)); \
+         asm(\"add %0, 2\" : \"+m\"(c)); \
+         asm(\"imul %0, %0\" : \"=r\"(d) : \"0\"(a)); \
+         asm(\"mov ebx, 1\\n\\tadd %0, ebx\" : \"+a\"(a) : : \"rbx\"); \
+         return answer() + b + q + c + d + a; }",
+        101,
+    );
+}
+
+#[test]
+fn test_atomic_builtins() {
+    assert_exit_code(
+        "_Atomic int a; _Atomic(long) b = 10; int *_Atomic p; int arr[4]; unsigned char f = 12; \
+         int main() { int x = 5, e = 5; a = 3; a += 4; a++; --a; b *= 3; p = arr; p += 2; \
+         int r = a + b + (p - arr); \
+         r += __atomic_fetch_add(&x, 3, __ATOMIC_SEQ_CST); r += __atomic_sub_fetch(&x, 1, 0); \
+         __atomic_store_n(&x, 2, __ATOMIC_RELEASE); \
+         r += __atomic_exchange_n(&x, 4, __ATOMIC_ACQ_REL); r += __atomic_load_n(&x, 2); \
+         r += __atomic_compare_exchange_n(&x, &e, 6, 0, 5, 5); r += e; \
+         r += __atomic_compare_exchange_n(&x, &e, 6, 1, 5, 5); r += x; \
+         r += __sync_val_compare_and_swap(&x, 6, 1); r += __sync_bool_compare_and_swap(&x, 1, 3); \
+         r += __sync_fetch_and_or(&f, 3); r += __atomic_and_fetch(&f, 6, 5); \
+         r += __atomic_nand_fetch(&f, 1, 5) - 200; \
+         r += __sync_lock_test_and_set(&x, 9); __sync_lock_release(&x); \
+         __sync_synchronize(); __atomic_thread_fence(__ATOMIC_SEQ_CST); \
+         __c11_atomic_init(&a, 1); r += x; r += __c11_atomic_fetch_add(&a, 2, 5); \
+         return r + __c11_atomic_load(&a, 5); }",
+        155,
+    );
+}
+
+#[test]
+fn test_atomics_with_threads() {
+    assert_exit_code(
+        "int pthread_create(unsigned long *thread, void *attr, void *(*start)(void *), void *arg); \
+         int pthread_join(unsigned long thread, void **result); \
+         _Atomic int counter; long total; int lock; int guarded; _Atomic(long) product = 1; \
+         void *work(void *arg) { int i; for (i = 0; i < 100000; i++) { counter++; \
+         __atomic_fetch_add(&total, 2, __ATOMIC_RELAXED); \
+         while (__sync_lock_test_and_set(&lock, 1)) {} \
+         guarded = guarded + 1; __sync_lock_release(&lock); } \
+         for (i = 0; i < 4; i++) product *= 2; return arg; } \
+         int main() { unsigned long threads[4]; int i; \
+         for (i = 0; i < 4; i++) pthread_create(&threads[i], 0, work, 0); \
+         for (i = 0; i < 4; i++) pthread_join(threads[i], 0); \
+         return (counter == 400000) + (total == 800000) * 2 + (guarded == 400000) * 4 \
+         + (product == 65536) * 8; }",
+        15,
+    );
+}
+
+#[test]
+fn test_lock_free_stack() {
+    assert_exit_code(
+        "int pthread_create(unsigned long *thread, void *attr, void *(*start)(void *), void *arg); \
+         int pthread_join(unsigned long thread, void **result); \
+         struct Node { struct Node *next; int value; }; struct Node nodes[4000]; struct Node *head; \
+         void *push(void *arg) { struct Node *node = arg; int i; for (i = 0; i < 1000; i++) { \
+         struct Node *old = __atomic_load_n(&head, __ATOMIC_ACQUIRE); \
+         node->value = 1; node->next = old; \
+         while (__atomic_compare_exchange_n(&head, &old, node, 1, __ATOMIC_RELEASE, \
+         __ATOMIC_RELAXED) == 0) node->next = old; \
+         node = node + 1; } return 0; } \
+         int main() { unsigned long threads[4]; int i; int sum = 0; struct Node *node; \
+         for (i = 0; i < 4; i++) pthread_create(&threads[i], 0, push, &nodes[i * 1000]); \
+         for (i = 0; i < 4; i++) pthread_join(threads[i], 0); \
+         for (node = head; node; node = node->next) sum = sum + node->value; return sum / 100; }",
+        40,
+    );
+}
+
+const THREAD_LOCALS: &str =
+    "int pthread_create(unsigned long *thread, void *attr, void *(*start)(void *), void *arg); \
+     int pthread_join(unsigned long thread, void **result); \
+     _Thread_local int counter = 10; extern __thread int error; __thread int error; \
+     static __thread char names[8]; int *error_location(void) { return &error; } \
+     void *work(void *arg) { long n = (long)arg; int i; static _Thread_local long calls; \
+     for (i = 0; i < n; i++) counter++; calls++; names[n] = n; *error_location() = n; \
+     return (void *)(counter + names[n] + error + calls * 100); } \
+     int main() { unsigned long threads[4]; int i; long sum = 0; void *result; \
+     for (i = 0; i < 4; i++) pthread_create(&threads[i], 0, work, (void *)(long)(i + 1)); \
+     for (i = 0; i < 4; i++) { pthread_join(threads[i], &result); sum += (long)result; } \
+     return sum - 400 + counter + error + names[1]; }";
+
+#[test]
+fn test_thread_locals() {
+    assert_exit_code(THREAD_LOCALS, 80);
+    assert_exit_code_with_options(THREAD_LOCALS, &["-fPIC"], 80);
+}
+
+const REGISTER_NAMES: &str = "long sp(void) { return 5; } int rsp(int x) { return x * 2; } \
+     int ax = 3; int *offset = &ax; static _Thread_local int st = 4; extern int Byte; int Byte; \
+     __attribute__((constructor)) void xmm0(void) { Byte = 7; } \
+     int main(void) { long (*f)(void) = sp; st++; \
+     return sp() + rsp(ax) + *offset + st + f() + Byte; }";
+
+#[test]
+fn test_register_named_symbols() {
+    assert_exit_code(REGISTER_NAMES, 31);
+    assert_exit_code_with_options(REGISTER_NAMES, &["-fPIC"], 31);
+}
+
+#[test]
+fn test_shared_library() {
+    let run = |program: &str, args: &[&str]| {
+        let status = std::process::Command::new(program)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{program} {args:?}");
+    };
+    let asm = std::process::Command::new("target/debug/kanic")
+        .args([
+            "-fPIC",
+            "int g = 1; static int s = 2; extern int e; int a[4] = {1, 2, 3, 4}; \
+             int f(void) { int *p = &a[2]; s++; return g + s + e + *p + a[1] + __PIC__; }",
+        ])
+        .output()
+        .unwrap()
+        .stdout;
+    std::fs::write("tmp/shared_test.s", asm).unwrap();
+    std::fs::write(
+        "tmp/shared_test_main.c",
+        "int e = 5; int f(void); int main(void) { return f(); }",
+    )
+    .unwrap();
+
+    run(
+        "cc",
+        &[
+            "-shared",
+            "-o",
+            "tmp/libshared_test.so",
+            "tmp/shared_test.s",
+        ],
+    );
+    run(
+        "cc",
+        &[
+            "-o",
+            "tmp/shared_test",
+            "tmp/shared_test_main.c",
+            "-Ltmp",
+            "-lshared_test",
+            "-Wl,-rpath,tmp",
+        ],
+    );
+    let status = std::process::Command::new("tmp/shared_test")
+        .status()
+        .unwrap();
+    for file in [
+        "shared_test.s",
+        "shared_test_main.c",
+        "libshared_test.so",
+        "shared_test",
+    ] {
+        std::fs::remove_file(format!("tmp/{file}")).unwrap();
+    }
+
+    assert_eq!(status.code(), Some(16));
+}
+
+#[test]
+fn test_builtins() {
+    assert_exit_code(
+        "struct S { char a; long b; struct { int c[4]; } in; }; \
+         int main() { unsigned x = 240; long y = 4294967296; int m = -1, i = 2, r; unsigned char u; \
+         char buf[8]; \
+         int n = __builtin_clz(x) + __builtin_ctz(x) + __builtin_popcount(x) + __builtin_parity(x); \
+         n += __builtin_clzll(y) + __builtin_ffsl(y) + __builtin_ffs(0) + __builtin_popcountl(m) \
+         + __builtin_parityl(m); \
+         n += __builtin_bswap32(x) / 16777216 + __builtin_bswap16(256) \
+         + __builtin_bswap64(y) / 4294967296; \
+         n += __builtin_add_overflow(2147483647, 1, &r) * 10; n += r == -2147483647 - 1; \
+         n += __builtin_sub_overflow(1, 2, &u) * 10; n += u; \
+         n += __builtin_mul_overflow(i, 50, &u); n += u; \
+         n += __builtin_mul_overflow(x, x, &r); n += r == 57600; \
+         n += __builtin_sub_overflow(x, 300u, &y); n += y == -60; \
+         n += __builtin_offsetof(struct S, in.c[i]) + __builtin_types_compatible_p(int, const int); \
+         n += __builtin_constant_p(n) + __builtin_constant_p(sizeof(n) * 2) + __builtin_expect(i, 0); \
+         __builtin_memset(buf, 3, 8); __builtin_memcpy(buf, &i, 1); \
+         if (n == 0) __builtin_unreachable(); \
+         return n + buf[0] + buf[7] - 700; }",
+        112,
+    );
+}
+
+#[test]
+fn test_bit_counts() {
+    assert_exit_code(
+        "int main() { unsigned long x = 1, v; int errors = 0, i, j, bit; \
+         for (i = 0; i < 200; i++) { int low = 0, all = 0; v = x; \
+         for (j = 0; j < 64; j++) { bit = v - v / 2 * 2; all += bit; if (j < 32) low += bit; \
+         v /= 2; } \
+         errors += (__builtin_popcountl(x) != all) + (__builtin_parityl(x) != all - all / 2 * 2); \
+         errors += (__builtin_popcount(x) != low) + (__builtin_parity(x) != low - low / 2 * 2); \
+         x = x * 6364136223846793005 + 1442695040888963407; } \
+         return errors; }",
+        0,
+    );
+}
+
+#[test]
+fn test_macro_definitions() {
+    assert_exit_code_with_options(
+        "return FOO + BAR;",
+        &["-DFOO", "-DBAR=10", "-DBAZ=3", "-UBAZ"],
+        11,
+    );
+}
+
+#[test]
+fn test_predefined_macros() {
+    assert_exit_code("return __kanic__ + __SIZEOF_POINTER__ * __CHAR_BIT__;", 65);
+}
+
+#[test]
+fn test_dump_macros() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-dM", "-E", "-DFOO=42", "-U__kanic__", "-funsigned-char"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(res.stdout).unwrap();
+
+    assert!(stdout.contains("#define FOO 42\n"));
+    assert!(stdout.contains("#define __LP64__ 1\n"));
+    assert!(stdout.contains("#define __linux__ 1\n"));
+    assert!(stdout.contains("#define __CHAR_UNSIGNED__ 1\n"));
+    assert!(!stdout.contains("__kanic__"));
+}
+
+#[test]
+fn test_optimize_macros() {
+    assert_exit_code_with_options("return __OPTIMIZE__;", &["-O1"], 1);
+    assert_exit_code_with_options("return __OPTIMIZE__ + __OPTIMIZE_SIZE__;", &["-Os"], 2);
+    // Before the definitions on the command line.
+    assert_exit_code_with_options(
+        "return __OPTIMIZE__;",
+        &["-O2", "-U__OPTIMIZE__", "-D__OPTIMIZE__=5"],
+        5,
+    );
+}
+
+#[test]
+fn test_dump_ir_after() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args([
+            "-O2",
+            "-fdump-ir-after=sccp",
+            "int f() { int x = 3; return x * 4; }",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "\
+*** IR after sccp ***
+f:
+bb0:
+  %0 = ext.i32 3
+  %1 = mul 3, 4
+  %2 = ext.i32 12
+  ret 12
+"
+    );
+}
+
+#[test]
+fn test_time_report() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-O1", "-ftime-report", "int main() { return 0; }"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(res.stderr).unwrap();
+    let names: Vec<&str> = stderr
+        .lines()
+        .skip(1)
+        .map(|line| line.split(':').next().unwrap().trim())
+        .collect();
+
+    assert!(stderr.starts_with("Execution times (seconds)\n"));
+    assert_eq!(
+        names,
+        [
+            "mem2reg",
+            "fold",
+            "simplify-cfg",
+            "dce",
+            "out-of-ssa",
+            "codegen",
+            "peephole",
+            "TOTAL"
+        ]
+    );
+}
+
 #[test]
 fn test_ng_only_symbol() {
     let res = std::process::Command::new("target/debug/kanic")
@@ -75,8 +867,179 @@ fn test_ng_only_symbol() {
     assert_eq!(
         String::from_utf8(res.stderr).unwrap(),
         "\
+<input>:1:11: error: Invalid token
 10 + 2 == == 2
-          ^ Invalid token
+          ^~
+
+"
+    );
+}
+
+#[test]
+fn test_ng_in_macro_definition() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-DX=foo", "-DY=1 + X", "int main() { return Y; }"])
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "\
+<command-line>:1:1: error: Use of undeclared identifier 'foo'
+foo
+^~~
+<command-line>:1:5: note: Expanded from macro 'X'
+1 + X
+    ^
+<input>:1:21: note: Expanded from macro 'Y'
+int main() { return Y; }
+                    ^
+
+"
+    );
+}
+
+#[test]
+fn test_ng_multi_lines() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .arg("a = 1;\nb = 2;\nreturn a + / b;")
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "\
+<input>:3:12: error: Invalid token
+return a + / b;
+           ^
+
+"
+    );
+}
+
+#[test]
+fn test_ng_reports_every_error() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .arg("a = 1 b = 2;\nreturn a + ;")
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "\
+<input>:1:7: error: Expected ';'
+a = 1 b = 2;
+      ^
+      ;
+
+<input>:2:12: error: Invalid token
+return a + ;
+           ^
+
+"
+    );
+}
+
+#[test]
+fn test_ng_json_diagnostics() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-fdiagnostics-format=json", "return (1;"])
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        r#"[{"severity":"error","span":{"file":"<input>","start":{"line":1,"column":10},"end":{"line":1,"column":11}},"message":"Expected ')'","option":null,"labels":[{"span":{"file":"<input>","start":{"line":1,"column":8},"end":{"line":1,"column":9}},"message":"To match this '('"}],"notes":[],"fix_its":[{"span":{"file":"<input>","start":{"line":1,"column":10},"end":{"line":1,"column":10}},"replacement":")"}]}]
+"#
+    );
+}
+
+#[test]
+fn test_ng_color_diagnostics() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-fcolor-diagnostics", "return @;"])
+        .output()
+        .unwrap();
+
+    assert!(String::from_utf8(res.stderr)
+        .unwrap()
+        .contains("\x1b[1;31merror\x1b[0m"));
+}
+
+#[test]
+fn test_warnings_dont_stop_compilation() {
+    assert_exit_code_with_options("a = 1; b = 2; return 6 / 3;", &["-Wall"], 2);
+
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-Wall", "-fno-color-diagnostics", "a = 1; return 0;"])
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "<input>:1:1: warning: Unused variable 'a' [-Wunused-variable]\na = 1; return 0;\n^\n\n"
+    );
+}
+
+#[test]
+fn test_ng_werror() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args(["-Werror", "-fno-color-diagnostics", "return 1 / 0;"])
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "<input>:1:12: error: Division by zero is undefined [-Werror,-Wdiv-by-zero]\nreturn 1 / 0;\n           ^\n\n"
+    );
+}
+
+#[test]
+fn test_diagnostic_pragmas() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args([
+            "-Werror",
+            "#pragma GCC diagnostic push\n\
+             #pragma GCC diagnostic ignored \"-Wdiv-by-zero\"\n\
+             a = 1 / 0;\n\
+             #pragma GCC diagnostic pop\n\
+             return 0;",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(0));
+    assert_eq!(String::from_utf8(res.stderr).unwrap(), "");
+}
+
+#[test]
+fn test_ng_semantic_errors() {
+    let res = std::process::Command::new("target/debug/kanic")
+        .args([
+            "-fno-color-diagnostics",
+            "int f(int a) { int a; return b; }",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(res.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(res.stderr).unwrap(),
+        "\
+<input>:1:20: error: Redefinition of 'a'
+int f(int a) { int a; return b; }
+                   ^
+<input>:1:11: note: Previous definition is here
+int f(int a) { int a; return b; }
+          ^
+
+<input>:1:30: error: Use of undeclared identifier 'b'
+int f(int a) { int a; return b; }
+                             ^
 
 "
     );